@@ -0,0 +1,397 @@
+use crate::models::display::concepts::stave_spaces::StaveSpaces;
+use crate::models::display::grid::vertical::VerticalGridLineIndex;
+use crate::models::display::layout::block::{BlockConstraint, BlockIndex};
+use crate::models::display::layout::skyline::Skyline;
+use crate::models::display::layout::strength::ConstraintStrength;
+
+/// The default horizontal gap, in stave spaces, left between accidentals in
+/// neighbouring columns of an accidental stack.
+pub const DEFAULT_ACCIDENTAL_COLUMN_GAP: f32 = 0.2;
+
+/// A single accidental GlyphBlock attached to a note in a chord, ready for stacking.
+/// The top of the accidental is its vertical position relative to any fixed reference
+/// shared by every accidental in the chord, such as the top line of the stave; it is
+/// determined by the pitch of the note the accidental is attached to. The skyline
+/// should be built from the SMuFL cut-outs of the accidental's glyph, so that diagonally
+/// offset accidentals can be tucked into one another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackedAccidental {
+    index: BlockIndex,
+    top: StaveSpaces,
+    skyline: Skyline,
+}
+
+impl StackedAccidental {
+    pub fn new(index: BlockIndex, top: StaveSpaces, skyline: Skyline) -> Self {
+        StackedAccidental {
+            index,
+            top,
+            skyline,
+        }
+    }
+
+    #[inline]
+    pub fn get_index(&self) -> BlockIndex {
+        self.index
+    }
+
+    #[inline]
+    pub fn get_top(&self) -> StaveSpaces {
+        self.top
+    }
+
+    #[inline]
+    pub fn get_skyline(&self) -> &Skyline {
+        &self.skyline
+    }
+}
+
+/// The arrangement of the accidentals in one chord into columns, together with
+/// the BlockConstraints that position each accidental within the chord's accidental
+/// stack column. Columns are listed from the column closest to the noteheads outwards.
+#[derive(Debug)]
+pub struct AccidentalStack {
+    columns: Vec<Vec<BlockIndex>>,
+    constraints: Vec<(BlockIndex, BlockConstraint)>,
+}
+
+impl AccidentalStack {
+    /// Returns the block indices of the accidentals in each column of this stack,
+    /// starting with the column closest to the noteheads.
+    #[inline]
+    pub fn get_columns(&self) -> &[Vec<BlockIndex>] {
+        self.columns.as_slice()
+    }
+
+    /// Returns the BlockConstraints positioning the accidentals in this stack, each
+    /// paired with the index of the accidental GlyphBlock the constraint should be added to.
+    #[inline]
+    pub fn get_constraints(&self) -> &[(BlockIndex, BlockConstraint)] {
+        self.constraints.as_slice()
+    }
+
+    /// Consumes this AccidentalStack, returning its BlockConstraints.
+    #[inline]
+    pub fn into_constraints(self) -> Vec<(BlockIndex, BlockConstraint)> {
+        self.constraints
+    }
+}
+
+/// Arranges the accidentals attached to the notes of a chord into columns between
+/// a pair of NoteheadLine0AccidentalStackStart and NoteheadLine0AccidentalStackEnd
+/// vertical grid lines, following the conventional zig-zag ordering: the highest
+/// accidental is placed closest to the noteheads, then the lowest, then the second highest,
+/// the second lowest, and so on, with each accidental taking the column closest to the
+/// noteheads in which it does not collide with an accidental already placed.
+///
+/// Within the stack, each accidental is moved away from the noteheads only as far as
+/// its skyline requires to clear the accidentals in the columns before it, so the cut-outs
+/// of diagonally offset accidentals may overlap horizontally.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AccidentalStacker {
+    stack_start: VerticalGridLineIndex,
+    stack_end: VerticalGridLineIndex,
+    column_gap: StaveSpaces,
+}
+
+impl AccidentalStacker {
+    /// Creates a new AccidentalStacker for the accidental stack column bounded
+    /// by the given vertical grid lines.
+    pub fn new(
+        stack_start: VerticalGridLineIndex,
+        stack_end: VerticalGridLineIndex,
+        column_gap: StaveSpaces,
+    ) -> Self {
+        AccidentalStacker {
+            stack_start,
+            stack_end,
+            column_gap,
+        }
+    }
+
+    #[inline]
+    pub fn get_stack_start(&self) -> VerticalGridLineIndex {
+        self.stack_start
+    }
+
+    #[inline]
+    pub fn get_stack_end(&self) -> VerticalGridLineIndex {
+        self.stack_end
+    }
+
+    #[inline]
+    pub fn get_column_gap(&self) -> StaveSpaces {
+        self.column_gap
+    }
+
+    /// Returns the stacking of the given accidentals, all attached to notes in the same chord.
+    pub fn get_stack(&self, accidentals: &[StackedAccidental]) -> AccidentalStack {
+        // Order the accidentals from highest to lowest, then visit them in zig-zag order,
+        // alternating between the highest and lowest accidentals not yet placed.
+
+        let mut by_height = accidentals.iter().collect::<Vec<_>>();
+
+        by_height.sort_by(|a, b| a.top.value.total_cmp(&b.top.value));
+
+        let mut zig_zag = Vec::with_capacity(by_height.len());
+
+        let mut highest = 0;
+
+        let mut lowest = by_height.len();
+
+        while highest < lowest {
+            zig_zag.push(by_height[highest]);
+            highest += 1;
+
+            if highest < lowest {
+                lowest -= 1;
+                zig_zag.push(by_height[lowest]);
+            }
+        }
+
+        // Place each accidental in the first column in which it does not collide
+        // with any accidental already placed, with the ends of all accidentals aligned.
+
+        let mut columns: Vec<Vec<&StackedAccidental>> = Vec::new();
+
+        for accidental in zig_zag {
+            let column = columns.iter().position(|column| {
+                column
+                    .iter()
+                    .all(|other| !Self::collides_when_aligned(accidental, other))
+            });
+
+            match column {
+                Some(column) => columns[column].push(accidental),
+                None => columns.push(vec![accidental]),
+            }
+        }
+
+        // Work outwards from the noteheads, moving each accidental away from the noteheads
+        // by the distance needed to clear every accidental in the columns before it.
+        // The accidental requiring the greatest distance determines the accidental's position.
+
+        let gap = self.column_gap.value as f64;
+
+        let mut offsets: Vec<(&StackedAccidental, f64)> = Vec::new();
+
+        let mut constraints = Vec::new();
+
+        for (column_index, column) in columns.iter().enumerate() {
+            let mut column_offsets = Vec::new();
+
+            for accidental in column.iter() {
+                if column_index == 0 {
+                    constraints.push((
+                        accidental.index,
+                        BlockConstraint::LockEndToVerticalGridLine(self.stack_end, None),
+                    ));
+
+                    column_offsets.push((*accidental, 0.0));
+                } else {
+                    let (start, top) = Self::place(accidental, 0.0);
+
+                    let placed = accidental.skyline.place(start, top);
+
+                    let blocker = offsets
+                        .iter()
+                        .map(|(other, other_offset)| {
+                            let (other_start, other_top) = Self::place(other, *other_offset);
+
+                            // With this accidental's end aligned with the end of the stack,
+                            // the distance the other accidental would need to move towards
+                            // the noteheads to clear this accidental is the distance this
+                            // accidental needs to move away from the noteheads instead.
+
+                            let clearance = other
+                                .skyline
+                                .place(other_start, other_top)
+                                .get_sideways_clearance(&placed, gap);
+
+                            (*other, *other_offset, clearance)
+                        })
+                        .max_by(|(_, _, a), (_, _, b)| a.total_cmp(b));
+
+                    if let Some((other, other_offset, offset)) = blocker {
+                        // Lock the end of this accidental before the start of the blocking
+                        // accidental. The distance may be negative where cut-outs allow
+                        // the two accidentals to overlap.
+
+                        constraints.push((
+                            accidental.index,
+                            BlockConstraint::LockBeforeBlockByDistance(
+                                other.index,
+                                (offset - other_offset - other.skyline.get_width() as f64) as f32,
+                                None,
+                            ),
+                        ));
+
+                        column_offsets.push((*accidental, offset));
+                    }
+                }
+
+                // However the accidentals are arranged, the whole stack must sit
+                // after the start of the stack column. This must outweigh the default
+                // weak strength of a float constraint, since the stack start is
+                // typically itself locked to the preceding column of the system.
+
+                constraints.push((
+                    accidental.index,
+                    BlockConstraint::FloatStartAfterVerticalGridLine(
+                        self.stack_start,
+                        Some(ConstraintStrength::Strong),
+                    ),
+                ));
+            }
+
+            offsets.append(&mut column_offsets);
+        }
+
+        AccidentalStack {
+            columns: columns
+                .iter()
+                .map(|column| column.iter().map(|accidental| accidental.index).collect())
+                .collect(),
+            constraints,
+        }
+    }
+
+    /// Returns the (start, top) position of the given accidental with its end placed
+    /// the given distance before the end of the stack column, taken as x = 0.
+    #[inline]
+    fn place(accidental: &StackedAccidental, offset: f64) -> (f64, f64) {
+        (
+            -offset - accidental.skyline.get_width() as f64,
+            accidental.top.value as f64,
+        )
+    }
+
+    /// Returns true if the given accidentals collide when their ends are aligned,
+    /// and so cannot share a column.
+    fn collides_when_aligned(a: &StackedAccidental, b: &StackedAccidental) -> bool {
+        let (start_a, top_a) = Self::place(a, 0.0);
+
+        let (start_b, top_b) = Self::place(b, 0.0);
+
+        a.skyline
+            .place(start_a, top_a)
+            .collides_with(&b.skyline.place(start_b, top_b))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::concepts::stave_spaces::{
+        AsStaveSpacesExt, StavePoint, StaveSpaces,
+    };
+    use crate::models::display::layout::accidental_stack::{
+        AccidentalStacker, StackedAccidental, DEFAULT_ACCIDENTAL_COLUMN_GAP,
+    };
+    use crate::models::display::layout::block::BlockConstraint;
+    use crate::models::display::layout::skyline::{Skyline, SkylineCutOuts};
+
+    #[test]
+    fn test_zig_zag_columns() {
+        // Five accidentals, each 2 stave spaces tall, on notes a third apart. Only
+        // accidentals at least 2 stave spaces apart can share a column.
+
+        let accidentals = [0.0, 1.0, 2.0, 3.0, 4.0]
+            .iter()
+            .enumerate()
+            .map(|(index, top)| {
+                StackedAccidental::new(
+                    index,
+                    StaveSpaces::new(*top),
+                    Skyline::from_bounding_box(1.as_stave_spaces(), 2.as_stave_spaces()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let stack = AccidentalStacker::new(0, 1, StaveSpaces::new(DEFAULT_ACCIDENTAL_COLUMN_GAP))
+            .get_stack(accidentals.as_slice());
+
+        // Zig-zag ordering visits the accidentals in the order 0, 4, 1, 3, 2. The highest
+        // and lowest accidentals share the first column; the second highest and second
+        // lowest share the second column; the middle accidental is far enough from both
+        // the highest and lowest accidentals to fit back into the first column.
+
+        assert_eq!(stack.get_columns(), &[vec![0, 4, 2], vec![1, 3]]);
+
+        // Accidentals in the first column are locked to the end of the stack; accidentals
+        // in later columns are locked before the accidental that blocks them.
+
+        assert!(stack
+            .get_constraints()
+            .iter()
+            .any(|(index, constraint)| *index == 0
+                && matches!(
+                    constraint,
+                    BlockConstraint::LockEndToVerticalGridLine(1, None)
+                )));
+
+        assert!(stack
+            .get_constraints()
+            .iter()
+            .any(|(index, constraint)| *index == 1
+                && matches!(
+                    constraint,
+                    BlockConstraint::LockBeforeBlockByDistance(_, distance, None)
+                        if (*distance - DEFAULT_ACCIDENTAL_COLUMN_GAP).abs() < 0.001
+                )));
+
+        // Every accidental must start after the start of the stack.
+
+        for index in 0..5 {
+            assert!(stack
+                .get_constraints()
+                .iter()
+                .any(|(other, constraint)| *other == index
+                    && matches!(
+                        constraint,
+                        BlockConstraint::FloatStartAfterVerticalGridLine(0, Some(_))
+                    )));
+        }
+    }
+
+    #[test]
+    fn test_cut_outs_allow_overlap() {
+        // The lower accidental has its top right corner cut away, so it can tuck beneath
+        // the upper accidental, overlapping it horizontally by a quarter of a stave space.
+
+        let upper = StackedAccidental::new(
+            0,
+            0.as_stave_spaces(),
+            Skyline::from_bounding_box(1.as_stave_spaces(), 3.as_stave_spaces()),
+        );
+
+        let lower = StackedAccidental::new(
+            1,
+            2.as_stave_spaces(),
+            Skyline::from_cut_outs(
+                1.as_stave_spaces(),
+                3.as_stave_spaces(),
+                &SkylineCutOuts {
+                    north_east: Some(StavePoint::new(
+                        StaveSpaces::new(0.5),
+                        StaveSpaces::new(1.5),
+                    )),
+                    ..Default::default()
+                },
+            ),
+        );
+
+        let stack = AccidentalStacker::new(0, 1, StaveSpaces::new(0.25)).get_stack(&[upper, lower]);
+
+        assert_eq!(stack.get_columns(), &[vec![0], vec![1]]);
+
+        assert!(stack
+            .get_constraints()
+            .iter()
+            .any(|(index, constraint)| *index == 1
+                && matches!(
+                    constraint,
+                    BlockConstraint::LockBeforeBlockByDistance(0, distance, None)
+                        if (*distance + 0.25).abs() < 0.001
+                )));
+    }
+}
@@ -0,0 +1,619 @@
+use crate::models::display::concepts::stave_spaces::{StaveSpaces, STAVE_SPACES_ZERO};
+use crate::models::display::grid::horizontal::HorizontalGridLineIndex;
+use crate::models::display::layout::block::{
+    Block, BlockConstraint, BlockEnum, BlockIndex, BlockLayer,
+};
+use crate::models::display::layout::skyline::{Skyline, SkylineColumn};
+use crate::models::music::concepts::ticks::Ticks;
+use crate::models::music::spine::{MomentSpineItem, PartIndex, VoiceIndex};
+use serde::{Deserialize, Serialize};
+
+pub type BeamIndex = usize;
+
+/// The default thickness of a beam, in stave spaces.
+pub const DEFAULT_BEAM_THICKNESS: f32 = 0.5;
+
+/// The default maximum slope of a beam, expressed as the vertical distance the beam
+/// may rise or fall for every stave space of horizontal distance it covers.
+pub const DEFAULT_MAXIMUM_BEAM_SLOPE: f32 = 0.25;
+
+/// The default minimum length, in stave spaces, of every stem in a beamed group,
+/// measured from the end of the stem attached to the notehead to the beam.
+pub const DEFAULT_MINIMUM_BEAMED_STEM_LENGTH: f32 = 2.5;
+
+/// The vertical distance between neighbouring quantized beam positions, in stave spaces.
+const BEAM_QUANTUM: f64 = 0.25;
+
+/// Which side of the noteheads a beam is placed on.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BeamDirection {
+    /// The stems point up, and the beam is placed above the noteheads.
+    Above,
+
+    /// The stems point down, and the beam is placed beneath the noteheads.
+    Below,
+}
+
+/// A beam linking the stems of a beamed group of notes. Each stem is a Block with
+/// a variable height, one end of which is attached to its notehead by the Block's own
+/// constraints; the other end, the tip of the stem, is lengthened to meet the beam.
+///
+/// The y positions of the beam at the first and last stems, and the rise of the beam
+/// between them, are variables in the linear constraint solver. The rise is bounded by the
+/// maximum slope of the beam, and each stem is kept at least the minimum stem length long.
+/// The ends of the beam are quantized to positions that sit on, straddle, or hang from
+/// stavelines. Each beam is accompanied by a BeamBlock, so the beam takes part in collision
+/// detection and resolution like any other Block.
+///
+/// The position of a beam is the position of its outer edge, where the stem tips meet it:
+/// the top edge of a beam above the noteheads, the bottom edge of a beam beneath them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutBeam {
+    stems: Vec<BlockIndex>,
+    direction: BeamDirection,
+    stave_top: HorizontalGridLineIndex,
+    stave_bottom: HorizontalGridLineIndex,
+    thickness: StaveSpaces,
+    maximum_slope: f32,
+    minimum_stem_length: StaveSpaces,
+}
+
+impl LayoutBeam {
+    /// Creates a new LayoutBeam linking the stem Blocks at the given indices, ordered from
+    /// the start of the system to the end. The given horizontal grid lines mark the top
+    /// and bottom stavelines of the stave the beamed notes sit on; all stavelines are
+    /// assumed to be one stave space apart.
+    pub fn new(
+        stems: Vec<BlockIndex>,
+        direction: BeamDirection,
+        stave_top: HorizontalGridLineIndex,
+        stave_bottom: HorizontalGridLineIndex,
+    ) -> Self {
+        LayoutBeam {
+            stems,
+            direction,
+            stave_top,
+            stave_bottom,
+            thickness: StaveSpaces::new(DEFAULT_BEAM_THICKNESS),
+            maximum_slope: DEFAULT_MAXIMUM_BEAM_SLOPE,
+            minimum_stem_length: StaveSpaces::new(DEFAULT_MINIMUM_BEAMED_STEM_LENGTH),
+        }
+    }
+
+    /// Returns the indices of the stem Blocks linked by this beam.
+    #[inline]
+    pub fn get_stems(&self) -> &[BlockIndex] {
+        self.stems.as_slice()
+    }
+
+    #[inline]
+    pub fn get_direction(&self) -> BeamDirection {
+        self.direction
+    }
+
+    #[inline]
+    pub fn get_stave_top(&self) -> HorizontalGridLineIndex {
+        self.stave_top
+    }
+
+    #[inline]
+    pub fn get_stave_bottom(&self) -> HorizontalGridLineIndex {
+        self.stave_bottom
+    }
+
+    #[inline]
+    pub fn get_thickness(&self) -> StaveSpaces {
+        self.thickness
+    }
+
+    /// Sets the thickness of this beam. Defaults to DEFAULT_BEAM_THICKNESS.
+    #[inline]
+    pub fn set_thickness(&mut self, thickness: StaveSpaces) {
+        self.thickness = thickness;
+    }
+
+    #[inline]
+    pub fn get_maximum_slope(&self) -> f32 {
+        self.maximum_slope
+    }
+
+    /// Sets the maximum slope of this beam, typically taken from the stylesheet.
+    /// Defaults to DEFAULT_MAXIMUM_BEAM_SLOPE.
+    #[inline]
+    pub fn set_maximum_slope(&mut self, slope: f32) {
+        self.maximum_slope = slope;
+    }
+
+    #[inline]
+    pub fn get_minimum_stem_length(&self) -> StaveSpaces {
+        self.minimum_stem_length
+    }
+
+    /// Sets the minimum length of every stem linked by this beam, typically taken from
+    /// the stylesheet. Defaults to DEFAULT_MINIMUM_BEAMED_STEM_LENGTH.
+    #[inline]
+    pub fn set_minimum_stem_length(&mut self, length: StaveSpaces) {
+        self.minimum_stem_length = length;
+    }
+
+    /// Returns the (top, bottom) extent of this beam if its outer edge were at the given position.
+    #[inline]
+    pub fn get_vertical_extent(&self, position: f64) -> (f64, f64) {
+        let thickness = self.thickness.value as f64;
+
+        match self.direction {
+            BeamDirection::Above => (position, position + thickness),
+            BeamDirection::Below => (position - thickness, position),
+        }
+    }
+
+    /// Returns true if this beam may end at the given position on a stave whose top and
+    /// bottom stavelines are at the given positions. Outside the stave, any position is
+    /// acceptable. Inside the stave, a beam must sit on, straddle, or hang from a staveline;
+    /// a beam floating in the middle of a stave space leaves thin wedges of white between
+    /// beam and stavelines that are easily lost in print.
+    pub fn is_quantized_position_allowed(
+        &self,
+        position: f64,
+        stave_top: f64,
+        stave_bottom: f64,
+    ) -> bool {
+        let (top, bottom) = self.get_vertical_extent(position);
+
+        if bottom <= stave_top || top >= stave_bottom {
+            return true;
+        }
+
+        // Allow a tiny tolerance for floating point noise in the solver.
+
+        const TOLERANCE: f64 = 0.001;
+
+        let first_line = ((top - stave_top) - TOLERANCE).ceil().max(0.0);
+
+        let line = stave_top + first_line;
+
+        line <= bottom + TOLERANCE && line <= stave_bottom + TOLERANCE
+    }
+
+    /// Returns the allowed quantized position closest to the given position for an end of
+    /// this beam, on a stave whose top and bottom stavelines are at the given positions.
+    /// Where two positions are equally close, the one lengthening the stems is preferred.
+    /// If the position may only move away from the noteheads, lengthening the stems,
+    /// then positions that would shorten the stems are not considered.
+    pub fn get_quantized_position(
+        &self,
+        position: f64,
+        stave_top: f64,
+        stave_bottom: f64,
+        is_lengthening_only: bool,
+    ) -> f64 {
+        // Positions closer to the top of the system have a smaller y value, so stems
+        // beneath a beam above the noteheads lengthen as the beam's position decreases.
+
+        let lengthening = match self.direction {
+            BeamDirection::Above => -1.0,
+            BeamDirection::Below => 1.0,
+        };
+
+        let nearest = ((position - stave_top) / BEAM_QUANTUM).round() as i64;
+
+        // A beam can never be more than a few quanta from an allowed position,
+        // so a small search window around the nearest quantum is sufficient.
+
+        (-8..=8)
+            .map(|step| stave_top + (nearest + step) as f64 * BEAM_QUANTUM)
+            .filter(|candidate| {
+                !is_lengthening_only || (candidate - position) * lengthening >= -0.001
+            })
+            .filter(|candidate| {
+                self.is_quantized_position_allowed(*candidate, stave_top, stave_bottom)
+            })
+            .min_by(|a, b| {
+                (a - position)
+                    .abs()
+                    .total_cmp(&(b - position).abs())
+                    .then(((b - a) * lengthening).total_cmp(&0.0))
+            })
+            .unwrap_or(position)
+    }
+
+    /// Returns quantized positions for both ends of this beam, given the solved positions
+    /// of its ends and the horizontal distance between its first and last stems. Ends only
+    /// ever move away from the noteheads, so no stem is shortened below its solved length.
+    /// If quantizing the ends independently would steepen the beam beyond its maximum slope,
+    /// the end with the shorter stem is moved further until the slope is acceptable.
+    pub fn get_quantized_positions(
+        &self,
+        start_position: f64,
+        end_position: f64,
+        span: f64,
+        stave_top: f64,
+        stave_bottom: f64,
+    ) -> (f64, f64) {
+        let lengthening = match self.direction {
+            BeamDirection::Above => -1.0,
+            BeamDirection::Below => 1.0,
+        };
+
+        let maximum_rise = self.maximum_slope as f64 * span.max(0.0) + 0.001;
+
+        let mut start = self.get_quantized_position(start_position, stave_top, stave_bottom, true);
+
+        let mut end = self.get_quantized_position(end_position, stave_top, stave_bottom, true);
+
+        // Moving the end with the shorter stem towards the other end always flattens
+        // the beam, so this settles within a handful of quanta; the limit on attempts
+        // is a safeguard only.
+
+        for _ in 0..64 {
+            if (end - start).abs() <= maximum_rise {
+                break;
+            }
+
+            if (end - start) * lengthening > 0.0 {
+                start = self.get_quantized_position(
+                    start + BEAM_QUANTUM * lengthening,
+                    stave_top,
+                    stave_bottom,
+                    true,
+                );
+            } else {
+                end = self.get_quantized_position(
+                    end + BEAM_QUANTUM * lengthening,
+                    stave_top,
+                    stave_bottom,
+                    true,
+                );
+            }
+        }
+
+        (start, end)
+    }
+
+    /// Returns a Skyline tracing the outline of this beam when its outer edge runs from
+    /// the given start position to the given end position, across stems at the given
+    /// x positions, together with the solved (start, top) position at which the Skyline
+    /// should be placed. Each column of the Skyline spans the beam between neighbouring stems.
+    pub fn get_skyline(
+        &self,
+        stem_positions: &[f64],
+        start_position: f64,
+        end_position: f64,
+    ) -> (Skyline, f64, f64) {
+        let first = stem_positions.first().copied().unwrap_or(0.0);
+
+        let last = stem_positions.last().copied().unwrap_or(first);
+
+        let position_at = |x: f64| {
+            if last > first {
+                start_position + (end_position - start_position) * (x - first) / (last - first)
+            } else {
+                start_position
+            }
+        };
+
+        let top = self
+            .get_vertical_extent(start_position)
+            .0
+            .min(self.get_vertical_extent(end_position).0);
+
+        let columns = stem_positions
+            .windows(2)
+            .filter(|window| window[1] > window[0])
+            .map(|window| {
+                let (top_a, bottom_a) = self.get_vertical_extent(position_at(window[0]));
+
+                let (top_b, bottom_b) = self.get_vertical_extent(position_at(window[1]));
+
+                SkylineColumn::new(
+                    (window[0] - first) as f32,
+                    (window[1] - first) as f32,
+                    (top_a.min(top_b) - top) as f32,
+                    (bottom_a.max(bottom_b) - top) as f32,
+                )
+            })
+            .collect();
+
+        (Skyline::new(columns), first, top)
+    }
+}
+
+/// A Block standing in for a beam during collision detection and resolution, so other Blocks
+/// avoid the beam and the beam avoids them. LayoutSystem::add_beam() adds a BeamBlock
+/// alongside every beam. The solver fits the BeamBlock's bounding box around the beam,
+/// from the start of the first stem to the end of the last stem, and its Skyline follows
+/// the slope of the beam. A BeamBlock is never engraved; the beam is engraved separately.
+///
+/// A beam above the noteheads can move up to avoid a collision, and a beam beneath the
+/// noteheads can move down, lengthening its stems. The stems linked by a beam never
+/// collide with it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BeamBlock {
+    source_moment_spine_item: Option<MomentSpineItem>,
+    source_onset: Option<Ticks>,
+    source_part_index: Option<PartIndex>,
+    source_voice_index: Option<VoiceIndex>,
+    is_visible: bool,
+    is_collidable: bool,
+    can_move_up_to_avoid_vertical_collision: bool,
+    can_move_down_to_avoid_vertical_collision: bool,
+    top_padding: StaveSpaces,
+    bottom_padding: StaveSpaces,
+    start_padding: StaveSpaces,
+    end_padding: StaveSpaces,
+    minimum_width: Option<StaveSpaces>,
+    skyline: Option<Skyline>,
+    constraints: Vec<BlockConstraint>,
+    index: BeamIndex,
+    beam: LayoutBeam,
+}
+
+impl BeamBlock {
+    /// Creates a new BeamBlock for the given beam, held at the given index in its LayoutSystem.
+    /// The BeamBlock takes its source from the given first stem of the beam, if any.
+    pub fn new(index: BeamIndex, beam: LayoutBeam, first_stem: Option<&BlockEnum>) -> Self {
+        let direction = beam.get_direction();
+
+        let source_moment_spine_item =
+            first_stem.and_then(|stem| stem.get_source_moment_spine_item().cloned());
+
+        BeamBlock {
+            source_part_index: source_moment_spine_item
+                .as_ref()
+                .map(|item| item.get_part_index()),
+            source_moment_spine_item,
+            source_onset: first_stem.and_then(|stem| stem.get_source_onset()),
+            source_voice_index: first_stem.and_then(|stem| stem.get_source_voice_index()),
+            is_visible: false,
+            is_collidable: true,
+            can_move_up_to_avoid_vertical_collision: direction == BeamDirection::Above,
+            can_move_down_to_avoid_vertical_collision: direction == BeamDirection::Below,
+            top_padding: STAVE_SPACES_ZERO,
+            bottom_padding: STAVE_SPACES_ZERO,
+            start_padding: STAVE_SPACES_ZERO,
+            end_padding: STAVE_SPACES_ZERO,
+            minimum_width: None,
+            skyline: None,
+            constraints: vec![],
+            index,
+            beam,
+        }
+    }
+
+    /// Returns the index of the beam this BeamBlock stands in for.
+    #[inline]
+    pub fn get_beam_index(&self) -> BeamIndex {
+        self.index
+    }
+
+    #[inline]
+    pub fn get_beam(&self) -> &LayoutBeam {
+        &self.beam
+    }
+}
+
+impl Block for BeamBlock {
+    #[inline]
+    fn is_fixed_width(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn get_fixed_width(&self) -> StaveSpaces {
+        STAVE_SPACES_ZERO
+    }
+
+    #[inline]
+    fn is_fixed_height(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn get_fixed_height(&self) -> StaveSpaces {
+        STAVE_SPACES_ZERO
+    }
+
+    #[inline]
+    fn get_source_moment_spine_item(&self) -> Option<&MomentSpineItem> {
+        self.source_moment_spine_item.as_ref()
+    }
+
+    #[inline]
+    fn get_source_onset(&self) -> Option<Ticks> {
+        self.source_onset
+    }
+
+    #[inline]
+    fn set_source_onset(&mut self, onset: Option<Ticks>) {
+        self.source_onset = onset;
+    }
+
+    #[inline]
+    fn get_source_part_index(&self) -> Option<PartIndex> {
+        self.source_part_index
+    }
+
+    #[inline]
+    fn get_source_voice_index(&self) -> Option<VoiceIndex> {
+        self.source_voice_index
+    }
+
+    #[inline]
+    fn get_layer(&self) -> BlockLayer {
+        BlockLayer::Foreground
+    }
+
+    #[inline]
+    fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    #[inline]
+    fn set_visible(&mut self, is_visible: bool) {
+        self.is_visible = is_visible;
+    }
+
+    #[inline]
+    fn is_collidable(&self) -> bool {
+        self.is_collidable
+    }
+
+    #[inline]
+    fn set_collidable(&mut self, is_collidable: bool) {
+        self.is_collidable = is_collidable;
+    }
+
+    #[inline]
+    fn get_can_move_up_to_avoid_vertical_collision(&self) -> bool {
+        self.can_move_up_to_avoid_vertical_collision
+    }
+
+    #[inline]
+    fn set_can_move_up_to_avoid_vertical_collision(&mut self, can_move: bool) {
+        self.can_move_up_to_avoid_vertical_collision = can_move;
+    }
+
+    #[inline]
+    fn get_can_move_down_to_avoid_vertical_collision(&self) -> bool {
+        self.can_move_down_to_avoid_vertical_collision
+    }
+
+    #[inline]
+    fn set_can_move_down_to_avoid_vertical_collision(&mut self, can_move: bool) {
+        self.can_move_down_to_avoid_vertical_collision = can_move;
+    }
+
+    #[inline]
+    fn get_skyline(&self) -> Option<&Skyline> {
+        self.skyline.as_ref()
+    }
+
+    #[inline]
+    fn set_skyline(&mut self, skyline: Option<Skyline>) {
+        self.skyline = skyline;
+    }
+
+    #[inline]
+    fn get_top_padding(&self) -> StaveSpaces {
+        self.top_padding
+    }
+
+    #[inline]
+    fn set_top_padding(&mut self, padding: StaveSpaces) {
+        self.top_padding = padding;
+    }
+
+    #[inline]
+    fn get_bottom_padding(&self) -> StaveSpaces {
+        self.bottom_padding
+    }
+
+    #[inline]
+    fn set_bottom_padding(&mut self, padding: StaveSpaces) {
+        self.bottom_padding = padding;
+    }
+
+    #[inline]
+    fn get_start_padding(&self) -> StaveSpaces {
+        self.start_padding
+    }
+
+    #[inline]
+    fn set_start_padding(&mut self, padding: StaveSpaces) {
+        self.start_padding = padding;
+    }
+
+    #[inline]
+    fn get_end_padding(&self) -> StaveSpaces {
+        self.end_padding
+    }
+
+    #[inline]
+    fn set_end_padding(&mut self, padding: StaveSpaces) {
+        self.end_padding = padding;
+    }
+
+    #[inline]
+    fn get_minimum_width(&self) -> Option<StaveSpaces> {
+        self.minimum_width
+    }
+
+    #[inline]
+    fn set_minimum_width(&mut self, minimum_width: Option<StaveSpaces>) {
+        self.minimum_width = minimum_width;
+    }
+
+    #[inline]
+    fn get_constraints(&self) -> &[BlockConstraint] {
+        self.constraints.as_slice()
+    }
+
+    #[inline]
+    fn add_constraint(&mut self, constraint: BlockConstraint) {
+        self.constraints.push(constraint);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::layout::beam::{BeamDirection, LayoutBeam};
+
+    #[test]
+    fn test_beam_quantization() {
+        let beam = LayoutBeam::new(vec![0, 1], BeamDirection::Above, 0, 1);
+
+        // Inside a stave from 0 to 4, a beam above the noteheads 0.5 stave spaces thick
+        // may sit on a staveline, straddle it, or hang from it, but may not float
+        // in the middle of a stave space.
+
+        assert!(beam.is_quantized_position_allowed(0.5, 0.0, 4.0));
+        assert!(beam.is_quantized_position_allowed(0.75, 0.0, 4.0));
+        assert!(beam.is_quantized_position_allowed(1.0, 0.0, 4.0));
+        assert!(!beam.is_quantized_position_allowed(1.25, 0.0, 4.0));
+
+        // Outside the stave, any position is allowed.
+
+        assert!(beam.is_quantized_position_allowed(-1.75, 0.0, 4.0));
+
+        assert_eq!(beam.get_quantized_position(1.3, 0.0, 4.0, false), 1.5);
+        assert_eq!(beam.get_quantized_position(1.2, 0.0, 4.0, false), 1.0);
+
+        // Equally close positions are resolved by lengthening the stems.
+
+        assert_eq!(beam.get_quantized_position(1.25, 0.0, 4.0, false), 1.0);
+
+        // When the beam may only move away from the noteheads, it never moves down.
+
+        assert_eq!(beam.get_quantized_position(1.45, 0.0, 4.0, true), 1.0);
+    }
+
+    #[test]
+    fn test_beam_quantized_slope() {
+        let beam = LayoutBeam::new(vec![0, 1], BeamDirection::Above, 0, 1);
+
+        // Across 4 stave spaces, the default maximum slope allows a rise of 1 stave space.
+        // Quantizing 1.4 and 3.1 independently gives 1.0 and 3.0, which is too steep,
+        // so the lower end, with the shorter stem, is raised to 2.0.
+
+        assert_eq!(
+            beam.get_quantized_positions(1.4, 3.1, 4.0, 0.0, 4.0),
+            (1.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn test_beam_skyline() {
+        let beam = LayoutBeam::new(vec![0, 1, 2], BeamDirection::Below, 0, 1);
+
+        // A beam beneath the noteheads falling from 6 to 7 across three stems.
+
+        let (skyline, start, top) = beam.get_skyline(&[10.0, 12.0, 14.0], 6.0, 7.0);
+
+        assert_eq!(start, 10.0);
+        assert_eq!(top, 5.5);
+        assert_eq!(skyline.get_columns().len(), 2);
+        assert_eq!(skyline.get_columns()[0].get_bottom(), 1.0);
+        assert_eq!(skyline.get_columns()[1].get_top(), 0.5);
+        assert_eq!(skyline.get_columns()[1].get_bottom(), 1.5);
+    }
+}
@@ -0,0 +1,817 @@
+use crate::models::display::concepts::stave_spaces::{StaveSpaces, STAVE_SPACES_ZERO};
+use crate::models::display::grid::horizontal::HorizontalGridLineIndex;
+use crate::models::display::grid::vertical::VerticalGridLineIndex;
+use crate::models::display::layout::beam::BeamBlock;
+use crate::models::display::layout::block::glyph::GlyphBlock;
+use crate::models::display::layout::block::line::LineBlock;
+use crate::models::display::layout::block::markup::MarkupBlock;
+use crate::models::display::layout::block::spacing::SpacingBlock;
+use crate::models::display::layout::curve::CurveBlock;
+use crate::models::display::layout::skyline::Skyline;
+use crate::models::display::layout::spanner::SpannerBlock;
+use crate::models::display::layout::strength::ConstraintStrength;
+use crate::models::music::concepts::ticks::Ticks;
+use crate::models::music::spine::{MomentSpineItem, PartIndex, VoiceIndex};
+use serde::{Deserialize, Serialize};
+
+pub mod glyph;
+pub mod line;
+pub mod markup;
+pub mod spacing;
+
+pub type BlockIndex = usize;
+
+/// The layer a Block is engraved into. Layers are painted in order, so anything in the
+/// midground is painted over anything in the background, and anything in the foreground
+/// is painted over both.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockLayer {
+    Background,
+    Midground,
+    Foreground,
+}
+
+/// A single constraint on the position of a Block, relative either to grid lines or to
+/// other Blocks in the same LayoutSystem. Every constraint carries an optional
+/// ConstraintStrength; if no strength is given, LayoutSystem::engrave() represents Lock*
+/// and Push* constraints by a STRONG constraint in the solver and Float* constraints
+/// by a WEAK constraint, so lock constraints override float constraints.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BlockConstraint {
+    LockTopToHorizontalGridLine(HorizontalGridLineIndex, Option<ConstraintStrength>),
+    FloatTopAfterHorizontalGridLine(HorizontalGridLineIndex, Option<ConstraintStrength>),
+    FloatBottomBeforeHorizontalGridLine(HorizontalGridLineIndex, Option<ConstraintStrength>),
+    LockBottomToHorizontalGridLine(HorizontalGridLineIndex, Option<ConstraintStrength>),
+    LockStartToVerticalGridLine(VerticalGridLineIndex, Option<ConstraintStrength>),
+    FloatStartAfterVerticalGridLine(VerticalGridLineIndex, Option<ConstraintStrength>),
+    FloatEndBeforeVerticalGridLine(VerticalGridLineIndex, Option<ConstraintStrength>),
+    LockEndToVerticalGridLine(VerticalGridLineIndex, Option<ConstraintStrength>),
+    LockVerticalCenterHalfwayBetweenHorizontalGridLines(
+        HorizontalGridLineIndex,
+        HorizontalGridLineIndex,
+        Option<ConstraintStrength>,
+    ),
+    LockVerticalCenterToHorizontalGridLine(HorizontalGridLineIndex, Option<ConstraintStrength>),
+    LockHorizontalCenterHalfwayBetweenVerticalGridLines(
+        VerticalGridLineIndex,
+        VerticalGridLineIndex,
+        Option<ConstraintStrength>,
+    ),
+    LockHorizontalCenterToVerticalGridLine(VerticalGridLineIndex, Option<ConstraintStrength>),
+    PushHorizontalGridLineDownToAccommodateBlockHeight(
+        HorizontalGridLineIndex,
+        Option<ConstraintStrength>,
+    ),
+    PushVerticalGridLineSidewaysToAccommodateBlockWidth(
+        VerticalGridLineIndex,
+        Option<ConstraintStrength>,
+    ),
+    FloatAfterBlockByDistance(BlockIndex, f64, Option<ConstraintStrength>),
+    FloatBeforeBlockByDistance(BlockIndex, f64, Option<ConstraintStrength>),
+    FloatAboveBlockByDistance(BlockIndex, f64, Option<ConstraintStrength>),
+    FloatBeneathBlockByDistance(BlockIndex, f64, Option<ConstraintStrength>),
+    LockStartToBlockStart(BlockIndex, Option<ConstraintStrength>),
+    LockEndToBlockEnd(BlockIndex, Option<ConstraintStrength>),
+    LockTopToBlockTop(BlockIndex, Option<ConstraintStrength>),
+    LockBottomToBlockBottom(BlockIndex, Option<ConstraintStrength>),
+    LockHorizontalCenterBetweenBlocks(BlockIndex, BlockIndex, Option<ConstraintStrength>),
+    LockVerticalCenterBetweenBlocks(BlockIndex, BlockIndex, Option<ConstraintStrength>),
+    LockHorizontalCenterToBlockCenter(BlockIndex, Option<ConstraintStrength>),
+    FloatHorizontalCenterToBlockCenter(BlockIndex, Option<ConstraintStrength>),
+    LockVerticalCenterToBlockCenter(BlockIndex, Option<ConstraintStrength>),
+    LockAfterBlockByDistance(BlockIndex, f64, Option<ConstraintStrength>),
+    LockBeforeBlockByDistance(BlockIndex, f64, Option<ConstraintStrength>),
+    LockAboveBlockByDistance(BlockIndex, f64, Option<ConstraintStrength>),
+    LockBeneathBlockByDistance(BlockIndex, f64, Option<ConstraintStrength>),
+    LockTopToBlockCenter(BlockIndex, Option<ConstraintStrength>),
+    LockBottomToBlockCenter(BlockIndex, Option<ConstraintStrength>),
+}
+
+/// A rectangular item of notation positioned in a LayoutSystem by BlockConstraints.
+///
+/// Each builder method adds its constraints with the default strength for its constraint
+/// type; a matching *_with_strength() method adds the same constraints with the given
+/// strength instead.
+pub trait Block {
+    /// Returns true if the width of this Block is fixed by its content. Blocks without
+    /// a fixed width stretch between the grid lines they are constrained to.
+    fn is_fixed_width(&self) -> bool;
+
+    fn get_fixed_width(&self) -> StaveSpaces;
+
+    /// Returns true if the height of this Block is fixed by its content. Blocks without
+    /// a fixed height stretch between the grid lines they are constrained to.
+    fn is_fixed_height(&self) -> bool;
+
+    fn get_fixed_height(&self) -> StaveSpaces;
+
+    /// Returns the distance between the bottom of this Block and its baseline.
+    #[inline]
+    fn get_descent(&self) -> StaveSpaces {
+        STAVE_SPACES_ZERO
+    }
+
+    /// Returns true if this Block represents rhythmic space rather than notation.
+    /// The width of spacing blocks is stretched or compressed during justification.
+    #[inline]
+    fn is_spacing_block(&self) -> bool {
+        false
+    }
+
+    fn get_source_moment_spine_item(&self) -> Option<&MomentSpineItem>;
+
+    fn get_source_onset(&self) -> Option<Ticks>;
+
+    /// Sets the onset of the musical event this Block was created from. Spacing blocks
+    /// are typically created without a source, but need an onset for the rhythmic spacing
+    /// model to determine the duration they represent.
+    fn set_source_onset(&mut self, onset: Option<Ticks>);
+
+    fn get_source_part_index(&self) -> Option<PartIndex>;
+
+    fn get_source_voice_index(&self) -> Option<VoiceIndex>;
+
+    fn get_layer(&self) -> BlockLayer;
+
+    fn is_visible(&self) -> bool;
+
+    fn set_visible(&mut self, is_visible: bool);
+
+    /// Returns true if this Block takes part in collision detection.
+    fn is_collidable(&self) -> bool;
+
+    fn set_collidable(&mut self, is_collidable: bool);
+
+    fn get_can_move_up_to_avoid_vertical_collision(&self) -> bool;
+
+    fn set_can_move_up_to_avoid_vertical_collision(&mut self, can_move: bool);
+
+    fn get_can_move_down_to_avoid_vertical_collision(&self) -> bool;
+
+    fn set_can_move_down_to_avoid_vertical_collision(&mut self, can_move: bool);
+
+    /// Returns the Skyline of this Block, if one has been set. Blocks without a Skyline
+    /// are treated as solid rectangles during collision detection.
+    fn get_skyline(&self) -> Option<&Skyline>;
+
+    /// Sets the Skyline of this Block, typically built from the SMuFL bounding box cut-outs
+    /// of the glyph the Block engraves. Collision detection and resolution will follow
+    /// the Skyline rather than the Block's bounding box, allowing closely nested symbols
+    /// to sit as tightly as they would in hand-engraved music.
+    fn set_skyline(&mut self, skyline: Option<Skyline>);
+
+    fn get_top_padding(&self) -> StaveSpaces;
+
+    fn set_top_padding(&mut self, padding: StaveSpaces);
+
+    fn get_bottom_padding(&self) -> StaveSpaces;
+
+    fn set_bottom_padding(&mut self, padding: StaveSpaces);
+
+    fn get_start_padding(&self) -> StaveSpaces;
+
+    fn set_start_padding(&mut self, padding: StaveSpaces);
+
+    fn get_end_padding(&self) -> StaveSpaces;
+
+    fn set_end_padding(&mut self, padding: StaveSpaces);
+
+    /// Returns the width below which this spacing block may not be compressed when
+    /// its system is overfull, if one has been set. Spacing blocks without a minimum width
+    /// may be compressed down to their ideal width multiplied by the system's minimum
+    /// compression ratio.
+    fn get_minimum_width(&self) -> Option<StaveSpaces>;
+
+    fn set_minimum_width(&mut self, minimum_width: Option<StaveSpaces>);
+
+    fn get_constraints(&self) -> &[BlockConstraint];
+
+    fn add_constraint(&mut self, constraint: BlockConstraint);
+
+    /// Locks the top of this Block to the given HorizontalGridLine.
+    #[inline]
+    fn lock_top_to_grid_line(&mut self, grid_line: HorizontalGridLineIndex) {
+        self.add_constraint(BlockConstraint::LockTopToHorizontalGridLine(
+            grid_line, None,
+        ));
+    }
+
+    #[inline]
+    fn lock_top_to_grid_line_with_strength(
+        &mut self,
+        grid_line: HorizontalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(BlockConstraint::LockTopToHorizontalGridLine(
+            grid_line,
+            Some(strength),
+        ));
+    }
+
+    /// Locks the bottom of this Block to the given HorizontalGridLine.
+    #[inline]
+    fn lock_bottom_to_grid_line(&mut self, grid_line: HorizontalGridLineIndex) {
+        self.add_constraint(BlockConstraint::LockBottomToHorizontalGridLine(
+            grid_line, None,
+        ));
+    }
+
+    #[inline]
+    fn lock_bottom_to_grid_line_with_strength(
+        &mut self,
+        grid_line: HorizontalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(BlockConstraint::LockBottomToHorizontalGridLine(
+            grid_line,
+            Some(strength),
+        ));
+    }
+
+    /// Locks the vertical center of this Block to the given HorizontalGridLine.
+    #[inline]
+    fn lock_vertical_center_to_grid_line(&mut self, grid_line: HorizontalGridLineIndex) {
+        self.add_constraint(BlockConstraint::LockVerticalCenterToHorizontalGridLine(
+            grid_line, None,
+        ));
+    }
+
+    #[inline]
+    fn lock_vertical_center_to_grid_line_with_strength(
+        &mut self,
+        grid_line: HorizontalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(BlockConstraint::LockVerticalCenterToHorizontalGridLine(
+            grid_line,
+            Some(strength),
+        ));
+    }
+
+    /// Locks the vertical center of this Block halfway between the given HorizontalGridLines.
+    #[inline]
+    fn lock_vertical_center_between_grid_lines(
+        &mut self,
+        grid_line_above: HorizontalGridLineIndex,
+        grid_line_below: HorizontalGridLineIndex,
+    ) {
+        self.add_constraint(
+            BlockConstraint::LockVerticalCenterHalfwayBetweenHorizontalGridLines(
+                grid_line_above,
+                grid_line_below,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    fn lock_vertical_center_between_grid_lines_with_strength(
+        &mut self,
+        grid_line_above: HorizontalGridLineIndex,
+        grid_line_below: HorizontalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            BlockConstraint::LockVerticalCenterHalfwayBetweenHorizontalGridLines(
+                grid_line_above,
+                grid_line_below,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Floats this Block between the given HorizontalGridLines, pushing the lower grid line
+    /// down if necessary to accommodate the height of the Block.
+    #[inline]
+    fn float_vertically_between_grid_lines(
+        &mut self,
+        grid_line_above: HorizontalGridLineIndex,
+        grid_line_below: HorizontalGridLineIndex,
+    ) {
+        self.add_constraint(BlockConstraint::FloatTopAfterHorizontalGridLine(
+            grid_line_above,
+            None,
+        ));
+        self.add_constraint(BlockConstraint::FloatBottomBeforeHorizontalGridLine(
+            grid_line_below,
+            None,
+        ));
+        self.add_constraint(
+            BlockConstraint::PushHorizontalGridLineDownToAccommodateBlockHeight(
+                grid_line_below,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    fn float_vertically_between_grid_lines_with_strength(
+        &mut self,
+        grid_line_above: HorizontalGridLineIndex,
+        grid_line_below: HorizontalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(BlockConstraint::FloatTopAfterHorizontalGridLine(
+            grid_line_above,
+            Some(strength),
+        ));
+        self.add_constraint(BlockConstraint::FloatBottomBeforeHorizontalGridLine(
+            grid_line_below,
+            Some(strength),
+        ));
+        self.add_constraint(
+            BlockConstraint::PushHorizontalGridLineDownToAccommodateBlockHeight(
+                grid_line_below,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Locks the start of this Block to the given VerticalGridLine.
+    #[inline]
+    fn lock_start_to_grid_line(&mut self, grid_line: VerticalGridLineIndex) {
+        self.add_constraint(BlockConstraint::LockStartToVerticalGridLine(
+            grid_line, None,
+        ));
+    }
+
+    #[inline]
+    fn lock_start_to_grid_line_with_strength(
+        &mut self,
+        grid_line: VerticalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(BlockConstraint::LockStartToVerticalGridLine(
+            grid_line,
+            Some(strength),
+        ));
+    }
+
+    /// Locks the end of this Block to the given VerticalGridLine.
+    #[inline]
+    fn lock_end_to_grid_line(&mut self, grid_line: VerticalGridLineIndex) {
+        self.add_constraint(BlockConstraint::LockEndToVerticalGridLine(grid_line, None));
+    }
+
+    #[inline]
+    fn lock_end_to_grid_line_with_strength(
+        &mut self,
+        grid_line: VerticalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(BlockConstraint::LockEndToVerticalGridLine(
+            grid_line,
+            Some(strength),
+        ));
+    }
+
+    /// Locks the horizontal center of this Block to the given VerticalGridLine.
+    #[inline]
+    fn lock_horizontal_center_to_grid_line(&mut self, grid_line: VerticalGridLineIndex) {
+        self.add_constraint(BlockConstraint::LockHorizontalCenterToVerticalGridLine(
+            grid_line, None,
+        ));
+    }
+
+    #[inline]
+    fn lock_horizontal_center_to_grid_line_with_strength(
+        &mut self,
+        grid_line: VerticalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(BlockConstraint::LockHorizontalCenterToVerticalGridLine(
+            grid_line,
+            Some(strength),
+        ));
+    }
+
+    /// Locks the horizontal center of this Block halfway between the given VerticalGridLines.
+    #[inline]
+    fn lock_horizontal_center_between_grid_lines(
+        &mut self,
+        grid_line_before: VerticalGridLineIndex,
+        grid_line_after: VerticalGridLineIndex,
+    ) {
+        self.add_constraint(
+            BlockConstraint::LockHorizontalCenterHalfwayBetweenVerticalGridLines(
+                grid_line_before,
+                grid_line_after,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    fn lock_horizontal_center_between_grid_lines_with_strength(
+        &mut self,
+        grid_line_before: VerticalGridLineIndex,
+        grid_line_after: VerticalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            BlockConstraint::LockHorizontalCenterHalfwayBetweenVerticalGridLines(
+                grid_line_before,
+                grid_line_after,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Floats this Block between the given VerticalGridLines, pushing the later grid line
+    /// sideways if necessary to accommodate the width of the Block.
+    #[inline]
+    fn float_horizontally_between_grid_lines(
+        &mut self,
+        grid_line_before: VerticalGridLineIndex,
+        grid_line_after: VerticalGridLineIndex,
+    ) {
+        self.add_constraint(BlockConstraint::FloatStartAfterVerticalGridLine(
+            grid_line_before,
+            None,
+        ));
+        self.add_constraint(BlockConstraint::FloatEndBeforeVerticalGridLine(
+            grid_line_after,
+            None,
+        ));
+        self.add_constraint(
+            BlockConstraint::PushVerticalGridLineSidewaysToAccommodateBlockWidth(
+                grid_line_after,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    fn float_horizontally_between_grid_lines_with_strength(
+        &mut self,
+        grid_line_before: VerticalGridLineIndex,
+        grid_line_after: VerticalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(BlockConstraint::FloatStartAfterVerticalGridLine(
+            grid_line_before,
+            Some(strength),
+        ));
+        self.add_constraint(BlockConstraint::FloatEndBeforeVerticalGridLine(
+            grid_line_after,
+            Some(strength),
+        ));
+        self.add_constraint(
+            BlockConstraint::PushVerticalGridLineSidewaysToAccommodateBlockWidth(
+                grid_line_after,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Locks the start of this Block to the earlier of the given VerticalGridLines, pushing
+    /// the later grid line sideways if necessary to accommodate the width of the Block
+    /// and the given end padding.
+    #[inline]
+    fn lock_start_between_grid_lines(
+        &mut self,
+        grid_line_before: VerticalGridLineIndex,
+        grid_line_after: VerticalGridLineIndex,
+        end_padding: StaveSpaces,
+    ) {
+        self.set_end_padding(end_padding);
+        self.add_constraint(BlockConstraint::LockStartToVerticalGridLine(
+            grid_line_before,
+            None,
+        ));
+        self.add_constraint(
+            BlockConstraint::PushVerticalGridLineSidewaysToAccommodateBlockWidth(
+                grid_line_after,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    fn lock_start_between_grid_lines_with_strength(
+        &mut self,
+        grid_line_before: VerticalGridLineIndex,
+        grid_line_after: VerticalGridLineIndex,
+        end_padding: StaveSpaces,
+        strength: ConstraintStrength,
+    ) {
+        self.set_end_padding(end_padding);
+        self.add_constraint(BlockConstraint::LockStartToVerticalGridLine(
+            grid_line_before,
+            Some(strength),
+        ));
+        self.add_constraint(
+            BlockConstraint::PushVerticalGridLineSidewaysToAccommodateBlockWidth(
+                grid_line_after,
+                Some(strength),
+            ),
+        );
+    }
+}
+
+/// A Block of any kind, so that Blocks of different kinds can be held together
+/// in a single LayoutSystem.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BlockEnum {
+    Glyph(GlyphBlock),
+    Line(LineBlock),
+    Markup(MarkupBlock),
+    Spacing(SpacingBlock),
+    Beam(BeamBlock),
+    Curve(CurveBlock),
+    Spanner(SpannerBlock),
+}
+
+impl BlockEnum {
+    #[inline]
+    fn as_block(&self) -> &dyn Block {
+        match self {
+            BlockEnum::Glyph(block) => block,
+            BlockEnum::Line(block) => block,
+            BlockEnum::Markup(block) => block,
+            BlockEnum::Spacing(block) => block,
+            BlockEnum::Beam(block) => block,
+            BlockEnum::Curve(block) => block,
+            BlockEnum::Spanner(block) => block,
+        }
+    }
+
+    #[inline]
+    fn as_block_mut(&mut self) -> &mut dyn Block {
+        match self {
+            BlockEnum::Glyph(block) => block,
+            BlockEnum::Line(block) => block,
+            BlockEnum::Markup(block) => block,
+            BlockEnum::Spacing(block) => block,
+            BlockEnum::Beam(block) => block,
+            BlockEnum::Curve(block) => block,
+            BlockEnum::Spanner(block) => block,
+        }
+    }
+}
+
+impl Block for BlockEnum {
+    #[inline]
+    fn is_fixed_width(&self) -> bool {
+        self.as_block().is_fixed_width()
+    }
+
+    #[inline]
+    fn get_fixed_width(&self) -> StaveSpaces {
+        self.as_block().get_fixed_width()
+    }
+
+    #[inline]
+    fn is_fixed_height(&self) -> bool {
+        self.as_block().is_fixed_height()
+    }
+
+    #[inline]
+    fn get_fixed_height(&self) -> StaveSpaces {
+        self.as_block().get_fixed_height()
+    }
+
+    #[inline]
+    fn get_descent(&self) -> StaveSpaces {
+        self.as_block().get_descent()
+    }
+
+    #[inline]
+    fn is_spacing_block(&self) -> bool {
+        self.as_block().is_spacing_block()
+    }
+
+    #[inline]
+    fn get_source_moment_spine_item(&self) -> Option<&MomentSpineItem> {
+        self.as_block().get_source_moment_spine_item()
+    }
+
+    #[inline]
+    fn get_source_onset(&self) -> Option<Ticks> {
+        self.as_block().get_source_onset()
+    }
+
+    #[inline]
+    fn set_source_onset(&mut self, onset: Option<Ticks>) {
+        self.as_block_mut().set_source_onset(onset);
+    }
+
+    #[inline]
+    fn get_source_part_index(&self) -> Option<PartIndex> {
+        self.as_block().get_source_part_index()
+    }
+
+    #[inline]
+    fn get_source_voice_index(&self) -> Option<VoiceIndex> {
+        self.as_block().get_source_voice_index()
+    }
+
+    #[inline]
+    fn get_layer(&self) -> BlockLayer {
+        self.as_block().get_layer()
+    }
+
+    #[inline]
+    fn is_visible(&self) -> bool {
+        self.as_block().is_visible()
+    }
+
+    #[inline]
+    fn set_visible(&mut self, is_visible: bool) {
+        self.as_block_mut().set_visible(is_visible);
+    }
+
+    #[inline]
+    fn is_collidable(&self) -> bool {
+        self.as_block().is_collidable()
+    }
+
+    #[inline]
+    fn set_collidable(&mut self, is_collidable: bool) {
+        self.as_block_mut().set_collidable(is_collidable);
+    }
+
+    #[inline]
+    fn get_can_move_up_to_avoid_vertical_collision(&self) -> bool {
+        self.as_block()
+            .get_can_move_up_to_avoid_vertical_collision()
+    }
+
+    #[inline]
+    fn set_can_move_up_to_avoid_vertical_collision(&mut self, can_move: bool) {
+        self.as_block_mut()
+            .set_can_move_up_to_avoid_vertical_collision(can_move);
+    }
+
+    #[inline]
+    fn get_can_move_down_to_avoid_vertical_collision(&self) -> bool {
+        self.as_block()
+            .get_can_move_down_to_avoid_vertical_collision()
+    }
+
+    #[inline]
+    fn set_can_move_down_to_avoid_vertical_collision(&mut self, can_move: bool) {
+        self.as_block_mut()
+            .set_can_move_down_to_avoid_vertical_collision(can_move);
+    }
+
+    #[inline]
+    fn get_skyline(&self) -> Option<&Skyline> {
+        self.as_block().get_skyline()
+    }
+
+    #[inline]
+    fn set_skyline(&mut self, skyline: Option<Skyline>) {
+        self.as_block_mut().set_skyline(skyline);
+    }
+
+    #[inline]
+    fn get_top_padding(&self) -> StaveSpaces {
+        self.as_block().get_top_padding()
+    }
+
+    #[inline]
+    fn set_top_padding(&mut self, padding: StaveSpaces) {
+        self.as_block_mut().set_top_padding(padding);
+    }
+
+    #[inline]
+    fn get_bottom_padding(&self) -> StaveSpaces {
+        self.as_block().get_bottom_padding()
+    }
+
+    #[inline]
+    fn set_bottom_padding(&mut self, padding: StaveSpaces) {
+        self.as_block_mut().set_bottom_padding(padding);
+    }
+
+    #[inline]
+    fn get_start_padding(&self) -> StaveSpaces {
+        self.as_block().get_start_padding()
+    }
+
+    #[inline]
+    fn set_start_padding(&mut self, padding: StaveSpaces) {
+        self.as_block_mut().set_start_padding(padding);
+    }
+
+    #[inline]
+    fn get_end_padding(&self) -> StaveSpaces {
+        self.as_block().get_end_padding()
+    }
+
+    #[inline]
+    fn set_end_padding(&mut self, padding: StaveSpaces) {
+        self.as_block_mut().set_end_padding(padding);
+    }
+
+    #[inline]
+    fn get_minimum_width(&self) -> Option<StaveSpaces> {
+        self.as_block().get_minimum_width()
+    }
+
+    #[inline]
+    fn set_minimum_width(&mut self, minimum_width: Option<StaveSpaces>) {
+        self.as_block_mut().set_minimum_width(minimum_width);
+    }
+
+    #[inline]
+    fn get_constraints(&self) -> &[BlockConstraint] {
+        self.as_block().get_constraints()
+    }
+
+    #[inline]
+    fn add_constraint(&mut self, constraint: BlockConstraint) {
+        self.as_block_mut().add_constraint(constraint);
+    }
+}
+
+impl From<GlyphBlock> for BlockEnum {
+    #[inline]
+    fn from(block: GlyphBlock) -> Self {
+        BlockEnum::Glyph(block)
+    }
+}
+
+impl From<LineBlock> for BlockEnum {
+    #[inline]
+    fn from(block: LineBlock) -> Self {
+        BlockEnum::Line(block)
+    }
+}
+
+impl From<MarkupBlock> for BlockEnum {
+    #[inline]
+    fn from(block: MarkupBlock) -> Self {
+        BlockEnum::Markup(block)
+    }
+}
+
+impl From<SpacingBlock> for BlockEnum {
+    #[inline]
+    fn from(block: SpacingBlock) -> Self {
+        BlockEnum::Spacing(block)
+    }
+}
+
+impl From<BeamBlock> for BlockEnum {
+    #[inline]
+    fn from(block: BeamBlock) -> Self {
+        BlockEnum::Beam(block)
+    }
+}
+
+impl From<CurveBlock> for BlockEnum {
+    #[inline]
+    fn from(block: CurveBlock) -> Self {
+        BlockEnum::Curve(block)
+    }
+}
+
+impl From<SpannerBlock> for BlockEnum {
+    #[inline]
+    fn from(block: SpannerBlock) -> Self {
+        BlockEnum::Spanner(block)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::concepts::stave_spaces::AsStaveSpacesExt;
+    use crate::models::display::layout::block::spacing::SpacingBlock;
+    use crate::models::display::layout::block::{Block, BlockConstraint, BlockEnum};
+    use crate::models::display::layout::strength::ConstraintStrength;
+
+    #[test]
+    fn test_builders_with_strength() {
+        let mut block: BlockEnum = SpacingBlock::new(1.as_stave_spaces()).into();
+
+        block.lock_start_to_grid_line(0);
+        block.lock_end_to_grid_line_with_strength(1, ConstraintStrength::Medium);
+        block.float_horizontally_between_grid_lines_with_strength(
+            2,
+            3,
+            ConstraintStrength::Custom(5.0),
+        );
+
+        assert_eq!(
+            block.get_constraints(),
+            &[
+                BlockConstraint::LockStartToVerticalGridLine(0, None),
+                BlockConstraint::LockEndToVerticalGridLine(1, Some(ConstraintStrength::Medium)),
+                BlockConstraint::FloatStartAfterVerticalGridLine(
+                    2,
+                    Some(ConstraintStrength::Custom(5.0))
+                ),
+                BlockConstraint::FloatEndBeforeVerticalGridLine(
+                    3,
+                    Some(ConstraintStrength::Custom(5.0))
+                ),
+                BlockConstraint::PushVerticalGridLineSidewaysToAccommodateBlockWidth(
+                    3,
+                    Some(ConstraintStrength::Custom(5.0))
+                ),
+            ]
+        );
+    }
+}
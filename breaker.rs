@@ -0,0 +1,374 @@
+use crate::models::display::concepts::stave_spaces::{StaveSpaces, STAVE_SPACES_ZERO};
+use crate::models::display::layout::system::LayoutSystem;
+use crate::models::music::concepts::ticks::Ticks;
+
+/// The default demerits added to every system in a candidate set of system breaks.
+/// Larger values favour solutions using fewer systems.
+pub const DEFAULT_SYSTEM_PENALTY: f64 = 10.0;
+
+/// The badness assigned to a system that cannot be made to fit the target system width,
+/// i.e. a single bar that is too wide for the system on its own even when compressed. Such systems are only
+/// ever chosen when there is no alternative.
+const OVERFULL_BADNESS: f64 = 10000.0;
+
+/// The horizontal measurements of a single bar, taken from an unjustified engraving
+/// of the music in that bar. The natural width is the full engraved width of the bar;
+/// the rhythmic spacing is that portion of the natural width taken up by spacing blocks,
+/// and is therefore the portion that justification is able to stretch. The shrinkability
+/// is how far those spacing blocks can be compressed in total before reaching their
+/// minimum widths.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BarMeasurement {
+    start_ticks: Ticks,
+    end_ticks: Ticks,
+    natural_width: StaveSpaces,
+    rhythmic_spacing: StaveSpaces,
+    shrinkability: StaveSpaces,
+}
+
+impl BarMeasurement {
+    pub fn new(
+        start_ticks: Ticks,
+        end_ticks: Ticks,
+        natural_width: StaveSpaces,
+        rhythmic_spacing: StaveSpaces,
+        shrinkability: StaveSpaces,
+    ) -> Self {
+        BarMeasurement {
+            start_ticks,
+            end_ticks,
+            natural_width,
+            rhythmic_spacing,
+            shrinkability,
+        }
+    }
+
+    #[inline]
+    pub fn get_start_ticks(&self) -> Ticks {
+        self.start_ticks
+    }
+
+    #[inline]
+    pub fn get_end_ticks(&self) -> Ticks {
+        self.end_ticks
+    }
+
+    #[inline]
+    pub fn get_natural_width(&self) -> StaveSpaces {
+        self.natural_width
+    }
+
+    #[inline]
+    pub fn get_rhythmic_spacing(&self) -> StaveSpaces {
+        self.rhythmic_spacing
+    }
+
+    #[inline]
+    pub fn get_shrinkability(&self) -> StaveSpaces {
+        self.shrinkability
+    }
+}
+
+/// Chooses the system breaks across a movement, in the style of Knuth and Plass's
+/// line breaking algorithm for TeX. Candidate break points are the barlines between
+/// consecutive bars. Rather than filling each system greedily, every feasible set
+/// of breaks is scored and the set with the lowest total demerits is chosen, so that
+/// a tightly packed system early in the movement cannot force a badly stretched system later.
+///
+/// The badness of a single system is derived from the adjustment ratio LayoutSystem
+/// would need to justify the system to the target system width, whether by stretching
+/// or by compressing its spacing blocks; the further the ratio is from 0, the worse
+/// the system.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SystemBreaker {
+    target_system_width: StaveSpaces,
+    system_prefix_width: StaveSpaces,
+    system_penalty: f64,
+    is_last_system_ragged: bool,
+}
+
+impl SystemBreaker {
+    /// Creates a new SystemBreaker from the given arguments. The system prefix width is
+    /// the width of any content repeated at the start of every system, such as clefs
+    /// and key signatures, that does not belong to any bar.
+    pub fn new(
+        target_system_width: StaveSpaces,
+        system_prefix_width: StaveSpaces,
+        system_penalty: f64,
+        is_last_system_ragged: bool,
+    ) -> Self {
+        SystemBreaker {
+            target_system_width,
+            system_prefix_width,
+            system_penalty,
+            is_last_system_ragged,
+        }
+    }
+
+    #[inline]
+    pub fn get_target_system_width(&self) -> StaveSpaces {
+        self.target_system_width
+    }
+
+    #[inline]
+    pub fn get_system_prefix_width(&self) -> StaveSpaces {
+        self.system_prefix_width
+    }
+
+    #[inline]
+    pub fn get_system_penalty(&self) -> f64 {
+        self.system_penalty
+    }
+
+    #[inline]
+    pub fn is_last_system_ragged(&self) -> bool {
+        self.is_last_system_ragged
+    }
+
+    /// Returns the badness of a system containing the given bars, or None if the bars
+    /// cannot be justified to the target system width. A badness of 0 indicates the
+    /// bars fill the system exactly at their natural width. An overfull system is not
+    /// rejected outright: like LayoutSystem, it is compressed as far as its bars' minimum
+    /// widths allow, and is scored by how much of that compression it uses.
+    pub fn get_badness(&self, bars: &[BarMeasurement], is_last_system: bool) -> Option<f64> {
+        let natural_width = self.get_natural_width(bars);
+
+        if is_last_system && self.is_last_system_ragged && natural_width <= self.target_system_width
+        {
+            // The last system will not be stretched, so its fullness does not matter.
+            // It is still compressed if it is overfull.
+
+            return Some(0.0);
+        }
+
+        let (total_rhythmic_spacing, total_shrinkability) = bars.iter().fold(
+            (STAVE_SPACES_ZERO, STAVE_SPACES_ZERO),
+            |(spacing, shrinkability), bar| {
+                (
+                    spacing + bar.rhythmic_spacing,
+                    shrinkability + bar.shrinkability,
+                )
+            },
+        );
+
+        LayoutSystem::get_justification_adjustment_ratio(
+            total_rhythmic_spacing,
+            total_shrinkability,
+            self.target_system_width,
+            natural_width,
+        )
+        .map(|ratio| 100.0 * (ratio as f64).abs().powi(3))
+    }
+
+    /// Returns the range of ticks covered by each system in the optimal set of system
+    /// breaks for the given bars. Every bar is placed in exactly one system, and the
+    /// returned ranges are in movement order.
+    pub fn get_system_breaks(&self, bars: &[BarMeasurement]) -> Vec<(Ticks, Ticks)> {
+        let count = bars.len();
+
+        // best[j] holds the lowest total demerits for setting the first j bars,
+        // along with the index of the bar that starts the last system in that solution.
+
+        let mut best: Vec<Option<(f64, usize)>> = vec![None; count + 1];
+
+        best[0] = Some((0.0, 0));
+
+        for end in 1..=count {
+            for start in (0..end).rev() {
+                let is_single_bar = end - start == 1;
+
+                let badness = match self.get_badness(&bars[start..end], end == count) {
+                    Some(badness) => badness,
+                    None if is_single_bar => OVERFULL_BADNESS,
+                    None => {
+                        if self.is_overfull(&bars[start..end]) {
+                            // Every bar is wider than it can shrink, so adding further bars
+                            // to the start of this system can only make it more overfull.
+                            // There is no point looking further back.
+
+                            break;
+                        }
+
+                        continue;
+                    }
+                };
+
+                if let Some((demerits, _)) = best[start] {
+                    let demerits = demerits + (self.system_penalty + badness).powi(2);
+
+                    let is_better = match best[end] {
+                        Some((best_demerits, _)) => demerits < best_demerits,
+                        None => true,
+                    };
+
+                    if is_better {
+                        best[end] = Some((demerits, start));
+                    }
+                }
+            }
+        }
+
+        // Walk back through the chosen breaks from the end of the movement.
+
+        let mut systems = Vec::new();
+
+        let mut end = count;
+
+        while end > 0 {
+            let start = match best[end] {
+                Some((_, start)) => start,
+                None => end - 1,
+            };
+
+            systems.push((bars[start].start_ticks, bars[end - 1].end_ticks));
+
+            end = start;
+        }
+
+        systems.reverse();
+
+        systems
+    }
+
+    #[inline]
+    fn get_natural_width(&self, bars: &[BarMeasurement]) -> StaveSpaces {
+        bars.iter().fold(self.system_prefix_width, |width, bar| {
+            width + bar.natural_width
+        })
+    }
+
+    /// Returns true if the given bars cannot be compressed to fit the target system width.
+    #[inline]
+    fn is_overfull(&self, bars: &[BarMeasurement]) -> bool {
+        let total_shrinkability = bars.iter().fold(STAVE_SPACES_ZERO, |shrinkability, bar| {
+            shrinkability + bar.shrinkability
+        });
+
+        self.get_natural_width(bars) > self.target_system_width + total_shrinkability
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::concepts::stave_spaces::AsStaveSpacesExt;
+    use crate::models::display::layout::breaker::{
+        BarMeasurement, SystemBreaker, DEFAULT_SYSTEM_PENALTY,
+    };
+    use crate::models::music::concepts::ticks::AsTicksExt;
+
+    #[test]
+    fn test_optimal_breaks() {
+        // Filling systems greedily at their natural widths would give bars [0, 1, 2], [3, 4],
+        // [5], leaving the second system very loose at 55 of 100 stave spaces. The optimal
+        // breaks instead compress the first system slightly to take in bar 3, leaving
+        // the remaining bars for the ragged last system.
+
+        let bars = create_bars(&[30.0, 30.0, 20.0, 30.0, 25.0, 50.0]);
+
+        let breaker = SystemBreaker::new(
+            100.as_stave_spaces(),
+            0.as_stave_spaces(),
+            DEFAULT_SYSTEM_PENALTY,
+            true,
+        );
+
+        assert_eq!(
+            breaker.get_system_breaks(&bars),
+            vec![(0.as_ticks(), 4.as_ticks()), (4.as_ticks(), 6.as_ticks())]
+        );
+    }
+
+    #[test]
+    fn test_overfull_bar() {
+        // A bar too wide for the target system width, even when compressed, is placed
+        // on a system by itself.
+
+        let bars = create_bars(&[40.0, 160.0, 40.0]);
+
+        let breaker = SystemBreaker::new(
+            100.as_stave_spaces(),
+            0.as_stave_spaces(),
+            DEFAULT_SYSTEM_PENALTY,
+            true,
+        );
+
+        assert_eq!(
+            breaker.get_system_breaks(&bars),
+            vec![
+                (0.as_ticks(), 1.as_ticks()),
+                (1.as_ticks(), 2.as_ticks()),
+                (2.as_ticks(), 3.as_ticks()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_badness() {
+        let bars = create_bars(&[40.0, 40.0]);
+
+        let breaker = SystemBreaker::new(
+            100.as_stave_spaces(),
+            20.as_stave_spaces(),
+            DEFAULT_SYSTEM_PENALTY,
+            true,
+        );
+
+        // The system prefix fills the system exactly.
+
+        assert_eq!(breaker.get_badness(&bars, false), Some(0.0));
+
+        let breaker = SystemBreaker::new(
+            100.as_stave_spaces(),
+            0.as_stave_spaces(),
+            DEFAULT_SYSTEM_PENALTY,
+            true,
+        );
+
+        // 40 stave spaces of rhythmic spacing must stretch to fill 60.
+
+        assert_eq!(breaker.get_badness(&bars, false), Some(12.5));
+        assert_eq!(breaker.get_badness(&bars, true), Some(0.0));
+
+        let breaker = SystemBreaker::new(
+            70.as_stave_spaces(),
+            0.as_stave_spaces(),
+            DEFAULT_SYSTEM_PENALTY,
+            true,
+        );
+
+        // 10 stave spaces of overflow must be taken from 20 stave spaces of shrinkability.
+        // An overfull last system is compressed too, so its badness still counts.
+
+        assert_eq!(breaker.get_badness(&bars, false), Some(12.5));
+        assert_eq!(breaker.get_badness(&bars, true), Some(12.5));
+
+        let breaker = SystemBreaker::new(
+            50.as_stave_spaces(),
+            0.as_stave_spaces(),
+            DEFAULT_SYSTEM_PENALTY,
+            true,
+        );
+
+        // 30 stave spaces of overflow cannot be taken from 20 stave spaces of shrinkability.
+
+        assert_eq!(breaker.get_badness(&bars, false), None);
+    }
+
+    /// Creates a bar for each of the given natural widths, one tick long, with half
+    /// of each bar's width given over to rhythmic spacing, which can shrink by half.
+    fn create_bars(widths: &[f32]) -> Vec<BarMeasurement> {
+        widths
+            .iter()
+            .enumerate()
+            .map(|(index, width)| {
+                BarMeasurement::new(
+                    (index as u32).as_ticks(),
+                    (index as u32 + 1).as_ticks(),
+                    (*width).as_stave_spaces(),
+                    (width / 2.0).as_stave_spaces(),
+                    (width / 4.0).as_stave_spaces(),
+                )
+            })
+            .collect()
+    }
+}
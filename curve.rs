@@ -0,0 +1,757 @@
+use crate::models::display::concepts::color::Color;
+use crate::models::display::concepts::stave_spaces::{StavePoint, StaveSpaces, STAVE_SPACES_ZERO};
+use crate::models::display::layout::block::{
+    Block, BlockConstraint, BlockEnum, BlockIndex, BlockLayer,
+};
+use crate::models::display::layout::skyline::Skyline;
+use crate::models::music::concepts::ticks::Ticks;
+use crate::models::music::spine::{MomentSpineItem, PartIndex, VoiceIndex};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+pub type CurveIndex = usize;
+
+/// The default thickness of a slur or tie at its thickest point, in stave spaces.
+pub const DEFAULT_CURVE_THICKNESS: f32 = 0.2;
+
+/// The default minimum height of a slur or tie above (or below) the straight line
+/// joining its ends, in stave spaces.
+pub const DEFAULT_MINIMUM_CURVE_HEIGHT: f32 = 0.5;
+
+/// The default maximum height of a slur or tie above (or below) the straight line
+/// joining its ends, in stave spaces. Curves that would need to be taller than this
+/// to clear the notation beneath them are moved bodily away from it instead.
+pub const DEFAULT_MAXIMUM_CURVE_HEIGHT: f32 = 3.0;
+
+/// The default gap, in stave spaces, left between the ends of a slur or tie and the
+/// Blocks it is anchored to.
+pub const DEFAULT_CURVE_ENDPOINT_GAP: f32 = 0.25;
+
+/// The height of a curve, as a proportion of its width, before obstacles are considered.
+const CURVE_HEIGHT_RATIO: f64 = 0.15;
+
+/// The number of straight line segments used to approximate a curve when flattening it,
+/// either to measure its extent or to engrave it.
+pub const CURVE_FLATTENING_SEGMENTS: usize = 16;
+
+/// The kind of notation a curve represents. A slur runs between the outer edges of
+/// its anchors; a tie runs from the end of its start anchor to the start of its end anchor.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CurveKind {
+    Slur,
+    Tie,
+}
+
+/// Which way a curve bows: above its anchors, or beneath them.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CurveDirection {
+    Above,
+    Below,
+}
+
+/// A slur or tie anchored to two Blocks in a LayoutSystem. A curve's ends are taken from
+/// the solved positions of its anchors, and its shape is chosen once the layout has settled
+/// so that it clears any collidable Blocks lying between its anchors. Each curve is
+/// accompanied by a CurveBlock occupying the curve's extent in the layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutCurve {
+    kind: CurveKind,
+    direction: CurveDirection,
+    start_anchor: BlockIndex,
+    end_anchor: BlockIndex,
+    thickness: StaveSpaces,
+    minimum_height: StaveSpaces,
+    maximum_height: StaveSpaces,
+    endpoint_gap: StaveSpaces,
+}
+
+impl LayoutCurve {
+    /// Creates a new LayoutCurve of the given kind, bowing in the given direction,
+    /// running from the Block at the given start anchor index to the Block at the
+    /// given end anchor index.
+    pub fn new(
+        kind: CurveKind,
+        direction: CurveDirection,
+        start_anchor: BlockIndex,
+        end_anchor: BlockIndex,
+    ) -> Self {
+        LayoutCurve {
+            kind,
+            direction,
+            start_anchor,
+            end_anchor,
+            thickness: StaveSpaces::new(DEFAULT_CURVE_THICKNESS),
+            minimum_height: StaveSpaces::new(DEFAULT_MINIMUM_CURVE_HEIGHT),
+            maximum_height: StaveSpaces::new(DEFAULT_MAXIMUM_CURVE_HEIGHT),
+            endpoint_gap: StaveSpaces::new(DEFAULT_CURVE_ENDPOINT_GAP),
+        }
+    }
+
+    #[inline]
+    pub fn get_kind(&self) -> CurveKind {
+        self.kind
+    }
+
+    #[inline]
+    pub fn get_direction(&self) -> CurveDirection {
+        self.direction
+    }
+
+    #[inline]
+    pub fn get_start_anchor(&self) -> BlockIndex {
+        self.start_anchor
+    }
+
+    #[inline]
+    pub fn get_end_anchor(&self) -> BlockIndex {
+        self.end_anchor
+    }
+
+    #[inline]
+    pub fn get_thickness(&self) -> StaveSpaces {
+        self.thickness
+    }
+
+    /// Sets the thickness of this curve. Defaults to DEFAULT_CURVE_THICKNESS.
+    #[inline]
+    pub fn set_thickness(&mut self, thickness: StaveSpaces) {
+        self.thickness = thickness;
+    }
+
+    #[inline]
+    pub fn get_minimum_height(&self) -> StaveSpaces {
+        self.minimum_height
+    }
+
+    /// Sets the minimum height of this curve, typically taken from the stylesheet.
+    /// Defaults to DEFAULT_MINIMUM_CURVE_HEIGHT.
+    #[inline]
+    pub fn set_minimum_height(&mut self, height: StaveSpaces) {
+        self.minimum_height = height;
+    }
+
+    #[inline]
+    pub fn get_maximum_height(&self) -> StaveSpaces {
+        self.maximum_height
+    }
+
+    /// Sets the maximum height of this curve, typically taken from the stylesheet.
+    /// Defaults to DEFAULT_MAXIMUM_CURVE_HEIGHT.
+    #[inline]
+    pub fn set_maximum_height(&mut self, height: StaveSpaces) {
+        self.maximum_height = height;
+    }
+
+    #[inline]
+    pub fn get_endpoint_gap(&self) -> StaveSpaces {
+        self.endpoint_gap
+    }
+
+    /// Sets the gap left between the ends of this curve and its anchors.
+    /// Defaults to DEFAULT_CURVE_ENDPOINT_GAP.
+    #[inline]
+    pub fn set_endpoint_gap(&mut self, gap: StaveSpaces) {
+        self.endpoint_gap = gap;
+    }
+
+    /// Returns the direction in which positions move as this curve bows outwards:
+    /// towards the top of the system for a curve above its anchors, with a smaller
+    /// y value, or towards the bottom for a curve beneath them.
+    #[inline]
+    fn get_outwards(&self) -> f64 {
+        match self.direction {
+            CurveDirection::Above => -1.0,
+            CurveDirection::Below => 1.0,
+        }
+    }
+
+    /// Returns the (x, y) positions of the ends of this curve, given the solved
+    /// (start..end) and (top..bottom) ranges of its start and end anchors.
+    pub fn get_endpoints(
+        &self,
+        start_anchor: (&Range<f64>, &Range<f64>),
+        end_anchor: (&Range<f64>, &Range<f64>),
+    ) -> ((f64, f64), (f64, f64)) {
+        let gap = self.endpoint_gap.value as f64;
+
+        let outwards = self.get_outwards();
+
+        let (start_x, start_y) = start_anchor;
+
+        let (end_x, end_y) = end_anchor;
+
+        match self.kind {
+            CurveKind::Slur => {
+                // A slur starts and ends just clear of the outer edge of each anchor,
+                // horizontally centered on the anchor.
+
+                let edge = |y: &Range<f64>| match self.direction {
+                    CurveDirection::Above => y.start - gap,
+                    CurveDirection::Below => y.end + gap,
+                };
+
+                (
+                    ((start_x.start + start_x.end) / 2.0, edge(start_y)),
+                    ((end_x.start + end_x.end) / 2.0, edge(end_y)),
+                )
+            }
+            CurveKind::Tie => {
+                // A tie spans the space between its anchors, starting and ending
+                // slightly outwards of the vertical center of each anchor.
+
+                (
+                    (
+                        start_x.end + gap,
+                        (start_y.start + start_y.end) / 2.0 + gap * outwards,
+                    ),
+                    (
+                        end_x.start - gap,
+                        (end_y.start + end_y.end) / 2.0 + gap * outwards,
+                    ),
+                )
+            }
+        }
+    }
+
+    /// Returns the shape of this curve running between the given (x, y) end positions,
+    /// bowing just far enough to clear each of the given obstacles, given as (start..end)
+    /// and (top..bottom) rectangles, by at least the given gap.
+    ///
+    /// The control points of the curve are placed a third and two thirds of the way between
+    /// its ends, so the curve's x position advances evenly along it, and its height above
+    /// (or below) the straight line joining its ends at a fraction t of the way along is
+    /// 4t(1 - t) times its height at its midpoint. This makes the height needed to clear
+    /// any single point of an obstacle easy to calculate. If clearing an obstacle needs
+    /// a curve taller than the maximum height, the curve is instead moved bodily away from
+    /// the obstacle, ends and all.
+    pub fn get_shape(
+        &self,
+        start: (f64, f64),
+        end: (f64, f64),
+        obstacles: &[(Range<f64>, Range<f64>)],
+        gap: f64,
+    ) -> EngravedCurve {
+        let (start_x, start_y) = start;
+
+        let (end_x, end_y) = end;
+
+        let width = end_x - start_x;
+
+        let outwards = self.get_outwards();
+
+        let half_thickness = self.thickness.value as f64 / 2.0;
+
+        let minimum_height = self.minimum_height.value as f64;
+
+        let maximum_height = (self.maximum_height.value as f64).max(minimum_height);
+
+        // For every point on every obstacle lying between the ends of the curve, determine
+        // how far outwards of the straight line joining the ends the curve must reach there
+        // to clear the obstacle. Since the curve bows fastest at its ends and flattens
+        // towards its midpoint, checking the ends of each obstacle, and the curve's midpoint
+        // if the obstacle spans it, is sufficient.
+
+        let mut requirements = Vec::new();
+
+        if width > 0.0 {
+            for (x, y) in obstacles {
+                let overlap_start = x.start.max(start_x);
+
+                let overlap_end = x.end.min(end_x);
+
+                if overlap_end <= overlap_start {
+                    continue;
+                }
+
+                let midpoint = (start_x + end_x) / 2.0;
+
+                let mut samples = vec![overlap_start, overlap_end];
+
+                if overlap_start < midpoint && midpoint < overlap_end {
+                    samples.push(midpoint);
+                }
+
+                for sample in samples {
+                    let t = (sample - start_x) / width;
+
+                    let chord = start_y + (end_y - start_y) * t;
+
+                    let required = match self.direction {
+                        CurveDirection::Above => chord + half_thickness + gap - y.start,
+                        CurveDirection::Below => y.end + half_thickness + gap - chord,
+                    };
+
+                    if required > 0.0 {
+                        requirements.push((4.0 * t * (1.0 - t), required));
+                    }
+                }
+            }
+        }
+
+        let natural_height = (width.max(0.0) * CURVE_HEIGHT_RATIO)
+            .max(minimum_height)
+            .min(maximum_height);
+
+        let height = requirements
+            .iter()
+            .filter(|(profile, _)| *profile > 0.0)
+            .map(|(profile, required)| required / profile)
+            .fold(natural_height, f64::max)
+            .min(maximum_height);
+
+        // Any requirement the curve still cannot meet at its maximum height is met by
+        // moving the whole curve outwards.
+
+        let shift = requirements
+            .iter()
+            .map(|(profile, required)| required - height * profile)
+            .fold(0.0, f64::max);
+
+        let point = |x: f64, y: f64| {
+            StavePoint::new(
+                StaveSpaces::new(x as f32),
+                StaveSpaces::new((y + shift * outwards) as f32),
+            )
+        };
+
+        // With control points a third of the way along, a cubic Bezier curve's midpoint
+        // reaches three quarters of the way to its control points.
+
+        let control_offset = height * 4.0 / 3.0 * outwards;
+
+        EngravedCurve::new(
+            point(start_x, start_y),
+            point(
+                start_x + width / 3.0,
+                start_y + (end_y - start_y) / 3.0 + control_offset,
+            ),
+            point(
+                start_x + width * 2.0 / 3.0,
+                start_y + (end_y - start_y) * 2.0 / 3.0 + control_offset,
+            ),
+            point(end_x, end_y),
+            self.thickness,
+        )
+    }
+}
+
+/// A Block standing in for a slur or tie in its LayoutSystem. LayoutSystem::add_curve() adds
+/// a CurveBlock alongside every curve, taking its source from the curve's start anchor.
+/// Once the curve has been shaped, the solver fits the CurveBlock's bounding box around it,
+/// so the curve contributes to the extent of the engraved system.
+///
+/// Curves are shaped around Blocks that have already settled, so a CurveBlock takes no part
+/// in collision detection. A CurveBlock is never engraved; the curve is engraved separately
+/// as a run of lines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurveBlock {
+    source_moment_spine_item: Option<MomentSpineItem>,
+    source_onset: Option<Ticks>,
+    source_part_index: Option<PartIndex>,
+    source_voice_index: Option<VoiceIndex>,
+    is_visible: bool,
+    is_collidable: bool,
+    can_move_up_to_avoid_vertical_collision: bool,
+    can_move_down_to_avoid_vertical_collision: bool,
+    top_padding: StaveSpaces,
+    bottom_padding: StaveSpaces,
+    start_padding: StaveSpaces,
+    end_padding: StaveSpaces,
+    minimum_width: Option<StaveSpaces>,
+    skyline: Option<Skyline>,
+    constraints: Vec<BlockConstraint>,
+    index: CurveIndex,
+}
+
+impl CurveBlock {
+    /// Creates a new CurveBlock for the curve held at the given index in its LayoutSystem.
+    /// The CurveBlock takes its source from the given start anchor of the curve, if any.
+    pub fn new(index: CurveIndex, start_anchor: Option<&BlockEnum>) -> Self {
+        let source_moment_spine_item =
+            start_anchor.and_then(|anchor| anchor.get_source_moment_spine_item().cloned());
+
+        CurveBlock {
+            source_part_index: source_moment_spine_item
+                .as_ref()
+                .map(|item| item.get_part_index()),
+            source_moment_spine_item,
+            source_onset: start_anchor.and_then(|anchor| anchor.get_source_onset()),
+            source_voice_index: start_anchor.and_then(|anchor| anchor.get_source_voice_index()),
+            is_visible: false,
+            is_collidable: false,
+            can_move_up_to_avoid_vertical_collision: false,
+            can_move_down_to_avoid_vertical_collision: false,
+            top_padding: STAVE_SPACES_ZERO,
+            bottom_padding: STAVE_SPACES_ZERO,
+            start_padding: STAVE_SPACES_ZERO,
+            end_padding: STAVE_SPACES_ZERO,
+            minimum_width: None,
+            skyline: None,
+            constraints: vec![],
+            index,
+        }
+    }
+
+    /// Returns the index of the curve this CurveBlock stands in for.
+    #[inline]
+    pub fn get_curve_index(&self) -> CurveIndex {
+        self.index
+    }
+}
+
+impl Block for CurveBlock {
+    #[inline]
+    fn is_fixed_width(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn get_fixed_width(&self) -> StaveSpaces {
+        STAVE_SPACES_ZERO
+    }
+
+    #[inline]
+    fn is_fixed_height(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn get_fixed_height(&self) -> StaveSpaces {
+        STAVE_SPACES_ZERO
+    }
+
+    #[inline]
+    fn get_source_moment_spine_item(&self) -> Option<&MomentSpineItem> {
+        self.source_moment_spine_item.as_ref()
+    }
+
+    #[inline]
+    fn get_source_onset(&self) -> Option<Ticks> {
+        self.source_onset
+    }
+
+    #[inline]
+    fn set_source_onset(&mut self, onset: Option<Ticks>) {
+        self.source_onset = onset;
+    }
+
+    #[inline]
+    fn get_source_part_index(&self) -> Option<PartIndex> {
+        self.source_part_index
+    }
+
+    #[inline]
+    fn get_source_voice_index(&self) -> Option<VoiceIndex> {
+        self.source_voice_index
+    }
+
+    #[inline]
+    fn get_layer(&self) -> BlockLayer {
+        BlockLayer::Foreground
+    }
+
+    #[inline]
+    fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    #[inline]
+    fn set_visible(&mut self, is_visible: bool) {
+        self.is_visible = is_visible;
+    }
+
+    #[inline]
+    fn is_collidable(&self) -> bool {
+        self.is_collidable
+    }
+
+    #[inline]
+    fn set_collidable(&mut self, is_collidable: bool) {
+        self.is_collidable = is_collidable;
+    }
+
+    #[inline]
+    fn get_can_move_up_to_avoid_vertical_collision(&self) -> bool {
+        self.can_move_up_to_avoid_vertical_collision
+    }
+
+    #[inline]
+    fn set_can_move_up_to_avoid_vertical_collision(&mut self, can_move: bool) {
+        self.can_move_up_to_avoid_vertical_collision = can_move;
+    }
+
+    #[inline]
+    fn get_can_move_down_to_avoid_vertical_collision(&self) -> bool {
+        self.can_move_down_to_avoid_vertical_collision
+    }
+
+    #[inline]
+    fn set_can_move_down_to_avoid_vertical_collision(&mut self, can_move: bool) {
+        self.can_move_down_to_avoid_vertical_collision = can_move;
+    }
+
+    #[inline]
+    fn get_skyline(&self) -> Option<&Skyline> {
+        self.skyline.as_ref()
+    }
+
+    #[inline]
+    fn set_skyline(&mut self, skyline: Option<Skyline>) {
+        self.skyline = skyline;
+    }
+
+    #[inline]
+    fn get_top_padding(&self) -> StaveSpaces {
+        self.top_padding
+    }
+
+    #[inline]
+    fn set_top_padding(&mut self, padding: StaveSpaces) {
+        self.top_padding = padding;
+    }
+
+    #[inline]
+    fn get_bottom_padding(&self) -> StaveSpaces {
+        self.bottom_padding
+    }
+
+    #[inline]
+    fn set_bottom_padding(&mut self, padding: StaveSpaces) {
+        self.bottom_padding = padding;
+    }
+
+    #[inline]
+    fn get_start_padding(&self) -> StaveSpaces {
+        self.start_padding
+    }
+
+    #[inline]
+    fn set_start_padding(&mut self, padding: StaveSpaces) {
+        self.start_padding = padding;
+    }
+
+    #[inline]
+    fn get_end_padding(&self) -> StaveSpaces {
+        self.end_padding
+    }
+
+    #[inline]
+    fn set_end_padding(&mut self, padding: StaveSpaces) {
+        self.end_padding = padding;
+    }
+
+    #[inline]
+    fn get_minimum_width(&self) -> Option<StaveSpaces> {
+        self.minimum_width
+    }
+
+    #[inline]
+    fn set_minimum_width(&mut self, minimum_width: Option<StaveSpaces>) {
+        self.minimum_width = minimum_width;
+    }
+
+    #[inline]
+    fn get_constraints(&self) -> &[BlockConstraint] {
+        self.constraints.as_slice()
+    }
+
+    #[inline]
+    fn add_constraint(&mut self, constraint: BlockConstraint) {
+        self.constraints.push(constraint);
+    }
+}
+
+/// A positioned cubic Bezier curve, ready for rendering: a slur or tie, drawn from its
+/// start point to its end point, pulled towards its two control points. Curves are engraved
+/// as the straight lines of the flattened curve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngravedCurve {
+    start: StavePoint,
+    first_control: StavePoint,
+    second_control: StavePoint,
+    end: StavePoint,
+    thickness: StaveSpaces,
+    color: Color,
+}
+
+impl EngravedCurve {
+    pub fn new(
+        start: StavePoint,
+        first_control: StavePoint,
+        second_control: StavePoint,
+        end: StavePoint,
+        thickness: StaveSpaces,
+    ) -> Self {
+        EngravedCurve {
+            start,
+            first_control,
+            second_control,
+            end,
+            thickness,
+            color: Color::BLACK,
+        }
+    }
+
+    #[inline]
+    pub fn get_start(&self) -> &StavePoint {
+        &self.start
+    }
+
+    #[inline]
+    pub fn get_first_control(&self) -> &StavePoint {
+        &self.first_control
+    }
+
+    #[inline]
+    pub fn get_second_control(&self) -> &StavePoint {
+        &self.second_control
+    }
+
+    #[inline]
+    pub fn get_end(&self) -> &StavePoint {
+        &self.end
+    }
+
+    #[inline]
+    pub fn get_thickness(&self) -> StaveSpaces {
+        self.thickness
+    }
+
+    #[inline]
+    pub fn get_color(&self) -> &Color {
+        &self.color
+    }
+
+    /// Sets the color of this curve. Defaults to Color::BLACK.
+    #[inline]
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    /// Returns the (left, top, right, bottom) extent of this curve, including its thickness.
+    /// The extent is measured from the flattened curve, so it hugs the curve itself rather
+    /// than the looser hull of its control points.
+    pub fn get_extent(&self) -> (f32, f32, f32, f32) {
+        let half_thickness = self.thickness.value / 2.0;
+
+        self.flatten(CURVE_FLATTENING_SEGMENTS).iter().fold(
+            (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+            |(left, top, right, bottom), point| {
+                (
+                    left.min(point.x.value - half_thickness),
+                    top.min(point.y.value - half_thickness),
+                    right.max(point.x.value + half_thickness),
+                    bottom.max(point.y.value + half_thickness),
+                )
+            },
+        )
+    }
+
+    /// Returns the point on this curve a fraction t of the way from its start to its end.
+    pub fn get_point(&self, t: f32) -> StavePoint {
+        let u = 1.0 - t;
+
+        let weights = [u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t];
+
+        let points = [
+            &self.start,
+            &self.first_control,
+            &self.second_control,
+            &self.end,
+        ];
+
+        let (x, y) = weights
+            .iter()
+            .zip(points.iter())
+            .fold((0.0, 0.0), |(x, y), (weight, point)| {
+                (x + weight * point.x.value, y + weight * point.y.value)
+            });
+
+        StavePoint::new(StaveSpaces::new(x), StaveSpaces::new(y))
+    }
+
+    /// Returns the points joining the given number of straight line segments approximating
+    /// this curve, from its start to its end.
+    pub fn flatten(&self, segments: usize) -> Vec<StavePoint> {
+        let segments = segments.max(1);
+
+        (0..=segments)
+            .map(|segment| self.get_point(segment as f32 / segments as f32))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::layout::curve::{CurveDirection, CurveKind, LayoutCurve};
+
+    #[test]
+    fn test_curve_endpoints() {
+        let slur = LayoutCurve::new(CurveKind::Slur, CurveDirection::Above, 0, 1);
+
+        let tie = LayoutCurve::new(CurveKind::Tie, CurveDirection::Below, 0, 1);
+
+        // Two noteheads a stave space high, 4 stave spaces apart.
+
+        let start = (0.0..1.0, 2.0..3.0);
+
+        let end = (4.0..5.0, 2.0..3.0);
+
+        assert_eq!(
+            slur.get_endpoints((&start.0, &start.1), (&end.0, &end.1)),
+            ((0.5, 1.75), (4.5, 1.75))
+        );
+
+        assert_eq!(
+            tie.get_endpoints((&start.0, &start.1), (&end.0, &end.1)),
+            ((1.25, 2.75), (3.75, 2.75))
+        );
+    }
+
+    #[test]
+    fn test_curve_shape() {
+        let slur = LayoutCurve::new(CurveKind::Slur, CurveDirection::Above, 0, 1);
+
+        // With nothing in the way, a slur 10 stave spaces wide bows 1.5 stave spaces
+        // above its ends, so its control points sit 2 stave spaces above them.
+
+        let curve = slur.get_shape((0.0, 4.0), (10.0, 4.0), &[], 0.25);
+
+        assert_eq!(curve.get_first_control().y.value, 2.0);
+        assert!((curve.get_point(0.5).y.value - 2.5).abs() < 0.001);
+
+        // A stem reaching up to 2.0 at the curve's midpoint forces the curve up to clear it:
+        // the curve's lower edge, half its thickness beneath its center line, must sit
+        // a quarter of a stave space above the stem.
+
+        let curve = slur.get_shape((0.0, 4.0), (10.0, 4.0), &[(4.95..5.05, 2.0..4.0)], 0.25);
+
+        assert!((curve.get_point(0.5).y.value - 1.65).abs() < 0.001);
+
+        // An obstacle too tall to clear at the maximum height of 3 stave spaces
+        // lifts the whole curve, ends and all.
+
+        let curve = slur.get_shape((0.0, 4.0), (10.0, 4.0), &[(4.95..5.05, -1.0..4.0)], 0.25);
+
+        assert!((curve.get_start().y.value - 1.65).abs() < 0.001);
+        assert!((curve.get_point(0.5).y.value - -1.35).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_curve_extent() {
+        let slur = LayoutCurve::new(CurveKind::Slur, CurveDirection::Above, 0, 1);
+
+        // The unobstructed slur 10 stave spaces wide reaches 2.5 at its midpoint. Its extent
+        // includes half its thickness of 0.2 stave spaces on every side, and stops well
+        // short of its control points at 2.0.
+
+        let curve = slur.get_shape((0.0, 4.0), (10.0, 4.0), &[], 0.25);
+
+        let (left, top, right, bottom) = curve.get_extent();
+
+        assert!((left - -0.1).abs() < 0.001);
+        assert!((top - 2.4).abs() < 0.001);
+        assert!((right - 10.1).abs() < 0.001);
+        assert!((bottom - 4.1).abs() < 0.001);
+    }
+}
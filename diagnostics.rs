@@ -0,0 +1,529 @@
+use crate::models::display::grid::horizontal::{
+    HorizontalGridLineConstraint, HorizontalGridLineIndex,
+};
+use crate::models::display::grid::vertical::{VerticalGridLineConstraint, VerticalGridLineIndex};
+use crate::models::display::layout::beam::BeamIndex;
+use crate::models::display::layout::block::{BlockConstraint, BlockIndex};
+use crate::models::display::layout::spanner::SpannerIndex;
+use cassowary::strength::{MEDIUM, REQUIRED, STRONG, WEAK};
+use cassowary::{Constraint, RelationalOperator, Solver, Variable};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Where a single constraint added to the linear constraint solver during engraving
+/// came from: a grid line or Block in the layout, or one of the layout passes that run
+/// once all grid line and Block constraints are in place.
+#[derive(Debug, Clone)]
+pub enum ConstraintOrigin {
+    /// The constraint fixing the top edge of the system, at the given HorizontalGridLine, at 0.
+    SystemTopEdge(HorizontalGridLineIndex),
+
+    /// The constraint fixing the leading edge of the system, at the given VerticalGridLine,
+    /// to the aligned start of the system.
+    SystemLeadingEdge(VerticalGridLineIndex),
+
+    /// A constraint on the HorizontalGridLine at the given index.
+    HorizontalGridLine(HorizontalGridLineIndex, HorizontalGridLineConstraint),
+
+    /// A constraint on the VerticalGridLine at the given index.
+    VerticalGridLine(VerticalGridLineIndex, VerticalGridLineConstraint),
+
+    /// The constraint maintaining the width of the fixed width Block at the given index.
+    BlockFixedWidth(BlockIndex),
+
+    /// The constraint maintaining the height of the fixed height Block at the given index.
+    BlockFixedHeight(BlockIndex),
+
+    /// A constraint on the Block at the given index.
+    Block(BlockIndex, BlockConstraint),
+
+    /// A constraint moving the Block at the given index to resolve a collision with another Block.
+    CollisionResolution(BlockIndex),
+
+    /// The constraint setting the spacing block at the given index to its ideal width under
+    /// the system's rhythmic spacing model.
+    RhythmicSpacing(BlockIndex),
+
+    /// A constraint padding or compressing the Block at the given index during system justification.
+    Justification(BlockIndex),
+
+    /// A constraint positioning the beam at the given index, or lengthening one of its stems.
+    Beam(BeamIndex),
+
+    /// A constraint fitting the BeamBlock at the given index around its beam.
+    BeamBlock(BlockIndex),
+
+    /// A constraint fitting the CurveBlock at the given index around its curve.
+    CurveBlock(BlockIndex),
+
+    /// A constraint fitting the SpannerBlock at the given index around its spanner.
+    SpannerBlock(BlockIndex),
+
+    /// The constraint keeping the spanner at the given index at least its minimum length.
+    Spanner(SpannerIndex),
+}
+
+impl Display for ConstraintOrigin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstraintOrigin::SystemTopEdge(index) => {
+                write!(f, "system top edge at horizontal grid line {}", index)
+            }
+            ConstraintOrigin::SystemLeadingEdge(index) => {
+                write!(f, "system leading edge at vertical grid line {}", index)
+            }
+            ConstraintOrigin::HorizontalGridLine(index, constraint) => {
+                write!(f, "horizontal grid line {}: {:?}", index, constraint)
+            }
+            ConstraintOrigin::VerticalGridLine(index, constraint) => {
+                write!(f, "vertical grid line {}: {:?}", index, constraint)
+            }
+            ConstraintOrigin::BlockFixedWidth(index) => write!(f, "block {}: fixed width", index),
+            ConstraintOrigin::BlockFixedHeight(index) => {
+                write!(f, "block {}: fixed height", index)
+            }
+            ConstraintOrigin::Block(index, constraint) => {
+                write!(f, "block {}: {:?}", index, constraint)
+            }
+            ConstraintOrigin::CollisionResolution(index) => {
+                write!(f, "block {}: collision resolution", index)
+            }
+            ConstraintOrigin::RhythmicSpacing(index) => {
+                write!(f, "block {}: rhythmic spacing", index)
+            }
+            ConstraintOrigin::Justification(index) => {
+                write!(f, "block {}: system justification", index)
+            }
+            ConstraintOrigin::Beam(index) => write!(f, "beam {}", index),
+            ConstraintOrigin::BeamBlock(index) => write!(f, "block {}: beam extent", index),
+            ConstraintOrigin::CurveBlock(index) => write!(f, "block {}: curve extent", index),
+            ConstraintOrigin::SpannerBlock(index) => {
+                write!(f, "block {}: spanner extent", index)
+            }
+            ConstraintOrigin::Spanner(index) => write!(f, "spanner {}", index),
+        }
+    }
+}
+
+/// A description of a single constraint added to the solver during engraving:
+/// its origin, and the strength with which the solver applied it.
+#[derive(Debug, Clone)]
+pub struct ConstraintRecord {
+    origin: ConstraintOrigin,
+    strength: f64,
+}
+
+impl ConstraintRecord {
+    pub fn new(origin: ConstraintOrigin, strength: f64) -> Self {
+        ConstraintRecord { origin, strength }
+    }
+
+    #[inline]
+    pub fn get_origin(&self) -> &ConstraintOrigin {
+        &self.origin
+    }
+
+    #[inline]
+    pub fn get_strength(&self) -> f64 {
+        self.strength
+    }
+}
+
+impl Display for ConstraintRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.origin, describe_strength(self.strength))
+    }
+}
+
+/// Returns a human-readable name for the given solver strength.
+pub fn describe_strength(strength: f64) -> String {
+    if strength >= REQUIRED {
+        "required".to_string()
+    } else if strength == STRONG {
+        "strong".to_string()
+    } else if strength == MEDIUM {
+        "medium".to_string()
+    } else if strength == WEAK {
+        "weak".to_string()
+    } else {
+        format!("strength {}", strength)
+    }
+}
+
+/// A record of every constraint currently added to a solver, in the order the constraints
+/// were added, used to diagnose conflicts when the solver rejects a constraint as unsatisfiable.
+#[derive(Default)]
+pub(crate) struct ConstraintLog {
+    entries: Vec<(Constraint, ConstraintRecord)>,
+}
+
+impl ConstraintLog {
+    pub fn new() -> Self {
+        ConstraintLog {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Records that the given constraint, with the given origin, has been added to the solver.
+    pub fn record(&mut self, origin: ConstraintOrigin, constraint: &Constraint) {
+        self.entries.push((
+            constraint.clone(),
+            ConstraintRecord::new(origin, constraint.strength()),
+        ));
+    }
+
+    /// Records that the given constraint has been removed from the solver.
+    pub fn forget(&mut self, constraint: &Constraint) {
+        self.entries.retain(|(recorded, _)| recorded != constraint);
+    }
+
+    /// Returns all constraints currently added to the solver, along with their records.
+    #[inline]
+    pub fn get_entries(&self) -> &[(Constraint, ConstraintRecord)] {
+        self.entries.as_slice()
+    }
+
+    /// Returns a minimal set of constraints that cannot all be satisfied together,
+    /// given that the solver has just rejected the given constraint as unsatisfiable.
+    /// The rejected constraint is always the last entry in the returned set.
+    ///
+    /// Only required constraints can make a solver unsatisfiable, so the search begins
+    /// from every required constraint in the log plus the rejected constraint. Each
+    /// candidate is then dropped in turn; if the remaining candidates still cannot be
+    /// satisfied, the dropped candidate played no part in the conflict and stays dropped.
+    /// Every constraint that survives is therefore necessary for the conflict to occur.
+    pub fn get_minimal_conflicting_set(
+        &self,
+        origin: ConstraintOrigin,
+        constraint: &Constraint,
+    ) -> Vec<ConstraintRecord> {
+        let mut candidates = self
+            .entries
+            .iter()
+            .filter(|(recorded, _)| recorded.strength() >= REQUIRED)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        candidates.push((
+            constraint.clone(),
+            ConstraintRecord::new(origin, constraint.strength()),
+        ));
+
+        if Self::is_satisfiable(candidates.as_slice()) {
+            // The conflict does not involve any other required constraint, so the
+            // rejected constraint is all we can report.
+
+            return candidates
+                .pop()
+                .map(|(_, record)| vec![record])
+                .unwrap_or_default();
+        }
+
+        let mut index = 0;
+
+        while index + 1 < candidates.len() {
+            let candidate = candidates.remove(index);
+
+            if Self::is_satisfiable(candidates.as_slice()) {
+                // The candidate is part of the conflict; put it back.
+
+                candidates.insert(index, candidate);
+
+                index += 1;
+            }
+        }
+
+        candidates.into_iter().map(|(_, record)| record).collect()
+    }
+
+    /// Returns true if the given constraints can all be added to an empty solver.
+    fn is_satisfiable(candidates: &[(Constraint, ConstraintRecord)]) -> bool {
+        let mut solver = Solver::new();
+
+        candidates
+            .iter()
+            .all(|(constraint, _)| solver.add_constraint(constraint.clone()).is_ok())
+    }
+}
+
+/// The edge of a Block positioned by a single solver variable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum BlockEdge {
+    Top,
+    Bottom,
+    Start,
+    End,
+}
+
+/// The property of a beam represented by a single solver variable.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub enum BeamVariable {
+    Start,
+    End,
+    Rise,
+}
+
+/// What a single solver variable represents in the layout.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TraceVariableKind {
+    /// The edit variable used to start align, end align, or center the system.
+    AlignedStart,
+
+    /// The y position of the HorizontalGridLine at the given index, of the given type.
+    HorizontalGridLine {
+        index: HorizontalGridLineIndex,
+        grid_line_type: String,
+    },
+
+    /// The x position of the VerticalGridLine at the given index, of the given type.
+    VerticalGridLine {
+        index: VerticalGridLineIndex,
+        grid_line_type: String,
+    },
+
+    /// The position of the given edge of the Block at the given index.
+    Block { index: BlockIndex, edge: BlockEdge },
+
+    /// The given property of the beam at the given index.
+    Beam {
+        index: BeamIndex,
+        variable: BeamVariable,
+    },
+}
+
+/// A single solver variable in a LayoutTrace, together with its final solved value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceVariable {
+    id: usize,
+    kind: TraceVariableKind,
+    value: f64,
+}
+
+impl TraceVariable {
+    /// Returns the identifier used to refer to this variable from the terms of a TraceConstraint.
+    #[inline]
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    #[inline]
+    pub fn get_kind(&self) -> &TraceVariableKind {
+        &self.kind
+    }
+
+    #[inline]
+    pub fn get_value(&self) -> f64 {
+        self.value
+    }
+}
+
+/// A single term in the linear expression of a TraceConstraint. The variable is the
+/// identifier of a TraceVariable, or None if the solver variable was not created by
+/// the layout.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceTerm {
+    variable: Option<usize>,
+    coefficient: f64,
+}
+
+impl TraceTerm {
+    /// Returns the identifier of the TraceVariable in this term, or None if the solver
+    /// variable was not created by the layout.
+    #[inline]
+    pub fn get_variable(&self) -> Option<usize> {
+        self.variable
+    }
+
+    #[inline]
+    pub fn get_coefficient(&self) -> f64 {
+        self.coefficient
+    }
+}
+
+/// A single constraint in a LayoutTrace. The constraint is expressed as
+/// (sum of terms + constant) (relation) 0, where the relation is one of
+/// "==", "<=", or ">=", exactly as the solver holds it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceConstraint {
+    origin: String,
+    strength: f64,
+    strength_name: String,
+    terms: Vec<TraceTerm>,
+    constant: f64,
+    relation: String,
+}
+
+impl TraceConstraint {
+    /// Returns a human-readable description of the grid line, Block, or layout pass
+    /// this constraint originated from.
+    #[inline]
+    pub fn get_origin(&self) -> &str {
+        self.origin.as_str()
+    }
+
+    #[inline]
+    pub fn get_strength(&self) -> f64 {
+        self.strength
+    }
+
+    /// Returns the terms of the linear expression of this constraint.
+    #[inline]
+    pub fn get_terms(&self) -> &[TraceTerm] {
+        self.terms.as_slice()
+    }
+}
+
+/// A structured record of a single engraving of a LayoutSystem: every variable created
+/// in the linear constraint solver, every constraint in the solver once the layout
+/// settled, and the final solved value of every variable. The trace can be serialized
+/// to JSON, so that external tooling can visualise a layout or diff the layouts
+/// produced by two different builds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayoutTrace {
+    variables: Vec<TraceVariable>,
+    constraints: Vec<TraceConstraint>,
+}
+
+impl LayoutTrace {
+    /// Creates a new LayoutTrace from the given solver variables, each with its kind and
+    /// final value, and the constraints in the given ConstraintLog.
+    pub(crate) fn new(
+        variables: Vec<(Variable, TraceVariableKind, f64)>,
+        log: &ConstraintLog,
+    ) -> Self {
+        let ids = variables
+            .iter()
+            .enumerate()
+            .map(|(id, (variable, _, _))| (*variable, id))
+            .collect::<HashMap<_, _>>();
+
+        let constraints = log
+            .get_entries()
+            .iter()
+            .map(|(constraint, record)| TraceConstraint {
+                origin: record.get_origin().to_string(),
+                strength: record.get_strength(),
+                strength_name: describe_strength(record.get_strength()),
+                terms: constraint
+                    .expr()
+                    .terms
+                    .iter()
+                    .map(|term| TraceTerm {
+                        variable: ids.get(&term.variable).copied(),
+                        coefficient: term.coefficient,
+                    })
+                    .collect(),
+                constant: constraint.expr().constant,
+                relation: match constraint.op() {
+                    RelationalOperator::Equal => "==",
+                    RelationalOperator::LessOrEqual => "<=",
+                    RelationalOperator::GreaterOrEqual => ">=",
+                }
+                .to_string(),
+            })
+            .collect();
+
+        LayoutTrace {
+            variables: variables
+                .into_iter()
+                .enumerate()
+                .map(|(id, (_, kind, value))| TraceVariable { id, kind, value })
+                .collect(),
+            constraints,
+        }
+    }
+
+    /// Returns every solver variable in this trace, in the order the variables were created.
+    #[inline]
+    pub fn get_variables(&self) -> &[TraceVariable] {
+        self.variables.as_slice()
+    }
+
+    /// Returns every constraint in this trace, in the order the constraints were added.
+    #[inline]
+    pub fn get_constraints(&self) -> &[TraceConstraint] {
+        self.constraints.as_slice()
+    }
+
+    /// Returns the amount by which the given constraint is violated by the solved values
+    /// of the variables in this trace, or 0 if the constraint is satisfied. Terms referring
+    /// to variables not in this trace are taken to be 0.
+    pub fn get_violation(&self, constraint: &TraceConstraint) -> f64 {
+        let value = constraint
+            .terms
+            .iter()
+            .map(|term| {
+                term.variable
+                    .and_then(|id| self.variables.get(id))
+                    .map(|variable| variable.value * term.coefficient)
+                    .unwrap_or(0.0)
+            })
+            .sum::<f64>()
+            + constraint.constant;
+
+        match constraint.relation.as_str() {
+            "<=" => value.max(0.0),
+            ">=" => (-value).max(0.0),
+            _ => value.abs(),
+        }
+    }
+
+    /// Serializes this trace to a pretty-printed JSON string.
+    #[inline]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::layout::diagnostics::{ConstraintLog, ConstraintOrigin};
+    use cassowary::strength::{REQUIRED, STRONG};
+    use cassowary::Variable;
+    use cassowary::WeightedRelation::{EQ, GE};
+
+    #[test]
+    fn test_minimal_conflicting_set() {
+        let a = Variable::new();
+        let b = Variable::new();
+        let c = Variable::new();
+
+        let mut log = ConstraintLog::new();
+
+        log.record(
+            ConstraintOrigin::BlockFixedWidth(0),
+            &(a | EQ(REQUIRED) | 0.0),
+        );
+        log.record(
+            ConstraintOrigin::BlockFixedWidth(1),
+            &(c | EQ(REQUIRED) | 5.0),
+        );
+        log.record(
+            ConstraintOrigin::BlockFixedWidth(2),
+            &(b | EQ(REQUIRED) | (a + 10.0)),
+        );
+        log.record(
+            ConstraintOrigin::BlockFixedWidth(3),
+            &(b | EQ(STRONG) | 20.0),
+        );
+
+        // Requiring b to be at most 5 conflicts with a = 0 and b = a + 10, but not with c = 5
+        // or with the strong constraint on b.
+
+        let conflict = log.get_minimal_conflicting_set(
+            ConstraintOrigin::BlockFixedWidth(4),
+            &(a | GE(REQUIRED) | b - 5.0),
+        );
+
+        let blocks = conflict
+            .iter()
+            .map(|record| match record.get_origin() {
+                ConstraintOrigin::BlockFixedWidth(index) => *index,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(blocks, vec![0, 2, 4]);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::models::display::concepts::stave_spaces::{StaveSpaces, STAVE_SPACES_ZERO};
+use crate::models::display::layout::strength::ConstraintStrength;
+use serde::{Deserialize, Serialize};
+
+pub type HorizontalGridLineIndex = usize;
+
+/// The role a HorizontalGridLine plays in a LayoutSystem. The type of a grid line
+/// has no effect on layout; it identifies the grid line when debugging and tracing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HorizontalGridLineType {
+    SystemTop,
+    Staveline5,
+    Staveline4,
+    Staveline3,
+    Staveline2,
+    Staveline1,
+    LyricBelowStaveLine1Top,
+    LyricBelowStaveLine1Bottom,
+    SystemBottom,
+}
+
+/// A single constraint on the position of a HorizontalGridLine, relative to another
+/// HorizontalGridLine. As with BlockConstraint, every constraint carries an optional
+/// ConstraintStrength; if no strength is given, Lock* constraints are STRONG and Float*
+/// constraints are WEAK.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum HorizontalGridLineConstraint {
+    LockAboveHorizontalGridLineByDistance(HorizontalGridLineIndex, f64, Option<ConstraintStrength>),
+    FloatAboveHorizontalGridLineByDistance(
+        HorizontalGridLineIndex,
+        f64,
+        Option<ConstraintStrength>,
+    ),
+    LockBelowHorizontalGridLineByDistance(HorizontalGridLineIndex, f64, Option<ConstraintStrength>),
+    FloatBelowHorizontalGridLineByDistance(
+        HorizontalGridLineIndex,
+        f64,
+        Option<ConstraintStrength>,
+    ),
+    VerticallyCenterBetweenHorizontalGridLines(
+        HorizontalGridLineIndex,
+        HorizontalGridLineIndex,
+        Option<ConstraintStrength>,
+    ),
+}
+
+/// A flat horizontal line expressing a single, initially undefined, y coordinate
+/// in a LayoutSystem. Blocks are aligned vertically by constraining their edges
+/// to horizontal grid lines.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HorizontalGridLine {
+    grid_line_type: HorizontalGridLineType,
+    constraints: Vec<HorizontalGridLineConstraint>,
+}
+
+impl HorizontalGridLine {
+    pub fn new(grid_line_type: HorizontalGridLineType) -> Self {
+        HorizontalGridLine {
+            grid_line_type,
+            constraints: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn get_grid_line_type(&self) -> HorizontalGridLineType {
+        self.grid_line_type
+    }
+
+    #[inline]
+    pub fn get_constraints(&self) -> &[HorizontalGridLineConstraint] {
+        self.constraints.as_slice()
+    }
+
+    #[inline]
+    pub fn add_constraint(&mut self, constraint: HorizontalGridLineConstraint) {
+        self.constraints.push(constraint);
+    }
+
+    /// Locks this grid line to the given HorizontalGridLine.
+    #[inline]
+    pub fn lock_to_grid_line(&mut self, grid_line: HorizontalGridLineIndex) {
+        self.lock_below_grid_line(grid_line, STAVE_SPACES_ZERO);
+    }
+
+    #[inline]
+    pub fn lock_to_grid_line_with_strength(
+        &mut self,
+        grid_line: HorizontalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.lock_below_grid_line_with_strength(grid_line, STAVE_SPACES_ZERO, strength);
+    }
+
+    /// Locks this grid line the given distance above the given HorizontalGridLine.
+    #[inline]
+    pub fn lock_above_grid_line(
+        &mut self,
+        grid_line_below: HorizontalGridLineIndex,
+        distance: StaveSpaces,
+    ) {
+        self.add_constraint(
+            HorizontalGridLineConstraint::LockAboveHorizontalGridLineByDistance(
+                grid_line_below,
+                distance.value as f64,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    pub fn lock_above_grid_line_with_strength(
+        &mut self,
+        grid_line_below: HorizontalGridLineIndex,
+        distance: StaveSpaces,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            HorizontalGridLineConstraint::LockAboveHorizontalGridLineByDistance(
+                grid_line_below,
+                distance.value as f64,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Floats this grid line at least the given distance above the given HorizontalGridLine.
+    #[inline]
+    pub fn float_above_grid_line(
+        &mut self,
+        grid_line_below: HorizontalGridLineIndex,
+        distance: StaveSpaces,
+    ) {
+        self.add_constraint(
+            HorizontalGridLineConstraint::FloatAboveHorizontalGridLineByDistance(
+                grid_line_below,
+                distance.value as f64,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    pub fn float_above_grid_line_with_strength(
+        &mut self,
+        grid_line_below: HorizontalGridLineIndex,
+        distance: StaveSpaces,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            HorizontalGridLineConstraint::FloatAboveHorizontalGridLineByDistance(
+                grid_line_below,
+                distance.value as f64,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Locks this grid line the given distance below the given HorizontalGridLine.
+    #[inline]
+    pub fn lock_below_grid_line(
+        &mut self,
+        grid_line_above: HorizontalGridLineIndex,
+        distance: StaveSpaces,
+    ) {
+        self.add_constraint(
+            HorizontalGridLineConstraint::LockBelowHorizontalGridLineByDistance(
+                grid_line_above,
+                distance.value as f64,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    pub fn lock_below_grid_line_with_strength(
+        &mut self,
+        grid_line_above: HorizontalGridLineIndex,
+        distance: StaveSpaces,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            HorizontalGridLineConstraint::LockBelowHorizontalGridLineByDistance(
+                grid_line_above,
+                distance.value as f64,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Floats this grid line at least the given distance below the given HorizontalGridLine.
+    #[inline]
+    pub fn float_below_grid_line(
+        &mut self,
+        grid_line_above: HorizontalGridLineIndex,
+        distance: StaveSpaces,
+    ) {
+        self.add_constraint(
+            HorizontalGridLineConstraint::FloatBelowHorizontalGridLineByDistance(
+                grid_line_above,
+                distance.value as f64,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    pub fn float_below_grid_line_with_strength(
+        &mut self,
+        grid_line_above: HorizontalGridLineIndex,
+        distance: StaveSpaces,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            HorizontalGridLineConstraint::FloatBelowHorizontalGridLineByDistance(
+                grid_line_above,
+                distance.value as f64,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Locks this grid line halfway between the given HorizontalGridLines.
+    #[inline]
+    pub fn vertically_center_between_grid_lines(
+        &mut self,
+        grid_line_above: HorizontalGridLineIndex,
+        grid_line_below: HorizontalGridLineIndex,
+    ) {
+        self.add_constraint(
+            HorizontalGridLineConstraint::VerticallyCenterBetweenHorizontalGridLines(
+                grid_line_above,
+                grid_line_below,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    pub fn vertically_center_between_grid_lines_with_strength(
+        &mut self,
+        grid_line_above: HorizontalGridLineIndex,
+        grid_line_below: HorizontalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            HorizontalGridLineConstraint::VerticallyCenterBetweenHorizontalGridLines(
+                grid_line_above,
+                grid_line_below,
+                Some(strength),
+            ),
+        );
+    }
+}
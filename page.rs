@@ -0,0 +1,572 @@
+use crate::models::display::concepts::stave_spaces::{StaveSpaces, STAVE_SPACES_ZERO};
+use crate::models::display::engraving::region::system::EngravedSystem;
+use crate::protos::display::stylesheet::SystemJustification;
+use cassowary::strength::{MEDIUM, REQUIRED, STRONG, WEAK};
+use cassowary::WeightedRelation::{EQ, GE, LE};
+use cassowary::{AddConstraintError, Solver, Variable};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// The margins around the printable area of a page.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PageMargins {
+    pub top: StaveSpaces,
+    pub bottom: StaveSpaces,
+    pub start: StaveSpaces,
+    pub end: StaveSpaces,
+}
+
+impl PageMargins {
+    pub fn new(
+        top: StaveSpaces,
+        bottom: StaveSpaces,
+        start: StaveSpaces,
+        end: StaveSpaces,
+    ) -> Self {
+        PageMargins {
+            top,
+            bottom,
+            start,
+            end,
+        }
+    }
+
+    /// Creates a new PageMargins with the same margin on all four sides of the page.
+    pub fn uniform(margin: StaveSpaces) -> Self {
+        Self::new(margin, margin, margin, margin)
+    }
+}
+
+/// A vertical layout of EngravedSystems on a single page. Systems are stacked from the top
+/// of the printable area to the bottom, between an optional title frame at the top of the
+/// page and an optional footer frame at the bottom. The vertical position of each system
+/// is expressed as a set of linear constraints in the same way as the grid lines
+/// in a LayoutSystem, and resolved with the same linear constraint solver.
+///
+/// The space between consecutive systems must lie between the minimum and maximum
+/// system spacing. Within those limits, the page justification determines how any
+/// remaining vertical space is distributed: AlignStart packs systems towards the top
+/// of the page, AlignEnd packs them towards the bottom, Centered places them in the middle,
+/// and Justified spreads them out evenly so the last system sits on the footer frame.
+#[derive(Debug)]
+pub struct LayoutPage {
+    page_width: StaveSpaces,
+    page_height: StaveSpaces,
+    margins: PageMargins,
+    minimum_system_spacing: StaveSpaces,
+    maximum_system_spacing: StaveSpaces,
+    justification: SystemJustification,
+    title_frame_height: Option<StaveSpaces>,
+    footer_frame_height: Option<StaveSpaces>,
+    systems: Vec<EngravedSystem>,
+}
+
+impl LayoutPage {
+    /// Creates a new LayoutPage from the given arguments. Systems will be placed on the
+    /// page in order of their index in the movement, irrespective of the order in which
+    /// they are given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        page_width: StaveSpaces,
+        page_height: StaveSpaces,
+        margins: PageMargins,
+        minimum_system_spacing: StaveSpaces,
+        maximum_system_spacing: StaveSpaces,
+        justification: SystemJustification,
+        title_frame_height: Option<StaveSpaces>,
+        footer_frame_height: Option<StaveSpaces>,
+        mut systems: Vec<EngravedSystem>,
+    ) -> Self {
+        systems.sort_by_key(|system| system.get_index_in_movement());
+
+        LayoutPage {
+            page_width,
+            page_height,
+            margins,
+            minimum_system_spacing,
+            maximum_system_spacing,
+            justification,
+            title_frame_height,
+            footer_frame_height,
+            systems,
+        }
+    }
+
+    #[inline]
+    pub fn get_page_width(&self) -> StaveSpaces {
+        self.page_width
+    }
+
+    #[inline]
+    pub fn get_page_height(&self) -> StaveSpaces {
+        self.page_height
+    }
+
+    #[inline]
+    pub fn get_margins(&self) -> PageMargins {
+        self.margins
+    }
+
+    #[inline]
+    pub fn get_minimum_system_spacing(&self) -> StaveSpaces {
+        self.minimum_system_spacing
+    }
+
+    #[inline]
+    pub fn get_maximum_system_spacing(&self) -> StaveSpaces {
+        self.maximum_system_spacing
+    }
+
+    #[inline]
+    pub fn get_justification(&self) -> SystemJustification {
+        self.justification
+    }
+
+    #[inline]
+    pub fn get_systems(&self) -> &[EngravedSystem] {
+        self.systems.as_slice()
+    }
+
+    /// Returns the vertical extent of the region of the page available to systems,
+    /// i.e. the printable area less the title and footer frames and the minimum system
+    /// spacing separating those frames from the first and last systems.
+    pub fn get_content_extent(&self) -> (StaveSpaces, StaveSpaces) {
+        let content_top = match self.title_frame_height {
+            Some(height) => self.margins.top + height + self.minimum_system_spacing,
+            None => self.margins.top,
+        };
+
+        let content_bottom = match self.footer_frame_height {
+            Some(height) => {
+                self.page_height - self.margins.bottom - height - self.minimum_system_spacing
+            }
+            None => self.page_height - self.margins.bottom,
+        };
+
+        (content_top, content_bottom)
+    }
+
+    /// Lays out the systems on this page, returning an EngravedPage containing the
+    /// final vertical position of every system, along with the title and footer frames.
+    pub fn engrave(self) -> Result<EngravedPage, PageLayoutError> {
+        let (content_top, content_bottom) = self.get_content_extent();
+
+        // The systems must fit on the page when stacked with the minimum system spacing.
+        // If they don't, there is no point running the solver.
+
+        let minimum_content_height = self
+            .systems
+            .iter()
+            .map(|system| system.get_height().value)
+            .sum::<f32>()
+            + self.minimum_system_spacing.value * self.systems.len().saturating_sub(1) as f32;
+
+        let available_content_height = (content_bottom - content_top).value;
+
+        if minimum_content_height > available_content_height {
+            return Err(PageLayoutError::PageOverfull(StaveSpaces::new(
+                minimum_content_height - available_content_height,
+            )));
+        }
+
+        let mut solver = Solver::new();
+
+        let system_top_variables = self
+            .systems
+            .iter()
+            .map(|_| Variable::new())
+            .collect::<Vec<_>>();
+
+        for (index, (system, top)) in self
+            .systems
+            .iter()
+            .zip(system_top_variables.iter())
+            .enumerate()
+        {
+            // Every system must lie entirely within the content region of the page.
+
+            solver
+                .add_constraint(*top | GE(REQUIRED) | content_top.value)
+                .map_err(|err| PageLayoutError::AddConstraintErrorOnSystem(err, index))?;
+
+            solver
+                .add_constraint(
+                    (*top + system.get_height().value) | LE(REQUIRED) | content_bottom.value,
+                )
+                .map_err(|err| PageLayoutError::AddConstraintErrorOnSystem(err, index))?;
+
+            if index > 0 {
+                // The spacing between this system and the previous system must lie between
+                // the minimum and maximum system spacing. Within those limits we prefer
+                // the minimum spacing, and we prefer every gap to be the same size.
+
+                let previous_top = system_top_variables[index - 1];
+
+                let previous_bottom = previous_top + self.systems[index - 1].get_height().value;
+
+                solver
+                    .add_constraint(
+                        (*top - previous_bottom.clone())
+                            | GE(REQUIRED)
+                            | self.minimum_system_spacing.value,
+                    )
+                    .map_err(|err| PageLayoutError::AddConstraintErrorOnSystem(err, index))?;
+
+                solver
+                    .add_constraint(
+                        (*top - previous_bottom.clone())
+                            | LE(REQUIRED)
+                            | self.maximum_system_spacing.value,
+                    )
+                    .map_err(|err| PageLayoutError::AddConstraintErrorOnSystem(err, index))?;
+
+                solver
+                    .add_constraint(
+                        (*top - previous_bottom.clone())
+                            | EQ(WEAK)
+                            | self.minimum_system_spacing.value,
+                    )
+                    .map_err(|err| PageLayoutError::AddConstraintErrorOnSystem(err, index))?;
+
+                if index > 1 {
+                    let earlier_bottom = system_top_variables[index - 2]
+                        + self.systems[index - 2].get_height().value;
+
+                    solver
+                        .add_constraint(
+                            (*top - previous_bottom) | EQ(MEDIUM) | (previous_top - earlier_bottom),
+                        )
+                        .map_err(|err| PageLayoutError::AddConstraintErrorOnSystem(err, index))?;
+                }
+            }
+        }
+
+        if let (Some(first_top), Some(last_top), Some(last_system)) = (
+            system_top_variables.first(),
+            system_top_variables.last(),
+            self.systems.last(),
+        ) {
+            let last_bottom = *last_top + last_system.get_height().value;
+
+            let first_index = 0;
+
+            let last_index = self.systems.len() - 1;
+
+            match self.justification {
+                SystemJustification::AlignStart => {
+                    solver
+                        .add_constraint(*first_top | EQ(STRONG) | content_top.value)
+                        .map_err(|err| {
+                            PageLayoutError::AddConstraintErrorOnSystem(err, first_index)
+                        })?;
+                }
+                SystemJustification::AlignEnd => {
+                    solver
+                        .add_constraint(last_bottom | EQ(STRONG) | content_bottom.value)
+                        .map_err(|err| {
+                            PageLayoutError::AddConstraintErrorOnSystem(err, last_index)
+                        })?;
+                }
+                SystemJustification::Centered => {
+                    // The space above the first system should match the space below
+                    // the last system.
+
+                    solver
+                        .add_constraint(
+                            (*first_top - content_top.value)
+                                | EQ(STRONG)
+                                | (content_bottom.value - last_bottom),
+                        )
+                        .map_err(|err| {
+                            PageLayoutError::AddConstraintErrorOnSystem(err, first_index)
+                        })?;
+                }
+                SystemJustification::Justified => {
+                    // Pin the first system to the top of the content region and try to pin
+                    // the last system to the bottom. The bottom is pinned more weakly than
+                    // the top, so that if the maximum system spacing prevents the last system
+                    // from reaching the bottom, the page is left ragged rather than the
+                    // systems drifting down the page.
+
+                    solver
+                        .add_constraint(*first_top | EQ(STRONG) | content_top.value)
+                        .map_err(|err| {
+                            PageLayoutError::AddConstraintErrorOnSystem(err, first_index)
+                        })?;
+
+                    solver
+                        .add_constraint(last_bottom | EQ(MEDIUM) | content_bottom.value)
+                        .map_err(|err| {
+                            PageLayoutError::AddConstraintErrorOnSystem(err, last_index)
+                        })?;
+                }
+            }
+        }
+
+        let system_positions = system_top_variables
+            .iter()
+            .map(|variable| StaveSpaces::new(solver.get_value(*variable) as f32))
+            .collect::<Vec<_>>();
+
+        let title_frame = self
+            .title_frame_height
+            .map(|height| (self.margins.top, self.margins.top + height));
+
+        let footer_frame = self.footer_frame_height.map(|height| {
+            (
+                self.page_height - self.margins.bottom - height,
+                self.page_height - self.margins.bottom,
+            )
+        });
+
+        Ok(EngravedPage {
+            page_width: self.page_width,
+            page_height: self.page_height,
+            margins: self.margins,
+            title_frame,
+            footer_frame,
+            content_bottom,
+            system_positions,
+            systems: self.systems,
+        })
+    }
+}
+
+/// The final layout of a single page of EngravedSystems, as computed by LayoutPage::engrave().
+#[derive(Debug)]
+pub struct EngravedPage {
+    page_width: StaveSpaces,
+    page_height: StaveSpaces,
+    margins: PageMargins,
+    title_frame: Option<(StaveSpaces, StaveSpaces)>,
+    footer_frame: Option<(StaveSpaces, StaveSpaces)>,
+    content_bottom: StaveSpaces,
+    system_positions: Vec<StaveSpaces>,
+    systems: Vec<EngravedSystem>,
+}
+
+impl EngravedPage {
+    #[inline]
+    pub fn get_page_width(&self) -> StaveSpaces {
+        self.page_width
+    }
+
+    #[inline]
+    pub fn get_page_height(&self) -> StaveSpaces {
+        self.page_height
+    }
+
+    #[inline]
+    pub fn get_margins(&self) -> PageMargins {
+        self.margins
+    }
+
+    /// Returns the top and bottom positions of the title frame, if this page has one.
+    #[inline]
+    pub fn get_title_frame(&self) -> Option<(StaveSpaces, StaveSpaces)> {
+        self.title_frame
+    }
+
+    /// Returns the top and bottom positions of the footer frame, if this page has one.
+    #[inline]
+    pub fn get_footer_frame(&self) -> Option<(StaveSpaces, StaveSpaces)> {
+        self.footer_frame
+    }
+
+    #[inline]
+    pub fn get_systems(&self) -> &[EngravedSystem] {
+        self.systems.as_slice()
+    }
+
+    /// Returns the position of the top left corner of the system at the given index,
+    /// measured from the top left corner of the page.
+    pub fn get_system_position(&self, index: usize) -> Option<(StaveSpaces, StaveSpaces)> {
+        self.system_positions
+            .get(index)
+            .map(|top| (self.margins.start, *top))
+    }
+
+    /// Returns the amount of vertical space left unused between the bottom of the last
+    /// system and the bottom of the content region of the page.
+    pub fn get_unused_height(&self) -> StaveSpaces {
+        match (self.system_positions.last(), self.systems.last()) {
+            (Some(top), Some(system)) => {
+                // The minimum system spacing between the last system and the footer frame
+                // is not counted as unused, since the page layout always enforces it.
+
+                let last_bottom = *top + system.get_height();
+
+                if last_bottom < self.content_bottom {
+                    self.content_bottom - last_bottom
+                } else {
+                    STAVE_SPACES_ZERO
+                }
+            }
+            _ => STAVE_SPACES_ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PageLayoutError {
+    AddConstraintErrorOnSystem(AddConstraintError, usize),
+    PageOverfull(StaveSpaces),
+}
+
+impl Display for PageLayoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PageLayoutError::AddConstraintErrorOnSystem(err, index) => format!(
+                    "Unable to add constraint on page system index {}: {:?}",
+                    index, err
+                ),
+                PageLayoutError::PageOverfull(overflow) => format!(
+                    "Systems overflow the available page height by {} stave spaces",
+                    overflow.value
+                ),
+            }
+        )
+    }
+}
+
+impl Error for PageLayoutError {}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::concepts::stave_spaces::{AsStaveSpacesExt, StaveSpaces};
+    use crate::models::display::engraving::region::system::EngravedSystem;
+    use crate::models::display::layout::page::{
+        EngravedPage, LayoutPage, PageLayoutError, PageMargins,
+    };
+    use crate::models::display::stylesheet::stylesheet_option::SystemJustification;
+    use crate::models::music::concepts::ticks::AsTicksExt;
+
+    #[test]
+    fn test_align_start() {
+        let page = create_page(SystemJustification::AlignStart, 4.0, 100.0)
+            .engrave()
+            .unwrap();
+
+        // Title frame occupies 10..20; the first system starts after 4 stave spaces
+        // of minimum spacing. Each system is 20 stave spaces tall.
+
+        assert_eq!(get_top(&page, 0), 24.0);
+        assert_eq!(get_top(&page, 1), 48.0);
+        assert_eq!(get_top(&page, 2), 72.0);
+    }
+
+    #[test]
+    fn test_align_end() {
+        let page = create_page(SystemJustification::AlignEnd, 4.0, 100.0)
+            .engrave()
+            .unwrap();
+
+        // Footer frame occupies 180..190; the last system ends 4 stave spaces above it.
+
+        assert_eq!(get_top(&page, 2), 156.0);
+        assert_eq!(get_top(&page, 1), 132.0);
+        assert_eq!(get_top(&page, 0), 108.0);
+    }
+
+    #[test]
+    fn test_justified() {
+        let page = create_page(SystemJustification::Justified, 4.0, 100.0)
+            .engrave()
+            .unwrap();
+
+        // The content region is 24..176, i.e. 152 stave spaces. Systems occupy 60,
+        // leaving 92 stave spaces to be shared evenly between two gaps.
+
+        assert_eq!(get_top(&page, 0), 24.0);
+        assert_eq!(get_top(&page, 1), 90.0);
+        assert_eq!(get_top(&page, 2), 156.0);
+        assert_eq!(page.get_unused_height().value, 0.0);
+    }
+
+    #[test]
+    fn test_justified_with_maximum_spacing() {
+        let page = create_page(SystemJustification::Justified, 4.0, 10.0)
+            .engrave()
+            .unwrap();
+
+        // The maximum system spacing stops the systems from filling the page.
+
+        assert_eq!(get_top(&page, 0), 24.0);
+        assert_eq!(get_top(&page, 1), 54.0);
+        assert_eq!(get_top(&page, 2), 84.0);
+        assert_eq!(page.get_unused_height().value, 72.0);
+    }
+
+    #[test]
+    fn test_page_overfull() {
+        let result = LayoutPage::new(
+            100.as_stave_spaces(),
+            50.as_stave_spaces(),
+            PageMargins::uniform(5.as_stave_spaces()),
+            4.as_stave_spaces(),
+            10.as_stave_spaces(),
+            SystemJustification::AlignStart,
+            None,
+            None,
+            vec![create_system(0, 20.0), create_system(1, 20.0)],
+        )
+        .engrave();
+
+        // 40 stave spaces of content plus 4 of spacing, in a content region of 40.
+
+        match result {
+            Err(PageLayoutError::PageOverfull(overflow)) => assert_eq!(overflow.value, 4.0),
+            _ => panic!("Expected page to be overfull"),
+        }
+    }
+
+    fn create_page(
+        justification: SystemJustification,
+        minimum_system_spacing: f32,
+        maximum_system_spacing: f32,
+    ) -> LayoutPage {
+        // Systems are deliberately given out of order, to check they are placed
+        // in movement order.
+
+        LayoutPage::new(
+            100.as_stave_spaces(),
+            200.as_stave_spaces(),
+            PageMargins::uniform(10.as_stave_spaces()),
+            minimum_system_spacing.as_stave_spaces(),
+            maximum_system_spacing.as_stave_spaces(),
+            justification,
+            Some(10.as_stave_spaces()),
+            Some(10.as_stave_spaces()),
+            vec![
+                create_system(2, 20.0),
+                create_system(0, 20.0),
+                create_system(1, 20.0),
+            ],
+        )
+    }
+
+    fn create_system(index_in_movement: u32, height: f32) -> EngravedSystem {
+        EngravedSystem::new(
+            index_in_movement,
+            vec![],
+            vec![],
+            0.as_ticks(),
+            0.as_ticks(),
+            80.as_stave_spaces(),
+            height.as_stave_spaces(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        )
+    }
+
+    fn get_top(page: &EngravedPage, index: usize) -> f32 {
+        let (_, top): (StaveSpaces, StaveSpaces) = page.get_system_position(index).unwrap();
+
+        (top.value * 1000.0).round() / 1000.0
+    }
+}
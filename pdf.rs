@@ -0,0 +1,587 @@
+use crate::models::display::concepts::color::Color;
+use crate::models::display::concepts::stave_spaces::{StavePoint, StaveSpaces};
+use crate::models::display::concepts::stroke::StrokeStyle;
+use crate::models::display::engraving::engravable::glyph::EngravedGlyph;
+use crate::models::display::engraving::engravable::line::EngravedLine;
+use crate::models::display::engraving::engravable::markup::EngravedMarkup;
+use crate::models::display::engraving::engravable::{Engravable, EngravableItem};
+use crate::models::display::engraving::region::system::EngravedSystem;
+use pdfium_render::prelude::*;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// The number of stave spaces in one em of a SMuFL music font. The SMuFL specification
+/// defines the em as the height of a five-line stave, i.e. four stave spaces.
+const SMUFL_STAVE_SPACES_PER_EM: f32 = 4.0;
+
+/// The default printed size of a single stave space, in PDF points. A stave space of
+/// 5 points gives a stave height of a little over 7mm, a common size for instrumental parts.
+pub const DEFAULT_PDF_STAVE_SPACE_SIZE: f32 = 5.0;
+
+/// The position of a single EngravedSystem within an exported PDF document.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PdfSystemPlacement {
+    /// The index of the system in the slice of systems passed to the exporter.
+    pub system: usize,
+
+    /// The zero-based index of the page on which the system is placed.
+    pub page: usize,
+
+    /// The distance from the top edge of the page to the top of the system.
+    pub top: PdfPoints,
+}
+
+/// Renders a sequence of EngravedSystems into a PDF document using Pdfium.
+///
+/// Systems are placed in order of their index in the movement, stacked downwards from
+/// the top margin of each page using their engraved heights. A new page is started
+/// whenever the next system would overflow the bottom margin. Both the SMuFL music
+/// font and the text font are embedded in the output document, so the document can
+/// be printed without either font being installed.
+pub struct PdfExporter {
+    music_font_path: PathBuf,
+    text_font_path: PathBuf,
+    paper_size: PdfPagePaperSize,
+    margin: PdfPoints,
+    system_spacing: PdfPoints,
+    stave_space_size: f32,
+}
+
+impl PdfExporter {
+    pub fn new(
+        music_font_path: &Path,
+        text_font_path: &Path,
+        paper_size: PdfPagePaperSize,
+        margin: PdfPoints,
+        system_spacing: PdfPoints,
+        stave_space_size: f32,
+    ) -> Self {
+        PdfExporter {
+            music_font_path: music_font_path.to_path_buf(),
+            text_font_path: text_font_path.to_path_buf(),
+            paper_size,
+            margin,
+            system_spacing,
+            stave_space_size,
+        }
+    }
+
+    #[inline]
+    pub fn get_paper_size(&self) -> PdfPagePaperSize {
+        self.paper_size
+    }
+
+    #[inline]
+    pub fn get_margin(&self) -> PdfPoints {
+        self.margin
+    }
+
+    #[inline]
+    pub fn get_system_spacing(&self) -> PdfPoints {
+        self.system_spacing
+    }
+
+    #[inline]
+    pub fn get_stave_space_size(&self) -> f32 {
+        self.stave_space_size
+    }
+
+    /// Renders the given EngravedSystems into a new PDF document.
+    pub fn export<'a>(
+        &self,
+        pdfium: &'a Pdfium,
+        systems: &[EngravedSystem],
+    ) -> Result<PdfDocument<'a>, PdfExportError> {
+        let placements = self.get_system_placements(systems)?;
+
+        let mut document = pdfium
+            .create_new_pdf()
+            .map_err(PdfExportError::PdfiumError)?;
+
+        // Loading the fonts as CID fonts embeds them in the document. SMuFL glyphs
+        // live in the Unicode Private Use Area, so the music font must be embedded
+        // for the glyphs to print correctly.
+
+        let music_font = document
+            .fonts_mut()
+            .load_true_type_from_file(&self.music_font_path, true)
+            .map_err(PdfExportError::PdfiumError)?;
+
+        let text_font = document
+            .fonts_mut()
+            .load_true_type_from_file(&self.text_font_path, true)
+            .map_err(PdfExportError::PdfiumError)?;
+
+        let page_count = placements
+            .last()
+            .map(|placement| placement.page + 1)
+            .unwrap_or(0);
+
+        for page_index in 0..page_count {
+            let mut page = document
+                .pages_mut()
+                .create_page_at_end(self.paper_size)
+                .map_err(PdfExportError::PdfiumError)?;
+
+            for placement in placements
+                .iter()
+                .filter(|placement| placement.page == page_index)
+            {
+                self.export_system(
+                    &mut page,
+                    &systems[placement.system],
+                    placement.top,
+                    music_font,
+                    text_font,
+                )?;
+            }
+        }
+
+        Ok(document)
+    }
+
+    /// Renders the given EngravedSystems into a new PDF document and saves it to the given path.
+    pub fn export_to_file(
+        &self,
+        pdfium: &Pdfium,
+        systems: &[EngravedSystem],
+        path: &Path,
+    ) -> Result<(), PdfExportError> {
+        self.export(pdfium, systems)?
+            .save_to_file(path)
+            .map_err(PdfExportError::PdfiumError)
+    }
+
+    /// Determines the page and vertical offset of each of the given EngravedSystems,
+    /// ordered by each system's index in the movement.
+    pub fn get_system_placements(
+        &self,
+        systems: &[EngravedSystem],
+    ) -> Result<Vec<PdfSystemPlacement>, PdfExportError> {
+        let mut order = (0..systems.len()).collect::<Vec<_>>();
+
+        order.sort_by_key(|index| systems[*index].get_index_in_movement());
+
+        let page_bottom = self.paper_size.height() - self.margin;
+
+        let mut placements = Vec::with_capacity(systems.len());
+
+        let mut page = 0;
+
+        let mut top = self.margin;
+
+        let mut is_page_empty = true;
+
+        for index in order {
+            let system = &systems[index];
+
+            let height = self.to_points(system.get_height().value);
+
+            if !is_page_empty && top + height > page_bottom {
+                page += 1;
+                top = self.margin;
+                is_page_empty = true;
+            }
+
+            if top + height > page_bottom {
+                // The system does not fit even on an empty page.
+
+                return Err(PdfExportError::SystemTooTall(
+                    system.get_index_in_movement(),
+                ));
+            }
+
+            placements.push(PdfSystemPlacement {
+                system: index,
+                page,
+                top,
+            });
+
+            top = top + height + self.system_spacing;
+            is_page_empty = false;
+        }
+
+        Ok(placements)
+    }
+
+    fn export_system(
+        &self,
+        page: &mut PdfPage,
+        system: &EngravedSystem,
+        top: PdfPoints,
+        music_font: PdfFontToken,
+        text_font: PdfFontToken,
+    ) -> Result<(), PdfExportError> {
+        // Layers are drawn back to front, so that later layers are painted over earlier ones.
+
+        for engravable in system
+            .get_background()
+            .iter()
+            .chain(system.get_midground().iter())
+            .chain(system.get_foreground().iter())
+        {
+            match engravable {
+                Engravable::Glyph(glyph) => {
+                    self.export_glyph(page, glyph, top, music_font)?;
+                }
+                Engravable::Line(line) => {
+                    self.export_line(page, line, top)?;
+                }
+                Engravable::Markup(markup) => {
+                    self.export_markup(page, markup, top, text_font)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn export_glyph(
+        &self,
+        page: &mut PdfPage,
+        glyph: &EngravedGlyph,
+        top: PdfPoints,
+        font: PdfFontToken,
+    ) -> Result<(), PdfExportError> {
+        // PDF positions text by its baseline rather than by the top of its bounding box,
+        // so we must offset the glyph by its height less any descent below the baseline.
+
+        let baseline = glyph.get_y() + glyph.get_height() - glyph.get_descent();
+
+        let mut object = page
+            .objects_mut()
+            .create_text_object(
+                self.get_x(glyph.get_x().value),
+                self.get_y(top, baseline.value),
+                glyph.get_glyph().get_codepoint().to_string(),
+                font,
+                self.to_points(SMUFL_STAVE_SPACES_PER_EM),
+            )
+            .map_err(PdfExportError::PdfiumError)?;
+
+        object
+            .set_fill_color(Self::to_pdf_color(glyph.get_color()))
+            .map_err(PdfExportError::PdfiumError)
+    }
+
+    fn export_line(
+        &self,
+        page: &mut PdfPage,
+        line: &EngravedLine,
+        top: PdfPoints,
+    ) -> Result<(), PdfExportError> {
+        self.export_stroke(
+            page,
+            line.get_from(),
+            line.get_to(),
+            line.get_stroke_style(),
+            line.get_thickness(),
+            line.get_color(),
+            top,
+        )
+    }
+
+    /// Draws a straight line between the given points in the given stroke style. Dashed
+    /// and dotted lines are drawn as a sequence of solid dashes.
+    fn export_stroke(
+        &self,
+        page: &mut PdfPage,
+        from: &StavePoint,
+        to: &StavePoint,
+        stroke_style: &StrokeStyle,
+        thickness: StaveSpaces,
+        color: &Color,
+        top: PdfPoints,
+    ) -> Result<(), PdfExportError> {
+        for (from, to) in Self::get_dashes(from, to, stroke_style, thickness) {
+            page.objects_mut()
+                .create_path_object_line(
+                    self.get_x(from.x.value),
+                    self.get_y(top, from.y.value),
+                    self.get_x(to.x.value),
+                    self.get_y(top, to.y.value),
+                    Self::to_pdf_color(color),
+                    self.to_points(thickness.value),
+                )
+                .map_err(PdfExportError::PdfiumError)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the (start, end) points of the solid dashes making up a line between the given
+    /// points in the given stroke style. As in SVG exports, dash lengths are proportional to
+    /// the stroke thickness: a dashed line alternates dashes four times its thickness with
+    /// gaps twice its thickness, and a dotted line places a square dot as wide as the line
+    /// every two thicknesses. A solid line is a single dash; the final dash of any line
+    /// is cut short at the line's end.
+    fn get_dashes(
+        from: &StavePoint,
+        to: &StavePoint,
+        stroke_style: &StrokeStyle,
+        thickness: StaveSpaces,
+    ) -> Vec<(StavePoint, StavePoint)> {
+        let point = |t: f32| {
+            StavePoint::new(
+                StaveSpaces::new(from.x.value + (to.x.value - from.x.value) * t),
+                StaveSpaces::new(from.y.value + (to.y.value - from.y.value) * t),
+            )
+        };
+
+        let (dash, gap) = match stroke_style {
+            StrokeStyle::Solid => return vec![(point(0.0), point(1.0))],
+            StrokeStyle::Dashed => (thickness.value * 4.0, thickness.value * 2.0),
+            StrokeStyle::Dotted => (thickness.value, thickness.value),
+        };
+
+        let length = (to.x.value - from.x.value).hypot(to.y.value - from.y.value);
+
+        if length <= 0.0 || dash <= 0.0 {
+            return vec![(point(0.0), point(1.0))];
+        }
+
+        let mut dashes = Vec::new();
+
+        let mut start = 0.0;
+
+        while start < length {
+            dashes.push((
+                point(start / length),
+                point((start + dash).min(length) / length),
+            ));
+
+            start += dash + gap;
+        }
+
+        dashes
+    }
+
+    fn export_markup(
+        &self,
+        page: &mut PdfPage,
+        markup: &EngravedMarkup,
+        top: PdfPoints,
+        font: PdfFontToken,
+    ) -> Result<(), PdfExportError> {
+        // Each marked up line is emitted as its own text object, positioned at the line's
+        // baseline. Lines are stacked downwards from the top of the markup.
+
+        let mut line_top = markup.get_y();
+
+        for line in markup.get_lines().iter() {
+            let baseline =
+                line_top + line.get_top_padding() + line.get_height() - line.get_descent();
+
+            let mut object = page
+                .objects_mut()
+                .create_text_object(
+                    self.get_x((markup.get_x() + line.get_start_padding()).value),
+                    self.get_y(top, baseline.value),
+                    line.get_text(),
+                    font,
+                    self.to_points(line.get_height().value),
+                )
+                .map_err(PdfExportError::PdfiumError)?;
+
+            object
+                .set_fill_color(Self::to_pdf_color(markup.get_color()))
+                .map_err(PdfExportError::PdfiumError)?;
+
+            line_top =
+                line_top + line.get_top_padding() + line.get_height() + line.get_bottom_padding();
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn to_points(&self, stave_spaces: f32) -> PdfPoints {
+        PdfPoints::new(stave_spaces * self.stave_space_size)
+    }
+
+    #[inline]
+    fn get_x(&self, x: f32) -> PdfPoints {
+        self.margin + self.to_points(x)
+    }
+
+    /// Converts a vertical position within a system, measured downwards from the top of
+    /// the system, into a PDF page coordinate, measured upwards from the bottom of the page.
+    #[inline]
+    fn get_y(&self, top: PdfPoints, y: f32) -> PdfPoints {
+        self.paper_size.height() - top - self.to_points(y)
+    }
+
+    #[inline]
+    fn to_pdf_color(color: &Color) -> PdfColor {
+        PdfColor::new(
+            color.get_red(),
+            color.get_green(),
+            color.get_blue(),
+            color.get_alpha(),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum PdfExportError {
+    PdfiumError(PdfiumError),
+    SystemTooTall(u32),
+}
+
+impl Display for PdfExportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PdfExportError::PdfiumError(error) => format!("Pdfium error: {:?}", error),
+                PdfExportError::SystemTooTall(index) => format!(
+                    "System at index {} in movement is too tall to fit on a page",
+                    index
+                ),
+            }
+        )
+    }
+}
+
+impl Error for PdfExportError {}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::concepts::stave_spaces::{AsStaveSpacesExt, StavePoint};
+    use crate::models::display::concepts::stroke::StrokeStyle;
+    use crate::models::display::engraving::export::pdf::{
+        PdfExportError, PdfExporter, PdfSystemPlacement, DEFAULT_PDF_STAVE_SPACE_SIZE,
+    };
+    use crate::models::display::engraving::region::system::EngravedSystem;
+    use crate::models::music::concepts::ticks::AsTicksExt;
+    use pdfium_render::prelude::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_system_placements() {
+        // A 200 x 200 point page with 20 point margins leaves 160 points of printable
+        // height. Systems of 10 stave spaces are 50 points tall, so with 10 points of
+        // spacing between systems, two systems take up 110 points; a third would need
+        // 170 points, so only two systems fit on each page.
+
+        let exporter = create_exporter();
+
+        let systems = (0..4)
+            .rev()
+            .map(|index| create_system(index, 10.0))
+            .collect::<Vec<_>>();
+
+        let placements = exporter.get_system_placements(&systems).unwrap();
+
+        assert_eq!(
+            placements,
+            vec![
+                PdfSystemPlacement {
+                    system: 3,
+                    page: 0,
+                    top: PdfPoints::new(20.0),
+                },
+                PdfSystemPlacement {
+                    system: 2,
+                    page: 0,
+                    top: PdfPoints::new(80.0),
+                },
+                PdfSystemPlacement {
+                    system: 1,
+                    page: 1,
+                    top: PdfPoints::new(20.0),
+                },
+                PdfSystemPlacement {
+                    system: 0,
+                    page: 1,
+                    top: PdfPoints::new(80.0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_system_too_tall() {
+        let exporter = create_exporter();
+
+        let systems = vec![create_system(0, 10.0), create_system(1, 40.0)];
+
+        assert!(matches!(
+            exporter.get_system_placements(&systems),
+            Err(PdfExportError::SystemTooTall(1))
+        ));
+    }
+
+    #[test]
+    fn test_dashes() {
+        let from = StavePoint::new(0.as_stave_spaces(), 2.as_stave_spaces());
+
+        let to = StavePoint::new(10.as_stave_spaces(), 2.as_stave_spaces());
+
+        let dashes = |stroke_style: StrokeStyle| {
+            PdfExporter::get_dashes(&from, &to, &stroke_style, 0.25.as_stave_spaces())
+                .iter()
+                .map(|(start, end)| (start.x.value, end.x.value))
+                .collect::<Vec<_>>()
+        };
+
+        // A solid line is drawn as a single stroke.
+
+        assert_eq!(dashes(StrokeStyle::Solid), vec![(0.0, 10.0)]);
+
+        // A dashed line 0.25 stave spaces thick alternates dashes of 1 stave space with
+        // gaps of 0.5 stave spaces, so an octave line is never printed solid.
+
+        let expected = vec![
+            (0.0, 1.0),
+            (1.5, 2.5),
+            (3.0, 4.0),
+            (4.5, 5.5),
+            (6.0, 7.0),
+            (7.5, 8.5),
+            (9.0, 10.0),
+        ];
+
+        let actual = dashes(StrokeStyle::Dashed);
+
+        assert_eq!(actual.len(), expected.len());
+
+        for ((start, end), (expected_start, expected_end)) in actual.iter().zip(expected.iter()) {
+            assert!((start - expected_start).abs() < 0.001);
+            assert!((end - expected_end).abs() < 0.001);
+        }
+
+        // A dotted line places a dot every two thicknesses.
+
+        assert_eq!(dashes(StrokeStyle::Dotted).len(), 20);
+    }
+
+    fn create_exporter() -> PdfExporter {
+        PdfExporter::new(
+            Path::new("Bravura.otf"),
+            Path::new("Academico.ttf"),
+            PdfPagePaperSize::from_points(PdfPoints::new(200.0), PdfPoints::new(200.0)),
+            PdfPoints::new(20.0),
+            PdfPoints::new(10.0),
+            DEFAULT_PDF_STAVE_SPACE_SIZE,
+        )
+    }
+
+    fn create_system(index_in_movement: u32, height: f32) -> EngravedSystem {
+        EngravedSystem::new(
+            index_in_movement,
+            vec![],
+            vec![],
+            0.as_ticks(),
+            0.as_ticks(),
+            20.as_stave_spaces(),
+            height.as_stave_spaces(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        )
+    }
+}
@@ -0,0 +1,132 @@
+use crate::models::display::concepts::stave_spaces::StaveSpaces;
+use crate::models::music::concepts::ticks::Ticks;
+use serde::{Deserialize, Serialize};
+
+/// A model relating the duration of a rhythmic event to the horizontal space that
+/// event should occupy in an engraved system.
+///
+/// Each model expresses the ideal width of an event as a proportion of the ideal width
+/// of the shortest event in the system. The same proportion is used as the stretchability
+/// of the event's spacing block during justification, so that a justified system still
+/// follows the spacing model, just at a larger scale: under a non-linear model,
+/// longer durations receive proportionally less of the additional width than shorter ones.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum RhythmicSpacingModel {
+    /// Space is directly proportional to duration: a minim takes twice the space of a
+    /// crotchet. During justification every spacing block is stretched by the same ratio,
+    /// irrespective of the duration it represents.
+    #[default]
+    Linear,
+
+    /// Every doubling of duration adds a fixed increment to the ideal width, expressed
+    /// as a fraction of the ideal width of the shortest duration. An increment of 0.5
+    /// means a crotchet takes 1.5 times the space of a quaver, and a minim takes twice
+    /// the space of a quaver.
+    Logarithmic { increment: f32 },
+
+    /// Ideal width grows with a fixed power of duration, after Gourlay. An exponent of 1
+    /// is equivalent to linear spacing; an exponent of 0.5 gives the traditional
+    /// "square root" spacing, where a minim takes roughly 1.4 times the space of a crotchet.
+    Gourlay { exponent: f32 },
+}
+
+impl RhythmicSpacingModel {
+    /// Returns the ideal width of an event with the given duration, given the duration
+    /// and ideal width of the shortest event in the system.
+    pub fn get_ideal_width(
+        &self,
+        duration: Ticks,
+        shortest_duration: Ticks,
+        shortest_width: StaveSpaces,
+    ) -> StaveSpaces {
+        StaveSpaces::new(
+            shortest_width.value * self.get_stretchability(duration, shortest_duration),
+        )
+    }
+
+    /// Returns the relative stretchability of the spacing block of an event with the given
+    /// duration, given the duration of the shortest event in the system. When a system
+    /// is justified, the additional width is shared between spacing blocks in proportion
+    /// to their stretchability.
+    pub fn get_stretchability(&self, duration: Ticks, shortest_duration: Ticks) -> f32 {
+        if shortest_duration.value == 0 {
+            return 1.0;
+        }
+
+        let relative_duration = duration.value as f32 / shortest_duration.value as f32;
+
+        match self {
+            RhythmicSpacingModel::Linear => relative_duration,
+            RhythmicSpacingModel::Logarithmic { increment } => {
+                1.0 + increment * relative_duration.log2().max(0.0)
+            }
+            RhythmicSpacingModel::Gourlay { exponent } => relative_duration.powf(*exponent),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::concepts::stave_spaces::AsStaveSpacesExt;
+    use crate::models::display::layout::rhythmic_spacing::RhythmicSpacingModel;
+    use crate::models::music::concepts::ticks::AsTicksExt;
+    use crate::protos::music::concepts::NotatedDuration;
+
+    #[test]
+    fn test_linear_spacing() {
+        let model = RhythmicSpacingModel::Linear;
+
+        assert_eq!(
+            model.get_ideal_width(
+                NotatedDuration::Minim.as_ticks(),
+                NotatedDuration::Quaver.as_ticks(),
+                2.as_stave_spaces()
+            ),
+            8.as_stave_spaces()
+        );
+    }
+
+    #[test]
+    fn test_logarithmic_spacing() {
+        let model = RhythmicSpacingModel::Logarithmic { increment: 0.5 };
+
+        let quaver = NotatedDuration::Quaver.as_ticks();
+
+        assert_eq!(model.get_stretchability(quaver, quaver), 1.0);
+        assert_eq!(
+            model.get_stretchability(NotatedDuration::Crotchet.as_ticks(), quaver),
+            1.5
+        );
+        assert_eq!(
+            model.get_stretchability(NotatedDuration::Minim.as_ticks(), quaver),
+            2.0
+        );
+        assert_eq!(
+            model.get_ideal_width(
+                NotatedDuration::Semibreve.as_ticks(),
+                quaver,
+                2.as_stave_spaces()
+            ),
+            5.as_stave_spaces()
+        );
+    }
+
+    #[test]
+    fn test_gourlay_spacing() {
+        let model = RhythmicSpacingModel::Gourlay { exponent: 0.5 };
+
+        let crotchet = NotatedDuration::Crotchet.as_ticks();
+
+        assert_eq!(
+            model.get_stretchability(NotatedDuration::Semibreve.as_ticks(), crotchet),
+            2.0
+        );
+
+        // Longer durations should receive proportionally less stretch than shorter ones.
+
+        let minim = model.get_stretchability(NotatedDuration::Minim.as_ticks(), crotchet);
+
+        assert!(minim > 1.0);
+        assert!(minim < 2.0);
+    }
+}
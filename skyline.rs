@@ -0,0 +1,436 @@
+use crate::models::display::concepts::stave_spaces::{StavePoint, StaveSpaces};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// The rectangular areas cut out of the corners of a Block's bounding box, following
+/// the cut-outs defined for each glyph in a SMuFL font's metadata. Each cut-out is given
+/// by the inner corner of the rectangle removed from that corner of the bounding box,
+/// relative to the (start, top) corner of the Block, with y increasing downwards.
+/// Note that this differs from SMuFL's own coordinate system, which is relative to
+/// the glyph origin with y increasing upwards.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SkylineCutOuts {
+    pub north_west: Option<StavePoint>,
+    pub north_east: Option<StavePoint>,
+    pub south_west: Option<StavePoint>,
+    pub south_east: Option<StavePoint>,
+}
+
+impl SkylineCutOuts {
+    /// Creates a new set of cut-outs from the cutOutNW, cutOutNE, cutOutSW, and cutOutSE
+    /// anchors defined for a glyph in a SMuFL font's metadata, along with the bBoxSW and
+    /// bBoxNE corners of the glyph's bounding box. All positions are given as they appear
+    /// in the metadata, relative to the glyph origin with y increasing upwards, and are
+    /// converted to positions relative to the (start, top) corner of the bounding box.
+    pub fn from_smufl_anchors(
+        bounding_box_south_west: StavePoint,
+        bounding_box_north_east: StavePoint,
+        north_west: Option<StavePoint>,
+        north_east: Option<StavePoint>,
+        south_west: Option<StavePoint>,
+        south_east: Option<StavePoint>,
+    ) -> Self {
+        let convert = |anchor: StavePoint| {
+            StavePoint::new(
+                StaveSpaces::new(anchor.x.value - bounding_box_south_west.x.value),
+                StaveSpaces::new(bounding_box_north_east.y.value - anchor.y.value),
+            )
+        };
+
+        SkylineCutOuts {
+            north_west: north_west.map(convert),
+            north_east: north_east.map(convert),
+            south_west: south_west.map(convert),
+            south_east: south_east.map(convert),
+        }
+    }
+}
+
+/// A single vertical slice of a Skyline: the vertical extent of a Block across
+/// a horizontal range, both relative to the (start, top) corner of the Block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkylineColumn {
+    start: f32,
+    end: f32,
+    top: f32,
+    bottom: f32,
+}
+
+impl SkylineColumn {
+    #[inline]
+    pub fn new(start: f32, end: f32, top: f32, bottom: f32) -> Self {
+        SkylineColumn {
+            start,
+            end,
+            top,
+            bottom,
+        }
+    }
+
+    #[inline]
+    pub fn get_start(&self) -> f32 {
+        self.start
+    }
+
+    #[inline]
+    pub fn get_end(&self) -> f32 {
+        self.end
+    }
+
+    #[inline]
+    pub fn get_top(&self) -> f32 {
+        self.top
+    }
+
+    #[inline]
+    pub fn get_bottom(&self) -> f32 {
+        self.bottom
+    }
+}
+
+/// The outline of a Block, expressed as piecewise top and bottom profiles across
+/// the Block's width. Where a bounding box treats a Block as a solid rectangle,
+/// a Skyline follows the actual shape of the Block's contents, so that closely nested
+/// symbols - a flag over a notehead, an accidental tucked beneath a stem - are not
+/// considered to collide, and can be resolved by moving them no further than necessary.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Skyline {
+    columns: Vec<SkylineColumn>,
+}
+
+impl Skyline {
+    /// Creates a new Skyline from the given columns. Columns should not overlap horizontally.
+    #[inline]
+    pub fn new(columns: Vec<SkylineColumn>) -> Self {
+        Skyline { columns }
+    }
+
+    /// Creates a new Skyline that fills a bounding box of the given width and height.
+    #[inline]
+    pub fn from_bounding_box(width: StaveSpaces, height: StaveSpaces) -> Self {
+        Self::new(vec![SkylineColumn::new(
+            0.0,
+            width.value,
+            0.0,
+            height.value,
+        )])
+    }
+
+    /// Creates a new Skyline that fills a bounding box of the given width and height,
+    /// less any of the given corner cut-outs.
+    pub fn from_cut_outs(
+        width: StaveSpaces,
+        height: StaveSpaces,
+        cut_outs: &SkylineCutOuts,
+    ) -> Self {
+        let width = width.value;
+
+        let height = height.value;
+
+        // Every horizontal edge of a cut-out potentially changes the profile, so
+        // we split the bounding box into columns at each cut-out's inner x position.
+
+        let mut breaks = vec![0.0, width];
+
+        for cut_out in [
+            &cut_outs.north_west,
+            &cut_outs.north_east,
+            &cut_outs.south_west,
+            &cut_outs.south_east,
+        ]
+        .iter()
+        .copied()
+        .flatten()
+        {
+            breaks.push(cut_out.x.value.max(0.0).min(width));
+        }
+
+        breaks.sort_by(|a, b| a.total_cmp(b));
+        breaks.dedup();
+
+        let mut columns = Vec::new();
+
+        for window in breaks.windows(2) {
+            let (start, end) = (window[0], window[1]);
+
+            if end <= start {
+                continue;
+            }
+
+            let middle = (start + end) / 2.0;
+
+            let mut top: f32 = 0.0;
+
+            let mut bottom = height;
+
+            if let Some(cut_out) = &cut_outs.north_west {
+                if middle < cut_out.x.value {
+                    top = top.max(cut_out.y.value);
+                }
+            }
+
+            if let Some(cut_out) = &cut_outs.north_east {
+                if middle > cut_out.x.value {
+                    top = top.max(cut_out.y.value);
+                }
+            }
+
+            if let Some(cut_out) = &cut_outs.south_west {
+                if middle < cut_out.x.value {
+                    bottom = bottom.min(cut_out.y.value);
+                }
+            }
+
+            if let Some(cut_out) = &cut_outs.south_east {
+                if middle > cut_out.x.value {
+                    bottom = bottom.min(cut_out.y.value);
+                }
+            }
+
+            if bottom > top {
+                columns.push(SkylineColumn::new(start, end, top, bottom));
+            }
+        }
+
+        Self::new(columns)
+    }
+
+    /// Creates a new Skyline for a glyph from the bounding box and cut-out anchors defined
+    /// for it in a SMuFL font's metadata, as for SkylineCutOuts::from_smufl_anchors().
+    pub fn from_smufl_anchors(
+        bounding_box_south_west: StavePoint,
+        bounding_box_north_east: StavePoint,
+        north_west: Option<StavePoint>,
+        north_east: Option<StavePoint>,
+        south_west: Option<StavePoint>,
+        south_east: Option<StavePoint>,
+    ) -> Self {
+        Self::from_cut_outs(
+            StaveSpaces::new(bounding_box_north_east.x.value - bounding_box_south_west.x.value),
+            StaveSpaces::new(bounding_box_north_east.y.value - bounding_box_south_west.y.value),
+            &SkylineCutOuts::from_smufl_anchors(
+                bounding_box_south_west,
+                bounding_box_north_east,
+                north_west,
+                north_east,
+                south_west,
+                south_east,
+            ),
+        )
+    }
+
+    /// Returns the columns making up this Skyline.
+    #[inline]
+    pub fn get_columns(&self) -> &[SkylineColumn] {
+        self.columns.as_slice()
+    }
+
+    /// Returns the width of this Skyline, measured from the start of the Block
+    /// to the end of its last column.
+    #[inline]
+    pub fn get_width(&self) -> f32 {
+        self.columns
+            .iter()
+            .map(|column| column.end)
+            .fold(0.0, f32::max)
+    }
+
+    /// Returns this Skyline placed with its (start, top) corner at the given solved position.
+    #[inline]
+    pub fn place(&self, start: f64, top: f64) -> PlacedSkyline {
+        PlacedSkyline {
+            skyline: self,
+            start,
+            top,
+        }
+    }
+}
+
+/// A Skyline placed at a solved position in a system.
+#[derive(Debug, Clone)]
+pub struct PlacedSkyline<'a> {
+    skyline: &'a Skyline,
+    start: f64,
+    top: f64,
+}
+
+impl<'a> PlacedSkyline<'a> {
+    /// Returns the (start..end) and (top..bottom) ranges of every column in this Skyline
+    /// at its placed position.
+    fn get_columns(&self) -> impl Iterator<Item = (Range<f64>, Range<f64>)> + '_ {
+        self.skyline.columns.iter().map(move |column| {
+            (
+                (self.start + column.start as f64)..(self.start + column.end as f64),
+                (self.top + column.top as f64)..(self.top + column.bottom as f64),
+            )
+        })
+    }
+
+    /// Returns true if any part of this Skyline overlaps any part of the given Skyline.
+    /// Skylines that merely touch do not collide.
+    pub fn collides_with(&self, other: &PlacedSkyline) -> bool {
+        self.get_columns().any(|(x, y)| {
+            other.get_columns().any(|(other_x, other_y)| {
+                Self::overlaps(&x, &other_x, 0.0) && Self::overlaps(&y, &other_y, 0.0)
+            })
+        })
+    }
+
+    /// Returns the distance this Skyline would need to move up so that it sits above
+    /// the given Skyline, at least the given gap clear of it, wherever the two share
+    /// a horizontal position. Returns zero if no movement is needed.
+    pub fn get_upward_clearance(&self, other: &PlacedSkyline, gap: f64) -> f64 {
+        self.get_clearance(other, |(x, y), (other_x, other_y)| {
+            if Self::overlaps(x, other_x, 0.0) {
+                Some(y.end + gap - other_y.start)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the distance this Skyline would need to move down so that it sits beneath
+    /// the given Skyline, at least the given gap clear of it, wherever the two share
+    /// a horizontal position. Returns zero if no movement is needed.
+    pub fn get_downward_clearance(&self, other: &PlacedSkyline, gap: f64) -> f64 {
+        self.get_clearance(other, |(x, y), (other_x, other_y)| {
+            if Self::overlaps(x, other_x, 0.0) {
+                Some(other_y.end + gap - y.start)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the distance this Skyline would need to move towards the end of the system
+    /// so that every part of it within the given gap of the given Skyline's vertical extent
+    /// sits after that part of the given Skyline, at least the given gap clear of it.
+    /// Returns zero if no movement is needed.
+    pub fn get_sideways_clearance(&self, other: &PlacedSkyline, gap: f64) -> f64 {
+        self.get_clearance(other, |(x, y), (other_x, other_y)| {
+            if Self::overlaps(y, other_y, gap) {
+                Some(other_x.end + gap - x.start)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the largest distance required by the given function across every pair
+    /// of columns from this Skyline and the given Skyline, or zero if no distance
+    /// is required.
+    fn get_clearance(
+        &self,
+        other: &PlacedSkyline,
+        distance: impl Fn((&Range<f64>, &Range<f64>), (&Range<f64>, &Range<f64>)) -> Option<f64>,
+    ) -> f64 {
+        let mut clearance: f64 = 0.0;
+
+        for (x, y) in self.get_columns() {
+            for (other_x, other_y) in other.get_columns() {
+                if let Some(distance) = distance((&x, &y), (&other_x, &other_y)) {
+                    clearance = clearance.max(distance);
+                }
+            }
+        }
+
+        clearance
+    }
+
+    /// Returns true if the two given ranges overlap, or come within the given gap of one another.
+    #[inline]
+    fn overlaps(a: &Range<f64>, b: &Range<f64>, gap: f64) -> bool {
+        a.start < b.end + gap && b.start < a.end + gap
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::concepts::stave_spaces::{AsStaveSpacesExt, StavePoint};
+    use crate::models::display::layout::skyline::{Skyline, SkylineColumn, SkylineCutOuts};
+
+    #[test]
+    fn test_skyline_from_cut_outs() {
+        // A 2 x 4 bounding box with the top right corner cut away, as for a flag
+        // sitting above a notehead.
+
+        let skyline = Skyline::from_cut_outs(
+            2.as_stave_spaces(),
+            4.as_stave_spaces(),
+            &SkylineCutOuts {
+                north_east: Some(StavePoint::new(1.as_stave_spaces(), 3.as_stave_spaces())),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            skyline.get_columns(),
+            &[
+                SkylineColumn::new(0.0, 1.0, 0.0, 4.0),
+                SkylineColumn::new(1.0, 2.0, 3.0, 4.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skyline_from_smufl_anchors() {
+        // The same shape as above, described as a SMuFL font's metadata would describe it:
+        // a glyph whose bounding box runs from (0, -1) to (2, 3) around its origin,
+        // with its top right corner cut away down to (1, 0).
+
+        let skyline = Skyline::from_smufl_anchors(
+            StavePoint::new(0.as_stave_spaces(), StaveSpaces::new(-1.0)),
+            StavePoint::new(2.as_stave_spaces(), 3.as_stave_spaces()),
+            None,
+            Some(StavePoint::new(1.as_stave_spaces(), 0.as_stave_spaces())),
+            None,
+            None,
+        );
+
+        assert_eq!(
+            skyline.get_columns(),
+            &[
+                SkylineColumn::new(0.0, 1.0, 0.0, 4.0),
+                SkylineColumn::new(1.0, 2.0, 3.0, 4.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skyline_clearances() {
+        let notch = Skyline::from_cut_outs(
+            2.as_stave_spaces(),
+            4.as_stave_spaces(),
+            &SkylineCutOuts {
+                north_east: Some(StavePoint::new(1.as_stave_spaces(), 3.as_stave_spaces())),
+                ..Default::default()
+            },
+        );
+
+        let square = Skyline::from_bounding_box(1.as_stave_spaces(), 1.as_stave_spaces());
+
+        // A 1 x 1 square tucked into the cut-out corner overlaps the notch's bounding box,
+        // but not the notch itself.
+
+        let notch = notch.place(0.0, 0.0);
+
+        let tucked = square.place(1.0, 1.0);
+
+        assert!(!tucked.collides_with(&notch));
+
+        // Moving the square down so it reaches into the notch's remaining ink
+        // creates a collision, which can be resolved by moving the square back up
+        // or sideways clear of the notch.
+
+        let lowered = square.place(1.0, 2.5);
+
+        assert!(lowered.collides_with(&notch));
+        assert_eq!(lowered.get_upward_clearance(&notch, 0.25), 0.75);
+        assert_eq!(lowered.get_sideways_clearance(&notch, 0.25), 1.25);
+
+        // Resolving a collision vertically only needs to consider columns that share
+        // a horizontal position.
+
+        assert_eq!(notch.get_downward_clearance(&lowered, 0.0), 0.5);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::models::display::engraving::engravable::{Engravable, EngravableItem};
+use crate::models::display::engraving::region::system::EngravedSystem;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// The default tolerance, in stave spaces, within which two snapshotted positions
+/// are considered equal. This absorbs floating point noise in the constraint solver
+/// without hiding genuine layout changes.
+pub const DEFAULT_SNAPSHOT_TOLERANCE: f32 = 0.001;
+
+/// The environment variable that, when set to any value, puts the golden file harness
+/// into blessing mode. In blessing mode, golden files are rewritten from the current
+/// engraving rather than compared against it, so an intended layout change can be
+/// accepted by re-running the tests once with this variable set.
+pub const BLESS_ENVIRONMENT_VARIABLE: &str = "BLESS_ENGRAVING_SNAPSHOTS";
+
+/// The directory holding golden files, relative to the manifest directory of the crate
+/// being tested. Golden files live alongside the host crate's integration tests rather
+/// than alongside this module, so they are found wherever the layout modules are placed
+/// within the crate.
+pub const GOLDEN_FILE_DIRECTORY: &str = "tests/golden";
+
+/// The position and size of a single Engravable in an EngravedSystemSnapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngravableSnapshot {
+    kind: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl EngravableSnapshot {
+    pub fn new(engravable: &Engravable) -> Self {
+        EngravableSnapshot {
+            kind: match engravable {
+                Engravable::Glyph(_) => "glyph",
+                Engravable::Line(_) => "line",
+                Engravable::Markup(_) => "markup",
+            }
+            .to_string(),
+            x: engravable.get_x().value,
+            y: engravable.get_y().value,
+            width: engravable.get_width().value,
+            height: engravable.get_height().value,
+        }
+    }
+}
+
+/// A snapshot of everything LayoutSystem::engrave() decides about an EngravedSystem:
+/// the system's size, the position of every grid line, and the position and size
+/// of every Engravable in each layer. Snapshots can be saved as JSON golden files
+/// and compared against later engravings, within a tolerance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngravedSystemSnapshot {
+    width: f32,
+    height: f32,
+    horizontal_grid_line_positions: Vec<f32>,
+    vertical_grid_line_positions: Vec<f32>,
+    background: Vec<EngravableSnapshot>,
+    midground: Vec<EngravableSnapshot>,
+    foreground: Vec<EngravableSnapshot>,
+}
+
+impl EngravedSystemSnapshot {
+    pub fn new(system: &EngravedSystem) -> Self {
+        let snapshot_layer = |engravables: &[Engravable]| {
+            engravables
+                .iter()
+                .map(EngravableSnapshot::new)
+                .collect::<Vec<_>>()
+        };
+
+        EngravedSystemSnapshot {
+            width: system.get_width().value,
+            height: system.get_height().value,
+            horizontal_grid_line_positions: system
+                .get_horizontal_grid_line_positions()
+                .iter()
+                .map(|position| position.value)
+                .collect(),
+            vertical_grid_line_positions: system
+                .get_vertical_grid_line_positions()
+                .iter()
+                .map(|position| position.value)
+                .collect(),
+            background: snapshot_layer(system.get_background()),
+            midground: snapshot_layer(system.get_midground()),
+            foreground: snapshot_layer(system.get_foreground()),
+        }
+    }
+
+    /// Returns a human-readable description of every difference between this snapshot
+    /// and the given expected snapshot. Positions and sizes within the given tolerance
+    /// of one another are considered equal. An empty list means the snapshots match.
+    pub fn get_differences(
+        &self,
+        expected: &EngravedSystemSnapshot,
+        tolerance: f32,
+    ) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        let mut compare = |name: String, actual: f32, expected: f32| {
+            if (actual - expected).abs() > tolerance {
+                differences.push(format!("{}: expected {}, found {}", name, expected, actual));
+            }
+        };
+
+        compare("width".to_string(), self.width, expected.width);
+        compare("height".to_string(), self.height, expected.height);
+
+        for (name, actual, expected) in [
+            (
+                "horizontal grid line",
+                &self.horizontal_grid_line_positions,
+                &expected.horizontal_grid_line_positions,
+            ),
+            (
+                "vertical grid line",
+                &self.vertical_grid_line_positions,
+                &expected.vertical_grid_line_positions,
+            ),
+        ] {
+            for (index, (actual, expected)) in actual.iter().zip(expected.iter()).enumerate() {
+                compare(format!("{} {}", name, index), *actual, *expected);
+            }
+        }
+
+        for (name, actual, expected) in [
+            ("background", &self.background, &expected.background),
+            ("midground", &self.midground, &expected.midground),
+            ("foreground", &self.foreground, &expected.foreground),
+        ] {
+            for (index, (actual, expected)) in actual.iter().zip(expected.iter()).enumerate() {
+                if actual.kind != expected.kind {
+                    differences.push(format!(
+                        "{} engravable {}: expected {}, found {}",
+                        name, index, expected.kind, actual.kind
+                    ));
+
+                    continue;
+                }
+
+                let mut compare = |field: &str, actual: f32, expected: f32| {
+                    if (actual - expected).abs() > tolerance {
+                        differences.push(format!(
+                            "{} engravable {} {}: expected {}, found {}",
+                            name, index, field, expected, actual
+                        ));
+                    }
+                };
+
+                compare("x", actual.x, expected.x);
+                compare("y", actual.y, expected.y);
+                compare("width", actual.width, expected.width);
+                compare("height", actual.height, expected.height);
+            }
+        }
+
+        for (name, actual, expected) in [
+            (
+                "horizontal grid lines",
+                self.horizontal_grid_line_positions.len(),
+                expected.horizontal_grid_line_positions.len(),
+            ),
+            (
+                "vertical grid lines",
+                self.vertical_grid_line_positions.len(),
+                expected.vertical_grid_line_positions.len(),
+            ),
+            (
+                "background engravables",
+                self.background.len(),
+                expected.background.len(),
+            ),
+            (
+                "midground engravables",
+                self.midground.len(),
+                expected.midground.len(),
+            ),
+            (
+                "foreground engravables",
+                self.foreground.len(),
+                expected.foreground.len(),
+            ),
+        ] {
+            if actual != expected {
+                differences.push(format!(
+                    "number of {}: expected {}, found {}",
+                    name, expected, actual
+                ));
+            }
+        }
+
+        differences
+    }
+}
+
+/// Returns the path of the golden file with the given name, within GOLDEN_FILE_DIRECTORY
+/// of the crate being tested.
+pub fn get_golden_file_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join(GOLDEN_FILE_DIRECTORY)
+        .join(format!("{}.json", name))
+}
+
+/// Compares the given EngravedSystem against the golden file with the given name,
+/// panicking with a list of every difference if the two do not match within the
+/// default snapshot tolerance. Intended for use in tests.
+///
+/// In blessing mode, the golden file is written from the given EngravedSystem instead,
+/// creating it if necessary. Outside blessing mode, a missing golden file is an error,
+/// so a test can never pass without checking anything.
+pub fn assert_matches_golden_file(name: &str, system: &EngravedSystem) {
+    let actual = EngravedSystemSnapshot::new(system);
+
+    let path = get_golden_file_path(name);
+
+    if std::env::var_os(BLESS_ENVIRONMENT_VARIABLE).is_some() {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).unwrap();
+        }
+
+        fs::write(&path, serde_json::to_string_pretty(&actual).unwrap()).unwrap();
+
+        return;
+    }
+
+    assert!(
+        path.exists(),
+        "Golden file {} does not exist; set {} to create it",
+        path.display(),
+        BLESS_ENVIRONMENT_VARIABLE
+    );
+
+    let expected: EngravedSystemSnapshot =
+        serde_json::from_str(fs::read_to_string(&path).unwrap().as_str()).unwrap();
+
+    let differences = actual.get_differences(&expected, DEFAULT_SNAPSHOT_TOLERANCE);
+
+    assert!(
+        differences.is_empty(),
+        "Engraving does not match golden file {}; set {} to update it if the change is intended:\n{}",
+        path.display(),
+        BLESS_ENVIRONMENT_VARIABLE,
+        differences.join("\n")
+    );
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::layout::snapshot::{
+        EngravableSnapshot, EngravedSystemSnapshot, DEFAULT_SNAPSHOT_TOLERANCE,
+    };
+
+    #[test]
+    fn test_snapshot_differences() {
+        let expected = create_snapshot(10.0, 2.0);
+
+        // Differences within the tolerance should be ignored.
+
+        assert!(
+            create_snapshot(10.0, 2.0 + DEFAULT_SNAPSHOT_TOLERANCE / 2.0)
+                .get_differences(&expected, DEFAULT_SNAPSHOT_TOLERANCE)
+                .is_empty()
+        );
+
+        // Differences beyond the tolerance should each be reported.
+
+        assert_eq!(
+            create_snapshot(12.0, 2.5).get_differences(&expected, DEFAULT_SNAPSHOT_TOLERANCE),
+            vec![
+                "width: expected 10, found 12".to_string(),
+                "vertical grid line 1: expected 10, found 12".to_string(),
+                "foreground engravable 0 x: expected 2, found 2.5".to_string(),
+            ]
+        );
+    }
+
+    fn create_snapshot(width: f32, x: f32) -> EngravedSystemSnapshot {
+        EngravedSystemSnapshot {
+            width,
+            height: 4.0,
+            horizontal_grid_line_positions: vec![0.0],
+            vertical_grid_line_positions: vec![0.0, width],
+            background: vec![],
+            midground: vec![],
+            foreground: vec![EngravableSnapshot {
+                kind: "glyph".to_string(),
+                x,
+                y: 0.0,
+                width: 1.0,
+                height: 1.0,
+            }],
+        }
+    }
+}
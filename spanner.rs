@@ -0,0 +1,827 @@
+use crate::models::display::concepts::color::Color;
+use crate::models::display::concepts::stave_spaces::{StavePoint, StaveSpaces, STAVE_SPACES_ZERO};
+use crate::models::display::concepts::stroke::StrokeStyle;
+use crate::models::display::grid::horizontal::HorizontalGridLineIndex;
+use crate::models::display::grid::vertical::{
+    VerticalGridLine, VerticalGridLineIndex, VerticalGridLineType,
+};
+use crate::models::display::layout::block::{Block, BlockConstraint, BlockLayer};
+use crate::models::display::layout::skyline::Skyline;
+use crate::models::music::concepts::ticks::Ticks;
+use crate::models::music::spine::{MomentSpineItem, PartIndex, VoiceIndex};
+use serde::{Deserialize, Serialize};
+
+pub type SpannerIndex = usize;
+
+/// The default thickness of the lines making up a spanner, in stave spaces.
+pub const DEFAULT_SPANNER_THICKNESS: f32 = 0.16;
+
+/// The default height of a spanner, in stave spaces: the opening of a hairpin at its
+/// open end, the length of the hooks closing octave and pedal lines, or the height of
+/// the wavy line of a trill extension.
+pub const DEFAULT_SPANNER_HEIGHT: f32 = 1.0;
+
+/// The default minimum length of a spanner, in stave spaces. Shorter spanners are hard
+/// to read, so the grid lines a spanner is locked to are pushed apart where possible.
+pub const DEFAULT_MINIMUM_SPANNER_LENGTH: f32 = 2.0;
+
+/// The horizontal distance covered by a single rise or fall of the wavy line of a
+/// trill extension, in stave spaces.
+const TRILL_EXTENSION_WAVE_LENGTH: f32 = 0.25;
+
+/// The kind of notation a spanner represents.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SpannerKind {
+    /// A hairpin opening from its start to its end.
+    Crescendo,
+
+    /// A hairpin closing from its start to its end.
+    Diminuendo,
+
+    /// A dashed line above the stave, closed by a downwards hook at its end. The octave
+    /// marking at the start of the line is not part of the spanner; it is added as a Block,
+    /// with the spanner's start anchored past it.
+    OctaveAbove,
+
+    /// A dashed line beneath the stave, closed by an upwards hook at its end. As with
+    /// OctaveAbove, the octave marking at the start of the line is added as a Block.
+    OctaveBelow,
+
+    /// A solid line beneath the stave, closed by upwards hooks at both ends.
+    Pedal,
+
+    /// A wavy line extending a trill.
+    TrillExtension,
+}
+
+/// One end of a spanner: the onset the spanner starts or ends at, and the VerticalGridLine
+/// marking that onset on this system. The grid line may be omitted for an onset that
+/// falls on a different system, since it plays no part in the layout of this system.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpannerAnchor {
+    onset: Ticks,
+    grid_line: Option<VerticalGridLineIndex>,
+}
+
+impl SpannerAnchor {
+    #[inline]
+    pub fn new(onset: Ticks, grid_line: Option<VerticalGridLineIndex>) -> Self {
+        SpannerAnchor { onset, grid_line }
+    }
+
+    #[inline]
+    pub fn get_onset(&self) -> Ticks {
+        self.onset
+    }
+
+    #[inline]
+    pub fn get_grid_line(&self) -> Option<VerticalGridLineIndex> {
+        self.grid_line
+    }
+}
+
+/// A hairpin, octave line, pedal line, or trill extension running horizontally between
+/// two onsets in a LayoutSystem, crossing any grid columns in between. The start and end
+/// of the spanner are locked to the VerticalGridLines of its anchor onsets, and the
+/// spanner is centered vertically on the given HorizontalGridLine.
+///
+/// A spanner may begin on an earlier system or end on a later one. The onsets that fall
+/// on a system are those from the system's start ticks up to, but not including,
+/// its end ticks. A spanner continued from the previous system starts at the start of the
+/// system interior, or at the system's leading edge if the system has no interior;
+/// a spanner continued onto the next system ends at the end of the system interior,
+/// or at the end of the system if the system has no interior. Continued ends are flagged
+/// on the engraved spanner so renderers can leave them open.
+///
+/// Each spanner is accompanied by a SpannerBlock occupying the spanner's extent in the layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutSpanner {
+    kind: SpannerKind,
+    start: SpannerAnchor,
+    end: SpannerAnchor,
+    line: HorizontalGridLineIndex,
+    thickness: StaveSpaces,
+    height: StaveSpaces,
+    minimum_length: StaveSpaces,
+}
+
+impl LayoutSpanner {
+    /// Creates a new LayoutSpanner of the given kind, running between the given anchors
+    /// and centered vertically on the HorizontalGridLine at the given index.
+    pub fn new(
+        kind: SpannerKind,
+        start: SpannerAnchor,
+        end: SpannerAnchor,
+        line: HorizontalGridLineIndex,
+    ) -> Self {
+        LayoutSpanner {
+            kind,
+            start,
+            end,
+            line,
+            thickness: StaveSpaces::new(DEFAULT_SPANNER_THICKNESS),
+            height: StaveSpaces::new(DEFAULT_SPANNER_HEIGHT),
+            minimum_length: StaveSpaces::new(DEFAULT_MINIMUM_SPANNER_LENGTH),
+        }
+    }
+
+    #[inline]
+    pub fn get_kind(&self) -> SpannerKind {
+        self.kind
+    }
+
+    #[inline]
+    pub fn get_start(&self) -> &SpannerAnchor {
+        &self.start
+    }
+
+    #[inline]
+    pub fn get_end(&self) -> &SpannerAnchor {
+        &self.end
+    }
+
+    /// Returns the index of the HorizontalGridLine this spanner is centered on.
+    #[inline]
+    pub fn get_line(&self) -> HorizontalGridLineIndex {
+        self.line
+    }
+
+    #[inline]
+    pub fn get_thickness(&self) -> StaveSpaces {
+        self.thickness
+    }
+
+    #[inline]
+    pub fn set_thickness(&mut self, thickness: StaveSpaces) {
+        self.thickness = thickness;
+    }
+
+    #[inline]
+    pub fn get_height(&self) -> StaveSpaces {
+        self.height
+    }
+
+    #[inline]
+    pub fn set_height(&mut self, height: StaveSpaces) {
+        self.height = height;
+    }
+
+    #[inline]
+    pub fn get_minimum_length(&self) -> StaveSpaces {
+        self.minimum_length
+    }
+
+    #[inline]
+    pub fn set_minimum_length(&mut self, length: StaveSpaces) {
+        self.minimum_length = length;
+    }
+
+    /// Returns true if this spanner starts before a system starting at the given ticks.
+    #[inline]
+    pub fn is_continued_from_previous_system(&self, system_start_ticks: Ticks) -> bool {
+        self.start.onset < system_start_ticks
+    }
+
+    /// Returns true if this spanner ends after a system ending at the given ticks.
+    #[inline]
+    pub fn is_continued_onto_next_system(&self, system_end_ticks: Ticks) -> bool {
+        self.end.onset >= system_end_ticks
+    }
+
+    /// Returns the indices of the VerticalGridLines this spanner's start and end are locked to
+    /// on a system with the given grid lines, leading edge, and start and end ticks.
+    /// Either index is None if that end of the spanner cannot be placed on the system.
+    pub fn get_grid_lines(
+        &self,
+        vertical_grid_lines: &[VerticalGridLine],
+        leading_edge: VerticalGridLineIndex,
+        system_start_ticks: Ticks,
+        system_end_ticks: Ticks,
+    ) -> (Option<VerticalGridLineIndex>, Option<VerticalGridLineIndex>) {
+        let first_of_type = |grid_line_type: VerticalGridLineType| {
+            vertical_grid_lines
+                .iter()
+                .position(|grid_line| grid_line.get_grid_line_type() == grid_line_type)
+        };
+
+        let start = if self.is_continued_from_previous_system(system_start_ticks) {
+            first_of_type(VerticalGridLineType::InteriorStart).or(Some(leading_edge))
+        } else {
+            self.start.grid_line
+        };
+
+        let end = if self.is_continued_onto_next_system(system_end_ticks) {
+            first_of_type(VerticalGridLineType::InteriorEnd)
+                .or_else(|| first_of_type(VerticalGridLineType::SystemEnd))
+        } else {
+            self.end.grid_line
+        };
+
+        (start, end)
+    }
+}
+
+/// A Block standing in for a spanner in its LayoutSystem. LayoutSystem::add_spanner() adds
+/// a SpannerBlock alongside every spanner, taking its source onset from the spanner's start.
+/// Once the layout has settled, the solver fits the SpannerBlock's bounding box around the
+/// spanner, so the spanner contributes to the extent of the engraved system.
+///
+/// Spanners are placed on HorizontalGridLines of their own rather than moved clear of other
+/// Blocks, so a SpannerBlock takes no part in collision detection. A SpannerBlock is never
+/// engraved; the spanner is engraved separately as a line for each of its segments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpannerBlock {
+    source_moment_spine_item: Option<MomentSpineItem>,
+    source_onset: Option<Ticks>,
+    source_part_index: Option<PartIndex>,
+    source_voice_index: Option<VoiceIndex>,
+    is_visible: bool,
+    is_collidable: bool,
+    can_move_up_to_avoid_vertical_collision: bool,
+    can_move_down_to_avoid_vertical_collision: bool,
+    top_padding: StaveSpaces,
+    bottom_padding: StaveSpaces,
+    start_padding: StaveSpaces,
+    end_padding: StaveSpaces,
+    minimum_width: Option<StaveSpaces>,
+    skyline: Option<Skyline>,
+    constraints: Vec<BlockConstraint>,
+    index: SpannerIndex,
+}
+
+impl SpannerBlock {
+    /// Creates a new SpannerBlock for the given spanner, held at the given index in its
+    /// LayoutSystem.
+    pub fn new(index: SpannerIndex, spanner: &LayoutSpanner) -> Self {
+        SpannerBlock {
+            source_moment_spine_item: None,
+            source_part_index: None,
+            source_onset: Some(spanner.get_start().get_onset()),
+            source_voice_index: None,
+            is_visible: false,
+            is_collidable: false,
+            can_move_up_to_avoid_vertical_collision: false,
+            can_move_down_to_avoid_vertical_collision: false,
+            top_padding: STAVE_SPACES_ZERO,
+            bottom_padding: STAVE_SPACES_ZERO,
+            start_padding: STAVE_SPACES_ZERO,
+            end_padding: STAVE_SPACES_ZERO,
+            minimum_width: None,
+            skyline: None,
+            constraints: vec![],
+            index,
+        }
+    }
+
+    /// Returns the index of the spanner this SpannerBlock stands in for.
+    #[inline]
+    pub fn get_spanner_index(&self) -> SpannerIndex {
+        self.index
+    }
+}
+
+impl Block for SpannerBlock {
+    #[inline]
+    fn is_fixed_width(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn get_fixed_width(&self) -> StaveSpaces {
+        STAVE_SPACES_ZERO
+    }
+
+    #[inline]
+    fn is_fixed_height(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn get_fixed_height(&self) -> StaveSpaces {
+        STAVE_SPACES_ZERO
+    }
+
+    #[inline]
+    fn get_source_moment_spine_item(&self) -> Option<&MomentSpineItem> {
+        self.source_moment_spine_item.as_ref()
+    }
+
+    #[inline]
+    fn get_source_onset(&self) -> Option<Ticks> {
+        self.source_onset
+    }
+
+    #[inline]
+    fn set_source_onset(&mut self, onset: Option<Ticks>) {
+        self.source_onset = onset;
+    }
+
+    #[inline]
+    fn get_source_part_index(&self) -> Option<PartIndex> {
+        self.source_part_index
+    }
+
+    #[inline]
+    fn get_source_voice_index(&self) -> Option<VoiceIndex> {
+        self.source_voice_index
+    }
+
+    #[inline]
+    fn get_layer(&self) -> BlockLayer {
+        BlockLayer::Foreground
+    }
+
+    #[inline]
+    fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    #[inline]
+    fn set_visible(&mut self, is_visible: bool) {
+        self.is_visible = is_visible;
+    }
+
+    #[inline]
+    fn is_collidable(&self) -> bool {
+        self.is_collidable
+    }
+
+    #[inline]
+    fn set_collidable(&mut self, is_collidable: bool) {
+        self.is_collidable = is_collidable;
+    }
+
+    #[inline]
+    fn get_can_move_up_to_avoid_vertical_collision(&self) -> bool {
+        self.can_move_up_to_avoid_vertical_collision
+    }
+
+    #[inline]
+    fn set_can_move_up_to_avoid_vertical_collision(&mut self, can_move: bool) {
+        self.can_move_up_to_avoid_vertical_collision = can_move;
+    }
+
+    #[inline]
+    fn get_can_move_down_to_avoid_vertical_collision(&self) -> bool {
+        self.can_move_down_to_avoid_vertical_collision
+    }
+
+    #[inline]
+    fn set_can_move_down_to_avoid_vertical_collision(&mut self, can_move: bool) {
+        self.can_move_down_to_avoid_vertical_collision = can_move;
+    }
+
+    #[inline]
+    fn get_skyline(&self) -> Option<&Skyline> {
+        self.skyline.as_ref()
+    }
+
+    #[inline]
+    fn set_skyline(&mut self, skyline: Option<Skyline>) {
+        self.skyline = skyline;
+    }
+
+    #[inline]
+    fn get_top_padding(&self) -> StaveSpaces {
+        self.top_padding
+    }
+
+    #[inline]
+    fn set_top_padding(&mut self, padding: StaveSpaces) {
+        self.top_padding = padding;
+    }
+
+    #[inline]
+    fn get_bottom_padding(&self) -> StaveSpaces {
+        self.bottom_padding
+    }
+
+    #[inline]
+    fn set_bottom_padding(&mut self, padding: StaveSpaces) {
+        self.bottom_padding = padding;
+    }
+
+    #[inline]
+    fn get_start_padding(&self) -> StaveSpaces {
+        self.start_padding
+    }
+
+    #[inline]
+    fn set_start_padding(&mut self, padding: StaveSpaces) {
+        self.start_padding = padding;
+    }
+
+    #[inline]
+    fn get_end_padding(&self) -> StaveSpaces {
+        self.end_padding
+    }
+
+    #[inline]
+    fn set_end_padding(&mut self, padding: StaveSpaces) {
+        self.end_padding = padding;
+    }
+
+    #[inline]
+    fn get_minimum_width(&self) -> Option<StaveSpaces> {
+        self.minimum_width
+    }
+
+    #[inline]
+    fn set_minimum_width(&mut self, minimum_width: Option<StaveSpaces>) {
+        self.minimum_width = minimum_width;
+    }
+
+    #[inline]
+    fn get_constraints(&self) -> &[BlockConstraint] {
+        self.constraints.as_slice()
+    }
+
+    #[inline]
+    fn add_constraint(&mut self, constraint: BlockConstraint) {
+        self.constraints.push(constraint);
+    }
+}
+
+/// A positioned spanner, ready for rendering. The start and end points lie on the
+/// spanner's center line; the flags record which ends, if any, continue onto an adjacent
+/// system, so those ends can be left open. Spanners are engraved as a line for each
+/// of their segments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngravedSpanner {
+    kind: SpannerKind,
+    start: StavePoint,
+    end: StavePoint,
+    thickness: StaveSpaces,
+    height: StaveSpaces,
+    is_continued_from_previous_system: bool,
+    is_continued_onto_next_system: bool,
+    color: Color,
+}
+
+impl EngravedSpanner {
+    pub fn new(
+        kind: SpannerKind,
+        start: StavePoint,
+        end: StavePoint,
+        thickness: StaveSpaces,
+        height: StaveSpaces,
+        is_continued_from_previous_system: bool,
+        is_continued_onto_next_system: bool,
+    ) -> Self {
+        EngravedSpanner {
+            kind,
+            start,
+            end,
+            thickness,
+            height,
+            is_continued_from_previous_system,
+            is_continued_onto_next_system,
+            color: Color::BLACK,
+        }
+    }
+
+    #[inline]
+    pub fn get_kind(&self) -> SpannerKind {
+        self.kind
+    }
+
+    #[inline]
+    pub fn get_start(&self) -> &StavePoint {
+        &self.start
+    }
+
+    #[inline]
+    pub fn get_end(&self) -> &StavePoint {
+        &self.end
+    }
+
+    #[inline]
+    pub fn get_thickness(&self) -> StaveSpaces {
+        self.thickness
+    }
+
+    #[inline]
+    pub fn get_height(&self) -> StaveSpaces {
+        self.height
+    }
+
+    #[inline]
+    pub fn is_continued_from_previous_system(&self) -> bool {
+        self.is_continued_from_previous_system
+    }
+
+    #[inline]
+    pub fn is_continued_onto_next_system(&self) -> bool {
+        self.is_continued_onto_next_system
+    }
+
+    #[inline]
+    pub fn get_color(&self) -> &Color {
+        &self.color
+    }
+
+    /// Sets the color of this spanner. Defaults to Color::BLACK.
+    #[inline]
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    /// Returns the (left, top, right, bottom) extent of the line segments making up
+    /// this spanner, including their thickness.
+    pub fn get_extent(&self) -> (f32, f32, f32, f32) {
+        let half_thickness = self.thickness.value / 2.0;
+
+        self.get_segments().iter().fold(
+            (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+            |(left, top, right, bottom), (from, to)| {
+                (
+                    left.min(from.x.value.min(to.x.value) - half_thickness),
+                    top.min(from.y.value.min(to.y.value) - half_thickness),
+                    right.max(from.x.value.max(to.x.value) + half_thickness),
+                    bottom.max(from.y.value.max(to.y.value) + half_thickness),
+                )
+            },
+        )
+    }
+
+    /// Returns the stroke style of the lines making up this spanner.
+    #[inline]
+    pub fn get_stroke_style(&self) -> StrokeStyle {
+        match self.kind {
+            SpannerKind::OctaveAbove | SpannerKind::OctaveBelow => StrokeStyle::Dashed,
+            _ => StrokeStyle::Solid,
+        }
+    }
+
+    /// Returns the (start, end) points of the straight line segments making up this spanner,
+    /// each of which is engraved as a line.
+    ///
+    /// Closed ends are drawn conventionally: the point of a hairpin, or the hooks at the ends
+    /// of octave and pedal lines. Continued ends are left open: a hairpin continued across
+    /// a system break is drawn half open at the break, and the hooks at continued ends of
+    /// octave and pedal lines are omitted.
+    pub fn get_segments(&self) -> Vec<(StavePoint, StavePoint)> {
+        let start_x = self.start.x.value;
+
+        let end_x = self.end.x.value;
+
+        let start_y = self.start.y.value;
+
+        let end_y = self.end.y.value;
+
+        let height = self.height.value;
+
+        let point = |x: f32, y: f32| StavePoint::new(StaveSpaces::new(x), StaveSpaces::new(y));
+
+        let mut segments = Vec::new();
+
+        match self.kind {
+            SpannerKind::Crescendo | SpannerKind::Diminuendo => {
+                let (closed, open) = if self.kind == SpannerKind::Crescendo {
+                    (
+                        self.is_continued_from_previous_system,
+                        self.is_continued_onto_next_system,
+                    )
+                } else {
+                    (
+                        self.is_continued_onto_next_system,
+                        self.is_continued_from_previous_system,
+                    )
+                };
+
+                let closed_opening = if closed { height / 2.0 } else { 0.0 };
+
+                let open_opening = if open { height / 2.0 } else { height };
+
+                let (start_opening, end_opening) = if self.kind == SpannerKind::Crescendo {
+                    (closed_opening, open_opening)
+                } else {
+                    (open_opening, closed_opening)
+                };
+
+                for side in [-0.5, 0.5] {
+                    segments.push((
+                        point(start_x, start_y + start_opening * side),
+                        point(end_x, end_y + end_opening * side),
+                    ));
+                }
+            }
+            SpannerKind::OctaveAbove | SpannerKind::OctaveBelow => {
+                segments.push((point(start_x, start_y), point(end_x, end_y)));
+
+                if !self.is_continued_onto_next_system {
+                    let hook = if self.kind == SpannerKind::OctaveAbove {
+                        height
+                    } else {
+                        -height
+                    };
+
+                    segments.push((point(end_x, end_y), point(end_x, end_y + hook)));
+                }
+            }
+            SpannerKind::Pedal => {
+                if !self.is_continued_from_previous_system {
+                    segments.push((point(start_x, start_y - height), point(start_x, start_y)));
+                }
+
+                segments.push((point(start_x, start_y), point(end_x, end_y)));
+
+                if !self.is_continued_onto_next_system {
+                    segments.push((point(end_x, end_y), point(end_x, end_y - height)));
+                }
+            }
+            SpannerKind::TrillExtension => {
+                // A zig-zag alternating between a quarter of the spanner's height above
+                // and below its center line.
+
+                let waves = ((end_x - start_x) / TRILL_EXTENSION_WAVE_LENGTH)
+                    .floor()
+                    .max(1.0) as usize;
+
+                let points = (0..=waves)
+                    .map(|wave| {
+                        let t = wave as f32 / waves as f32;
+
+                        let side = if wave % 2 == 0 { -0.25 } else { 0.25 };
+
+                        point(
+                            start_x + (end_x - start_x) * t,
+                            start_y + (end_y - start_y) * t + height * side,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                for pair in points.windows(2) {
+                    segments.push((
+                        point(pair[0].x.value, pair[0].y.value),
+                        point(pair[1].x.value, pair[1].y.value),
+                    ));
+                }
+            }
+        }
+
+        segments
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::concepts::stave_spaces::{AsStaveSpacesExt, StavePoint};
+    use crate::models::display::grid::vertical::{VerticalGridLine, VerticalGridLineType};
+    use crate::models::display::layout::spanner::{
+        EngravedSpanner, LayoutSpanner, SpannerAnchor, SpannerKind,
+    };
+    use crate::models::music::concepts::ticks::AsTicksExt;
+
+    #[test]
+    fn test_spanner_grid_lines() {
+        let grid_lines = vec![
+            VerticalGridLine::new(0, VerticalGridLineType::SystemStart),
+            VerticalGridLine::new(0, VerticalGridLineType::SystemEnd),
+            VerticalGridLine::new(1, VerticalGridLineType::NoteheadLine0NoteheadStackStart),
+            VerticalGridLine::new(2, VerticalGridLineType::NoteheadLine0NoteheadStackStart),
+        ];
+
+        // A spanner between two onsets on a system running from ticks 100 to 200
+        // is locked to the grid lines of those onsets.
+
+        let spanner = LayoutSpanner::new(
+            SpannerKind::Crescendo,
+            SpannerAnchor::new(100.as_ticks(), Some(2)),
+            SpannerAnchor::new(150.as_ticks(), Some(3)),
+            0,
+        );
+
+        assert_eq!(
+            spanner.get_grid_lines(grid_lines.as_slice(), 0, 100.as_ticks(), 200.as_ticks()),
+            (Some(2), Some(3))
+        );
+
+        // A spanner starting on the previous system starts at the system's leading edge;
+        // a spanner ending on the next system, including one ending on the first onset
+        // of the next system, ends at the end of the system.
+
+        let spanner = LayoutSpanner::new(
+            SpannerKind::Pedal,
+            SpannerAnchor::new(50.as_ticks(), None),
+            SpannerAnchor::new(200.as_ticks(), None),
+            0,
+        );
+
+        assert!(spanner.is_continued_from_previous_system(100.as_ticks()));
+        assert!(spanner.is_continued_onto_next_system(200.as_ticks()));
+
+        assert_eq!(
+            spanner.get_grid_lines(grid_lines.as_slice(), 0, 100.as_ticks(), 200.as_ticks()),
+            (Some(0), Some(1))
+        );
+
+        // An onset on the system with no grid line cannot be placed.
+
+        let spanner = LayoutSpanner::new(
+            SpannerKind::Pedal,
+            SpannerAnchor::new(100.as_ticks(), None),
+            SpannerAnchor::new(200.as_ticks(), None),
+            0,
+        );
+
+        assert_eq!(
+            spanner.get_grid_lines(grid_lines.as_slice(), 0, 100.as_ticks(), 200.as_ticks()),
+            (None, Some(1))
+        );
+    }
+
+    #[test]
+    fn test_spanner_segments() {
+        let start = StavePoint::new(0.as_stave_spaces(), 2.as_stave_spaces());
+
+        let end = StavePoint::new(10.as_stave_spaces(), 2.as_stave_spaces());
+
+        let hairpin = |kind: SpannerKind, is_continued_from: bool, is_continued_onto: bool| {
+            EngravedSpanner::new(
+                kind,
+                start.clone(),
+                end.clone(),
+                0.16.as_stave_spaces(),
+                1.as_stave_spaces(),
+                is_continued_from,
+                is_continued_onto,
+            )
+            .get_segments()
+            .iter()
+            .map(|(start, end)| (start.y.value, end.y.value))
+            .collect::<Vec<_>>()
+        };
+
+        // A crescendo opens from a point to the full height of the spanner.
+
+        assert_eq!(
+            hairpin(SpannerKind::Crescendo, false, false),
+            vec![(2.0, 1.5), (2.0, 2.5)]
+        );
+
+        // Split across a system break, the crescendo is drawn half open at the break,
+        // at the end of the first part and the start of the second.
+
+        assert_eq!(
+            hairpin(SpannerKind::Crescendo, false, true),
+            vec![(2.0, 1.75), (2.0, 2.25)]
+        );
+
+        assert_eq!(
+            hairpin(SpannerKind::Crescendo, true, false),
+            vec![(1.75, 1.5), (2.25, 2.5)]
+        );
+
+        // A diminuendo continued onto the next system does not close on this system.
+
+        assert_eq!(
+            hairpin(SpannerKind::Diminuendo, false, true),
+            vec![(1.5, 1.75), (2.5, 2.25)]
+        );
+
+        // Pedal lines are hooked at closed ends only.
+
+        let pedal = |is_continued_from: bool, is_continued_onto: bool| {
+            EngravedSpanner::new(
+                SpannerKind::Pedal,
+                start.clone(),
+                end.clone(),
+                0.16.as_stave_spaces(),
+                1.as_stave_spaces(),
+                is_continued_from,
+                is_continued_onto,
+            )
+            .get_segments()
+            .len()
+        };
+
+        assert_eq!(pedal(false, false), 3);
+        assert_eq!(pedal(true, false), 2);
+        assert_eq!(pedal(true, true), 1);
+    }
+
+    #[test]
+    fn test_spanner_extent() {
+        // An octave line above the stave hooks downwards by its height at its end, so its
+        // extent reaches from its center line down to the end of the hook, widened by half
+        // the line thickness on every side.
+
+        let spanner = EngravedSpanner::new(
+            SpannerKind::OctaveAbove,
+            StavePoint::new(0.as_stave_spaces(), 2.as_stave_spaces()),
+            StavePoint::new(10.as_stave_spaces(), 2.as_stave_spaces()),
+            0.16.as_stave_spaces(),
+            1.as_stave_spaces(),
+            false,
+            false,
+        );
+
+        let (left, top, right, bottom) = spanner.get_extent();
+
+        assert!((left - -0.08).abs() < 0.001);
+        assert!((top - 1.92).abs() < 0.001);
+        assert!((right - 10.08).abs() < 0.001);
+        assert!((bottom - 3.08).abs() < 0.001);
+    }
+}
@@ -0,0 +1,33 @@
+use cassowary::strength::{MEDIUM, REQUIRED, STRONG, WEAK};
+use serde::{Deserialize, Serialize};
+
+/// The strength with which the linear constraint solver should honour a single
+/// BlockConstraint, HorizontalGridLineConstraint, or VerticalGridLineConstraint.
+/// Where two constraints conflict, the stronger constraint wins. Constraints that do not
+/// specify a strength take a default strength based on their type: Lock* constraints
+/// default to Strong, Float* constraints default to Weak.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConstraintStrength {
+    /// The constraint must be satisfied. If it cannot be satisfied, engraving will fail.
+    Required,
+    Strong,
+    Medium,
+    Weak,
+    /// A custom constraint weight, as understood by the Cassowary solver. Values will be
+    /// clipped so they never exceed the Required strength.
+    Custom(f64),
+}
+
+impl ConstraintStrength {
+    /// Returns the Cassowary solver strength corresponding to this ConstraintStrength.
+    #[inline]
+    pub fn as_solver_strength(&self) -> f64 {
+        match self {
+            ConstraintStrength::Required => REQUIRED,
+            ConstraintStrength::Strong => STRONG,
+            ConstraintStrength::Medium => MEDIUM,
+            ConstraintStrength::Weak => WEAK,
+            ConstraintStrength::Custom(weight) => cassowary::strength::clip(*weight),
+        }
+    }
+}
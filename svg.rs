@@ -0,0 +1,394 @@
+use crate::models::display::concepts::color::Color;
+use crate::models::display::concepts::stave_spaces::StaveSpaces;
+use crate::models::display::concepts::stroke::StrokeStyle;
+use crate::models::display::engraving::engravable::glyph::EngravedGlyph;
+use crate::models::display::engraving::engravable::line::EngravedLine;
+use crate::models::display::engraving::engravable::markup::EngravedMarkup;
+use crate::models::display::engraving::engravable::{Engravable, EngravableItem};
+use crate::models::display::engraving::region::system::EngravedSystem;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The number of stave spaces in one em of a SMuFL music font. The SMuFL specification
+/// defines the em as the height of a five-line stave, i.e. four stave spaces.
+const SMUFL_STAVE_SPACES_PER_EM: f32 = 4.0;
+
+/// The default rendered size of a single stave space, in SVG user units (CSS pixels).
+pub const DEFAULT_SVG_STAVE_SPACE_SIZE: f32 = 8.0;
+
+/// Renders an EngravedSystem to a standalone SVG document.
+///
+/// All coordinates in the document are expressed in stave spaces by means of the
+/// SVG viewBox; only the width and height of the outermost <svg> element are scaled
+/// to the configured stave space size. Glyphs are rendered as text using their SMuFL
+/// codepoints, so the named music font (typically Bravura) must be available to
+/// whatever eventually displays the document.
+pub struct SvgExporter {
+    music_font_family: String,
+    text_font_family: String,
+    stave_space_size: f32,
+}
+
+impl SvgExporter {
+    pub fn new(music_font_family: &str, text_font_family: &str, stave_space_size: f32) -> Self {
+        SvgExporter {
+            music_font_family: music_font_family.to_string(),
+            text_font_family: text_font_family.to_string(),
+            stave_space_size,
+        }
+    }
+
+    #[inline]
+    pub fn get_music_font_family(&self) -> &str {
+        self.music_font_family.as_str()
+    }
+
+    #[inline]
+    pub fn get_text_font_family(&self) -> &str {
+        self.text_font_family.as_str()
+    }
+
+    #[inline]
+    pub fn get_stave_space_size(&self) -> f32 {
+        self.stave_space_size
+    }
+
+    /// Renders the given EngravedSystem to an SVG document. Layers are emitted back to
+    /// front - background, then midground, then foreground - so that later layers are
+    /// painted over earlier ones.
+    pub fn export(&self, system: &EngravedSystem) -> String {
+        let width = system.get_width();
+
+        let height = system.get_height();
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+            Self::format_number(width.value * self.stave_space_size),
+            Self::format_number(height.value * self.stave_space_size),
+            Self::format_number(width.value),
+            Self::format_number(height.value),
+        );
+
+        svg.push_str(&self.export_layer("background", system.get_background()));
+        svg.push_str(&self.export_layer("midground", system.get_midground()));
+        svg.push_str(&self.export_layer("foreground", system.get_foreground()));
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+
+    /// Renders the given EngravedSystem to an SVG document and writes it to the given path.
+    pub fn export_to_file(&self, system: &EngravedSystem, path: &Path) -> io::Result<()> {
+        fs::write(path, self.export(system))
+    }
+
+    fn export_layer(&self, name: &str, engravables: &[Engravable]) -> String {
+        let mut layer = format!("  <g class=\"{}\">\n", name);
+
+        for engravable in engravables.iter() {
+            let element = match engravable {
+                Engravable::Glyph(glyph) => self.export_glyph(glyph),
+                Engravable::Line(line) => self.export_line(line),
+                Engravable::Markup(markup) => self.export_markup(markup),
+            };
+
+            layer.push_str("    ");
+            layer.push_str(&element);
+            layer.push('\n');
+        }
+
+        layer.push_str("  </g>\n");
+
+        layer
+    }
+
+    fn export_glyph(&self, glyph: &EngravedGlyph) -> String {
+        // SVG positions text by its baseline rather than by the top of its bounding box,
+        // so we must offset the glyph by its height less any descent below the baseline.
+
+        let baseline = glyph.get_y() + glyph.get_height() - glyph.get_descent();
+
+        format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"{}\" fill=\"{}\"{}>{}</text>",
+            Self::format_number(glyph.get_x().value),
+            Self::format_number(baseline.value),
+            Self::escape(&self.music_font_family),
+            Self::format_number(SMUFL_STAVE_SPACES_PER_EM),
+            Self::format_color(glyph.get_color()),
+            Self::format_opacity("fill-opacity", glyph.get_color()),
+            Self::escape(&glyph.get_glyph().get_codepoint().to_string()),
+        )
+    }
+
+    fn export_line(&self, line: &EngravedLine) -> String {
+        let from = line.get_from();
+
+        let to = line.get_to();
+
+        format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\"{}{}/>",
+            Self::format_number(from.x.value),
+            Self::format_number(from.y.value),
+            Self::format_number(to.x.value),
+            Self::format_number(to.y.value),
+            Self::format_color(line.get_color()),
+            Self::format_number(line.get_thickness().value),
+            Self::format_opacity("stroke-opacity", line.get_color()),
+            Self::format_stroke_style(line.get_stroke_style(), line.get_thickness()),
+        )
+    }
+
+    fn export_markup(&self, markup: &EngravedMarkup) -> String {
+        let mut text = format!(
+            "<text font-family=\"{}\" fill=\"{}\">",
+            Self::escape(&self.text_font_family),
+            Self::format_color(markup.get_color()),
+        );
+
+        // Each marked up line is emitted as its own <tspan>, positioned at the line's
+        // baseline. Lines are stacked downwards from the top of the markup.
+
+        let mut line_top = markup.get_y();
+
+        for line in markup.get_lines().iter() {
+            let baseline =
+                line_top + line.get_top_padding() + line.get_height() - line.get_descent();
+
+            text.push_str(&format!(
+                "<tspan x=\"{}\" y=\"{}\" font-size=\"{}\">{}</tspan>",
+                Self::format_number((markup.get_x() + line.get_start_padding()).value),
+                Self::format_number(baseline.value),
+                Self::format_number(line.get_height().value),
+                Self::escape(&line.get_text()),
+            ));
+
+            line_top =
+                line_top + line.get_top_padding() + line.get_height() + line.get_bottom_padding();
+        }
+
+        text.push_str("</text>");
+
+        text
+    }
+
+    #[inline]
+    fn format_color(color: &Color) -> String {
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            color.get_red(),
+            color.get_green(),
+            color.get_blue()
+        )
+    }
+
+    #[inline]
+    fn format_opacity(attribute: &str, color: &Color) -> String {
+        if color.get_alpha() == u8::MAX {
+            String::new()
+        } else {
+            format!(
+                " {}=\"{}\"",
+                attribute,
+                Self::format_number(color.get_alpha() as f32 / u8::MAX as f32)
+            )
+        }
+    }
+
+    #[inline]
+    fn format_stroke_style(stroke_style: &StrokeStyle, thickness: StaveSpaces) -> String {
+        // Dash lengths are proportional to the stroke thickness, so that dashed and
+        // dotted lines retain their appearance irrespective of line weight.
+
+        match stroke_style {
+            StrokeStyle::Solid => String::new(),
+            StrokeStyle::Dashed => format!(
+                " stroke-dasharray=\"{} {}\"",
+                Self::format_number(thickness.value * 4.0),
+                Self::format_number(thickness.value * 2.0)
+            ),
+            StrokeStyle::Dotted => format!(
+                " stroke-dasharray=\"0 {}\" stroke-linecap=\"round\"",
+                Self::format_number(thickness.value * 2.0)
+            ),
+        }
+    }
+
+    /// Formats the given value with no more than three decimal places and without
+    /// trailing zeros, keeping the generated document compact and stable across runs.
+    fn format_number(value: f32) -> String {
+        let formatted = format!("{:.3}", value);
+
+        let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+
+        if trimmed == "-0" {
+            String::from("0")
+        } else {
+            trimmed.to_string()
+        }
+    }
+
+    /// Escapes the characters that are significant in SVG text content and
+    /// attribute values.
+    fn escape(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+
+        for c in value.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&apos;"),
+                _ => escaped.push(c),
+            }
+        }
+
+        escaped
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::concepts::color::Color;
+    use crate::models::display::concepts::stave_spaces::{AsStaveSpacesExt, StavePoint};
+    use crate::models::display::concepts::stroke::StrokeStyle;
+    use crate::models::display::engraving::engravable::line::EngravedLine;
+    use crate::models::display::engraving::export::svg::{
+        SvgExporter, DEFAULT_SVG_STAVE_SPACE_SIZE,
+    };
+    use crate::models::display::grid::horizontal::{HorizontalGridLine, HorizontalGridLineType};
+    use crate::models::display::grid::vertical::{VerticalGridLine, VerticalGridLineType};
+    use crate::models::display::layout::block::line::LineBlock;
+    use crate::models::display::layout::block::BlockLayer;
+    use crate::models::display::layout::spanner::{EngravedSpanner, SpannerKind};
+    use crate::models::display::layout::system::LayoutSystem;
+    use crate::models::display::stylesheet::stylesheet_option::SystemJustification;
+    use crate::models::music::concepts::ticks::{AsTicksExt, TICKS_ZERO};
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(SvgExporter::format_number(1.0), "1");
+        assert_eq!(SvgExporter::format_number(0.25), "0.25");
+        assert_eq!(SvgExporter::format_number(1.0 / 3.0), "0.333");
+        assert_eq!(SvgExporter::format_number(-0.0001), "0");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            SvgExporter::escape("<Allegro & \"vivace\">"),
+            "&lt;Allegro &amp; &quot;vivace&quot;&gt;"
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let h0 = HorizontalGridLine::new(HorizontalGridLineType::SystemTop);
+
+        let mut h1 = HorizontalGridLine::new(HorizontalGridLineType::SystemBottom);
+
+        h1.lock_below_grid_line(0, 4.as_stave_spaces());
+
+        let v0 = VerticalGridLine::new(0, VerticalGridLineType::SystemStart);
+
+        let mut v1 = VerticalGridLine::new(0, VerticalGridLineType::SystemEnd);
+
+        v1.lock_to_grid_line(0);
+
+        let mut b0 = LineBlock::new_horizontal(
+            None,
+            Some(TICKS_ZERO),
+            None,
+            0.25.as_stave_spaces(),
+            Color::BLACK,
+            StrokeStyle::Dashed,
+            BlockLayer::Foreground,
+        );
+
+        b0.lock_start_to_grid_line(0);
+        b0.lock_end_to_grid_line(1);
+        b0.lock_vertical_center_to_grid_line(1);
+
+        let system = LayoutSystem::new(
+            0,
+            0.as_ticks(),
+            0.as_ticks(),
+            SystemJustification::AlignStart,
+            30.as_stave_spaces(),
+            vec![h0, h1],
+            vec![v0, v1],
+            0,
+            0,
+            vec![b0.into()],
+            false,
+            false,
+            false,
+            false,
+        )
+        .engrave()
+        .unwrap();
+
+        let svg =
+            SvgExporter::new("Bravura", "Academico", DEFAULT_SVG_STAVE_SPACE_SIZE).export(&system);
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+
+        let background = svg.find("<g class=\"background\">").unwrap();
+
+        let midground = svg.find("<g class=\"midground\">").unwrap();
+
+        let foreground = svg.find("<g class=\"foreground\">").unwrap();
+
+        assert!(background < midground);
+        assert!(midground < foreground);
+
+        assert!(svg[foreground..].contains("<line "));
+        assert!(svg[foreground..].contains("stroke=\"#000000\""));
+        assert!(svg[foreground..].contains("stroke-dasharray=\"1 0.5\""));
+    }
+
+    #[test]
+    fn test_export_spanner() {
+        let spanner = EngravedSpanner::new(
+            SpannerKind::OctaveAbove,
+            StavePoint::new(0.as_stave_spaces(), 2.as_stave_spaces()),
+            StavePoint::new(10.as_stave_spaces(), 2.as_stave_spaces()),
+            0.25.as_stave_spaces(),
+            1.as_stave_spaces(),
+            false,
+            false,
+        );
+
+        // An octave line is engraved as dashed lines: the line itself, then its hook.
+
+        let exporter = SvgExporter::new("Bravura", "Academico", DEFAULT_SVG_STAVE_SPACE_SIZE);
+
+        let lines = spanner
+            .get_segments()
+            .into_iter()
+            .map(|(from, to)| {
+                exporter.export_line(&EngravedLine::new(
+                    None,
+                    None,
+                    None,
+                    None,
+                    from,
+                    to,
+                    spanner.get_thickness(),
+                    Color::BLACK,
+                    spanner.get_stroke_style(),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                "<line x1=\"0\" y1=\"2\" x2=\"10\" y2=\"2\" stroke=\"#000000\" stroke-width=\"0.25\" stroke-dasharray=\"1 0.5\"/>",
+                "<line x1=\"10\" y1=\"2\" x2=\"10\" y2=\"3\" stroke=\"#000000\" stroke-width=\"0.25\" stroke-dasharray=\"1 0.5\"/>",
+            ]
+        );
+    }
+}
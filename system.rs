@@ -3,6 +3,7 @@ use crate::models::display::concepts::stave_spaces::{StavePoint, StaveSpaces, ST
 use crate::models::display::concepts::stroke::StrokeStyle;
 use crate::models::display::engraving::engravable::line::EngravedLine;
 use crate::models::display::engraving::engravable::Engravable;
+use crate::models::display::engraving::region::bar::EngravedBar;
 use crate::models::display::engraving::region::system::EngravedSystem;
 use crate::models::display::grid::horizontal::{
     HorizontalGridLine, HorizontalGridLineConstraint, HorizontalGridLineIndex,
@@ -11,17 +12,76 @@ use crate::models::display::grid::horizontal::{
 use crate::models::display::grid::vertical::{
     VerticalGridLine, VerticalGridLineConstraint, VerticalGridLineIndex, VerticalGridLineType,
 };
+use crate::models::display::layout::beam::{BeamBlock, BeamDirection, BeamIndex, LayoutBeam};
+use crate::models::display::layout::block::line::LineBlock;
 use crate::models::display::layout::block::{Block, BlockIndex};
 use crate::models::display::layout::block::{BlockConstraint, BlockEnum, BlockLayer};
+use crate::models::display::layout::curve::{
+    CurveBlock, CurveDirection, CurveIndex, EngravedCurve, LayoutCurve, CURVE_FLATTENING_SEGMENTS,
+};
+use crate::models::display::layout::diagnostics::{
+    BeamVariable, BlockEdge, ConstraintLog, ConstraintOrigin, ConstraintRecord, LayoutTrace,
+    TraceVariableKind,
+};
+use crate::models::display::layout::rhythmic_spacing::RhythmicSpacingModel;
+use crate::models::display::layout::skyline::Skyline;
+use crate::models::display::layout::spanner::{
+    EngravedSpanner, LayoutSpanner, SpannerBlock, SpannerIndex,
+};
+use crate::models::display::layout::strength::ConstraintStrength;
+use crate::models::display::layout::validation::{
+    get_block_constraint_references, is_referenced, validate_layout, LayoutElement,
+    LayoutValidationError,
+};
 use crate::models::music::concepts::ticks::Ticks;
 use crate::protos::display::stylesheet::SystemJustification;
 use cassowary::strength::{REQUIRED, STRONG, WEAK};
 use cassowary::WeightedRelation::{EQ, GE, LE};
-use cassowary::{AddConstraintError, AddEditVariableError, Solver, SuggestValueError, Variable};
+use cassowary::{
+    AddConstraintError, AddEditVariableError, Constraint, RemoveConstraintError, Solver,
+    SuggestValueError, Variable,
+};
 use iset::IntervalMap;
 use itertools::izip;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::error::Error;
-use std::fmt::{Display, Formatter};
+use std::fmt::{Debug, Display, Formatter};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+/// The default maximum number of collision resolution passes attempted by
+/// LayoutSystem::engrave() before giving up on any collisions that remain.
+pub const DEFAULT_MAXIMUM_COLLISION_RESOLUTION_PASSES: usize = 8;
+
+/// The default minimum gap, in stave spaces, left between two colliding Blocks once
+/// one has been moved to avoid the other.
+pub const DEFAULT_MINIMUM_COLLISION_GAP: f32 = 0.25;
+
+/// The strength of the constraints that move a Block clear of a collision. Collision
+/// resolution must be able to override the Lock* constraints placing a Block, which
+/// default to STRONG, or the solver would only split the difference between the locked
+/// position and the clear position, leaving the Blocks overlapping; but it must never
+/// override a REQUIRED constraint. This is equivalent to strength::create(10.0, 0.0, 0.0, 1.0).
+const COLLISION_RESOLUTION_STRENGTH: f64 = 10_000_000.0;
+
+/// The strength of the constraints that set each spacing block to the ideal width given
+/// by the system's rhythmic spacing model. These must override the STRONG constraint
+/// maintaining the natural width of each spacing block, but give way to collision
+/// resolution and to REQUIRED justification. This is equivalent to
+/// strength::create(2.0, 0.0, 0.0, 1.0).
+const RHYTHMIC_SPACING_STRENGTH: f64 = 2_000_000.0;
+
+/// The strength of the constraints drawing the edges of each BeamBlock tight around its beam.
+/// These must be weaker than WEAK, so fitting a BeamBlock never pulls on the beam itself,
+/// which weakly prefers stems of the minimum length and a rise following the contour of
+/// its noteheads. This is equivalent to strength::create(0.0, 0.0, 0.001, 1.0).
+const BEAM_BLOCK_EXTENT_STRENGTH: f64 = 0.001;
+
+/// The default minimum ratio to which spacing blocks without a minimum width of their own
+/// may be compressed when the natural width of a system exceeds its target system width.
+pub const DEFAULT_MINIMUM_COMPRESSION_RATIO: f32 = 0.8;
 
 /// A two-dimensional layout of Blocks on a System, defined by flat vertical
 /// and horizontal grid lines. These grid lines have no width or height themselves;
@@ -30,11 +90,18 @@ use std::fmt::{Display, Formatter};
 /// to align with various grid lines. Once all Blocks have been positioned on the
 /// grid, computation of the final coordinate positions of all grid lines can be expressed
 /// as a set of linear constraints, suitable for feeding into a linear constraint solver.
-#[derive(Debug)]
+///
+/// A LayoutSystem can be serialized along with all its grid lines, Blocks, and constraints,
+/// so a system that engraves badly can be saved to a file and replayed later.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LayoutSystem {
     index_in_movement: u32,
     start_ticks: Ticks,
     end_ticks: Ticks,
+    #[serde(
+        serialize_with = "serialize_justification",
+        deserialize_with = "deserialize_justification"
+    )]
     justification: SystemJustification,
     target_system_width: StaveSpaces,
     horizontal_grid_lines: Vec<HorizontalGridLine>,
@@ -42,10 +109,56 @@ pub struct LayoutSystem {
     top_edge: HorizontalGridLineIndex,
     leading_edge: VerticalGridLineIndex,
     blocks: Vec<BlockEnum>,
+    beams: Vec<LayoutBeam>,
+    curves: Vec<LayoutCurve>,
+    spanners: Vec<LayoutSpanner>,
+    maximum_collision_resolution_passes: usize,
+    minimum_collision_gap: StaveSpaces,
+    justification_threshold: Option<f32>,
+    maximum_justification_ratio: Option<f32>,
+    rhythmic_spacing_model: RhythmicSpacingModel,
+    minimum_compression_ratio: f32,
     debug_do_draw_horizontal_grid_lines: bool,
     debug_do_draw_vertical_grid_lines: bool,
     debug_do_show_rhythmic_spacing: bool,
     debug_do_draw_block_outlines: bool,
+    debug_do_diagnose_constraint_conflicts: bool,
+}
+
+/// The names under which each SystemJustification is serialized. SystemJustification is
+/// generated from the stylesheet protobuf definitions, so it is serialized by name here
+/// rather than by deriving Serialize and Deserialize on the generated type.
+const SYSTEM_JUSTIFICATION_NAMES: &[&str] = &["AlignStart", "AlignEnd", "Centered", "Justified"];
+
+fn serialize_justification<S: Serializer>(
+    justification: &SystemJustification,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let index = match justification {
+        SystemJustification::AlignStart => 0,
+        SystemJustification::AlignEnd => 1,
+        SystemJustification::Centered => 2,
+        SystemJustification::Justified => 3,
+    };
+
+    serializer.serialize_str(SYSTEM_JUSTIFICATION_NAMES[index])
+}
+
+fn deserialize_justification<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<SystemJustification, D::Error> {
+    let name = String::deserialize(deserializer)?;
+
+    match name.as_str() {
+        "AlignStart" => Ok(SystemJustification::AlignStart),
+        "AlignEnd" => Ok(SystemJustification::AlignEnd),
+        "Centered" => Ok(SystemJustification::Centered),
+        "Justified" => Ok(SystemJustification::Justified),
+        _ => Err(de::Error::unknown_variant(
+            name.as_str(),
+            SYSTEM_JUSTIFICATION_NAMES,
+        )),
+    }
 }
 
 impl LayoutSystem {
@@ -80,10 +193,20 @@ impl LayoutSystem {
             top_edge,
             leading_edge,
             blocks,
+            beams: Vec::new(),
+            curves: Vec::new(),
+            spanners: Vec::new(),
+            maximum_collision_resolution_passes: DEFAULT_MAXIMUM_COLLISION_RESOLUTION_PASSES,
+            minimum_collision_gap: StaveSpaces::new(DEFAULT_MINIMUM_COLLISION_GAP),
+            justification_threshold: None,
+            maximum_justification_ratio: None,
+            rhythmic_spacing_model: RhythmicSpacingModel::default(),
+            minimum_compression_ratio: DEFAULT_MINIMUM_COMPRESSION_RATIO,
             debug_do_draw_horizontal_grid_lines,
             debug_do_draw_vertical_grid_lines,
             debug_do_show_rhythmic_spacing,
             debug_do_draw_block_outlines,
+            debug_do_diagnose_constraint_conflicts: false,
         }
     }
 
@@ -135,280 +258,320 @@ impl LayoutSystem {
         self.blocks.as_slice()
     }
 
-    /// Generates a final positioned EngravedSystem from this LayoutSystem
-    /// by expressing all constraints on grid lines and Blocks in the layout
-    /// as a linear constraint system. The output from the constraint solver
-    /// will be the final engraving positions of all Blocks in the System.
-    ///
-    /// Once all positions have been resolved, Blocks are converted into
-    /// Engravables and the result is returned as an EngravedSystem, ready to be
-    /// streamed to a Rescore client for display.
-    pub fn engrave(&self) -> Result<EngravedSystem, EngravingError> {
-        // Determine final layout positions for all lines and blocks on the
-        // system layout grid.
+    /// Returns the beams linking the stems of beamed groups on this LayoutSystem.
+    #[inline]
+    pub fn get_beams(&self) -> &[LayoutBeam] {
+        self.beams.as_slice()
+    }
 
-        let mut solver = Solver::new();
+    /// Adds the given beam to this LayoutSystem, returning its index. The beam's stems
+    /// are lengthened to meet it during engraving, and the beam is engraved in the
+    /// foreground after all Blocks. A BeamBlock standing in for the beam during collision
+    /// detection and resolution is added to the end of this LayoutSystem's Blocks.
+    pub fn add_beam(&mut self, beam: LayoutBeam) -> BeamIndex {
+        let index = self.beams.len();
 
-        // First, create linear constraint variables for all lines and blocks.
-        // Grid lines get one variable each (horizontal grid lines have a
-        // y position, vertical grid lines an x position), blocks get four variables
-        // each (blocks have two sets of x and y positions, representing the
-        // (start, top) and (end, bottom) corners of the block).
+        let first_stem = beam
+            .get_stems()
+            .first()
+            .and_then(|stem| self.blocks.get(*stem));
 
-        let horizontal_grid_line_variables = self
-            .get_horizontal_grid_lines()
-            .iter()
-            .map(|_| Variable::new())
-            .collect::<Vec<_>>();
+        let block = BeamBlock::new(index, beam.clone(), first_stem);
 
-        let vertical_grid_line_variables = self
-            .get_vertical_grid_lines()
-            .iter()
-            .map(|_| Variable::new())
-            .collect::<Vec<_>>();
+        self.blocks.push(block.into());
 
-        let block_top_position_variables = self
-            .get_blocks()
-            .iter()
-            .map(|_| Variable::new())
-            .collect::<Vec<_>>();
+        self.beams.push(beam);
 
-        let block_bottom_position_variables = self
-            .get_blocks()
-            .iter()
-            .map(|_| Variable::new())
-            .collect::<Vec<_>>();
+        index
+    }
 
-        let block_start_position_variables = self
-            .get_blocks()
-            .iter()
-            .map(|_| Variable::new())
-            .collect::<Vec<_>>();
+    /// Returns the slurs and ties on this LayoutSystem.
+    #[inline]
+    pub fn get_curves(&self) -> &[LayoutCurve] {
+        self.curves.as_slice()
+    }
 
-        let block_end_position_variables = self
-            .get_blocks()
-            .iter()
-            .map(|_| Variable::new())
-            .collect::<Vec<_>>();
+    /// Adds the given slur or tie to this LayoutSystem, returning its index. The curve's
+    /// shape is chosen once the positions of all Blocks are known, and the curve is
+    /// engraved in the foreground after all Blocks and beams. A CurveBlock is added
+    /// alongside the curve, taking up the curve's extent in the layout.
+    pub fn add_curve(&mut self, curve: LayoutCurve) -> CurveIndex {
+        let index = self.curves.len();
 
-        // Express the position for the system origin, (0,0), in terms of
-        // constraints on the top-most and leading-most grid lines.
-        // All other constraints are ultimately resolved in relation to this
-        // origin position, so we need to ensure it is defined.
+        let block = CurveBlock::new(index, self.blocks.get(curve.get_start_anchor()));
 
-        if let Some(system_top) = horizontal_grid_line_variables.get(self.get_top_edge()) {
-            solver
-                .add_constraint(*system_top | EQ(REQUIRED) | 0.0)
-                .map_err(|err| {
-                    EngravingError::AddConstraintErrorOnHorizontalGridLine(err, self.get_top_edge())
-                })?;
-        }
+        self.blocks.push(block.into());
 
-        // We constrain the system leading edge to match the aligned start of
-        // the system; the aligned start is an edit variable since, depending on
-        // the desired system alignment, we may need to adjust its value later
-        // to effect an end or center alignment.
+        self.curves.push(curve);
 
-        let aligned_start = Variable::new();
+        index
+    }
 
-        solver
-            .add_edit_variable(aligned_start, STRONG)
-            .map_err(EngravingError::DefineJustificationError)?;
+    /// Returns the hairpins, octave lines, pedal lines, and trill extensions on this LayoutSystem.
+    #[inline]
+    pub fn get_spanners(&self) -> &[LayoutSpanner] {
+        self.spanners.as_slice()
+    }
 
-        solver
-            .suggest_value(aligned_start, 0.0)
-            .map_err(EngravingError::ApplyJustificationError)?;
+    /// Adds the given spanner to this LayoutSystem, returning its index. The spanner's ends
+    /// are locked to the grid lines of its anchor onsets, or to the edges of the system
+    /// interior where the spanner continues across a system break. A SpannerBlock is added
+    /// alongside the spanner, taking up the spanner's extent in the layout.
+    pub fn add_spanner(&mut self, spanner: LayoutSpanner) -> SpannerIndex {
+        let index = self.spanners.len();
 
-        if let Some(system_leading_edge) = vertical_grid_line_variables.get(self.get_leading_edge())
-        {
-            solver
-                .add_constraint(*system_leading_edge | EQ(REQUIRED) | aligned_start)
-                .map_err(|err| {
-                    EngravingError::AddConstraintErrorOnVerticalGridLine(
-                        err,
-                        self.get_leading_edge(),
-                    )
-                })?;
-        }
+        let block = SpannerBlock::new(index, &spanner);
 
-        // Express constraints on lines and blocks in relation to variables,
-        // and add those constraints to the solver.
+        self.blocks.push(block.into());
 
-        // When expressing constraints on the vertical axis, we need to be careful
-        // about our coordinate system: with the system origin at (0,0),
-        // vertical positions closer to the _top_ of the system have a _smaller_
-        // y value, with 0 being the top-most position on the system.
+        self.spanners.push(spanner);
 
-        // The linear solver adjusts variables to fit constraints progressively as
-        // constraints are added to the system, so by the time all constraints are
-        // added, we have our layout solution.
+        index
+    }
 
-        for (index, grid_line) in self.get_horizontal_grid_lines().iter().enumerate() {
-            for constraint in grid_line.get_constraints() {
-                Self::add_horizontal_grid_line_constraint_to_solver(
-                    index,
-                    constraint,
-                    &mut solver,
-                    horizontal_grid_line_variables.as_slice(),
-                )?;
-            }
-        }
+    /// Returns the maximum number of times collision detection and resolution will be
+    /// run during engraving. If collisions still remain after this many passes,
+    /// engraving fails with an EngravingError::UnresolvedCollisions error.
+    #[inline]
+    pub fn get_maximum_collision_resolution_passes(&self) -> usize {
+        self.maximum_collision_resolution_passes
+    }
 
-        for (index, grid_line) in self.get_vertical_grid_lines().iter().enumerate() {
-            for constraint in grid_line.get_constraints() {
-                Self::add_vertical_grid_line_constraint_to_solver(
-                    index,
-                    constraint,
-                    &mut solver,
-                    vertical_grid_line_variables.as_slice(),
-                )?;
-            }
-        }
+    /// Sets the maximum number of times collision detection and resolution will be
+    /// run during engraving. Defaults to DEFAULT_MAXIMUM_COLLISION_RESOLUTION_PASSES.
+    #[inline]
+    pub fn set_maximum_collision_resolution_passes(&mut self, passes: usize) {
+        self.maximum_collision_resolution_passes = passes;
+    }
 
-        let mut spacing_blocks = Vec::new();
+    /// Returns the minimum gap left between two colliding Blocks once one has been moved,
+    /// either vertically or horizontally, to avoid the other.
+    #[inline]
+    pub fn get_minimum_collision_gap(&self) -> StaveSpaces {
+        self.minimum_collision_gap
+    }
 
-        let mut total_rhythmic_spacing = STAVE_SPACES_ZERO;
+    /// Sets the minimum gap left between two colliding Blocks once one has been moved
+    /// to avoid the other. This is typically taken from the stylesheet.
+    /// Defaults to DEFAULT_MINIMUM_COLLISION_GAP.
+    #[inline]
+    pub fn set_minimum_collision_gap(&mut self, gap: StaveSpaces) {
+        self.minimum_collision_gap = gap;
+    }
 
-        for (index, block) in self.get_blocks().iter().enumerate() {
-            if block.is_spacing_block() {
-                // Keep track of the indices of any spacing blocks on the grid.
-                // We'll need these later in order to justify the system.
+    /// Returns the fraction of the target system width that the natural width of this
+    /// system must reach before the system will be justified, if one has been set.
+    /// Systems using SystemJustification::Justified that are narrower than this
+    /// are start aligned instead, leaving the system ragged.
+    #[inline]
+    pub fn get_justification_threshold(&self) -> Option<f32> {
+        self.justification_threshold
+    }
 
-                spacing_blocks.push(index);
+    /// Sets the fraction of the target system width that the natural width of this
+    /// system must reach before the system will be justified. Defaults to None,
+    /// in which case a system using SystemJustification::Justified is always justified.
+    #[inline]
+    pub fn set_justification_threshold(&mut self, threshold: Option<f32>) {
+        self.justification_threshold = threshold;
+    }
 
-                // Keep track of the total amount of rhythmic space currently
-                // on the grid. The ratio of rhythmic space to system width
-                // is used during system justification.
+    /// Returns the maximum ratio by which spacing blocks will be stretched when this system
+    /// is justified, if one has been set. Any width left over once spacing blocks have been
+    /// stretched by this ratio is left empty at the end of the system.
+    #[inline]
+    pub fn get_maximum_justification_ratio(&self) -> Option<f32> {
+        self.maximum_justification_ratio
+    }
 
-                total_rhythmic_spacing += block.get_fixed_width();
-            }
+    /// Sets the maximum ratio by which spacing blocks will be stretched when this system
+    /// is justified. Defaults to None, in which case a system using
+    /// SystemJustification::Justified is always stretched out to its target system width.
+    #[inline]
+    pub fn set_maximum_justification_ratio(&mut self, ratio: Option<f32>) {
+        self.maximum_justification_ratio = ratio;
+    }
 
-            // If this block is fixed width, then ensure its width is taken into account
-            // when determining its end position.
+    /// Returns the rhythmic spacing model used to share out additional width between
+    /// spacing blocks when this system is justified.
+    #[inline]
+    pub fn get_rhythmic_spacing_model(&self) -> RhythmicSpacingModel {
+        self.rhythmic_spacing_model
+    }
 
-            if block.is_fixed_width() {
-                solver
-                    .add_constraint(
-                        *block_end_position_variables
-                            .get(index)
-                            .ok_or(EngravingError::UnknownBlockEndPosition(index))?
-                            | EQ(STRONG)
-                            | (*block_start_position_variables
-                                .get(index)
-                                .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                                + block.get_start_padding().value
-                                + block.get_fixed_width().value
-                                + block.get_end_padding().value),
-                    )
-                    .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))?;
-            }
+    /// Sets the rhythmic spacing model used to share out additional width between
+    /// spacing blocks when this system is justified. Defaults to RhythmicSpacingModel::Linear.
+    #[inline]
+    pub fn set_rhythmic_spacing_model(&mut self, model: RhythmicSpacingModel) {
+        self.rhythmic_spacing_model = model;
+    }
 
-            // If this block is fixed height, then ensure its height is taken into account
-            // when determining its bottom position.
+    /// Returns the minimum ratio to which each spacing block in this system may be compressed
+    /// if the natural width of the system exceeds the target system width. A spacing block
+    /// with a minimum width of its own is never compressed below that width; any other
+    /// spacing block is never compressed below its ideal width multiplied by this ratio.
+    /// If the system still does not fit once every spacing block is at its minimum width,
+    /// engraving fails with an EngravingError::SystemOverfull error.
+    ///
+    /// Compression applies whatever the system's justification: an overfull system is
+    /// compressed to fit its target system width even when it is start, end, or center
+    /// aligned, since there is no room left over to align it within.
+    #[inline]
+    pub fn get_minimum_compression_ratio(&self) -> f32 {
+        self.minimum_compression_ratio
+    }
 
-            if block.is_fixed_height() {
-                solver
-                    .add_constraint(
-                        *block_bottom_position_variables
-                            .get(index)
-                            .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
-                            | EQ(STRONG)
-                            | (*block_top_position_variables
-                                .get(index)
-                                .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                                + block.get_top_padding().value
-                                + block.get_fixed_height().value
-                                + block.get_bottom_padding().value),
-                    )
-                    .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))?;
-            }
+    /// Sets the minimum ratio to which each spacing block in this system may be compressed.
+    /// Defaults to DEFAULT_MINIMUM_COMPRESSION_RATIO.
+    #[inline]
+    pub fn set_minimum_compression_ratio(&mut self, ratio: f32) {
+        self.minimum_compression_ratio = ratio;
+    }
 
-            // Add all user-specified constraints to the solver.
+    /// Returns true if engraving this system will diagnose conflicts between constraints.
+    #[inline]
+    pub fn get_debug_do_diagnose_constraint_conflicts(&self) -> bool {
+        self.debug_do_diagnose_constraint_conflicts
+    }
 
-            for constraint in block.get_constraints() {
-                Self::add_block_constraint_to_solver(
-                    index,
-                    block,
-                    constraint,
-                    &mut solver,
-                    horizontal_grid_line_variables.as_slice(),
-                    vertical_grid_line_variables.as_slice(),
-                    block_top_position_variables.as_slice(),
-                    block_bottom_position_variables.as_slice(),
-                    block_start_position_variables.as_slice(),
-                    block_end_position_variables.as_slice(),
-                )?;
-            }
-        }
+    /// Sets whether engraving this system will diagnose conflicts between constraints.
+    /// When enabled, every constraint added to the linear constraint solver is logged
+    /// along with the grid line or Block it originated from. If the solver then rejects
+    /// a constraint as unsatisfiable, engraving fails with an
+    /// EngravingError::ConflictingConstraints error listing the smallest set of constraints
+    /// that cannot be satisfied together, rather than simply naming the grid line or Block
+    /// whose constraint was rejected. Diagnosis requires re-solving subsets of the logged
+    /// constraints, so this is intended for debugging only. Defaults to false.
+    #[inline]
+    pub fn set_debug_do_diagnose_constraint_conflicts(&mut self, do_diagnose: bool) {
+        self.debug_do_diagnose_constraint_conflicts = do_diagnose;
+    }
 
-        // Detect and resolve collisions between blocks.
+    /// Serializes this LayoutSystem, including all its grid lines, Blocks, and constraints,
+    /// to a JSON string.
+    #[inline]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
 
-        let collisions = Self::detect_colliding_blocks(
-            self.get_blocks(),
-            self.horizontal_grid_lines.len(),
-            self.vertical_grid_lines.len(),
-            &solver,
-            block_top_position_variables.as_slice(),
-            block_bottom_position_variables.as_slice(),
-            block_start_position_variables.as_slice(),
-            block_end_position_variables.as_slice(),
+    /// Deserializes a LayoutSystem from the given JSON string, as created by to_json().
+    #[inline]
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Saves this LayoutSystem as JSON to the file at the given path, so it can later
+    /// be reloaded with load_from_file() and engraved again.
+    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_json()?)
+    }
+
+    /// Loads a LayoutSystem previously saved with save_to_file() from the file at the given path.
+    pub fn load_from_file(path: &Path) -> io::Result<Self> {
+        Ok(Self::from_json(fs::read_to_string(path)?.as_str())?)
+    }
+
+    /// Checks the structure of this LayoutSystem without solving it, returning every
+    /// problem found: origin edges, constraints, beams, curves, or spanners that refer to grid lines
+    /// or Blocks that do not exist, beams whose stems cannot meet them, spanners that cannot be
+    /// placed on this system, and chains of Lock* constraints that lock the position of a grid
+    /// line or Block edge back to itself. Each problem records exactly where it was found.
+    /// engrave() checks the system origin and then calls this before solving, so structural
+    /// problems are always reported together rather than surfacing one at a time
+    /// as solver errors.
+    pub fn validate(&self) -> Result<(), Vec<LayoutValidationError>> {
+        let errors = validate_layout(
+            self.horizontal_grid_lines.as_slice(),
+            self.vertical_grid_lines.as_slice(),
+            self.top_edge,
+            self.leading_edge,
+            self.blocks.as_slice(),
+            self.beams.as_slice(),
+            self.curves.as_slice(),
+            self.spanners.as_slice(),
+            self.start_ticks,
+            self.end_ticks,
         );
 
-        Self::resolve_colliding_blocks(
-            self.get_blocks(),
-            collisions.as_slice(),
-            &mut solver,
-            horizontal_grid_line_variables.as_slice(),
-            vertical_grid_line_variables.as_slice(),
-            block_top_position_variables.as_slice(),
-            block_bottom_position_variables.as_slice(),
-            block_start_position_variables.as_slice(),
-            block_end_position_variables.as_slice(),
-        )?;
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 
-        // Determine the pre-justification engraved width of the system by scanning
-        // the solved block positions for maximal extents.
+    /// Returns true if the given grid line or Block is this LayoutSystem's top or leading
+    /// edge, or is referred to by any other grid line, Block, beam, curve, or spanner.
+    #[inline]
+    fn is_referenced(&self, element: LayoutElement) -> bool {
+        is_referenced(
+            element,
+            self.horizontal_grid_lines.as_slice(),
+            self.vertical_grid_lines.as_slice(),
+            self.top_edge,
+            self.leading_edge,
+            self.blocks.as_slice(),
+            self.beams.as_slice(),
+            self.curves.as_slice(),
+            self.spanners.as_slice(),
+            self.start_ticks,
+            self.end_ticks,
+        )
+    }
 
-        let engraved_system_width = block_end_position_variables
-            .iter()
-            .map(|variable| StaveSpaces::new(solver.get_value(*variable) as f32))
-            .max()
-            .unwrap_or(STAVE_SPACES_ZERO);
+    /// Generates a final positioned EngravedSystem from this LayoutSystem
+    /// by expressing all constraints on grid lines and Blocks in the layout
+    /// as a linear constraint system. The output from the constraint solver
+    /// will be the final engraving positions of all Blocks in the System.
+    ///
+    /// Once all positions have been resolved, Blocks are converted into
+    /// Engravables and the result is returned as an EngravedSystem, ready to be
+    /// streamed to a Rescore client for display.
+    pub fn engrave(&self) -> Result<EngravedSystem, EngravingError> {
+        // Determine final layout positions for all lines and blocks on the
+        // system layout grid.
 
-        Self::apply_justification_to_solver(
-            self.justification,
-            self.target_system_width,
-            engraved_system_width,
-            total_rhythmic_spacing,
-            &mut solver,
-            &aligned_start,
-            block_start_position_variables.as_slice(),
-            block_end_position_variables.as_slice(),
-            self.get_blocks(),
-            spacing_blocks.as_slice(),
-        )?;
+        let mut solver = LayoutSystemSolver::new(self, false)?;
+
+        solver.settle(self)?;
+
+        Ok(self.create_engraved_system(&solver))
+    }
+
+    /// Generates a final positioned EngravedSystem from this LayoutSystem, exactly as
+    /// engrave() does, together with a LayoutTrace recording every variable and constraint
+    /// in the linear constraint solver and the final solved value of every variable.
+    /// The trace can be serialized to JSON for inspection by external tooling.
+    pub fn engrave_with_trace(&self) -> Result<(EngravedSystem, LayoutTrace), EngravingError> {
+        let mut solver = LayoutSystemSolver::new(self, true)?;
+
+        solver.settle(self)?;
+
+        Ok((
+            self.create_engraved_system(&solver),
+            solver.create_trace(self),
+        ))
+    }
+
+    /// Consumes this LayoutSystem, returning an EngravingSession that keeps the
+    /// linear constraint solver for this LayoutSystem alive between engravings.
+    /// The session can be used to cheaply re-engrave the system after small edits.
+    #[inline]
+    pub fn into_engraving_session(self) -> Result<EngravingSession, EngravingError> {
+        EngravingSession::new(self)
+    }
 
+    /// Creates a positioned EngravedSystem from the solved positions in the given solver.
+    fn create_engraved_system(&self, solver: &LayoutSystemSolver) -> EngravedSystem {
         // Retrieve all finalized block positions from solver.
 
-        let block_top_positions = block_top_position_variables
-            .iter()
-            .map(|variable| StaveSpaces::new(solver.get_value(*variable) as f32))
-            .collect::<Vec<_>>();
+        let block_top_positions = solver.get_values(&solver.block_top_position_variables);
 
-        let block_bottom_positions = block_bottom_position_variables
-            .iter()
-            .map(|variable| StaveSpaces::new(solver.get_value(*variable) as f32))
-            .collect::<Vec<_>>();
+        let block_bottom_positions = solver.get_values(&solver.block_bottom_position_variables);
 
-        let block_start_positions = block_start_position_variables
-            .iter()
-            .map(|variable| StaveSpaces::new(solver.get_value(*variable) as f32))
-            .collect::<Vec<_>>();
+        let block_start_positions = solver.get_values(&solver.block_start_position_variables);
 
-        let block_end_positions = block_end_position_variables
-            .iter()
-            .map(|variable| StaveSpaces::new(solver.get_value(*variable) as f32))
-            .collect::<Vec<_>>();
+        let block_end_positions = solver.get_values(&solver.block_end_position_variables);
 
         // Determine the final engraved width and height of the system by scanning
         // the solved block positions for maximal extents.
@@ -429,7 +592,7 @@ impl LayoutSystem {
         // we can create positioned Engravables for each Block and return the
         // completed EngravedSystem.
 
-        let foreground = Self::create_engravables_from_blocks_in_layer(
+        let mut foreground = Self::create_engravables_from_blocks_in_layer(
             self.get_blocks(),
             BlockLayer::Foreground,
             block_top_positions.as_slice(),
@@ -439,6 +602,29 @@ impl LayoutSystem {
             self.debug_do_show_rhythmic_spacing,
         );
 
+        // Beams are engraved in the foreground, after all Blocks in that layer.
+
+        foreground.extend((0..self.get_beams().len()).filter_map(|index| {
+            self.get_solved_beam_line(index, solver)
+                .and_then(|line| self.create_engravable_for_beam(index, line))
+        }));
+
+        foreground.extend((0..self.get_spanners().len()).flat_map(|index| {
+            self.get_solved_spanner(index, solver)
+                .map(|spanner| self.create_engravables_for_spanner(index, &spanner))
+                .unwrap_or_default()
+        }));
+
+        // Curves are engraved last, so they are drawn over any Blocks and beams they cross.
+
+        foreground.extend(
+            solver
+                .curve_shapes
+                .iter()
+                .enumerate()
+                .flat_map(|(index, shape)| self.create_engravables_for_curve(index, shape)),
+        );
+
         let midground = Self::create_engravables_from_blocks_in_layer(
             self.get_blocks(),
             BlockLayer::Midground,
@@ -459,10 +645,8 @@ impl LayoutSystem {
             self.debug_do_show_rhythmic_spacing,
         );
 
-        let horizontal_grid_line_positions = horizontal_grid_line_variables
-            .iter()
-            .map(|variable| StaveSpaces::new(solver.get_value(*variable) as f32))
-            .collect::<Vec<_>>();
+        let horizontal_grid_line_positions =
+            solver.get_values(&solver.horizontal_grid_line_variables);
 
         if self.debug_do_draw_horizontal_grid_lines {
             // Add visual guides for horizontal grid lines and output debugging data.
@@ -489,10 +673,7 @@ impl LayoutSystem {
                 });
         }
 
-        let vertical_grid_line_positions = vertical_grid_line_variables
-            .iter()
-            .map(|variable| StaveSpaces::new(solver.get_value(*variable) as f32))
-            .collect::<Vec<_>>();
+        let vertical_grid_line_positions = solver.get_values(&solver.vertical_grid_line_variables);
 
         if self.debug_do_draw_vertical_grid_lines {
             // Add visual guides for vertical grid lines and output debugging data.
@@ -531,7 +712,9 @@ impl LayoutSystem {
             );
         }
 
-        Ok(EngravedSystem::new(
+        let bars = self.create_engraved_bars(vertical_grid_line_positions.as_slice(), width);
+
+        EngravedSystem::new(
             self.index_in_movement,
             horizontal_grid_line_positions,
             vertical_grid_line_positions,
@@ -539,35 +722,317 @@ impl LayoutSystem {
             self.end_ticks,
             width,
             height,
-            vec![], // TODO: AJRC - 8/9/21 - compute EngravedBar positions
-            // AJRC - 5/10/21 - casting metrics contains vec of CastBar, probably useful
+            bars,
             foreground,
             midground,
             background,
+        )
+    }
+
+    /// Returns the solved (start x, start y, end x, end y) position of the center line of
+    /// the beam at the given index, running from the start of its first stem to the end
+    /// of its last stem, or None if the beam has no stems.
+    fn get_solved_beam_line(
+        &self,
+        index: BeamIndex,
+        solver: &LayoutSystemSolver,
+    ) -> Option<(StaveSpaces, StaveSpaces, StaveSpaces, StaveSpaces)> {
+        let beam = self.get_beams().get(index)?;
+
+        let value = |variables: &[Variable], index: usize| {
+            variables
+                .get(index)
+                .map(|variable| solver.solver.get_value(*variable))
+        };
+
+        let center = |position: f64| {
+            let (top, bottom) = beam.get_vertical_extent(position);
+
+            StaveSpaces::new(((top + bottom) / 2.0) as f32)
+        };
+
+        Some((
+            StaveSpaces::new(value(
+                &solver.block_start_position_variables,
+                *beam.get_stems().first()?,
+            )? as f32),
+            center(value(&solver.beam_start_variables, index)?),
+            StaveSpaces::new(value(
+                &solver.block_end_position_variables,
+                *beam.get_stems().last()?,
+            )? as f32),
+            center(value(&solver.beam_end_variables, index)?),
         ))
     }
 
-    #[inline]
-    fn create_debug_engravables_for_horizontal_grid_lines(
-        horizontal_grid_lines: &[HorizontalGridLine],
-        positions: &[StaveSpaces],
-        width: StaveSpaces,
+    /// Creates an Engravable for the beam at the given index along the given solved
+    /// center line. The beam takes its source information from its first stem.
+    fn create_engravable_for_beam(
+        &self,
+        index: BeamIndex,
+        (start_x, start_y, end_x, end_y): (StaveSpaces, StaveSpaces, StaveSpaces, StaveSpaces),
+    ) -> Option<Engravable> {
+        let beam = self.get_beams().get(index)?;
+
+        let block = self.get_blocks().get(*beam.get_stems().first()?)?;
+
+        Some(Engravable::new_line(EngravedLine::new(
+            block.get_source_moment_spine_item().cloned(),
+            block.get_source_part_index(),
+            block.get_source_voice_index(),
+            block.get_source_onset(),
+            StavePoint::new(start_x, start_y),
+            StavePoint::new(end_x, end_y),
+            beam.get_thickness(),
+            Color::BLACK,
+            StrokeStyle::Solid,
+        )))
+    }
+
+    /// Returns the spanner at the given index, positioned at the solved positions of the grid
+    /// lines it is locked to, or None if the spanner cannot be placed on this system.
+    fn get_solved_spanner(
+        &self,
+        index: SpannerIndex,
+        solver: &LayoutSystemSolver,
+    ) -> Option<EngravedSpanner> {
+        let spanner = self.get_spanners().get(index)?;
+
+        let (start, end) = spanner.get_grid_lines(
+            self.get_vertical_grid_lines(),
+            self.get_leading_edge(),
+            self.start_ticks,
+            self.end_ticks,
+        );
+
+        let value = |variables: &[Variable], index: usize| {
+            variables
+                .get(index)
+                .map(|variable| StaveSpaces::new(solver.solver.get_value(*variable) as f32))
+        };
+
+        let y = value(&solver.horizontal_grid_line_variables, spanner.get_line())?;
+
+        Some(EngravedSpanner::new(
+            spanner.get_kind(),
+            StavePoint::new(value(&solver.vertical_grid_line_variables, start?)?, y),
+            StavePoint::new(value(&solver.vertical_grid_line_variables, end?)?, y),
+            spanner.get_thickness(),
+            spanner.get_height(),
+            spanner.is_continued_from_previous_system(self.start_ticks),
+            spanner.is_continued_onto_next_system(self.end_ticks),
+        ))
+    }
+
+    /// Creates Engravables for the spanner at the given index, positioned as given: one line
+    /// for each of the spanner's segments, drawn in the spanner's stroke style. Returns
+    /// no Engravables if there is no spanner at the given index. Each line takes its source
+    /// onset from the spanner's start anchor.
+    fn create_engravables_for_spanner(
+        &self,
+        index: SpannerIndex,
+        spanner: &EngravedSpanner,
     ) -> Vec<Engravable> {
-        horizontal_grid_lines
-            .iter()
-            .zip(positions)
-            .map(|(grid_line, position)| {
+        let layout = match self.get_spanners().get(index) {
+            Some(layout) => layout,
+            None => return Vec::new(),
+        };
+
+        spanner
+            .get_segments()
+            .into_iter()
+            .map(|(from, to)| {
                 Engravable::new_line(EngravedLine::new(
                     None,
                     None,
                     None,
-                    None,
-                    StavePoint::new(STAVE_SPACES_ZERO, *position),
-                    StavePoint::new(width, *position),
-                    StaveSpaces::new(0.1),
-                    match grid_line.get_grid_line_type() {
-                        HorizontalGridLineType::SystemTop => Color::RED,
-                        HorizontalGridLineType::SystemBottom => Color::RED,
+                    Some(layout.get_start().get_onset()),
+                    from,
+                    to,
+                    spanner.get_thickness(),
+                    spanner.get_color().clone(),
+                    spanner.get_stroke_style(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Creates Engravables for the curve at the given index with the given shape, flattening
+    /// the curve into CURVE_FLATTENING_SEGMENTS straight lines. Returns no Engravables if
+    /// the curve's start anchor does not exist. Each line takes its source information
+    /// from the curve's start anchor.
+    fn create_engravables_for_curve(
+        &self,
+        index: CurveIndex,
+        shape: &EngravedCurve,
+    ) -> Vec<Engravable> {
+        let block = match self
+            .get_curves()
+            .get(index)
+            .and_then(|curve| self.get_blocks().get(curve.get_start_anchor()))
+        {
+            Some(block) => block,
+            None => return Vec::new(),
+        };
+
+        shape
+            .flatten(CURVE_FLATTENING_SEGMENTS)
+            .windows(2)
+            .map(|points| {
+                Engravable::new_line(EngravedLine::new(
+                    block.get_source_moment_spine_item().cloned(),
+                    block.get_source_part_index(),
+                    block.get_source_voice_index(),
+                    block.get_source_onset(),
+                    StavePoint::new(points[0].x, points[0].y),
+                    StavePoint::new(points[1].x, points[1].y),
+                    shape.get_thickness(),
+                    shape.get_color().clone(),
+                    StrokeStyle::Solid,
+                ))
+            })
+            .collect()
+    }
+
+    /// Creates an EngravedBar for each bar in this system, using the solved positions
+    /// of the barline grid lines to determine the horizontal extent of each bar.
+    fn create_engraved_bars(
+        &self,
+        vertical_grid_line_positions: &[StaveSpaces],
+        width: StaveSpaces,
+    ) -> Vec<EngravedBar> {
+        self.get_bar_extents(vertical_grid_line_positions, width)
+            .into_iter()
+            .map(|(start_ticks, end_ticks, start_x, end_x)| {
+                EngravedBar::new(start_ticks, end_ticks, start_x, end_x)
+            })
+            .collect()
+    }
+
+    /// Returns the (start ticks, end ticks, start x, end x) extent of each bar in this system,
+    /// in order, given the solved positions of the system's vertical grid lines.
+    fn get_bar_extents(
+        &self,
+        vertical_grid_line_positions: &[StaveSpaces],
+        width: StaveSpaces,
+    ) -> Vec<(Ticks, Ticks, StaveSpaces, StaveSpaces)> {
+        let grid_lines = self.get_vertical_grid_lines();
+
+        let position = |index: VerticalGridLineIndex| {
+            vertical_grid_line_positions
+                .get(index)
+                .copied()
+                .unwrap_or(STAVE_SPACES_ZERO)
+        };
+
+        // Every barline occupies a column bounded by a BarlineStart and a BarlineEnd
+        // vertical grid line. Pair up the start and end grid lines of each barline column,
+        // then order the barlines by their solved positions.
+
+        let mut barlines = grid_lines
+            .iter()
+            .enumerate()
+            .filter(|(_, grid_line)| {
+                grid_line.get_grid_line_type() == VerticalGridLineType::BarlineStart
+            })
+            .filter_map(|(start, grid_line)| {
+                grid_lines
+                    .iter()
+                    .position(|other| {
+                        other.get_column() == grid_line.get_column()
+                            && other.get_grid_line_type() == VerticalGridLineType::BarlineEnd
+                    })
+                    .map(|end| (start, end))
+            })
+            .collect::<Vec<_>>();
+
+        barlines.sort_by_key(|(start, _)| position(*start));
+
+        // The first bar in the system starts at the start of the system interior,
+        // i.e. after any systemic line and part names, if the system defines an interior;
+        // otherwise, it starts at the system's leading edge.
+
+        let mut bar_start_x = grid_lines
+            .iter()
+            .enumerate()
+            .filter(|(_, grid_line)| {
+                grid_line.get_grid_line_type() == VerticalGridLineType::InteriorStart
+            })
+            .map(|(index, _)| position(index))
+            .min()
+            .unwrap_or_else(|| position(self.get_leading_edge()));
+
+        let mut bar_start_ticks = self.start_ticks;
+
+        let mut bars = Vec::new();
+
+        for (barline_start, barline_end) in barlines {
+            // The barline Blocks for this barline are those locked to either of the
+            // grid lines bounding its column. Other Blocks may happen to lie inside the
+            // column, so we cannot go by solved position alone. The onset of the barline
+            // marks the end of the bar. Not every Block locked to the column need carry
+            // that onset, so rather than trusting whichever Block comes first, we take
+            // the latest onset of any of them. If we cannot find one, we assume the bar
+            // runs to the end of the system.
+
+            let is_barline_block = |block: &&BlockEnum| {
+                block.get_constraints().iter().any(|constraint| {
+                    let (references, is_lock) = get_block_constraint_references(constraint);
+
+                    is_lock
+                        && (references.contains(&LayoutElement::VerticalGridLine(barline_start))
+                            || references.contains(&LayoutElement::VerticalGridLine(barline_end)))
+                })
+            };
+
+            let bar_end_ticks = self
+                .get_blocks()
+                .iter()
+                .filter(|block| block.is_visible())
+                .filter(is_barline_block)
+                .filter_map(|block| block.get_source_onset())
+                .max()
+                .unwrap_or(self.end_ticks);
+
+            let barline_end_x = position(barline_end);
+
+            bars.push((bar_start_ticks, bar_end_ticks, bar_start_x, barline_end_x));
+
+            bar_start_x = barline_end_x;
+            bar_start_ticks = bar_end_ticks;
+        }
+
+        // If the system does not end with a barline, then the final bar continues
+        // onto the next system. Include the part of that bar that falls on this system.
+
+        if bar_start_ticks < self.end_ticks {
+            bars.push((bar_start_ticks, self.end_ticks, bar_start_x, width));
+        }
+
+        bars
+    }
+
+    #[inline]
+    fn create_debug_engravables_for_horizontal_grid_lines(
+        horizontal_grid_lines: &[HorizontalGridLine],
+        positions: &[StaveSpaces],
+        width: StaveSpaces,
+    ) -> Vec<Engravable> {
+        horizontal_grid_lines
+            .iter()
+            .zip(positions)
+            .map(|(grid_line, position)| {
+                Engravable::new_line(EngravedLine::new(
+                    None,
+                    None,
+                    None,
+                    None,
+                    StavePoint::new(STAVE_SPACES_ZERO, *position),
+                    StavePoint::new(width, *position),
+                    StaveSpaces::new(0.1),
+                    match grid_line.get_grid_line_type() {
+                        HorizontalGridLineType::SystemTop => Color::RED,
+                        HorizontalGridLineType::SystemBottom => Color::RED,
                         _ => Color::BLUE,
                     },
                     StrokeStyle::Dashed,
@@ -724,718 +1189,658 @@ impl LayoutSystem {
         ]
     }
 
+    /// Returns the solver strength for a constraint with the given optional strength,
+    /// falling back to the given default if the constraint did not specify a strength.
+    #[inline]
+    fn get_solver_strength(strength: &Option<ConstraintStrength>, default: f64) -> f64 {
+        strength
+            .map(|strength| strength.as_solver_strength())
+            .unwrap_or(default)
+    }
+
+    /// Returns the solver constraint representing the given HorizontalGridLineConstraint
+    /// on the HorizontalGridLine at the given index.
     #[inline]
-    fn add_horizontal_grid_line_constraint_to_solver(
+    fn create_horizontal_grid_line_constraint(
         index: HorizontalGridLineIndex,
         constraint: &HorizontalGridLineConstraint,
-        solver: &mut Solver,
         horizontal_grid_line_variables: &[Variable],
-    ) -> Result<(), EngravingError> {
-        match constraint {
+    ) -> Result<Constraint, EngravingError> {
+        let solver_constraint = match constraint {
             HorizontalGridLineConstraint::LockAboveHorizontalGridLineByDistance(
                 grid_line_below,
                 distance,
-            ) => solver
-                .add_constraint(
-                    *horizontal_grid_line_variables
-                        .get(index)
+                strength,
+            ) => {
+                *horizontal_grid_line_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*horizontal_grid_line_variables
+                        .get(*grid_line_below)
                         .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
-                        | EQ(STRONG)
-                        | (*horizontal_grid_line_variables
-                            .get(*grid_line_below)
-                            .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
-                            - *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnHorizontalGridLine(err, index)),
+                        - *distance)
+            }
             HorizontalGridLineConstraint::FloatAboveHorizontalGridLineByDistance(
                 grid_line_below,
                 distance,
-            ) => solver
-                .add_constraint(
-                    *horizontal_grid_line_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
-                        | LE(WEAK)
-                        | (*horizontal_grid_line_variables
-                            .get(*grid_line_below)
-                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_below))?
-                            - *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnHorizontalGridLine(err, index)),
+                strength,
+            ) => {
+                *horizontal_grid_line_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
+                    | LE(Self::get_solver_strength(strength, WEAK))
+                    | (*horizontal_grid_line_variables
+                        .get(*grid_line_below)
+                        .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_below))?
+                        - *distance)
+            }
             HorizontalGridLineConstraint::LockBelowHorizontalGridLineByDistance(
                 grid_line_above,
                 distance,
-            ) => solver
-                .add_constraint(
-                    *horizontal_grid_line_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
-                        | EQ(STRONG)
-                        | (*horizontal_grid_line_variables
-                            .get(*grid_line_above)
-                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
-                            + *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnHorizontalGridLine(err, index)),
+                strength,
+            ) => {
+                *horizontal_grid_line_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*horizontal_grid_line_variables
+                        .get(*grid_line_above)
+                        .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
+                        + *distance)
+            }
             HorizontalGridLineConstraint::FloatBelowHorizontalGridLineByDistance(
                 grid_line_above,
                 distance,
-            ) => solver
-                .add_constraint(
-                    *horizontal_grid_line_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
-                        | GE(WEAK)
-                        | (*horizontal_grid_line_variables
-                            .get(*grid_line_above)
-                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
-                            + *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnHorizontalGridLine(err, index)),
+                strength,
+            ) => {
+                *horizontal_grid_line_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
+                    | GE(Self::get_solver_strength(strength, WEAK))
+                    | (*horizontal_grid_line_variables
+                        .get(*grid_line_above)
+                        .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
+                        + *distance)
+            }
             HorizontalGridLineConstraint::VerticallyCenterBetweenHorizontalGridLines(
                 grid_line_above,
                 grid_line_below,
-            ) => solver
-                .add_constraint(
-                    *horizontal_grid_line_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
-                        | EQ(STRONG)
-                        | ((*horizontal_grid_line_variables
-                            .get(*grid_line_above)
-                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
-                            + *horizontal_grid_line_variables.get(*grid_line_below).ok_or(
-                                EngravingError::UnknownHorizontalGridLine(*grid_line_below),
-                            )?)
-                            / 2.0),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnHorizontalGridLine(err, index)),
-        }
+                strength,
+            ) => {
+                *horizontal_grid_line_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | ((*horizontal_grid_line_variables
+                        .get(*grid_line_above)
+                        .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
+                        + *horizontal_grid_line_variables
+                            .get(*grid_line_below)
+                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_below))?)
+                        / 2.0)
+            }
+        };
+
+        Ok(solver_constraint)
     }
 
+    /// Returns the solver constraint representing the given VerticalGridLineConstraint
+    /// on the VerticalGridLine at the given index.
     #[inline]
-    fn add_vertical_grid_line_constraint_to_solver(
+    fn create_vertical_grid_line_constraint(
         index: VerticalGridLineIndex,
         constraint: &VerticalGridLineConstraint,
-        solver: &mut Solver,
         vertical_grid_line_variables: &[Variable],
-    ) -> Result<(), EngravingError> {
-        match constraint {
+    ) -> Result<Constraint, EngravingError> {
+        let solver_constraint = match constraint {
             VerticalGridLineConstraint::LockBeforeVerticalGridLineByDistance(
                 grid_line_after,
                 distance,
-            ) => solver
-                .add_constraint(
-                    *vertical_grid_line_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownVerticalGridLine(index))?
-                        | EQ(STRONG)
-                        | (*vertical_grid_line_variables
-                            .get(*grid_line_after)
-                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
-                            - *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnVerticalGridLine(err, index)),
+                strength,
+            ) => {
+                *vertical_grid_line_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownVerticalGridLine(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*vertical_grid_line_variables
+                        .get(*grid_line_after)
+                        .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
+                        - *distance)
+            }
             VerticalGridLineConstraint::FloatBeforeVerticalGridLineByDistance(
                 grid_line_after,
                 distance,
-            ) => solver
-                .add_constraint(
-                    *vertical_grid_line_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownVerticalGridLine(index))?
-                        | LE(WEAK)
-                        | (*vertical_grid_line_variables
-                            .get(*grid_line_after)
-                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
-                            - *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnVerticalGridLine(err, index)),
+                strength,
+            ) => {
+                *vertical_grid_line_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownVerticalGridLine(index))?
+                    | LE(Self::get_solver_strength(strength, WEAK))
+                    | (*vertical_grid_line_variables
+                        .get(*grid_line_after)
+                        .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
+                        - *distance)
+            }
             VerticalGridLineConstraint::LockAfterVerticalGridLineByDistance(
                 grid_line_before,
                 distance,
-            ) => solver
-                .add_constraint(
-                    *vertical_grid_line_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownVerticalGridLine(index))?
-                        | EQ(STRONG)
-                        | (*vertical_grid_line_variables
-                            .get(*grid_line_before)
-                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_before))?
-                            + *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnVerticalGridLine(err, index)),
+                strength,
+            ) => {
+                *vertical_grid_line_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownVerticalGridLine(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*vertical_grid_line_variables
+                        .get(*grid_line_before)
+                        .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_before))?
+                        + *distance)
+            }
             VerticalGridLineConstraint::FloatAfterVerticalGridLineByDistance(
                 grid_line_before,
                 distance,
-            ) => solver
-                .add_constraint(
-                    *vertical_grid_line_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownVerticalGridLine(index))?
-                        | GE(WEAK)
-                        | (*vertical_grid_line_variables
-                            .get(*grid_line_before)
-                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_before))?
-                            + *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnVerticalGridLine(err, index)),
-        }
+                strength,
+            ) => {
+                *vertical_grid_line_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownVerticalGridLine(index))?
+                    | GE(Self::get_solver_strength(strength, WEAK))
+                    | (*vertical_grid_line_variables
+                        .get(*grid_line_before)
+                        .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_before))?
+                        + *distance)
+            }
+        };
+
+        Ok(solver_constraint)
     }
 
+    /// Returns the solver constraint representing the given BlockConstraint on the Block
+    /// at the given index.
     #[inline]
     #[allow(clippy::too_many_arguments)]
-    fn add_block_constraint_to_solver(
+    fn create_block_constraint(
         index: usize,
         block: &BlockEnum,
         constraint: &BlockConstraint,
-        solver: &mut Solver,
         horizontal_grid_line_variables: &[Variable],
         vertical_grid_line_variables: &[Variable],
         block_top_position_variables: &[Variable],
         block_bottom_position_variables: &[Variable],
         block_start_position_variables: &[Variable],
         block_end_position_variables: &[Variable],
-    ) -> Result<(), EngravingError> {
-        // Apply block constraints. BlockConstraint::Lock* constraints should be represented
-        // by a STRONG constraint in the solver; BlockConstraint::Float* constraints should be
-        // represented by a WEAK constraint in the solver. This allows lock constraints to
+    ) -> Result<Constraint, EngravingError> {
+        // Apply block constraints. Unless the constraint specifies its own strength,
+        // BlockConstraint::Lock* constraints should be represented by a STRONG constraint
+        // in the solver; BlockConstraint::Float* constraints should be represented by
+        // a WEAK constraint in the solver. This allows lock constraints to
         // override float constraints.
 
-        match constraint {
-            BlockConstraint::LockTopToHorizontalGridLine(grid_line_above) => solver
-                .add_constraint(
-                    *block_top_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                        | EQ(STRONG)
-                        | (*horizontal_grid_line_variables
-                            .get(*grid_line_above)
-                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
-                            + block.get_top_padding().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::FloatTopAfterHorizontalGridLine(grid_line_above) => solver
-                .add_constraint(
-                    *block_top_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                        | GE(WEAK)
-                        | (*horizontal_grid_line_variables
-                            .get(*grid_line_above)
-                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
-                            + block.get_top_padding().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::FloatBottomBeforeHorizontalGridLine(grid_line_below) => {
+        let solver_constraint = match constraint {
+            BlockConstraint::LockTopToHorizontalGridLine(grid_line_above, strength) => {
+                *block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*horizontal_grid_line_variables
+                        .get(*grid_line_above)
+                        .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
+                        + block.get_top_padding().value)
+            }
+            BlockConstraint::FloatTopAfterHorizontalGridLine(grid_line_above, strength) => {
+                *block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                    | GE(Self::get_solver_strength(strength, WEAK))
+                    | (*horizontal_grid_line_variables
+                        .get(*grid_line_above)
+                        .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
+                        + block.get_top_padding().value)
+            }
+            BlockConstraint::FloatBottomBeforeHorizontalGridLine(grid_line_below, strength) => {
                 // The approach we take to this constraint depends on whether the target
                 // block has a fixed or variable height. If it's fixed height, then the
                 // block's top has to move; otherwise, the block's height can expand.
 
                 if block.is_fixed_height() {
-                    solver
-                        .add_constraint(
-                            *block_top_position_variables
-                                .get(index)
-                                .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                                | LE(WEAK)
-                                | (*horizontal_grid_line_variables.get(*grid_line_below).ok_or(
-                                    EngravingError::UnknownHorizontalGridLine(*grid_line_below),
-                                )? - block.get_fixed_height().value
-                                    - block.get_bottom_padding().value),
-                        )
-                        .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))
+                    *block_top_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                        | LE(Self::get_solver_strength(strength, WEAK))
+                        | (*horizontal_grid_line_variables
+                            .get(*grid_line_below)
+                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_below))?
+                            - block.get_fixed_height().value
+                            - block.get_bottom_padding().value)
                 } else {
-                    solver
-                        .add_constraint(
-                            *block_bottom_position_variables
-                                .get(index)
-                                .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
-                                | LE(WEAK)
-                                | (*horizontal_grid_line_variables.get(*grid_line_below).ok_or(
-                                    EngravingError::UnknownHorizontalGridLine(*grid_line_below),
-                                )? - block.get_bottom_padding().value),
-                        )
-                        .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))
+                    *block_bottom_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
+                        | LE(Self::get_solver_strength(strength, WEAK))
+                        | (*horizontal_grid_line_variables
+                            .get(*grid_line_below)
+                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_below))?
+                            - block.get_bottom_padding().value)
                 }
             }
-            BlockConstraint::LockBottomToHorizontalGridLine(grid_line_below) => {
+            BlockConstraint::LockBottomToHorizontalGridLine(grid_line_below, strength) => {
                 // The approach we take to this constraint depends on whether the target
                 // block has a fixed or variable height. If it's fixed height, then the
                 // block's top has to move; otherwise, the block's height can expand.
 
                 if block.is_fixed_height() {
-                    solver
-                        .add_constraint(
-                            *block_top_position_variables
-                                .get(index)
-                                .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                                | EQ(STRONG)
-                                | (*horizontal_grid_line_variables.get(*grid_line_below).ok_or(
-                                    EngravingError::UnknownHorizontalGridLine(*grid_line_below),
-                                )? - block.get_fixed_height().value
-                                    - block.get_bottom_padding().value),
-                        )
-                        .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))
+                    *block_top_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                        | EQ(Self::get_solver_strength(strength, STRONG))
+                        | (*horizontal_grid_line_variables
+                            .get(*grid_line_below)
+                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_below))?
+                            - block.get_fixed_height().value
+                            - block.get_bottom_padding().value)
                 } else {
-                    solver
-                        .add_constraint(
-                            *block_bottom_position_variables
-                                .get(index)
-                                .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
-                                | EQ(STRONG)
-                                | (*horizontal_grid_line_variables.get(*grid_line_below).ok_or(
-                                    EngravingError::UnknownHorizontalGridLine(*grid_line_below),
-                                )? - block.get_bottom_padding().value),
-                        )
-                        .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))
+                    *block_bottom_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
+                        | EQ(Self::get_solver_strength(strength, STRONG))
+                        | (*horizontal_grid_line_variables
+                            .get(*grid_line_below)
+                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_below))?
+                            - block.get_bottom_padding().value)
                 }
             }
-            BlockConstraint::LockStartToVerticalGridLine(grid_line_before) => solver
-                .add_constraint(
-                    *block_start_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                        | EQ(STRONG)
-                        | (*vertical_grid_line_variables
-                            .get(*grid_line_before)
-                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_before))?
-                            + block.get_start_padding().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::FloatStartAfterVerticalGridLine(grid_line_before) => solver
-                .add_constraint(
-                    *block_start_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                        | GE(WEAK)
-                        | (*vertical_grid_line_variables
-                            .get(*grid_line_before)
-                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_before))?
-                            + block.get_start_padding().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::FloatEndBeforeVerticalGridLine(grid_line_after) => {
+            BlockConstraint::LockStartToVerticalGridLine(grid_line_before, strength) => {
+                *block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*vertical_grid_line_variables
+                        .get(*grid_line_before)
+                        .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_before))?
+                        + block.get_start_padding().value)
+            }
+            BlockConstraint::FloatStartAfterVerticalGridLine(grid_line_before, strength) => {
+                *block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    | GE(Self::get_solver_strength(strength, WEAK))
+                    | (*vertical_grid_line_variables
+                        .get(*grid_line_before)
+                        .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_before))?
+                        + block.get_start_padding().value)
+            }
+            BlockConstraint::FloatEndBeforeVerticalGridLine(grid_line_after, strength) => {
                 // The approach we take to this constraint depends on whether the target
                 // block has a fixed or variable width. If it's fixed width, then the
                 // block's start has to move; otherwise, the block's width can expand.
 
                 if block.is_fixed_width() {
-                    solver
-                        .add_constraint(
-                            *block_start_position_variables
-                                .get(index)
-                                .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                                | LE(WEAK)
-                                | (*vertical_grid_line_variables.get(*grid_line_after).ok_or(
-                                    EngravingError::UnknownVerticalGridLine(*grid_line_after),
-                                )? - block.get_fixed_width().value
-                                    - block.get_end_padding().value),
-                        )
-                        .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))
+                    *block_start_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                        | LE(Self::get_solver_strength(strength, WEAK))
+                        | (*vertical_grid_line_variables
+                            .get(*grid_line_after)
+                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
+                            - block.get_fixed_width().value
+                            - block.get_end_padding().value)
                 } else {
-                    solver
-                        .add_constraint(
-                            *block_end_position_variables
-                                .get(index)
-                                .ok_or(EngravingError::UnknownBlockEndPosition(index))?
-                                | LE(WEAK)
-                                | (*vertical_grid_line_variables.get(*grid_line_after).ok_or(
-                                    EngravingError::UnknownVerticalGridLine(*grid_line_after),
-                                )? - block.get_end_padding().value),
-                        )
-                        .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))
+                    *block_end_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockEndPosition(index))?
+                        | LE(Self::get_solver_strength(strength, WEAK))
+                        | (*vertical_grid_line_variables
+                            .get(*grid_line_after)
+                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
+                            - block.get_end_padding().value)
                 }
             }
-            BlockConstraint::LockEndToVerticalGridLine(grid_line_after) => {
+            BlockConstraint::LockEndToVerticalGridLine(grid_line_after, strength) => {
                 // The approach we take to this constraint depends on whether the target
                 // block has a fixed or variable width. If it's fixed width, then the
                 // block's start has to move; otherwise, the block's width can expand.
 
                 if block.is_fixed_width() {
-                    solver
-                        .add_constraint(
-                            *block_start_position_variables
-                                .get(index)
-                                .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                                | EQ(STRONG)
-                                | (*vertical_grid_line_variables.get(*grid_line_after).ok_or(
-                                    EngravingError::UnknownVerticalGridLine(*grid_line_after),
-                                )? - block.get_fixed_width().value
-                                    - block.get_end_padding().value),
-                        )
-                        .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))
+                    *block_start_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                        | EQ(Self::get_solver_strength(strength, STRONG))
+                        | (*vertical_grid_line_variables
+                            .get(*grid_line_after)
+                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
+                            - block.get_fixed_width().value
+                            - block.get_end_padding().value)
                 } else {
-                    solver
-                        .add_constraint(
-                            *block_end_position_variables
-                                .get(index)
-                                .ok_or(EngravingError::UnknownBlockEndPosition(index))?
-                                | EQ(STRONG)
-                                | (*vertical_grid_line_variables.get(*grid_line_after).ok_or(
-                                    EngravingError::UnknownVerticalGridLine(*grid_line_after),
-                                )? - block.get_end_padding().value),
-                        )
-                        .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))
+                    *block_end_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockEndPosition(index))?
+                        | EQ(Self::get_solver_strength(strength, STRONG))
+                        | (*vertical_grid_line_variables
+                            .get(*grid_line_after)
+                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
+                            - block.get_end_padding().value)
                 }
             }
             BlockConstraint::LockVerticalCenterHalfwayBetweenHorizontalGridLines(
                 grid_line_above,
                 grid_line_below,
-            ) => solver
-                .add_constraint(
-                    *block_top_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                        | EQ(STRONG)
-                        | ((*horizontal_grid_line_variables
-                            .get(*grid_line_above)
-                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
-                            + *horizontal_grid_line_variables.get(*grid_line_below).ok_or(
-                                EngravingError::UnknownHorizontalGridLine(*grid_line_below),
-                            )?)
-                            / 2.0
-                            - block.get_descent().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockVerticalCenterToHorizontalGridLine(grid_line_center) => solver
-                .add_constraint(
-                    *block_top_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                        | EQ(STRONG)
-                        | (*horizontal_grid_line_variables
-                            .get(*grid_line_center)
-                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_center))?
-                            - block.get_descent().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
+                strength,
+            ) => {
+                *block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | ((*horizontal_grid_line_variables
+                        .get(*grid_line_above)
+                        .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_above))?
+                        + *horizontal_grid_line_variables
+                            .get(*grid_line_below)
+                            .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_below))?)
+                        / 2.0
+                        - block.get_descent().value)
+            }
+            BlockConstraint::LockVerticalCenterToHorizontalGridLine(grid_line_center, strength) => {
+                *block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*horizontal_grid_line_variables
+                        .get(*grid_line_center)
+                        .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_center))?
+                        - block.get_descent().value)
+            }
             BlockConstraint::LockHorizontalCenterHalfwayBetweenVerticalGridLines(
                 grid_line_before,
                 grid_line_after,
+                strength,
             ) => {
-                solver
-                    .add_constraint(
-                        *block_start_position_variables
-                            .get(index)
-                            .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                            | GE(STRONG)
-                            | ((*vertical_grid_line_variables.get(*grid_line_before).ok_or(
-                                EngravingError::UnknownVerticalGridLine(*grid_line_before),
-                            )? + *vertical_grid_line_variables.get(*grid_line_after).ok_or(
-                                EngravingError::UnknownVerticalGridLine(*grid_line_after),
-                            )? - block.get_fixed_width().value)
-                                / 2.0),
-                    )
-                    .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index))
+                *block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    | GE(Self::get_solver_strength(strength, STRONG))
+                    | ((*vertical_grid_line_variables
+                        .get(*grid_line_before)
+                        .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_before))?
+                        + *vertical_grid_line_variables
+                            .get(*grid_line_after)
+                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
+                        - block.get_fixed_width().value)
+                        / 2.0)
+            }
+            BlockConstraint::LockHorizontalCenterToVerticalGridLine(grid_line_center, strength) => {
+                *block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*vertical_grid_line_variables
+                        .get(*grid_line_center)
+                        .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_center))?
+                        - block.get_fixed_width().value / 2.0)
             }
-            BlockConstraint::LockHorizontalCenterToVerticalGridLine(grid_line_center) => solver
-                .add_constraint(
-                    *block_start_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                        | EQ(STRONG)
-                        | (*vertical_grid_line_variables
-                            .get(*grid_line_center)
-                            .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_center))?
-                            - block.get_fixed_width().value / 2.0),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
             BlockConstraint::PushHorizontalGridLineDownToAccommodateBlockHeight(
                 grid_line_below,
-            ) => solver
-                .add_constraint(
-                    *horizontal_grid_line_variables
-                        .get(*grid_line_below)
-                        .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_below))?
-                        | GE(STRONG)
-                        | (*block_top_position_variables
-                            .get(index)
-                            .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                            + block.get_fixed_height().value
-                            + block.get_bottom_padding().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
+                strength,
+            ) => {
+                *horizontal_grid_line_variables
+                    .get(*grid_line_below)
+                    .ok_or(EngravingError::UnknownHorizontalGridLine(*grid_line_below))?
+                    | GE(Self::get_solver_strength(strength, STRONG))
+                    | (*block_top_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                        + block.get_fixed_height().value
+                        + block.get_bottom_padding().value)
+            }
             BlockConstraint::PushVerticalGridLineSidewaysToAccommodateBlockWidth(
                 grid_line_after,
-            ) => solver
-                .add_constraint(
-                    *vertical_grid_line_variables
-                        .get(*grid_line_after)
-                        .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
-                        | GE(STRONG)
-                        | (*block_start_position_variables
-                            .get(index)
-                            .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                            + block.get_fixed_width().value
-                            + block.get_end_padding().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::FloatAfterBlockByDistance(block_before, distance) => solver
-                .add_constraint(
-                    *block_start_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                        | GE(WEAK)
-                        | (*block_end_position_variables
-                            .get(*block_before)
-                            .ok_or(EngravingError::UnknownBlockEndPosition(*block_before))?
-                            + *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::FloatBeforeBlockByDistance(block_after, distance) => solver
-                .add_constraint(
-                    *block_start_position_variables
-                        .get(*block_after)
-                        .ok_or(EngravingError::UnknownBlockStartPosition(*block_after))?
-                        | GE(WEAK)
-                        | (*block_end_position_variables
-                            .get(index)
-                            .ok_or(EngravingError::UnknownBlockEndPosition(index))?
-                            + *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::FloatAboveBlockByDistance(block_beneath, distance) => solver
-                .add_constraint(
-                    *block_top_position_variables
-                        .get(*block_beneath)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(*block_beneath))?
-                        | GE(WEAK)
-                        | (*block_bottom_position_variables
-                            .get(index)
-                            .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
-                            + *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::FloatBeneathBlockByDistance(block_above, distance) => solver
-                .add_constraint(
-                    *block_top_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                        | GE(WEAK)
-                        | (*block_bottom_position_variables
-                            .get(*block_above)
-                            .ok_or(EngravingError::UnknownBlockBottomPosition(*block_above))?
-                            + *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockStartToBlockStart(other_block) => solver
-                .add_constraint(
-                    *block_start_position_variables
+                strength,
+            ) => {
+                *vertical_grid_line_variables
+                    .get(*grid_line_after)
+                    .ok_or(EngravingError::UnknownVerticalGridLine(*grid_line_after))?
+                    | GE(Self::get_solver_strength(strength, STRONG))
+                    | (*block_start_position_variables
                         .get(index)
                         .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                        | EQ(STRONG)
-                        | (*block_start_position_variables
-                            .get(*other_block)
-                            .ok_or(EngravingError::UnknownBlockStartPosition(*other_block))?
-                            + block.get_start_padding().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockEndToBlockEnd(other_block) => solver
-                .add_constraint(
-                    *block_end_position_variables
+                        + block.get_fixed_width().value
+                        + block.get_end_padding().value)
+            }
+            BlockConstraint::FloatAfterBlockByDistance(block_before, distance, strength) => {
+                *block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    | GE(Self::get_solver_strength(strength, WEAK))
+                    | (*block_end_position_variables
+                        .get(*block_before)
+                        .ok_or(EngravingError::UnknownBlockEndPosition(*block_before))?
+                        + *distance)
+            }
+            BlockConstraint::FloatBeforeBlockByDistance(block_after, distance, strength) => {
+                *block_start_position_variables
+                    .get(*block_after)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(*block_after))?
+                    | GE(Self::get_solver_strength(strength, WEAK))
+                    | (*block_end_position_variables
                         .get(index)
                         .ok_or(EngravingError::UnknownBlockEndPosition(index))?
-                        | EQ(STRONG)
-                        | (*block_end_position_variables
-                            .get(*other_block)
-                            .ok_or(EngravingError::UnknownBlockEndPosition(*other_block))?
-                            - block.get_end_padding().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockTopToBlockTop(other_block) => solver
-                .add_constraint(
-                    *block_top_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                        | EQ(STRONG)
-                        | (*block_top_position_variables
-                            .get(*other_block)
-                            .ok_or(EngravingError::UnknownBlockTopPosition(*other_block))?
-                            + block.get_top_padding().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockBottomToBlockBottom(other_block) => solver
-                .add_constraint(
-                    *block_bottom_position_variables
+                        + *distance)
+            }
+            BlockConstraint::FloatAboveBlockByDistance(block_beneath, distance, strength) => {
+                *block_top_position_variables
+                    .get(*block_beneath)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(*block_beneath))?
+                    | GE(Self::get_solver_strength(strength, WEAK))
+                    | (*block_bottom_position_variables
                         .get(index)
                         .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
-                        | EQ(STRONG)
-                        | (*block_bottom_position_variables
-                            .get(*other_block)
-                            .ok_or(EngravingError::UnknownBlockBottomPosition(*other_block))?
-                            - block.get_bottom_padding().value),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockHorizontalCenterBetweenBlocks(block_before, block_after) => solver
-                .add_constraint(
-                    *block_start_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                        | EQ(STRONG)
-                        | ((*block_end_position_variables
-                            .get(*block_before)
-                            .ok_or(EngravingError::UnknownBlockEndPosition(*block_before))?
-                            + *block_start_position_variables
-                                .get(*block_after)
-                                .ok_or(EngravingError::UnknownBlockStartPosition(*block_after))?
-                            - block.get_fixed_width().value)
-                            / 2.0),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockVerticalCenterBetweenBlocks(block_above, block_beneath) => solver
-                .add_constraint(
-                    *block_top_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                        | EQ(STRONG)
-                        | ((*block_bottom_position_variables
-                            .get(*block_above)
-                            .ok_or(EngravingError::UnknownBlockBottomPosition(*block_above))?
-                            + *block_top_position_variables
-                                .get(*block_beneath)
-                                .ok_or(EngravingError::UnknownBlockTopPosition(*block_beneath))?
-                            - block.get_fixed_height().value)
-                            / 2.0),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockHorizontalCenterToBlockCenter(other_block) => solver
-                .add_constraint(
-                    ((*block_start_position_variables
+                        + *distance)
+            }
+            BlockConstraint::FloatBeneathBlockByDistance(block_above, distance, strength) => {
+                *block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                    | GE(Self::get_solver_strength(strength, WEAK))
+                    | (*block_bottom_position_variables
+                        .get(*block_above)
+                        .ok_or(EngravingError::UnknownBlockBottomPosition(*block_above))?
+                        + *distance)
+            }
+            BlockConstraint::LockStartToBlockStart(other_block, strength) => {
+                *block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*block_start_position_variables
+                        .get(*other_block)
+                        .ok_or(EngravingError::UnknownBlockStartPosition(*other_block))?
+                        + block.get_start_padding().value)
+            }
+            BlockConstraint::LockEndToBlockEnd(other_block, strength) => {
+                *block_end_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockEndPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*block_end_position_variables
+                        .get(*other_block)
+                        .ok_or(EngravingError::UnknownBlockEndPosition(*other_block))?
+                        - block.get_end_padding().value)
+            }
+            BlockConstraint::LockTopToBlockTop(other_block, strength) => {
+                *block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*block_top_position_variables
+                        .get(*other_block)
+                        .ok_or(EngravingError::UnknownBlockTopPosition(*other_block))?
+                        + block.get_top_padding().value)
+            }
+            BlockConstraint::LockBottomToBlockBottom(other_block, strength) => {
+                *block_bottom_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*block_bottom_position_variables
+                        .get(*other_block)
+                        .ok_or(EngravingError::UnknownBlockBottomPosition(*other_block))?
+                        - block.get_bottom_padding().value)
+            }
+            BlockConstraint::LockHorizontalCenterBetweenBlocks(
+                block_before,
+                block_after,
+                strength,
+            ) => {
+                *block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | ((*block_end_position_variables
+                        .get(*block_before)
+                        .ok_or(EngravingError::UnknownBlockEndPosition(*block_before))?
+                        + *block_start_position_variables
+                            .get(*block_after)
+                            .ok_or(EngravingError::UnknownBlockStartPosition(*block_after))?
+                        - block.get_fixed_width().value)
+                        / 2.0)
+            }
+            BlockConstraint::LockVerticalCenterBetweenBlocks(
+                block_above,
+                block_beneath,
+                strength,
+            ) => {
+                *block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | ((*block_bottom_position_variables
+                        .get(*block_above)
+                        .ok_or(EngravingError::UnknownBlockBottomPosition(*block_above))?
+                        + *block_top_position_variables
+                            .get(*block_beneath)
+                            .ok_or(EngravingError::UnknownBlockTopPosition(*block_beneath))?
+                        - block.get_fixed_height().value)
+                        / 2.0)
+            }
+            BlockConstraint::LockHorizontalCenterToBlockCenter(other_block, strength) => {
+                ((*block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    + *block_end_position_variables
                         .get(index)
-                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                        .ok_or(EngravingError::UnknownBlockEndPosition(index))?)
+                    / 2.0)
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | ((*block_start_position_variables
+                        .get(*other_block)
+                        .ok_or(EngravingError::UnknownBlockStartPosition(*other_block))?
                         + *block_end_position_variables
-                            .get(index)
-                            .ok_or(EngravingError::UnknownBlockEndPosition(index))?)
-                        / 2.0)
-                        | EQ(STRONG)
-                        | ((*block_start_position_variables
                             .get(*other_block)
-                            .ok_or(EngravingError::UnknownBlockStartPosition(*other_block))?
-                            + *block_end_position_variables
-                                .get(*other_block)
-                                .ok_or(EngravingError::UnknownBlockEndPosition(*other_block))?)
-                            / 2.0),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::FloatHorizontalCenterToBlockCenter(other_block) => solver
-                .add_constraint(
-                    ((*block_start_position_variables
+                            .ok_or(EngravingError::UnknownBlockEndPosition(*other_block))?)
+                        / 2.0)
+            }
+            BlockConstraint::FloatHorizontalCenterToBlockCenter(other_block, strength) => {
+                ((*block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    + *block_end_position_variables
                         .get(index)
-                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                        .ok_or(EngravingError::UnknownBlockEndPosition(index))?)
+                    / 2.0)
+                    | EQ(Self::get_solver_strength(strength, WEAK))
+                    | ((*block_start_position_variables
+                        .get(*other_block)
+                        .ok_or(EngravingError::UnknownBlockStartPosition(*other_block))?
                         + *block_end_position_variables
-                            .get(index)
-                            .ok_or(EngravingError::UnknownBlockEndPosition(index))?)
-                        / 2.0)
-                        | EQ(WEAK)
-                        | ((*block_start_position_variables
                             .get(*other_block)
-                            .ok_or(EngravingError::UnknownBlockStartPosition(*other_block))?
-                            + *block_end_position_variables
-                                .get(*other_block)
-                                .ok_or(EngravingError::UnknownBlockEndPosition(*other_block))?)
-                            / 2.0),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockVerticalCenterToBlockCenter(other_block) => solver
-                .add_constraint(
-                    ((*block_top_position_variables
+                            .ok_or(EngravingError::UnknownBlockEndPosition(*other_block))?)
+                        / 2.0)
+            }
+            BlockConstraint::LockVerticalCenterToBlockCenter(other_block, strength) => {
+                ((*block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                    + *block_bottom_position_variables
                         .get(index)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                        .ok_or(EngravingError::UnknownBlockBottomPosition(index))?)
+                    / 2.0)
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | ((*block_top_position_variables
+                        .get(*other_block)
+                        .ok_or(EngravingError::UnknownBlockTopPosition(*other_block))?
                         + *block_bottom_position_variables
-                            .get(index)
-                            .ok_or(EngravingError::UnknownBlockBottomPosition(index))?)
-                        / 2.0)
-                        | EQ(STRONG)
-                        | ((*block_top_position_variables
                             .get(*other_block)
-                            .ok_or(EngravingError::UnknownBlockTopPosition(*other_block))?
-                            + *block_bottom_position_variables.get(*other_block).ok_or(
-                                EngravingError::UnknownBlockBottomPosition(*other_block),
-                            )?)
-                            / 2.0),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockAfterBlockByDistance(block_before, distance) => solver
-                .add_constraint(
-                    *block_start_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
-                        | EQ(STRONG)
-                        | (*block_end_position_variables
-                            .get(*block_before)
-                            .ok_or(EngravingError::UnknownBlockEndPosition(*block_before))?
-                            + *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockBeforeBlockByDistance(block_after, distance) => solver
-                .add_constraint(
-                    *block_end_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockEndPosition(index))?
-                        | EQ(STRONG)
-                        | (*block_start_position_variables
-                            .get(*block_after)
-                            .ok_or(EngravingError::UnknownBlockStartPosition(*block_after))?
-                            - *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockAboveBlockByDistance(block_beneath, distance) => solver
-                .add_constraint(
-                    *block_bottom_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
-                        | EQ(STRONG)
-                        | (*block_top_position_variables
-                            .get(*block_beneath)
-                            .ok_or(EngravingError::UnknownBlockTopPosition(*block_beneath))?
-                            - *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockBeneathBlockByDistance(block_above, distance) => solver
-                .add_constraint(
-                    *block_top_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                        | EQ(STRONG)
-                        | (*block_bottom_position_variables
-                            .get(*block_above)
-                            .ok_or(EngravingError::UnknownBlockBottomPosition(*block_above))?
-                            + *distance),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockTopToBlockCenter(other_block) => solver
-                .add_constraint(
-                    *block_top_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
-                        | EQ(STRONG)
-                        | ((*block_top_position_variables
+                            .ok_or(EngravingError::UnknownBlockBottomPosition(*other_block))?)
+                        / 2.0)
+            }
+            BlockConstraint::LockAfterBlockByDistance(block_before, distance, strength) => {
+                *block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*block_end_position_variables
+                        .get(*block_before)
+                        .ok_or(EngravingError::UnknownBlockEndPosition(*block_before))?
+                        + *distance)
+            }
+            BlockConstraint::LockBeforeBlockByDistance(block_after, distance, strength) => {
+                *block_end_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockEndPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*block_start_position_variables
+                        .get(*block_after)
+                        .ok_or(EngravingError::UnknownBlockStartPosition(*block_after))?
+                        - *distance)
+            }
+            BlockConstraint::LockAboveBlockByDistance(block_beneath, distance, strength) => {
+                *block_bottom_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*block_top_position_variables
+                        .get(*block_beneath)
+                        .ok_or(EngravingError::UnknownBlockTopPosition(*block_beneath))?
+                        - *distance)
+            }
+            BlockConstraint::LockBeneathBlockByDistance(block_above, distance, strength) => {
+                *block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | (*block_bottom_position_variables
+                        .get(*block_above)
+                        .ok_or(EngravingError::UnknownBlockBottomPosition(*block_above))?
+                        + *distance)
+            }
+            BlockConstraint::LockTopToBlockCenter(other_block, strength) => {
+                *block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | ((*block_top_position_variables
+                        .get(*other_block)
+                        .ok_or(EngravingError::UnknownBlockTopPosition(*other_block))?
+                        + *block_bottom_position_variables
                             .get(*other_block)
-                            .ok_or(EngravingError::UnknownBlockTopPosition(*other_block))?
-                            + *block_bottom_position_variables.get(*other_block).ok_or(
-                                EngravingError::UnknownBlockBottomPosition(*other_block),
-                            )?)
-                            / 2.0),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-            BlockConstraint::LockBottomToBlockCenter(other_block) => solver
-                .add_constraint(
-                    *block_bottom_position_variables
-                        .get(index)
-                        .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
-                        | EQ(STRONG)
-                        | ((*block_top_position_variables
+                            .ok_or(EngravingError::UnknownBlockBottomPosition(*other_block))?)
+                        / 2.0)
+            }
+            BlockConstraint::LockBottomToBlockCenter(other_block, strength) => {
+                *block_bottom_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
+                    | EQ(Self::get_solver_strength(strength, STRONG))
+                    | ((*block_top_position_variables
+                        .get(*other_block)
+                        .ok_or(EngravingError::UnknownBlockTopPosition(*other_block))?
+                        + *block_bottom_position_variables
                             .get(*other_block)
-                            .ok_or(EngravingError::UnknownBlockTopPosition(*other_block))?
-                            + *block_bottom_position_variables.get(*other_block).ok_or(
-                                EngravingError::UnknownBlockBottomPosition(*other_block),
-                            )?)
-                            / 2.0),
-                )
-                .map_err(|err| EngravingError::AddConstraintErrorOnBlock(err, index)),
-        }
+                            .ok_or(EngravingError::UnknownBlockBottomPosition(*other_block))?)
+                        / 2.0)
+            }
+        };
+
+        Ok(solver_constraint)
     }
 
     #[inline]
@@ -1449,7 +1854,7 @@ impl LayoutSystem {
         block_bottom_position_variables: &[Variable],
         block_start_position_variables: &[Variable],
         block_end_position_variables: &[Variable],
-    ) -> Vec<(BlockIndex, BlockIndex)> {
+    ) -> Result<Vec<(BlockIndex, BlockIndex)>, EngravingError> {
         // Detect collisions between blocks.
 
         // Not every block needs to participate in collision detection; we narrow
@@ -1462,32 +1867,19 @@ impl LayoutSystem {
         // First, build index maps containing coordinate ranges in the horizontal and vertical
         // planes for every candidate block.
 
-        let mut x_plane_intervals = IntervalMap::new();
-
-        let mut y_plane_intervals = IntervalMap::new();
-
-        for (index, block) in blocks.iter().enumerate() {
-            if block.is_collidable() {
-                let start_position = solver.get_value(block_start_position_variables[index]);
-                let end_position = solver.get_value(block_end_position_variables[index]);
-
-                if end_position > start_position {
-                    x_plane_intervals.insert(start_position..end_position, index);
-
-                    let top_position = solver.get_value(block_top_position_variables[index]);
-                    let bottom_position = solver.get_value(block_bottom_position_variables[index]);
-
-                    if bottom_position > top_position {
-                        y_plane_intervals.insert(top_position..bottom_position, index);
-                    }
-                }
-            }
-        }
+        let (x_plane_intervals, y_plane_intervals) = Self::get_collision_interval_maps(
+            blocks,
+            solver,
+            block_top_position_variables,
+            block_bottom_position_variables,
+            block_start_position_variables,
+            block_end_position_variables,
+        )?;
 
         // Next, build a list of colliding blocks by scanning the horizontal and vertical planes
         // for collision candidates. Our first conundrum: which plane should we scan first?
 
-        if horizontal_grid_lines_count > vertical_grid_lines_count {
+        let candidates = if horizontal_grid_lines_count > vertical_grid_lines_count {
             // There are more horizontal grid lines than vertical grid lines in this system.
             // This suggests the system is more horizontally dense than vertically dense,
             // and thus collisions are more likely to occur horizontally than vertically.
@@ -1504,7 +1896,7 @@ impl LayoutSystem {
                 block_bottom_position_variables,
                 block_start_position_variables,
                 block_end_position_variables,
-            )
+            )?
         } else {
             // There are more vertical grid lines than horizontal grid lines in this system.
             // This suggests the system is more vertically dense than horizontally dense,
@@ -1522,10 +1914,142 @@ impl LayoutSystem {
                 block_bottom_position_variables,
                 block_start_position_variables,
                 block_end_position_variables,
-            )
+            )?
+        };
+
+        // Finally, blocks whose bounding boxes overlap may still be clear of one another
+        // if their contents nest together. Compare the skylines of each candidate pair
+        // of blocks, keeping only those pairs whose skylines actually overlap. Blocks
+        // without skylines of their own fill their bounding boxes, so for these,
+        // the bounding box collision stands.
+
+        let mut collisions = Vec::new();
+
+        for (index_a, index_b) in candidates {
+            let (skyline_a, start_a, top_a) = Self::get_solved_skyline(
+                index_a,
+                blocks,
+                solver,
+                block_top_position_variables,
+                block_bottom_position_variables,
+                block_start_position_variables,
+                block_end_position_variables,
+            )?;
+
+            let (skyline_b, start_b, top_b) = Self::get_solved_skyline(
+                index_b,
+                blocks,
+                solver,
+                block_top_position_variables,
+                block_bottom_position_variables,
+                block_start_position_variables,
+                block_end_position_variables,
+            )?;
+
+            if skyline_a
+                .place(start_a, top_a)
+                .collides_with(&skyline_b.place(start_b, top_b))
+            {
+                collisions.push((index_a, index_b));
+            }
+        }
+
+        Ok(collisions)
+    }
+
+    /// Returns true if the given Block is fitted around its content only once every other
+    /// Block has settled. Until then its position in the solver is undefined, so it takes
+    /// no part in collision detection and does not count towards the engraved width of
+    /// the system, whether or not it is marked as collidable.
+    #[inline]
+    fn is_fitted_after_settling(block: &BlockEnum) -> bool {
+        matches!(block, BlockEnum::Curve(_) | BlockEnum::Spanner(_))
+    }
+
+    /// Returns index maps of the solved (start..end) and (top..bottom) ranges of every
+    /// collidable Block, representing the horizontal and vertical coordinate planes.
+    /// Blocks with no width are left out of both maps; blocks with no height are left
+    /// out of the vertical map.
+    #[inline]
+    #[allow(clippy::type_complexity)]
+    fn get_collision_interval_maps(
+        blocks: &[BlockEnum],
+        solver: &Solver,
+        block_top_position_variables: &[Variable],
+        block_bottom_position_variables: &[Variable],
+        block_start_position_variables: &[Variable],
+        block_end_position_variables: &[Variable],
+    ) -> Result<(IntervalMap<f64, BlockIndex>, IntervalMap<f64, BlockIndex>), EngravingError> {
+        let mut x_plane_intervals = IntervalMap::new();
+
+        let mut y_plane_intervals = IntervalMap::new();
+
+        for (index, block) in blocks.iter().enumerate() {
+            if block.is_collidable() && !Self::is_fitted_after_settling(block) {
+                let x_range = Self::get_solved_horizontal_range(
+                    index,
+                    solver,
+                    block_start_position_variables,
+                    block_end_position_variables,
+                )?;
+
+                if x_range.end > x_range.start {
+                    x_plane_intervals.insert(x_range, index);
+
+                    let y_range = Self::get_solved_vertical_range(
+                        index,
+                        solver,
+                        block_top_position_variables,
+                        block_bottom_position_variables,
+                    )?;
+
+                    if y_range.end > y_range.start {
+                        y_plane_intervals.insert(y_range, index);
+                    }
+                }
+            }
+        }
+
+        Ok((x_plane_intervals, y_plane_intervals))
+    }
+
+    /// Returns true if the given Blocks were generated from the same source event.
+    /// Blocks are compared by source spine item where both have one. Blocks without
+    /// a source spine item, such as hand-placed dynamics and text, are compared by source
+    /// onset and source voice index instead, so they are still checked for collisions
+    /// against one another.
+    #[inline]
+    fn is_same_source(block_a: &BlockEnum, block_b: &BlockEnum) -> bool {
+        match (
+            block_a.get_source_moment_spine_item(),
+            block_b.get_source_moment_spine_item(),
+        ) {
+            (Some(item_a), Some(item_b)) => item_a == item_b,
+            (None, None) => {
+                block_a.get_source_onset() == block_b.get_source_onset()
+                    && block_a.get_source_voice_index() == block_b.get_source_voice_index()
+            }
+            _ => false,
         }
     }
 
+    /// Returns true if the given Blocks, at the given indices, are expected to touch, so
+    /// any collision between them is deliberate: either they were generated from the same
+    /// source event, or one is a BeamBlock and the other is a stem linked by its beam.
+    #[inline]
+    fn is_collision_expected(
+        index_a: BlockIndex,
+        block_a: &BlockEnum,
+        index_b: BlockIndex,
+        block_b: &BlockEnum,
+    ) -> bool {
+        let is_beam_stem = |beam: &BlockEnum, stem: BlockIndex| matches!(beam, BlockEnum::Beam(beam) if beam.get_beam().get_stems().contains(&stem));
+
+        Self::is_same_source(block_a, block_b)
+            || is_beam_stem(block_a, index_b)
+            || is_beam_stem(block_b, index_a)
+    }
+
     #[inline]
     #[allow(clippy::too_many_arguments)]
     fn detect_colliding_blocks_horizontally(
@@ -1537,36 +2061,51 @@ impl LayoutSystem {
         block_bottom_position_variables: &[Variable],
         block_start_position_variables: &[Variable],
         block_end_position_variables: &[Variable],
-    ) -> Vec<(BlockIndex, BlockIndex)> {
+    ) -> Result<Vec<(BlockIndex, BlockIndex)>, EngravingError> {
         // Build a list of colliding blocks by scanning for collisions in the horizontal plane.
 
         let mut collisions = Vec::new();
 
         for (index, block) in blocks.iter().enumerate() {
-            if block.is_collidable() {
-                for (_, horizontal_collision_candidate_index) in x_plane_intervals.iter(
-                    solver.get_value(block_start_position_variables[index])
-                        ..solver.get_value(block_end_position_variables[index]),
-                ) {
+            if block.is_collidable() && !Self::is_fitted_after_settling(block) {
+                for (_, horizontal_collision_candidate_index) in
+                    x_plane_intervals.iter(Self::get_solved_horizontal_range(
+                        index,
+                        solver,
+                        block_start_position_variables,
+                        block_end_position_variables,
+                    )?)
+                {
                     // We expect blocks to collide with their own coordinates; ignore this.
+                    // We also expect to encounter every collision twice, once from the
+                    // perspective of each block; only report each pair of blocks once.
 
-                    if *horizontal_collision_candidate_index != index {
+                    if *horizontal_collision_candidate_index > index {
                         // Additionally, ignore colliding blocks generated from the same source
-                        // spine item; we assume any such collisions (e.g. tail flags touching
+                        // event; we assume any such collisions (e.g. tail flags touching
                         // noteheads) are deliberate. It's only collisions from blocks generated
-                        // from _different_ source spine items that concern us.
-
-                        if block.get_source_moment_spine_item()
-                            != blocks[*horizontal_collision_candidate_index]
-                                .get_source_moment_spine_item()
-                        {
+                        // from _different_ source events that concern us. Likewise, a beam
+                        // always touches its own stems.
+
+                        if !Self::is_collision_expected(
+                            index,
+                            block,
+                            *horizontal_collision_candidate_index,
+                            blocks.get(*horizontal_collision_candidate_index).ok_or(
+                                EngravingError::UnknownBlock(*horizontal_collision_candidate_index),
+                            )?,
+                        ) {
                             // This is a valid collision on the horizontal plane. Check to see
                             // if these blocks collide on the vertical plane as well.
 
-                            for (_, vertical_collision_candidate_index) in y_plane_intervals.iter(
-                                solver.get_value(block_top_position_variables[index])
-                                    ..solver.get_value(block_bottom_position_variables[index]),
-                            ) {
+                            for (_, vertical_collision_candidate_index) in
+                                y_plane_intervals.iter(Self::get_solved_vertical_range(
+                                    index,
+                                    solver,
+                                    block_top_position_variables,
+                                    block_bottom_position_variables,
+                                )?)
+                            {
                                 if vertical_collision_candidate_index
                                     == horizontal_collision_candidate_index
                                 {
@@ -1582,7 +2121,7 @@ impl LayoutSystem {
             }
         }
 
-        collisions
+        Ok(collisions)
     }
 
     #[inline]
@@ -1596,36 +2135,51 @@ impl LayoutSystem {
         block_bottom_position_variables: &[Variable],
         block_start_position_variables: &[Variable],
         block_end_position_variables: &[Variable],
-    ) -> Vec<(BlockIndex, BlockIndex)> {
+    ) -> Result<Vec<(BlockIndex, BlockIndex)>, EngravingError> {
         // Build a list of colliding blocks by scanning for collisions in the vertical plane.
 
         let mut collisions = Vec::new();
 
         for (index, block) in blocks.iter().enumerate() {
-            if block.is_collidable() {
-                for (_, vertical_collision_candidate_index) in y_plane_intervals.iter(
-                    solver.get_value(block_top_position_variables[index])
-                        ..solver.get_value(block_bottom_position_variables[index]),
-                ) {
+            if block.is_collidable() && !Self::is_fitted_after_settling(block) {
+                for (_, vertical_collision_candidate_index) in
+                    y_plane_intervals.iter(Self::get_solved_vertical_range(
+                        index,
+                        solver,
+                        block_top_position_variables,
+                        block_bottom_position_variables,
+                    )?)
+                {
                     // We expect blocks to collide with their own coordinates; ignore this.
+                    // We also expect to encounter every collision twice, once from the
+                    // perspective of each block; only report each pair of blocks once.
 
-                    if *vertical_collision_candidate_index != index {
+                    if *vertical_collision_candidate_index > index {
                         // Additionally, ignore colliding blocks generated from the same source
-                        // spine item; we assume any such collisions (e.g. tail flags touching
+                        // event; we assume any such collisions (e.g. tail flags touching
                         // noteheads) are deliberate. It's only collisions from blocks generated
-                        // from _different_ source spine items that concern us.
-
-                        if block.get_source_moment_spine_item()
-                            != blocks[*vertical_collision_candidate_index]
-                                .get_source_moment_spine_item()
-                        {
+                        // from _different_ source events that concern us. Likewise, a beam
+                        // always touches its own stems.
+
+                        if !Self::is_collision_expected(
+                            index,
+                            block,
+                            *vertical_collision_candidate_index,
+                            blocks.get(*vertical_collision_candidate_index).ok_or(
+                                EngravingError::UnknownBlock(*vertical_collision_candidate_index),
+                            )?,
+                        ) {
                             // This is a valid collision on the vertical plane. Check to see
                             // if these blocks collide on the horizontal plane as well.
 
-                            for (_, horizontal_collision_candidate_index) in x_plane_intervals.iter(
-                                solver.get_value(block_start_position_variables[index])
-                                    ..solver.get_value(block_end_position_variables[index]),
-                            ) {
+                            for (_, horizontal_collision_candidate_index) in
+                                x_plane_intervals.iter(Self::get_solved_horizontal_range(
+                                    index,
+                                    solver,
+                                    block_start_position_variables,
+                                    block_end_position_variables,
+                                )?)
+                            {
                                 if horizontal_collision_candidate_index
                                     == vertical_collision_candidate_index
                                 {
@@ -1641,124 +2195,189 @@ impl LayoutSystem {
             }
         }
 
-        collisions
+        Ok(collisions)
+    }
+
+    /// Returns the solved (start..end) range of the Block at the given index.
+    #[inline]
+    fn get_solved_horizontal_range(
+        index: BlockIndex,
+        solver: &Solver,
+        block_start_position_variables: &[Variable],
+        block_end_position_variables: &[Variable],
+    ) -> Result<Range<f64>, EngravingError> {
+        Ok(solver.get_value(
+            *block_start_position_variables
+                .get(index)
+                .ok_or(EngravingError::UnknownBlockStartPosition(index))?,
+        )
+            ..solver.get_value(
+                *block_end_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockEndPosition(index))?,
+            ))
+    }
+
+    /// Returns the solved (top..bottom) range of the Block at the given index.
+    #[inline]
+    fn get_solved_vertical_range(
+        index: BlockIndex,
+        solver: &Solver,
+        block_top_position_variables: &[Variable],
+        block_bottom_position_variables: &[Variable],
+    ) -> Result<Range<f64>, EngravingError> {
+        Ok(solver.get_value(
+            *block_top_position_variables
+                .get(index)
+                .ok_or(EngravingError::UnknownBlockTopPosition(index))?,
+        )
+            ..solver.get_value(
+                *block_bottom_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockBottomPosition(index))?,
+            ))
     }
 
+    /// Returns the Skyline of the Block at the given index, together with the solved
+    /// (start, top) position at which the Skyline should be placed. Blocks without
+    /// a Skyline of their own are treated as solid rectangles filling their solved
+    /// bounding boxes. The Skyline of a BeamBlock traces its beam, which meets the solved
+    /// tips of the beam's first and last stems.
     #[inline]
     #[allow(clippy::too_many_arguments)]
-    fn resolve_colliding_blocks(
+    fn get_solved_skyline(
+        index: BlockIndex,
         blocks: &[BlockEnum],
-        collisions: &[(BlockIndex, BlockIndex)],
-        solver: &mut Solver,
-        horizontal_grid_line_variables: &[Variable],
-        vertical_grid_line_variables: &[Variable],
+        solver: &Solver,
         block_top_position_variables: &[Variable],
         block_bottom_position_variables: &[Variable],
         block_start_position_variables: &[Variable],
         block_end_position_variables: &[Variable],
-    ) -> Result<(), EngravingError> {
-        // Resolve block collisions by shifting blocks vertically or horizontally.
+    ) -> Result<(Skyline, f64, f64), EngravingError> {
+        let block = blocks
+            .get(index)
+            .ok_or(EngravingError::UnknownBlock(index))?;
 
-        // If either block can move vertically, then it might be able move up or down
-        // to avoid collision; the direction of vertical movement is based on the block's
-        // source voice index, with blocks sourced from lower-indexed voices moving
-        // upwards to avoid blocks sourced from higher-indexed voices. If neither block
-        // can move vertically, then push the block with the later start position sideways
-        // to avoid collision.
+        if let BlockEnum::Beam(beam_block) = block {
+            let beam = beam_block.get_beam();
 
-        // Any moved block needs to have collision detection run on it again to make
-        // sure we didn't create a new collision while resolving this collision
-        // TODO: AJRC - 22/12/21 - we only handle horizontal resolutions for T0
-        // TODO: AJRC - 22/12/21 - need to re-run collision detection on adjusted blocks
+            let stem_positions = beam
+                .get_stems()
+                .iter()
+                .map(|stem| {
+                    Ok(solver.get_value(
+                        *block_start_position_variables
+                            .get(*stem)
+                            .ok_or(EngravingError::UnknownBlockStartPosition(*stem))?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, EngravingError>>()?;
+
+            let tip = |stem: Option<&BlockIndex>| -> Result<f64, EngravingError> {
+                let stem = *stem.ok_or(EngravingError::UnknownBeam(beam_block.get_beam_index()))?;
+
+                Ok(solver.get_value(match beam.get_direction() {
+                    BeamDirection::Above => *block_top_position_variables
+                        .get(stem)
+                        .ok_or(EngravingError::UnknownBlockTopPosition(stem))?,
+                    BeamDirection::Below => *block_bottom_position_variables
+                        .get(stem)
+                        .ok_or(EngravingError::UnknownBlockBottomPosition(stem))?,
+                }))
+            };
+
+            return Ok(beam.get_skyline(
+                stem_positions.as_slice(),
+                tip(beam.get_stems().first())?,
+                tip(beam.get_stems().last())?,
+            ));
+        }
 
-        for (index_a, index_b) in collisions {
-            let index_a = *index_a;
+        let x_range = Self::get_solved_horizontal_range(
+            index,
+            solver,
+            block_start_position_variables,
+            block_end_position_variables,
+        )?;
 
-            let index_b = *index_b;
+        let y_range = Self::get_solved_vertical_range(
+            index,
+            solver,
+            block_top_position_variables,
+            block_bottom_position_variables,
+        )?;
 
-            if blocks[index_a].get_can_move_up_to_avoid_vertical_collision()
-                || blocks[index_a].get_can_move_down_to_avoid_vertical_collision()
-                || blocks[index_b].get_can_move_up_to_avoid_vertical_collision()
-                || blocks[index_b].get_can_move_down_to_avoid_vertical_collision()
-            {
-                Self::resolve_colliding_blocks_vertically(index_a, index_b)?;
-            } else {
-                Self::resolve_colliding_blocks_horizontally(
-                    index_a,
-                    index_b,
-                    blocks,
-                    solver,
-                    horizontal_grid_line_variables,
-                    vertical_grid_line_variables,
-                    block_top_position_variables,
-                    block_bottom_position_variables,
-                    block_start_position_variables,
-                    block_end_position_variables,
-                )?;
-            }
-        }
+        let skyline = match block.get_skyline() {
+            Some(skyline) => skyline.clone(),
+            None => Skyline::from_bounding_box(
+                StaveSpaces::new((x_range.end - x_range.start) as f32),
+                StaveSpaces::new((y_range.end - y_range.start) as f32),
+            ),
+        };
 
-        Ok(())
+        Ok((skyline, x_range.start, y_range.start))
     }
 
     #[inline]
     #[allow(clippy::too_many_arguments)]
-    fn resolve_colliding_blocks_vertically(
+    fn resolve_colliding_blocks(
+        blocks: &[BlockEnum],
         index_a: BlockIndex,
         index_b: BlockIndex,
-    ) -> Result<(), EngravingError> {
-        // TODO: AJRC - 27/12/21 - resolve block collisions vertically.
-
-        log::warn!(
-            "models::display::layout::system::resolve_colliding_blocks_vertically(): unresolved vertical collision between block indices {} and {}",
-            index_a,
-            index_b
-        );
-
-        Ok(())
-    }
-
-    #[inline]
-    #[allow(clippy::too_many_arguments)]
-    fn resolve_colliding_blocks_horizontally(
-        index_a: BlockIndex,
-        index_b: BlockIndex,
-        blocks: &[BlockEnum],
-        solver: &mut Solver,
-        horizontal_grid_line_variables: &[Variable],
-        vertical_grid_line_variables: &[Variable],
+        minimum_collision_gap: StaveSpaces,
+        solver: &Solver,
         block_top_position_variables: &[Variable],
         block_bottom_position_variables: &[Variable],
         block_start_position_variables: &[Variable],
         block_end_position_variables: &[Variable],
-    ) -> Result<(), EngravingError> {
-        if block_start_position_variables[index_a] > block_start_position_variables[index_b] {
-            // Add a new constraint to the solver that ensures the first block must start after the second.
-            // TODO: AJRC - 22/12/21 - if the blocks are glyphs and are aligned diagonally,
-            // then it may be possible to overlap their cut-offs. Check for this.
+    ) -> Result<Option<(BlockIndex, Constraint)>, EngravingError> {
+        // Resolve a block collision by shifting one block vertically or horizontally.
+        // We return the constraint that effects the move, along with the index of the
+        // moved block, for the caller to add to the solver; the constraint must be added
+        // before the next collision is resolved, so later resolutions see the move.
+
+        // If either block can move vertically, then it might be able move up or down
+        // to avoid collision; the direction of vertical movement is based on the block's
+        // source voice index, with blocks sourced from lower-indexed voices moving
+        // upwards to avoid blocks sourced from higher-indexed voices. If neither block
+        // can move vertically, then push the block that comes later in the music sideways
+        // to avoid collision. Either way, the moved block is kept at least the given
+        // minimum collision gap clear of the other block.
+
+        // Any moved block may have created a new collision while resolving this one;
+        // the caller is responsible for re-running collision detection afterwards.
+
+        let block_a = blocks
+            .get(index_a)
+            .ok_or(EngravingError::UnknownBlock(index_a))?;
 
-            Self::add_block_constraint_to_solver(
+        let block_b = blocks
+            .get(index_b)
+            .ok_or(EngravingError::UnknownBlock(index_b))?;
+
+        if block_a.get_can_move_up_to_avoid_vertical_collision()
+            || block_a.get_can_move_down_to_avoid_vertical_collision()
+            || block_b.get_can_move_up_to_avoid_vertical_collision()
+            || block_b.get_can_move_down_to_avoid_vertical_collision()
+        {
+            Self::resolve_colliding_blocks_vertically(
                 index_a,
-                &blocks[index_a],
-                &BlockConstraint::LockAfterBlockByDistance(index_b, 0.25),
+                index_b,
+                blocks,
+                minimum_collision_gap,
                 solver,
-                horizontal_grid_line_variables,
-                vertical_grid_line_variables,
                 block_top_position_variables,
                 block_bottom_position_variables,
                 block_start_position_variables,
                 block_end_position_variables,
             )
         } else {
-            // Add a new constraint to the solver that ensures the second block must start after the first.
-
-            Self::add_block_constraint_to_solver(
+            Self::resolve_colliding_blocks_horizontally(
+                index_a,
                 index_b,
-                &blocks[index_b],
-                &BlockConstraint::LockAfterBlockByDistance(index_a, 0.25),
+                blocks,
+                minimum_collision_gap,
                 solver,
-                horizontal_grid_line_variables,
-                vertical_grid_line_variables,
                 block_top_position_variables,
                 block_bottom_position_variables,
                 block_start_position_variables,
@@ -1767,10 +2386,332 @@ impl LayoutSystem {
         }
     }
 
+    #[inline]
+    #[allow(clippy::too_many_arguments)]
+    fn resolve_colliding_blocks_vertically(
+        index_a: BlockIndex,
+        index_b: BlockIndex,
+        blocks: &[BlockEnum],
+        minimum_collision_gap: StaveSpaces,
+        solver: &Solver,
+        block_top_position_variables: &[Variable],
+        block_bottom_position_variables: &[Variable],
+        block_start_position_variables: &[Variable],
+        block_end_position_variables: &[Variable],
+    ) -> Result<Option<(BlockIndex, Constraint)>, EngravingError> {
+        let top_a = *block_top_position_variables
+            .get(index_a)
+            .ok_or(EngravingError::UnknownBlockTopPosition(index_a))?;
+
+        let top_b = *block_top_position_variables
+            .get(index_b)
+            .ok_or(EngravingError::UnknownBlockTopPosition(index_b))?;
+
+        let bottom_a = *block_bottom_position_variables
+            .get(index_a)
+            .ok_or(EngravingError::UnknownBlockBottomPosition(index_a))?;
+
+        let bottom_b = *block_bottom_position_variables
+            .get(index_b)
+            .ok_or(EngravingError::UnknownBlockBottomPosition(index_b))?;
+
+        let (skyline_a, solved_start_a, solved_top_a) = Self::get_solved_skyline(
+            index_a,
+            blocks,
+            solver,
+            block_top_position_variables,
+            block_bottom_position_variables,
+            block_start_position_variables,
+            block_end_position_variables,
+        )?;
+
+        let (skyline_b, solved_start_b, solved_top_b) = Self::get_solved_skyline(
+            index_b,
+            blocks,
+            solver,
+            block_top_position_variables,
+            block_bottom_position_variables,
+            block_start_position_variables,
+            block_end_position_variables,
+        )?;
+
+        let placed_a = skyline_a.place(solved_start_a, solved_top_a);
+
+        let placed_b = skyline_b.place(solved_start_b, solved_top_b);
+
+        // Resolving an earlier collision may already have moved one of these blocks
+        // clear of the other; if so, there is nothing more to do.
+
+        if !placed_a.collides_with(&placed_b) {
+            return Ok(None);
+        }
+
+        // Each block that is permitted to move vertically offers up to two candidate
+        // resolutions: moving up until its bottom profile clears the top profile of
+        // the other block, or moving down until its top profile clears the bottom profile
+        // of the other block. Remember that positions closer to the top of the system
+        // have a smaller y value. Each candidate is a tuple of (index of moving block,
+        // index of stationary block, whether the block moves up, whether the move respects
+        // voice ordering, distance moved, constraint effecting the move). Each constraint
+        // fixes the maximum (or minimum) offset between the tops of the two blocks,
+        // so the moving block shifts by no more than is necessary to clear the overlap.
+        // A block without a fixed height, such as a BeamBlock, would only stretch if its top
+        // were moved up, so its bottom edge is constrained instead: its whole extent must
+        // then clear the other block.
+
+        // Moves that respect voice ordering are those where blocks sourced from
+        // lower-indexed voices move upwards and blocks sourced from higher-indexed
+        // voices move downwards.
+
+        let block_a = blocks
+            .get(index_a)
+            .ok_or(EngravingError::UnknownBlock(index_a))?;
+
+        let block_b = blocks
+            .get(index_b)
+            .ok_or(EngravingError::UnknownBlock(index_b))?;
+
+        let gap = minimum_collision_gap.value as f64;
+
+        let mut candidates = Vec::new();
+
+        for (
+            moving,
+            moving_block,
+            moving_top,
+            moving_bottom,
+            placed_moving,
+            stationary,
+            stationary_block,
+            stationary_top,
+            placed_stationary,
+        ) in [
+            (
+                index_a, block_a, top_a, bottom_a, &placed_a, index_b, block_b, top_b, &placed_b,
+            ),
+            (
+                index_b, block_b, top_b, bottom_b, &placed_b, index_a, block_a, top_a, &placed_a,
+            ),
+        ] {
+            let moving_voice = moving_block.get_source_voice_index();
+
+            let stationary_voice = stationary_block.get_source_voice_index();
+
+            if moving_block.get_can_move_up_to_avoid_vertical_collision() {
+                let distance = placed_moving.get_upward_clearance(placed_stationary, gap);
+
+                let moving_edge = if moving_block.is_fixed_height() {
+                    moving_top
+                } else {
+                    moving_bottom
+                };
+
+                let offset = solver.get_value(moving_edge) - solver.get_value(stationary_top);
+
+                candidates.push((
+                    moving,
+                    stationary,
+                    true,
+                    moving_voice < stationary_voice,
+                    distance,
+                    moving_edge
+                        | LE(COLLISION_RESOLUTION_STRENGTH)
+                        | (stationary_top + (offset - distance)),
+                ));
+            }
+
+            if moving_block.get_can_move_down_to_avoid_vertical_collision() {
+                let distance = placed_moving.get_downward_clearance(placed_stationary, gap);
+
+                let offset = solver.get_value(moving_top) - solver.get_value(stationary_top);
+
+                candidates.push((
+                    moving,
+                    stationary,
+                    false,
+                    moving_voice > stationary_voice,
+                    distance,
+                    moving_top
+                        | GE(COLLISION_RESOLUTION_STRENGTH)
+                        | (stationary_top + (offset + distance)),
+                ));
+            }
+        }
+
+        // Prefer candidates that respect voice ordering; amongst those, prefer the
+        // smallest movement.
+
+        let resolution = candidates.into_iter().min_by(
+            |(_, _, _, is_preferred_a, distance_a, _), (_, _, _, is_preferred_b, distance_b, _)| {
+                is_preferred_b
+                    .cmp(is_preferred_a)
+                    .then(distance_a.total_cmp(distance_b))
+            },
+        );
+
+        if let Some((moving, stationary, is_moving_up, _, distance, constraint)) = resolution {
+            log::debug!(
+                "models::display::layout::system::resolve_colliding_blocks_vertically(): moving block {} {} by {} to avoid block {}",
+                moving,
+                if is_moving_up { "up" } else { "down" },
+                distance,
+                stationary
+            );
+
+            Ok(Some((moving, constraint)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[inline]
+    #[allow(clippy::too_many_arguments)]
+    fn resolve_colliding_blocks_horizontally(
+        index_a: BlockIndex,
+        index_b: BlockIndex,
+        blocks: &[BlockEnum],
+        minimum_collision_gap: StaveSpaces,
+        solver: &Solver,
+        block_top_position_variables: &[Variable],
+        block_bottom_position_variables: &[Variable],
+        block_start_position_variables: &[Variable],
+        block_end_position_variables: &[Variable],
+    ) -> Result<Option<(BlockIndex, Constraint)>, EngravingError> {
+        let (skyline_a, solved_start_a, solved_top_a) = Self::get_solved_skyline(
+            index_a,
+            blocks,
+            solver,
+            block_top_position_variables,
+            block_bottom_position_variables,
+            block_start_position_variables,
+            block_end_position_variables,
+        )?;
+
+        let (skyline_b, solved_start_b, solved_top_b) = Self::get_solved_skyline(
+            index_b,
+            blocks,
+            solver,
+            block_top_position_variables,
+            block_bottom_position_variables,
+            block_start_position_variables,
+            block_end_position_variables,
+        )?;
+
+        let placed_a = skyline_a.place(solved_start_a, solved_top_a);
+
+        let placed_b = skyline_b.place(solved_start_b, solved_top_b);
+
+        // Resolving an earlier collision may already have moved one of these blocks
+        // clear of the other; if so, there is nothing more to do.
+
+        if !placed_a.collides_with(&placed_b) {
+            return Ok(None);
+        }
+
+        let block_a = blocks
+            .get(index_a)
+            .ok_or(EngravingError::UnknownBlock(index_a))?;
+
+        let block_b = blocks
+            .get(index_b)
+            .ok_or(EngravingError::UnknownBlock(index_b))?;
+
+        let (moving, stationary) = Self::get_horizontal_collision_order(
+            index_a,
+            block_a.get_source_onset(),
+            block_a.get_source_voice_index(),
+            solved_start_a,
+            index_b,
+            block_b.get_source_onset(),
+            block_b.get_source_voice_index(),
+            solved_start_b,
+        );
+
+        let (placed_moving, placed_stationary) = if moving == index_a {
+            (&placed_a, &placed_b)
+        } else {
+            (&placed_b, &placed_a)
+        };
+
+        // Create a new constraint that pushes the moving block sideways until
+        // its skyline sits after the stationary block's skyline, leaving the minimum
+        // collision gap between them. For blocks without skylines of their own, this
+        // places the start of the moving block the minimum collision gap after the end
+        // of the stationary block.
+
+        let moving_start = *block_start_position_variables
+            .get(moving)
+            .ok_or(EngravingError::UnknownBlockStartPosition(moving))?;
+
+        let stationary_start = *block_start_position_variables
+            .get(stationary)
+            .ok_or(EngravingError::UnknownBlockStartPosition(stationary))?;
+
+        let distance = placed_moving
+            .get_sideways_clearance(placed_stationary, minimum_collision_gap.value as f64);
+
+        let constraint = moving_start
+            | EQ(COLLISION_RESOLUTION_STRENGTH)
+            | (stationary_start
+                + (solver.get_value(moving_start) - solver.get_value(stationary_start) + distance));
+
+        log::debug!(
+            "models::display::layout::system::resolve_colliding_blocks_horizontally(): moving block {} sideways by {} to avoid block {}",
+            moving,
+            distance,
+            stationary
+        );
+
+        Ok(Some((moving, constraint)))
+    }
+
+    /// Decides which of two horizontally colliding Blocks should be pushed sideways
+    /// to avoid the other, returning a tuple of (index of moving block, index of stationary
+    /// block). The block that comes later in the music moves: first by source onset, if
+    /// both blocks have different known onsets; then by solved start position; then by
+    /// source voice index, with blocks sourced from higher-indexed voices moving aside
+    /// for blocks sourced from lower-indexed voices, as with unisons and seconds between
+    /// voices sharing a stave. If nothing distinguishes the blocks, the block with
+    /// the higher index moves.
+    #[inline]
+    #[allow(clippy::too_many_arguments)]
+    fn get_horizontal_collision_order<V: PartialOrd>(
+        index_a: BlockIndex,
+        onset_a: Option<Ticks>,
+        voice_a: V,
+        solved_start_a: f64,
+        index_b: BlockIndex,
+        onset_b: Option<Ticks>,
+        voice_b: V,
+        solved_start_b: f64,
+    ) -> (BlockIndex, BlockIndex) {
+        // Solved positions within this tolerance of one another are treated as equal,
+        // so floating point noise in the solver does not decide the order.
+
+        const SOLVED_POSITION_TOLERANCE: f64 = 0.001;
+
+        let is_a_later = match (onset_a, onset_b) {
+            (Some(onset_a), Some(onset_b)) if onset_a != onset_b => onset_a > onset_b,
+            _ if (solved_start_a - solved_start_b).abs() > SOLVED_POSITION_TOLERANCE => {
+                solved_start_a > solved_start_b
+            }
+            _ if voice_a != voice_b => voice_a > voice_b,
+            _ => index_a > index_b,
+        };
+
+        if is_a_later {
+            (index_a, index_b)
+        } else {
+            (index_b, index_a)
+        }
+    }
+
     #[inline]
     #[allow(clippy::too_many_arguments)]
     fn apply_justification_to_solver(
         justification: SystemJustification,
+        justification_threshold: Option<f32>,
+        maximum_justification_ratio: Option<f32>,
         target_system_width: StaveSpaces,
         engraved_system_width: StaveSpaces,
         total_rhythmic_spacing: StaveSpaces,
@@ -1778,12 +2719,57 @@ impl LayoutSystem {
         aligned_start: &Variable,
         block_start_position_variables: &[Variable],
         block_end_position_variables: &[Variable],
-        blocks: &[BlockEnum],
         spacing_blocks: &[BlockIndex],
-    ) -> Result<(), EngravingError> {
+        spacing_block_widths: &[f32],
+        spacing_block_minimum_widths: &[f32],
+    ) -> Result<Vec<(BlockIndex, Constraint)>, EngravingError> {
         // Find the maximal vertical grid line position in the solver. That
         // will correspond to the computed system width.
 
+        // The given width of each spacing block is its ideal width under the system's
+        // rhythmic spacing model. Every model makes the stretchability of a spacing block
+        // proportional to its ideal width, so the ideal widths double as stretchabilities.
+
+        // Alignment is applied by suggesting a value for the aligned start of the system.
+        // Justification padding is not added to the solver here; we return the padding
+        // constraints, along with the index of the affected block, for the caller to add.
+
+        let mut constraints = Vec::new();
+
+        if engraved_system_width > target_system_width {
+            // The system is overfull. Irrespective of the requested justification, the only
+            // way to make the system fit is to compress its spacing blocks. Each spacing block
+            // can shrink down to its minimum width, and each block's share of the compression
+            // is proportional to how far it can shrink.
+
+            let overflow = engraved_system_width.value - target_system_width.value;
+
+            let spacing_block_shrinkabilities = spacing_block_widths
+                .iter()
+                .zip(spacing_block_minimum_widths.iter())
+                .map(|(width, minimum_width)| (width - minimum_width).max(0.0))
+                .collect::<Vec<_>>();
+
+            let total_shrinkability = spacing_block_shrinkabilities.iter().sum::<f32>();
+
+            if overflow > total_shrinkability {
+                return Err(EngravingError::SystemOverfull(StaveSpaces::new(
+                    overflow - total_shrinkability,
+                )));
+            }
+
+            constraints.append(&mut Self::create_justification_padding_constraints(
+                -overflow,
+                block_start_position_variables,
+                block_end_position_variables,
+                spacing_blocks,
+                spacing_block_widths,
+                spacing_block_shrinkabilities.as_slice(),
+            )?);
+
+            return Ok(constraints);
+        }
+
         match justification {
             SystemJustification::AlignStart => {
                 // This should already be the default, but there's no harm setting
@@ -1820,37 +2806,229 @@ impl LayoutSystem {
             SystemJustification::Justified => {
                 // Pad the width of each spacing block so that the difference
                 // between the target system width and the actual engraved width
-                // is evenly spread out over the system.
-
-                if !spacing_blocks.is_empty() {
-                    let justification_padding_ratio = (total_rhythmic_spacing.value
-                        + target_system_width.value
-                        - engraved_system_width.value)
-                        / total_rhythmic_spacing.value;
-
-                    for &index in spacing_blocks {
-                        if let Some(block) = blocks.get(index) {
-                            solver
-                                .add_constraint(
-                                    *block_end_position_variables
-                                        .get(index)
-                                        .ok_or(EngravingError::UnknownBlockEndPosition(index))?
-                                        | EQ(REQUIRED)
-                                        | (*block_start_position_variables.get(index).ok_or(
-                                            EngravingError::UnknownBlockStartPosition(index),
-                                        )? + block.get_fixed_width().value
-                                            * justification_padding_ratio),
-                                )
-                                .map_err(|err| {
-                                    EngravingError::AddConstraintErrorOnBlock(err, index)
-                                })?;
+                // is spread out over the system according to the stretchability
+                // of each spacing block.
+
+                // If the system has a justification threshold, justify the system only if
+                // its natural width is already close to the target system width. Narrower
+                // systems - typically the last system in a movement - would be stretched
+                // so far that they become difficult to read, so we leave them start aligned.
+
+                let is_full = justification_threshold.map_or(true, |threshold| {
+                    engraved_system_width.value >= target_system_width.value * threshold
+                });
+
+                if is_full {
+                    // If the system has a maximum justification ratio, never add more padding
+                    // than would stretch the total rhythmic spacing by that ratio. Since the
+                    // aligned start of the system remains at 0, any width left over ends up
+                    // at the end of the system.
+
+                    let justification_padding =
+                        target_system_width.value - engraved_system_width.value;
+
+                    let justification_padding = match maximum_justification_ratio {
+                        Some(ratio) => {
+                            justification_padding.min(total_rhythmic_spacing.value * (ratio - 1.0))
                         }
-                    }
+                        None => justification_padding,
+                    };
+
+                    constraints.append(&mut Self::create_justification_padding_constraints(
+                        justification_padding,
+                        block_start_position_variables,
+                        block_end_position_variables,
+                        spacing_blocks,
+                        spacing_block_widths,
+                        spacing_block_widths,
+                    )?);
                 }
             }
         };
 
-        Ok(())
+        Ok(constraints)
+    }
+
+    /// Returns constraints fixing the width of every spacing block in the given list of
+    /// spacing blocks at the given width of the block plus a share of the given justification
+    /// padding. The padding is shared out in proportion to the given stretchability of
+    /// each spacing block.
+    #[inline]
+    fn create_justification_padding_constraints(
+        justification_padding: f32,
+        block_start_position_variables: &[Variable],
+        block_end_position_variables: &[Variable],
+        spacing_blocks: &[BlockIndex],
+        spacing_block_widths: &[f32],
+        spacing_block_stretchabilities: &[f32],
+    ) -> Result<Vec<(BlockIndex, Constraint)>, EngravingError> {
+        let mut constraints = Vec::new();
+
+        let total_stretchability = spacing_block_stretchabilities.iter().sum::<f32>();
+
+        if total_stretchability <= 0.0 {
+            // There is nothing we can stretch.
+
+            return Ok(constraints);
+        }
+
+        for ((&index, width), stretchability) in spacing_blocks
+            .iter()
+            .zip(spacing_block_widths.iter())
+            .zip(spacing_block_stretchabilities.iter())
+        {
+            let constraint = *block_end_position_variables
+                .get(index)
+                .ok_or(EngravingError::UnknownBlockEndPosition(index))?
+                | EQ(REQUIRED)
+                | (*block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                    + *width
+                    + justification_padding * stretchability / total_stretchability);
+
+            constraints.push((index, constraint));
+        }
+
+        Ok(constraints)
+    }
+
+    /// Returns the ideal width of each of the given spacing blocks under this system's
+    /// rhythmic spacing model. The spacing block representing the shortest duration keeps
+    /// its natural width, and the ideal widths of the others are scaled from it by the model.
+    /// If the durations of the spacing blocks cannot be determined, or the model is linear,
+    /// the natural width of each spacing block is used as its ideal width.
+    fn get_spacing_block_ideal_widths(&self, spacing_blocks: &[BlockIndex]) -> Vec<f32> {
+        let natural_widths = spacing_blocks
+            .iter()
+            .map(|index| {
+                self.blocks
+                    .get(*index)
+                    .map(|block| block.get_fixed_width().value)
+                    .unwrap_or(0.0)
+            })
+            .collect::<Vec<_>>();
+
+        if self.rhythmic_spacing_model == RhythmicSpacingModel::Linear {
+            return natural_widths;
+        }
+
+        let durations = match self.get_spacing_block_durations(spacing_blocks) {
+            Some(durations) => durations,
+            None => return natural_widths,
+        };
+
+        match durations
+            .iter()
+            .zip(natural_widths.iter())
+            .min_by_key(|(duration, _)| **duration)
+        {
+            Some((shortest_duration, shortest_width)) => durations
+                .iter()
+                .map(|duration| {
+                    self.rhythmic_spacing_model
+                        .get_ideal_width(
+                            *duration,
+                            *shortest_duration,
+                            StaveSpaces::new(*shortest_width),
+                        )
+                        .value
+                })
+                .collect(),
+            None => natural_widths,
+        }
+    }
+
+    /// Returns the width below which each of the given spacing blocks may not be compressed,
+    /// given the ideal width of each. This is the spacing block's own minimum width,
+    /// if it has one, or else its ideal width multiplied by this system's minimum
+    /// compression ratio.
+    fn get_spacing_block_minimum_widths(
+        &self,
+        spacing_blocks: &[BlockIndex],
+        spacing_block_widths: &[f32],
+    ) -> Vec<f32> {
+        spacing_blocks
+            .iter()
+            .zip(spacing_block_widths.iter())
+            .map(|(index, width)| {
+                self.blocks
+                    .get(*index)
+                    .and_then(|block| block.get_minimum_width())
+                    .map(|minimum_width| minimum_width.value)
+                    .unwrap_or(width * self.minimum_compression_ratio)
+            })
+            .collect()
+    }
+
+    /// Returns the duration represented by each of the given spacing blocks, taken to be
+    /// the distance from its onset to the next onset of any spacing block in the system,
+    /// or to the end of the system for the last onset. Returns None if the onset of any
+    /// spacing block is unknown, or if any duration would not be positive.
+    fn get_spacing_block_durations(&self, spacing_blocks: &[BlockIndex]) -> Option<Vec<Ticks>> {
+        let onsets = spacing_blocks
+            .iter()
+            .map(|index| {
+                self.blocks
+                    .get(*index)
+                    .and_then(|block| block.get_source_onset())
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut distinct_onsets = onsets.clone();
+
+        distinct_onsets.sort();
+        distinct_onsets.dedup();
+
+        onsets
+            .iter()
+            .map(|onset| {
+                let next_onset = distinct_onsets
+                    .iter()
+                    .find(|candidate| *candidate > onset)
+                    .copied()
+                    .unwrap_or(self.end_ticks);
+
+                if next_onset > *onset {
+                    Some(next_onset - *onset)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the adjustment ratio needed to justify a system with the given engraved width
+    /// to the given target width, following the model used by apply_justification_to_solver().
+    /// A system narrower than the target is stretched, and the ratio is the justification
+    /// padding as a proportion of the total stretchability of its spacing blocks. An overfull
+    /// system is compressed, and the ratio is the negative of the compression as a proportion
+    /// of the total shrinkability of its spacing blocks, so it never falls below -1.
+    /// Returns None if the system cannot reach the target width, either because it has
+    /// nothing to stretch or because it would have to shrink past its minimum width.
+    pub fn get_justification_adjustment_ratio(
+        total_stretchability: StaveSpaces,
+        total_shrinkability: StaveSpaces,
+        target_system_width: StaveSpaces,
+        engraved_system_width: StaveSpaces,
+    ) -> Option<f32> {
+        let justification_padding = target_system_width.value - engraved_system_width.value;
+
+        if justification_padding > 0.0 {
+            if total_stretchability.value > 0.0 {
+                Some(justification_padding / total_stretchability.value)
+            } else {
+                None
+            }
+        } else if justification_padding < 0.0 {
+            if -justification_padding <= total_shrinkability.value {
+                Some(justification_padding / total_shrinkability.value)
+            } else {
+                None
+            }
+        } else {
+            Some(0.0)
+        }
     }
 
     #[inline]
@@ -1882,1129 +3060,5649 @@ impl LayoutSystem {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-pub enum EngravingError {
-    UnknownHorizontalGridLine(HorizontalGridLineIndex),
-    UnknownVerticalGridLine(VerticalGridLineIndex),
-    UnknownBlockTopPosition(BlockIndex),
-    UnknownBlockBottomPosition(BlockIndex),
-    UnknownBlockStartPosition(BlockIndex),
-    UnknownBlockEndPosition(BlockIndex),
-    AddConstraintErrorOnHorizontalGridLine(AddConstraintError, HorizontalGridLineIndex),
-    AddConstraintErrorOnVerticalGridLine(AddConstraintError, VerticalGridLineIndex),
-    AddConstraintErrorOnBlock(AddConstraintError, BlockIndex),
-    DefineJustificationError(AddEditVariableError),
-    ApplyJustificationError(SuggestValueError),
+/// The linear constraint solver for a single LayoutSystem, together with the solver
+/// variables for every grid line and Block in the layout. Every constraint added to the
+/// solver is recorded alongside the grid line or Block it originated from, so that
+/// constraints can later be removed and replaced when the layout is edited.
+///
+/// If the LayoutSystem requests constraint conflict diagnostics, or a LayoutTrace is
+/// requested, every constraint in the solver is also logged with its origin. When diagnosing
+/// conflicts, the log is used to report the minimal set of conflicting constraints should
+/// the solver reject a constraint as unsatisfiable.
+struct LayoutSystemSolver {
+    solver: Solver,
+    aligned_start: Variable,
+    horizontal_grid_line_variables: Vec<Variable>,
+    vertical_grid_line_variables: Vec<Variable>,
+    block_top_position_variables: Vec<Variable>,
+    block_bottom_position_variables: Vec<Variable>,
+    block_start_position_variables: Vec<Variable>,
+    block_end_position_variables: Vec<Variable>,
+    beam_start_variables: Vec<Variable>,
+    beam_end_variables: Vec<Variable>,
+    beam_rise_variables: Vec<Variable>,
+    horizontal_grid_line_constraints: Vec<Vec<Constraint>>,
+    vertical_grid_line_constraints: Vec<Vec<Constraint>>,
+    block_constraints: Vec<Vec<Constraint>>,
+    layout_constraints: Vec<(BlockIndex, Constraint)>,
+    beam_layout_constraints: Vec<(BeamIndex, Constraint)>,
+    curve_shapes: Vec<EngravedCurve>,
+    constraint_log: Option<ConstraintLog>,
+    do_diagnose_constraint_conflicts: bool,
 }
 
-impl Display for EngravingError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                EngravingError::UnknownHorizontalGridLine(index) =>
-                    format!("Unknown horizontal grid line variable index: {}", index),
-                EngravingError::UnknownVerticalGridLine(index) =>
-                    format!("Unknown vertical grid line variable index: {}", index),
-                EngravingError::UnknownBlockTopPosition(index) =>
-                    format!("Unknown block top position variable index: {}", index),
-                EngravingError::UnknownBlockBottomPosition(index) =>
-                    format!("Unknown block bottom position variable index: {}", index),
-                EngravingError::UnknownBlockStartPosition(index) =>
-                    format!("Unknown block start position variable index: {}", index),
-                EngravingError::UnknownBlockEndPosition(index) =>
-                    format!("Unknown block end position variable index: {}", index),
-                EngravingError::AddConstraintErrorOnHorizontalGridLine(err, index) => match err {
-                    AddConstraintError::DuplicateConstraint => format!(
-                        "Error processing constraint on horizontal grid line {}: Duplicate constraint",
-                        index
-                    ),
-                    AddConstraintError::UnsatisfiableConstraint =>
-                        format!("Error processing constraint on horizontal grid line {}: Unsatisfiable constraint", index),
-                    AddConstraintError::InternalSolverError(err) =>
-                        format!("Error processing constraint on horizontal grid line {}: Internal solver error: {}",index, err),
-                },
-                EngravingError::AddConstraintErrorOnVerticalGridLine(err, index) => match err {
-                    AddConstraintError::DuplicateConstraint =>
-                        format!("Error processing constraint on vertical grid line {}: Duplicate constraint", index),
-                    AddConstraintError::UnsatisfiableConstraint =>
-                        format!("Error processing constraint on vertical grid line {}: Unsatisfiable constraint", index),
-                    AddConstraintError::InternalSolverError(err) =>
-                        format!("Error processing constraint on vertical grid line {}: Internal solver error: {}", index, err),
-                },
-                EngravingError::AddConstraintErrorOnBlock(err, index) => match err {
-                    AddConstraintError::DuplicateConstraint =>
-                        format!("Error processing constraint on block {}: Duplicate constraint", index),
-                    AddConstraintError::UnsatisfiableConstraint =>
-                        format!("Error processing constraint on block {}: Unsatisfiable constraint", index),
-                    AddConstraintError::InternalSolverError(err) =>
-                        format!("Error processing constraint on block {}: Internal solver error: {}", index, err),
-                },
-                EngravingError::DefineJustificationError(err) => match err {
-                    AddEditVariableError::DuplicateEditVariable =>
-                        "Error defining system justification: Duplicate edit variable".to_string(),
-                    AddEditVariableError::BadRequiredStrength =>
-                        "Error defining system justification: Invalid required strength".to_string(),
-                },
-                EngravingError::ApplyJustificationError(err) => match err {
-                    SuggestValueError::UnknownEditVariable =>
-                        "Error applying system justification: Unknown edit variable".to_string(),
-                    SuggestValueError::InternalSolverError(err) =>
-                        format!("Error applying system justification: Internal solver error: {}", err),
-                }
-            }
-        )
-    }
-}
-
-impl Error for EngravingError {}
-
-#[cfg(test)]
-pub mod tests {
-    use crate::models::display::concepts::border::Border;
-    use crate::models::display::concepts::color::Color;
-    use crate::models::display::concepts::markup::MarkedUpLine;
-    use crate::models::display::concepts::stave_spaces::{
-        AsStaveSpacesExt, StaveSpaces, STAVE_SPACES_ZERO,
-    };
-    use crate::models::display::concepts::stroke::StrokeStyle;
-    use crate::models::display::engraving::engravable::EngravableItem;
-    use crate::models::display::engraving::region::system::EngravedSystem;
-    use crate::models::display::glyphs::bravura::Bravura;
-    use crate::models::display::glyphs::smufl_font::SmuflFont;
-    use crate::models::display::glyphs::Glyph;
-    use crate::models::display::grid::horizontal::{
-        HorizontalGridLine, HorizontalGridLineIndex, HorizontalGridLineType,
-    };
-    use crate::models::display::grid::vertical::{
-        VerticalGridLine, VerticalGridLineIndex, VerticalGridLineType,
-    };
-    use crate::models::display::layout::block::glyph::GlyphBlock;
-    use crate::models::display::layout::block::line::LineBlock;
-    use crate::models::display::layout::block::markup::MarkupBlock;
-    use crate::models::display::layout::block::spacing::SpacingBlock;
-    use crate::models::display::layout::block::{Block, BlockLayer};
-    use crate::models::display::layout::system::{BlockIndex, EngravingError, LayoutSystem};
-    use crate::models::display::stylesheet::stylesheet_option::SystemJustification;
-    use crate::models::music::concepts::ticks::{AsTicksExt, Ticks, TICKS_ZERO};
-    use crate::protos::display::concepts::LineLayout;
-    use crate::protos::music::concepts::NotatedDuration;
+impl LayoutSystemSolver {
+    /// Creates a new LayoutSystemSolver containing variables and constraints for
+    /// all grid lines and Blocks in the given LayoutSystem. Collisions are not resolved
+    /// and justification is not applied until settle() is called. Constraints are logged
+    /// if the given flag is set or if the system requests constraint conflict diagnostics.
+    fn new(system: &LayoutSystem, do_log_constraints: bool) -> Result<Self, EngravingError> {
+        // Every position in the layout is ultimately resolved in relation to the system
+        // origin, so a system whose origin is missing is reported as such before the rest
+        // of its structure is validated.
+
+        system
+            .horizontal_grid_lines
+            .get(system.get_top_edge())
+            .ok_or(EngravingError::UnknownTopEdge(system.get_top_edge()))?;
+
+        system
+            .vertical_grid_lines
+            .get(system.get_leading_edge())
+            .ok_or(EngravingError::UnknownLeadingEdge(
+                system.get_leading_edge(),
+            ))?;
+
+        system.validate().map_err(EngravingError::InvalidLayout)?;
 
-    #[test]
-    fn test_engrave() {
-        // Simulate, by constructing blocks and grid lines by hand, a system containing
-        // two bars of 2/4 in two voices across two staves. Check computed engraved positions.
+        let mut solver = Solver::new();
 
-        let font = Bravura::new();
+        // First, create linear constraint variables for all lines and blocks.
+        // Grid lines get one variable each (horizontal grid lines have a
+        // y position, vertical grid lines an x position), blocks get four variables
+        // each (blocks have two sets of x and y positions, representing the
+        // (start, top) and (end, bottom) corners of the block).
 
-        let column_separation = 0.25.as_stave_spaces();
+        let horizontal_grid_line_variables = system
+            .get_horizontal_grid_lines()
+            .iter()
+            .map(|_| Variable::new())
+            .collect::<Vec<_>>();
 
-        let stave_separation = 3.as_stave_spaces();
+        let vertical_grid_line_variables = system
+            .get_vertical_grid_lines()
+            .iter()
+            .map(|_| Variable::new())
+            .collect::<Vec<_>>();
 
-        let rhythmic_space_separation = 1.5.as_stave_spaces();
+        let block_top_position_variables = system
+            .get_blocks()
+            .iter()
+            .map(|_| Variable::new())
+            .collect::<Vec<_>>();
 
-        let h0_system_top = HorizontalGridLine::new(HorizontalGridLineType::SystemTop);
+        let block_bottom_position_variables = system
+            .get_blocks()
+            .iter()
+            .map(|_| Variable::new())
+            .collect::<Vec<_>>();
 
-        let mut h1_system_bottom = HorizontalGridLine::new(HorizontalGridLineType::SystemBottom);
+        let block_start_position_variables = system
+            .get_blocks()
+            .iter()
+            .map(|_| Variable::new())
+            .collect::<Vec<_>>();
 
-        let v0_system_start = VerticalGridLine::new(0, VerticalGridLineType::SystemStart);
+        let block_end_position_variables = system
+            .get_blocks()
+            .iter()
+            .map(|_| Variable::new())
+            .collect::<Vec<_>>();
 
-        let mut v1_systemic_line = VerticalGridLine::new(0, VerticalGridLineType::SystemicLine);
+        // Beams get three variables each: the position of the beam at its first
+        // and last stems, and the rise of the beam between them.
 
-        v1_systemic_line.lock_to_grid_line(0);
+        let beam_start_variables = system
+            .get_beams()
+            .iter()
+            .map(|_| Variable::new())
+            .collect::<Vec<_>>();
 
-        let mut v2_system_end = VerticalGridLine::new(1, VerticalGridLineType::SystemEnd);
+        let beam_end_variables = system
+            .get_beams()
+            .iter()
+            .map(|_| Variable::new())
+            .collect::<Vec<_>>();
 
-        // Create grid lines and blocks for stavelines on stave 1.
+        let beam_rise_variables = system
+            .get_beams()
+            .iter()
+            .map(|_| Variable::new())
+            .collect::<Vec<_>>();
 
-        let mut h2_s1_l5 = HorizontalGridLine::new(HorizontalGridLineType::Staveline5);
-        let mut h3_s1_l4 = HorizontalGridLine::new(HorizontalGridLineType::Staveline4);
-        let mut h4_s1_l3 = HorizontalGridLine::new(HorizontalGridLineType::Staveline3);
-        let mut h5_s1_l2 = HorizontalGridLine::new(HorizontalGridLineType::Staveline2);
-        let mut h6_s1_l1 = HorizontalGridLine::new(HorizontalGridLineType::Staveline1);
+        // The aligned start of the system is an edit variable since, depending on
+        // the desired system alignment, we may need to adjust its value later
+        // to effect an end or center alignment.
 
-        h2_s1_l5.lock_to_grid_line(0);
-        h3_s1_l4.lock_below_grid_line(2, 1.as_stave_spaces());
-        h4_s1_l3.lock_below_grid_line(3, 1.as_stave_spaces());
-        h5_s1_l2.lock_below_grid_line(4, 1.as_stave_spaces());
-        h6_s1_l1.lock_below_grid_line(5, 1.as_stave_spaces());
+        let aligned_start = Variable::new();
 
-        let b0_s1_l5 = create_staveline_block(2, 1, 2);
-        let b1_s1_l4 = create_staveline_block(3, 1, 2);
-        let b2_s1_l3 = create_staveline_block(4, 1, 2);
-        let b3_s1_l2 = create_staveline_block(5, 1, 2);
-        let b4_s1_l1 = create_staveline_block(6, 1, 2);
+        solver
+            .add_edit_variable(aligned_start, STRONG)
+            .map_err(EngravingError::DefineJustificationError)?;
 
-        // Create lyric underlay grid lines between staves 1 and 2.
+        solver
+            .suggest_value(aligned_start, 0.0)
+            .map_err(EngravingError::ApplyJustificationError)?;
 
-        let mut h12_lyric_top =
-            HorizontalGridLine::new(HorizontalGridLineType::LyricBelowStaveLine1Top);
+        let mut result = LayoutSystemSolver {
+            solver,
+            aligned_start,
+            horizontal_grid_line_constraints: vec![vec![]; horizontal_grid_line_variables.len()],
+            vertical_grid_line_constraints: vec![vec![]; vertical_grid_line_variables.len()],
+            block_constraints: vec![vec![]; block_top_position_variables.len()],
+            layout_constraints: vec![],
+            beam_layout_constraints: vec![],
+            curve_shapes: vec![],
+            horizontal_grid_line_variables,
+            vertical_grid_line_variables,
+            block_top_position_variables,
+            block_bottom_position_variables,
+            block_start_position_variables,
+            block_end_position_variables,
+            beam_start_variables,
+            beam_end_variables,
+            beam_rise_variables,
+            constraint_log: if do_log_constraints
+                || system.get_debug_do_diagnose_constraint_conflicts()
+            {
+                Some(ConstraintLog::new())
+            } else {
+                None
+            },
+            do_diagnose_constraint_conflicts: system.get_debug_do_diagnose_constraint_conflicts(),
+        };
 
-        h12_lyric_top.lock_below_grid_line(6, stave_separation);
+        // Express the position for the system origin, (0,0), in terms of
+        // constraints on the top-most and leading-most grid lines.
+        // All other constraints are ultimately resolved in relation to this
+        // origin position, so we need to ensure it is defined.
 
-        let mut h13_lyric_bottom =
-            HorizontalGridLine::new(HorizontalGridLineType::LyricBelowStaveLine1Bottom);
+        let top_edge = system.get_top_edge();
 
-        h13_lyric_bottom.float_below_grid_line(12, 1.as_stave_spaces());
+        let constraint = *result
+            .horizontal_grid_line_variables
+            .get(top_edge)
+            .ok_or(EngravingError::UnknownTopEdge(top_edge))?
+            | EQ(REQUIRED)
+            | 0.0;
 
-        // Create grid lines and blocks for stavelines on stave 2.
+        result.add_constraint(
+            ConstraintOrigin::SystemTopEdge(top_edge),
+            &constraint,
+            |err| EngravingError::AddConstraintErrorOnHorizontalGridLine(err, top_edge),
+        )?;
 
-        let mut h7_s2_l5 = HorizontalGridLine::new(HorizontalGridLineType::Staveline5);
-        let mut h8_s2_l4 = HorizontalGridLine::new(HorizontalGridLineType::Staveline4);
-        let mut h9_s2_l3 = HorizontalGridLine::new(HorizontalGridLineType::Staveline3);
-        let mut h10_s2_l2 = HorizontalGridLine::new(HorizontalGridLineType::Staveline2);
-        let mut h11_s2_l1 = HorizontalGridLine::new(HorizontalGridLineType::Staveline1);
+        // We constrain the system leading edge to match the aligned start of the system.
 
-        h7_s2_l5.lock_below_grid_line(13, stave_separation);
-        h8_s2_l4.lock_below_grid_line(7, 1.as_stave_spaces());
-        h9_s2_l3.lock_below_grid_line(8, 1.as_stave_spaces());
-        h10_s2_l2.lock_below_grid_line(9, 1.as_stave_spaces());
-        h11_s2_l1.lock_below_grid_line(10, 1.as_stave_spaces());
+        let leading_edge = system.get_leading_edge();
 
-        let b5_s2_l5 = create_staveline_block(7, 1, 2);
-        let b6_s2_l4 = create_staveline_block(8, 1, 2);
-        let b7_s2_l3 = create_staveline_block(9, 1, 2);
-        let b8_s2_l2 = create_staveline_block(10, 1, 2);
-        let b9_s2_l1 = create_staveline_block(11, 1, 2);
+        let constraint = *result
+            .vertical_grid_line_variables
+            .get(leading_edge)
+            .ok_or(EngravingError::UnknownLeadingEdge(leading_edge))?
+            | EQ(REQUIRED)
+            | aligned_start;
 
-        h1_system_bottom.lock_to_grid_line(11);
+        result.add_constraint(
+            ConstraintOrigin::SystemLeadingEdge(leading_edge),
+            &constraint,
+            |err| EngravingError::AddConstraintErrorOnVerticalGridLine(err, leading_edge),
+        )?;
 
-        let b10_systemic_line = create_systemic_line_block(0, 1, 1);
+        // Express constraints on lines and blocks in relation to variables,
+        // and add those constraints to the solver.
 
-        // Place blocks on staves in relation to stavelines and columns.
+        // When expressing constraints on the vertical axis, we need to be careful
+        // about our coordinate system: with the system origin at (0,0),
+        // vertical positions closer to the _top_ of the system have a _smaller_
+        // y value, with 0 being the top-most position on the system.
 
-        // First bar of 2/4. Let's put a clef and time signature on each stave.
+        // The linear solver adjusts variables to fit constraints progressively as
+        // constraints are added to the system, so by the time all constraints are
+        // added, we have our layout solution.
 
-        let mut v3_bar1_clef_start =
-            VerticalGridLine::new(2, VerticalGridLineType::ClefColumnStart);
+        for index in 0..system.get_horizontal_grid_lines().len() {
+            result
+                .add_horizontal_grid_line_constraints(system.get_horizontal_grid_lines(), index)?;
+        }
 
-        v3_bar1_clef_start.float_after_grid_line(1, column_separation);
+        for index in 0..system.get_vertical_grid_lines().len() {
+            result.add_vertical_grid_line_constraints(system.get_vertical_grid_lines(), index)?;
+        }
 
-        let mut v4_bar1_clef_end = VerticalGridLine::new(2, VerticalGridLineType::ClefColumnEnd);
+        for index in 0..system.get_blocks().len() {
+            result.add_block_constraints(system.get_blocks(), index)?;
+        }
 
-        v4_bar1_clef_end.float_after_grid_line(3, STAVE_SPACES_ZERO);
+        for index in 0..system.get_beams().len() {
+            result.add_beam_constraints(system.get_beams(), index)?;
+        }
 
-        let b11_bar1_stave1_clef =
-            create_glyph_block_on_staveline(5, 3, 4, TICKS_ZERO, &font, Glyph::GClef);
+        for index in 0..system.get_spanners().len() {
+            result.add_spanner_constraints(system, index)?;
+        }
 
-        let b12_bar1_stave2_clef =
-            create_glyph_block_on_staveline(8, 3, 4, TICKS_ZERO, &font, Glyph::FClef);
+        Ok(result)
+    }
 
-        let mut v5_bar1_time_sig_start =
-            VerticalGridLine::new(3, VerticalGridLineType::TimeSignatureColumnStart);
+    /// Adds all constraints on the HorizontalGridLine at the given index to the solver.
+    fn add_horizontal_grid_line_constraints(
+        &mut self,
+        horizontal_grid_lines: &[HorizontalGridLine],
+        index: HorizontalGridLineIndex,
+    ) -> Result<(), EngravingError> {
+        let grid_line = horizontal_grid_lines
+            .get(index)
+            .ok_or(EngravingError::UnknownHorizontalGridLine(index))?;
+
+        for constraint in grid_line.get_constraints() {
+            let solver_constraint = LayoutSystem::create_horizontal_grid_line_constraint(
+                index,
+                constraint,
+                self.horizontal_grid_line_variables.as_slice(),
+            )?;
+
+            self.add_constraint(
+                ConstraintOrigin::HorizontalGridLine(index, constraint.clone()),
+                &solver_constraint,
+                |err| EngravingError::AddConstraintErrorOnHorizontalGridLine(err, index),
+            )?;
+
+            self.horizontal_grid_line_constraints
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
+                .push(solver_constraint);
+        }
 
-        v5_bar1_time_sig_start.float_after_grid_line(4, column_separation);
+        Ok(())
+    }
 
-        let mut v6_bar1_time_sig_end =
-            VerticalGridLine::new(3, VerticalGridLineType::TimeSignatureColumnEnd);
+    /// Removes all constraints on the HorizontalGridLine at the given index from the solver.
+    fn remove_horizontal_grid_line_constraints(
+        &mut self,
+        index: HorizontalGridLineIndex,
+    ) -> Result<(), EngravingError> {
+        let constraints = std::mem::take(
+            self.horizontal_grid_line_constraints
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownHorizontalGridLine(index))?,
+        );
 
-        v6_bar1_time_sig_end.float_after_grid_line(5, STAVE_SPACES_ZERO);
+        for constraint in constraints {
+            self.remove_constraint(&constraint, |err| {
+                EngravingError::RemoveConstraintErrorOnHorizontalGridLine(err, index)
+            })?;
+        }
 
-        let b13_bar1_stave1_time_sig_numerator =
-            create_glyph_block_on_staveline(3, 5, 6, TICKS_ZERO, &font, Glyph::TimeSig2Numerator);
+        Ok(())
+    }
 
-        let b14_bar1_stave1_time_sig_denominator =
-            create_glyph_block_on_staveline(5, 5, 6, TICKS_ZERO, &font, Glyph::TimeSig4Denominator);
+    /// Adds all constraints on the VerticalGridLine at the given index to the solver.
+    fn add_vertical_grid_line_constraints(
+        &mut self,
+        vertical_grid_lines: &[VerticalGridLine],
+        index: VerticalGridLineIndex,
+    ) -> Result<(), EngravingError> {
+        let grid_line = vertical_grid_lines
+            .get(index)
+            .ok_or(EngravingError::UnknownVerticalGridLine(index))?;
+
+        for constraint in grid_line.get_constraints() {
+            let solver_constraint = LayoutSystem::create_vertical_grid_line_constraint(
+                index,
+                constraint,
+                self.vertical_grid_line_variables.as_slice(),
+            )?;
+
+            self.add_constraint(
+                ConstraintOrigin::VerticalGridLine(index, constraint.clone()),
+                &solver_constraint,
+                |err| EngravingError::AddConstraintErrorOnVerticalGridLine(err, index),
+            )?;
+
+            self.vertical_grid_line_constraints
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownVerticalGridLine(index))?
+                .push(solver_constraint);
+        }
 
-        let b15_bar1_stave2_time_sig_numerator =
-            create_glyph_block_on_staveline(8, 5, 6, TICKS_ZERO, &font, Glyph::TimeSig2Numerator);
+        Ok(())
+    }
 
-        let b16_bar1_stave2_time_sig_denominator = create_glyph_block_on_staveline(
-            10,
-            5,
-            6,
-            TICKS_ZERO,
-            &font,
-            Glyph::TimeSig4Denominator,
+    /// Removes all constraints on the VerticalGridLine at the given index from the solver.
+    fn remove_vertical_grid_line_constraints(
+        &mut self,
+        index: VerticalGridLineIndex,
+    ) -> Result<(), EngravingError> {
+        let constraints = std::mem::take(
+            self.vertical_grid_line_constraints
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownVerticalGridLine(index))?,
         );
 
-        // In this test, we can only create noteheads on stavelines (not above or below
-        // stavelines), and we do not include stems, so our test musical data is
-        // rather artificial. The musical content will be:
+        for constraint in constraints {
+            self.remove_constraint(&constraint, |err| {
+                EngravingError::RemoveConstraintErrorOnVerticalGridLine(err, index)
+            })?;
+        }
 
-        // voice 1 = { G2 T:2/4 g4 bes | g ees | }
-        // voice 2 = { G2 T:2/4 f2 | d }
+        Ok(())
+    }
 
-        // Add noteheads in bar 1, voice 1.
+    /// Adds all constraints on the Block at the given index to the solver, including
+    /// constraints that maintain the width and height of fixed-size blocks and
+    /// the constraints fitting a BeamBlock around its beam.
+    fn add_block_constraints(
+        &mut self,
+        blocks: &[BlockEnum],
+        index: BlockIndex,
+    ) -> Result<(), EngravingError> {
+        let block = blocks
+            .get(index)
+            .ok_or(EngravingError::UnknownBlock(index))?;
+
+        let mut constraints = Vec::new();
+
+        // If this block is fixed width, then ensure its width is taken into account
+        // when determining its end position.
+
+        if block.is_fixed_width() {
+            constraints.push((
+                ConstraintOrigin::BlockFixedWidth(index),
+                *self
+                    .block_end_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockEndPosition(index))?
+                    | EQ(STRONG)
+                    | (*self
+                        .block_start_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                        + block.get_start_padding().value
+                        + block.get_fixed_width().value
+                        + block.get_end_padding().value),
+            ));
+        }
 
-        let mut v7_bar1_note1_start =
-            VerticalGridLine::new(4, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+        // If this block is fixed height, then ensure its height is taken into account
+        // when determining its bottom position.
+
+        if block.is_fixed_height() {
+            constraints.push((
+                ConstraintOrigin::BlockFixedHeight(index),
+                *self
+                    .block_bottom_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockBottomPosition(index))?
+                    | EQ(STRONG)
+                    | (*self
+                        .block_top_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockTopPosition(index))?
+                        + block.get_top_padding().value
+                        + block.get_fixed_height().value
+                        + block.get_bottom_padding().value),
+            ));
+        }
 
-        v7_bar1_note1_start.float_after_grid_line(6, column_separation);
+        if let BlockEnum::Beam(beam_block) = block {
+            for constraint in self.get_beam_block_constraints(index, beam_block)? {
+                constraints.push((ConstraintOrigin::BeamBlock(index), constraint));
+            }
+        }
 
-        let mut v8_bar1_note1_end =
-            VerticalGridLine::new(4, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+        for (origin, constraint) in constraints {
+            self.add_constraint(origin, &constraint, |err| {
+                EngravingError::AddConstraintErrorOnBlock(err, index)
+            })?;
 
-        v8_bar1_note1_end.float_after_grid_line(7, STAVE_SPACES_ZERO);
+            self.block_constraints
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownBlock(index))?
+                .push(constraint);
+        }
 
-        let mut b17_bar1_voice1_notehead1 =
-            create_glyph_block_on_staveline(5, 7, 8, TICKS_ZERO, &font, Glyph::NoteheadBlack);
+        // Add all user-specified constraints to the solver.
 
-        b17_bar1_voice1_notehead1.set_end_padding(rhythmic_space_separation); // Simulate rhythmic padding.
+        for constraint in block.get_constraints() {
+            let solver_constraint = LayoutSystem::create_block_constraint(
+                index,
+                block,
+                constraint,
+                self.horizontal_grid_line_variables.as_slice(),
+                self.vertical_grid_line_variables.as_slice(),
+                self.block_top_position_variables.as_slice(),
+                self.block_bottom_position_variables.as_slice(),
+                self.block_start_position_variables.as_slice(),
+                self.block_end_position_variables.as_slice(),
+            )?;
+
+            self.add_constraint(
+                ConstraintOrigin::Block(index, constraint.clone()),
+                &solver_constraint,
+                |err| EngravingError::AddConstraintErrorOnBlock(err, index),
+            )?;
+
+            self.block_constraints
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownBlock(index))?
+                .push(solver_constraint);
+        }
 
-        let mut v9_bar1_note2_start =
-            VerticalGridLine::new(5, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+        Ok(())
+    }
 
-        v9_bar1_note2_start.float_after_grid_line(8, column_separation);
+    /// Returns the constraints fitting the given BeamBlock, at the given index, around its beam.
+    /// The BeamBlock runs from the start of the beam's first stem to the end of its last stem,
+    /// and its top and bottom edges are held beyond the extent of the beam at both ends;
+    /// weakly, its top and bottom edges are drawn tight against the beam. Pushing the edge
+    /// of the BeamBlock nearest the noteheads away from them therefore moves the whole beam.
+    fn get_beam_block_constraints(
+        &self,
+        index: BlockIndex,
+        block: &BeamBlock,
+    ) -> Result<Vec<Constraint>, EngravingError> {
+        let beam_index = block.get_beam_index();
+
+        let beam = block.get_beam();
+
+        let start = *self
+            .beam_start_variables
+            .get(beam_index)
+            .ok_or(EngravingError::UnknownBeam(beam_index))?;
+
+        let end = *self
+            .beam_end_variables
+            .get(beam_index)
+            .ok_or(EngravingError::UnknownBeam(beam_index))?;
+
+        let first = *beam
+            .get_stems()
+            .first()
+            .ok_or(EngravingError::UnknownBeam(beam_index))?;
+
+        let last = *beam
+            .get_stems()
+            .last()
+            .ok_or(EngravingError::UnknownBeam(beam_index))?;
+
+        let top = *self
+            .block_top_position_variables
+            .get(index)
+            .ok_or(EngravingError::UnknownBlockTopPosition(index))?;
+
+        let bottom = *self
+            .block_bottom_position_variables
+            .get(index)
+            .ok_or(EngravingError::UnknownBlockBottomPosition(index))?;
+
+        let mut constraints = vec![
+            *self
+                .block_start_position_variables
+                .get(index)
+                .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                | EQ(REQUIRED)
+                | *self
+                    .block_start_position_variables
+                    .get(first)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(first))?,
+            *self
+                .block_end_position_variables
+                .get(index)
+                .ok_or(EngravingError::UnknownBlockEndPosition(index))?
+                | EQ(REQUIRED)
+                | *self
+                    .block_end_position_variables
+                    .get(last)
+                    .ok_or(EngravingError::UnknownBlockEndPosition(last))?,
+        ];
 
-        let mut v10_bar1_note2_end =
-            VerticalGridLine::new(5, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+        // The extent of the beam is a fixed offset from the position of its outer edge.
 
-        v10_bar1_note2_end.float_after_grid_line(9, STAVE_SPACES_ZERO);
+        let (top_offset, bottom_offset) = beam.get_vertical_extent(0.0);
 
-        let mut b18_bar1_voice1_notehead2 = create_glyph_block_on_staveline(
-            4,
-            9,
-            10,
-            NotatedDuration::Crotchet.as_ticks(),
-            &font,
-            Glyph::NoteheadBlack,
+        for position in [start, end] {
+            constraints.push(top | LE(REQUIRED) | position + top_offset);
+            constraints.push(bottom | GE(REQUIRED) | position + bottom_offset);
+            constraints.push(top | EQ(BEAM_BLOCK_EXTENT_STRENGTH) | position + top_offset);
+            constraints.push(bottom | EQ(BEAM_BLOCK_EXTENT_STRENGTH) | position + bottom_offset);
+        }
+
+        Ok(constraints)
+    }
+
+    /// Removes all constraints on the Block at the given index from the solver.
+    fn remove_block_constraints(&mut self, index: BlockIndex) -> Result<(), EngravingError> {
+        let constraints = std::mem::take(
+            self.block_constraints
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownBlock(index))?,
         );
 
-        b18_bar1_voice1_notehead2.set_end_padding(rhythmic_space_separation); // Simulate rhythmic padding.
+        for constraint in constraints {
+            self.remove_constraint(&constraint, |err| {
+                EngravingError::RemoveConstraintErrorOnBlock(err, index)
+            })?;
+        }
 
-        // Add notehead in bar 1, voice 2.
+        Ok(())
+    }
 
-        let b19_bar1_voice2_notehead =
-            create_glyph_block_on_staveline(8, 7, 8, TICKS_ZERO, &font, Glyph::NoteheadHalf);
+    /// Adds the constraints defining the beam at the given index to the solver: the ends
+    /// of the beam meet the tips of its first and last stems, the rise of the beam is bounded
+    /// by its maximum slope, and every stem linked by the beam is at least the minimum
+    /// stem length long. Weakly, the beam prefers stems of exactly the minimum length
+    /// at either end, and a rise that follows the contour of the noteheads.
+    ///
+    /// The tips of intermediate stems meet the beam at a fraction of its rise that depends
+    /// on the solved horizontal positions of the stems, so those constraints are only added
+    /// once the horizontal layout is final, by settle().
+    fn add_beam_constraints(
+        &mut self,
+        beams: &[LayoutBeam],
+        index: BeamIndex,
+    ) -> Result<(), EngravingError> {
+        let beam = beams.get(index).ok_or(EngravingError::UnknownBeam(index))?;
 
-        // Add barline at end of bar 1.
+        let start = *self
+            .beam_start_variables
+            .get(index)
+            .ok_or(EngravingError::UnknownBeam(index))?;
 
-        let mut v11_bar1_barline_start =
-            VerticalGridLine::new(6, VerticalGridLineType::BarlineStart);
+        let end = *self
+            .beam_end_variables
+            .get(index)
+            .ok_or(EngravingError::UnknownBeam(index))?;
 
-        v11_bar1_barline_start.float_after_grid_line(10, column_separation);
+        let rise = *self
+            .beam_rise_variables
+            .get(index)
+            .ok_or(EngravingError::UnknownBeam(index))?;
 
-        let mut v12_bar1_barline_end = VerticalGridLine::new(6, VerticalGridLineType::BarlineEnd);
+        let first = *beam
+            .get_stems()
+            .first()
+            .ok_or(EngravingError::UnknownBeam(index))?;
 
-        v12_bar1_barline_end.float_after_grid_line(11, STAVE_SPACES_ZERO);
+        let last = *beam
+            .get_stems()
+            .last()
+            .ok_or(EngravingError::UnknownBeam(index))?;
 
-        let b20_bar1_barline =
-            create_barline_block(0, 1, 11, 12, NotatedDuration::Minim.as_ticks());
+        let (first_tip, first_root) = self.get_stem_variables(beam, first)?;
 
-        // Add noteheads in bar 2, voice 1.
+        let (last_tip, last_root) = self.get_stem_variables(beam, last)?;
 
-        let mut v13_bar2_note1_start =
-            VerticalGridLine::new(7, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+        let first_start = *self
+            .block_start_position_variables
+            .get(first)
+            .ok_or(EngravingError::UnknownBlockStartPosition(first))?;
 
-        v13_bar2_note1_start.float_after_grid_line(12, column_separation);
+        let last_start = *self
+            .block_start_position_variables
+            .get(last)
+            .ok_or(EngravingError::UnknownBlockStartPosition(last))?;
 
-        let mut v14_bar2_note1_end =
-            VerticalGridLine::new(7, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+        let maximum_slope = beam.get_maximum_slope() as f64;
 
-        v14_bar2_note1_end.float_after_grid_line(13, STAVE_SPACES_ZERO);
+        let minimum_stem_length = beam.get_minimum_stem_length().value as f64;
 
-        let mut b21_bar2_voice1_notehead1 = create_glyph_block_on_staveline(
-            5,
-            13,
-            14,
-            NotatedDuration::Minim.as_ticks(),
-            &font,
-            Glyph::NoteheadBlack,
-        );
+        // Positions closer to the top of the system have a smaller y value, so the tip
+        // of an upwards stem sits above its root at a smaller y value, and the tip of
+        // a downwards stem sits below its root at a larger y value.
 
-        b21_bar2_voice1_notehead1.set_end_padding(rhythmic_space_separation); // Simulate rhythmic padding.
+        let lengthening = match beam.get_direction() {
+            BeamDirection::Above => -1.0,
+            BeamDirection::Below => 1.0,
+        };
 
-        let mut v15_bar2_note2_start =
-            VerticalGridLine::new(8, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+        let mut constraints = vec![
+            first_tip | EQ(STRONG) | start,
+            last_tip | EQ(STRONG) | end,
+            rise | EQ(REQUIRED) | end - start,
+            rise | LE(STRONG) | (last_start - first_start) * maximum_slope,
+            rise | GE(STRONG) | (first_start - last_start) * maximum_slope,
+        ];
 
-        v15_bar2_note2_start.float_after_grid_line(14, column_separation);
+        for stem in beam.get_stems() {
+            let (tip, root) = self.get_stem_variables(beam, *stem)?;
 
-        let mut v16_bar2_note2_end =
-            VerticalGridLine::new(8, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+            constraints.push(match beam.get_direction() {
+                BeamDirection::Above => tip | LE(STRONG) | root - minimum_stem_length,
+                BeamDirection::Below => tip | GE(STRONG) | root + minimum_stem_length,
+            });
+        }
 
-        v16_bar2_note2_end.float_after_grid_line(15, STAVE_SPACES_ZERO);
+        constraints.push(first_tip | EQ(WEAK) | first_root + minimum_stem_length * lengthening);
+        constraints.push(last_tip | EQ(WEAK) | last_root + minimum_stem_length * lengthening);
+        constraints.push(rise | EQ(WEAK) | last_root - first_root);
 
-        let mut b22_bar2_voice1_notehead2 = create_glyph_block_on_staveline(
-            6,
-            15,
-            16,
-            NotatedDuration::Minim * 1.5,
-            &font,
-            Glyph::NoteheadBlack,
-        );
+        for constraint in constraints {
+            self.add_constraint(ConstraintOrigin::Beam(index), &constraint, |err| {
+                EngravingError::AddConstraintErrorOnBeam(err, index)
+            })?;
+        }
 
-        b22_bar2_voice1_notehead2.set_end_padding(rhythmic_space_separation); // Simulate rhythmic padding.
+        Ok(())
+    }
 
-        // Add notehead in bar 2, voice 2.
+    /// Adds the constraint keeping the spanner at the given index at least its minimum length
+    /// to the solver. The spanner's ends are the grid lines it is locked to, so the constraint
+    /// pushes those grid lines apart where the rest of the layout allows.
+    fn add_spanner_constraints(
+        &mut self,
+        system: &LayoutSystem,
+        index: SpannerIndex,
+    ) -> Result<(), EngravingError> {
+        let spanner = system
+            .get_spanners()
+            .get(index)
+            .ok_or(EngravingError::UnknownSpanner(index))?;
+
+        let (start, end) = match spanner.get_grid_lines(
+            system.get_vertical_grid_lines(),
+            system.get_leading_edge(),
+            system.start_ticks,
+            system.end_ticks,
+        ) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Err(EngravingError::UnknownSpanner(index)),
+        };
 
-        let b23_bar1_voice2_notehead2 = create_glyph_block_on_staveline(
-            9,
-            13,
-            14,
-            NotatedDuration::Minim.as_ticks(),
-            &font,
-            Glyph::NoteheadHalf,
-        );
+        let start = *self
+            .vertical_grid_line_variables
+            .get(start)
+            .ok_or(EngravingError::UnknownVerticalGridLine(start))?;
 
-        // Add barline at end of bar 2.
+        let end = *self
+            .vertical_grid_line_variables
+            .get(end)
+            .ok_or(EngravingError::UnknownVerticalGridLine(end))?;
 
-        let mut v17_bar2_barline_start =
-            VerticalGridLine::new(9, VerticalGridLineType::BarlineStart);
+        let constraint = end | GE(STRONG) | start + spanner.get_minimum_length().value;
 
-        v17_bar2_barline_start.float_after_grid_line(16, column_separation);
+        self.add_constraint(ConstraintOrigin::Spanner(index), &constraint, |err| {
+            EngravingError::AddConstraintErrorOnSpanner(err, index)
+        })
+    }
 
-        let mut v18_bar2_barline_end = VerticalGridLine::new(9, VerticalGridLineType::BarlineEnd);
+    /// Returns the (tip, root) variables of the stem Block at the given index, linked by
+    /// the given beam. The tip of the stem meets the beam; the root of the stem is attached
+    /// to its notehead by the Block's own constraints.
+    fn get_stem_variables(
+        &self,
+        beam: &LayoutBeam,
+        stem: BlockIndex,
+    ) -> Result<(Variable, Variable), EngravingError> {
+        let top = *self
+            .block_top_position_variables
+            .get(stem)
+            .ok_or(EngravingError::UnknownBlockTopPosition(stem))?;
+
+        let bottom = *self
+            .block_bottom_position_variables
+            .get(stem)
+            .ok_or(EngravingError::UnknownBlockBottomPosition(stem))?;
+
+        Ok(match beam.get_direction() {
+            BeamDirection::Above => (top, bottom),
+            BeamDirection::Below => (bottom, top),
+        })
+    }
 
-        v18_bar2_barline_end.float_after_grid_line(17, STAVE_SPACES_ZERO);
+    /// Settles beams and resolves collisions between Blocks, applies system justification,
+    /// settles beams again, shapes curves, and fits spanners, bringing the solver to its final
+    /// layout solution. Any constraints added by a previous call to settle() are removed
+    /// first, so this can safely be called again after the layout has been edited.
+    fn settle(&mut self, system: &LayoutSystem) -> Result<(), EngravingError> {
+        for (index, constraint) in std::mem::take(&mut self.layout_constraints) {
+            self.remove_constraint(&constraint, |err| {
+                EngravingError::RemoveConstraintErrorOnBlock(err, index)
+            })?;
+        }
 
-        let b24_bar2_barline =
-            create_barline_block(0, 1, 17, 18, NotatedDuration::Minim.as_ticks());
+        for (index, constraint) in std::mem::take(&mut self.beam_layout_constraints) {
+            self.remove_constraint(&constraint, |err| {
+                EngravingError::RemoveConstraintErrorOnBeam(err, index)
+            })?;
+        }
 
-        // Create lyrics underneath voice 1 noteheads in bar 1. To do this,
-        // we create a vertical grid line locked at the center of the target notehead,
-        // then center a markup block containing the lyric on that grid line.
-        // We float the lyric inside the grid lines that denote the start and end
-        // of each notehead's containing column.
+        self.solver
+            .suggest_value(self.aligned_start, 0.0)
+            .map_err(EngravingError::ApplyJustificationError)?;
 
-        let v19_bar1_voice1_notehead1_center =
-            VerticalGridLine::new(4, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+        // Keep track of the indices of any spacing blocks on the grid, and the ideal width
+        // of each under the system's rhythmic spacing model. Wherever the ideal width
+        // differs from the natural width of a spacing block, the ideal width takes over,
+        // so the rhythmic spacing model sets the natural spacing of the system whether
+        // or not the system is later justified.
 
-        b17_bar1_voice1_notehead1.lock_horizontal_center_to_grid_line(19);
+        let spacing_blocks = system
+            .get_blocks()
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| block.is_spacing_block())
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
 
-        let b25_bar1_lyric1 = create_lyric_underlay_block(12, 13, 7, 19, 8, "A");
+        let spacing_block_widths = system.get_spacing_block_ideal_widths(spacing_blocks.as_slice());
+
+        for (&index, width) in spacing_blocks.iter().zip(spacing_block_widths.iter()) {
+            let block = system
+                .get_blocks()
+                .get(index)
+                .ok_or(EngravingError::UnknownBlock(index))?;
+
+            if (block.get_fixed_width().value - width).abs() > f32::EPSILON {
+                let constraint = *self
+                    .block_end_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockEndPosition(index))?
+                    | EQ(RHYTHMIC_SPACING_STRENGTH)
+                    | (*self
+                        .block_start_position_variables
+                        .get(index)
+                        .ok_or(EngravingError::UnknownBlockStartPosition(index))?
+                        + *width);
 
-        let v20_bar1_voice1_notehead2_center =
-            VerticalGridLine::new(5, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+                self.add_layout_constraint(ConstraintOrigin::RhythmicSpacing, index, constraint)?;
+            }
+        }
 
-        b18_bar1_voice1_notehead2.lock_horizontal_center_to_grid_line(20);
+        // Detect and resolve collisions between blocks, including the beams and the stems
+        // lengthened to meet them. If the layout has not settled after the maximum number
+        // of passes, we give up.
 
-        let b26_bar1_lyric2 = create_lyric_underlay_block(12, 13, 9, 20, 10, "ve");
+        let mut passes = 0;
 
-        // Similarly, create lyrics underneath voice 1 noteheads in bar2.
-        // Let's make the lyric underneath the first notehead a silly length, to test
-        // that the grid lines either side of the notehead push apart to accommodate it.
+        self.resolve_collisions(system, &mut passes)?;
 
-        let v21_bar2_voice1_notehead1_center =
-            VerticalGridLine::new(7, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+        // The total amount of rhythmic space on the grid is used during system justification.
 
-        b21_bar2_voice1_notehead1.lock_horizontal_center_to_grid_line(21);
+        let total_rhythmic_spacing = StaveSpaces::new(spacing_block_widths.iter().sum());
 
-        let b27_bar2_lyric1 =
-            create_lyric_underlay_block(12, 13, 13, 21, 14, "A lyric of very silly length");
+        // Determine the pre-justification engraved width of the system by scanning
+        // the solved block positions for maximal extents. Blocks fitted around curves
+        // and spanners have not been placed yet, so they are left out.
 
-        let v22_bar2_voice1_notehead2_center =
-            VerticalGridLine::new(8, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+        let engraved_system_width = system
+            .get_blocks()
+            .iter()
+            .zip(self.get_values(&self.block_end_position_variables))
+            .filter(|(block, _)| !LayoutSystem::is_fitted_after_settling(block))
+            .map(|(_, end)| end)
+            .max()
+            .unwrap_or(STAVE_SPACES_ZERO);
 
-        b22_bar2_voice1_notehead2.lock_horizontal_center_to_grid_line(22);
+        let constraints = LayoutSystem::apply_justification_to_solver(
+            system.get_justification(),
+            system.get_justification_threshold(),
+            system.get_maximum_justification_ratio(),
+            system.get_target_system_width(),
+            engraved_system_width,
+            total_rhythmic_spacing,
+            &mut self.solver,
+            &self.aligned_start,
+            self.block_start_position_variables.as_slice(),
+            self.block_end_position_variables.as_slice(),
+            spacing_blocks.as_slice(),
+            spacing_block_widths.as_slice(),
+            system
+                .get_spacing_block_minimum_widths(
+                    spacing_blocks.as_slice(),
+                    spacing_block_widths.as_slice(),
+                )
+                .as_slice(),
+        )?;
 
-        let b28_bar2_lyric2 = create_lyric_underlay_block(12, 13, 15, 22, 16, "Short");
+        for (index, constraint) in constraints {
+            self.add_layout_constraint(ConstraintOrigin::Justification, index, constraint)?;
+        }
 
-        // Connect the end of the system to the trailing edge of the second barline.
-        // Since all stavelines are connected to the end of the system, this will
-        // set the width of all stavelines.
+        // Justification moves the stems of each beamed group, so the beams must be settled
+        // again. Any collisions this creates are resolved within the same limit on passes.
 
-        v2_system_end.float_after_grid_line(18, STAVE_SPACES_ZERO);
+        self.resolve_collisions(system, &mut passes)?;
 
-        // Add all grid lines and blocks to layout.
+        // Finally, with every Block in its final position, shape each curve around
+        // the Blocks lying between its anchors, and fit each SpannerBlock around its spanner.
 
-        let layout = LayoutSystem::new(
-            0,
-            0.as_ticks(),
-            0.as_ticks(),
-            SystemJustification::AlignStart,
-            100.as_stave_spaces(),
-            vec![
-                h0_system_top,
-                h1_system_bottom,
-                h2_s1_l5,
-                h3_s1_l4,
-                h4_s1_l3,
-                h5_s1_l2,
-                h6_s1_l1,
-                h7_s2_l5,
-                h8_s2_l4,
-                h9_s2_l3,
-                h10_s2_l2,
-                h11_s2_l1,
-                h12_lyric_top,
-                h13_lyric_bottom,
-            ],
-            vec![
-                v0_system_start,
-                v1_systemic_line,
-                v2_system_end,
-                v3_bar1_clef_start,
-                v4_bar1_clef_end,
-                v5_bar1_time_sig_start,
-                v6_bar1_time_sig_end,
-                v7_bar1_note1_start,
-                v8_bar1_note1_end,
-                v9_bar1_note2_start,
-                v10_bar1_note2_end,
-                v11_bar1_barline_start,
-                v12_bar1_barline_end,
-                v13_bar2_note1_start,
-                v14_bar2_note1_end,
-                v15_bar2_note2_start,
-                v16_bar2_note2_end,
-                v17_bar2_barline_start,
-                v18_bar2_barline_end,
-                v19_bar1_voice1_notehead1_center,
-                v20_bar1_voice1_notehead2_center,
-                v21_bar2_voice1_notehead1_center,
-                v22_bar2_voice1_notehead2_center,
-            ],
-            0,
-            0,
-            vec![
-                b0_s1_l5.into(),
-                b1_s1_l4.into(),
-                b2_s1_l3.into(),
-                b3_s1_l2.into(),
-                b4_s1_l1.into(),
-                b5_s2_l5.into(),
-                b6_s2_l4.into(),
-                b7_s2_l3.into(),
-                b8_s2_l2.into(),
-                b9_s2_l1.into(),
-                b10_systemic_line.into(),
-                b11_bar1_stave1_clef.into(),
-                b12_bar1_stave2_clef.into(),
-                b13_bar1_stave1_time_sig_numerator.into(),
-                b14_bar1_stave1_time_sig_denominator.into(),
-                b15_bar1_stave2_time_sig_numerator.into(),
-                b16_bar1_stave2_time_sig_denominator.into(),
-                b17_bar1_voice1_notehead1.into(),
-                b18_bar1_voice1_notehead2.into(),
-                b19_bar1_voice2_notehead.into(),
-                b20_bar1_barline.into(),
-                b21_bar2_voice1_notehead1.into(),
-                b22_bar2_voice1_notehead2.into(),
-                b23_bar1_voice2_notehead2.into(),
-                b24_bar2_barline.into(),
-                b25_bar1_lyric1.into(),
-                b26_bar1_lyric2.into(),
-                b27_bar2_lyric1.into(),
-                b28_bar2_lyric2.into(),
-            ],
-            false,
-            false,
-            false,
-            false,
-        );
+        self.settle_curves(system)?;
 
-        // Check computed engraved positions.
+        self.settle_spanners(system)
+    }
 
-        let solution = layout.engrave();
+    /// Settles every beam, then detects and resolves collisions between Blocks. Moving a Block
+    /// to resolve one collision can easily create another, and moving a beam lengthens
+    /// its stems, so beams are settled again and detection and resolution repeated until
+    /// the layout settles with no collisions remaining. Each pass of collision resolution
+    /// is added to the given count of passes; if collisions remain once the count reaches
+    /// the system's maximum number of passes, an EngravingError::UnresolvedCollisions
+    /// error is returned.
+    fn resolve_collisions(
+        &mut self,
+        system: &LayoutSystem,
+        passes: &mut usize,
+    ) -> Result<(), EngravingError> {
+        loop {
+            for index in 0..system.get_beams().len() {
+                self.settle_beam(system, index)?;
+            }
 
-        assert!(solution.is_ok());
+            let collisions = LayoutSystem::detect_colliding_blocks(
+                system.get_blocks(),
+                system.get_horizontal_grid_lines().len(),
+                system.get_vertical_grid_lines().len(),
+                &self.solver,
+                self.block_top_position_variables.as_slice(),
+                self.block_bottom_position_variables.as_slice(),
+                self.block_start_position_variables.as_slice(),
+                self.block_end_position_variables.as_slice(),
+            )?;
+
+            if collisions.is_empty() {
+                return Ok(());
+            }
 
-        // Let's check the staveline positions first.
+            if *passes == system.get_maximum_collision_resolution_passes() {
+                return Err(EngravingError::UnresolvedCollisions(collisions));
+            }
 
-        assert_eq!(unwrap_h_line(&solution, 0), STAVE_SPACES_ZERO); // h0_system_top
-        assert_eq!(unwrap_h_line(&solution, 1), unwrap_h_line(&solution, 11)); // h1_system_bottom should be aligned to last staveline, h11_s2_l1
-        assert_eq!(unwrap_h_line(&solution, 2), unwrap_h_line(&solution, 0)); // h2_s1_l5 should be at the system top
-        assert_eq!(
-            unwrap_h_line(&solution, 3),
-            unwrap_h_line(&solution, 2) + 1.as_stave_spaces()
-        ); // h3_s1_l4 == h2_s1_l5 + 1
-        assert_eq!(
-            unwrap_h_line(&solution, 4),
-            unwrap_h_line(&solution, 3) + 1.as_stave_spaces()
-        ); // h4_s1_l3 == h3_s1_l4 + 1
-        assert_eq!(
-            unwrap_h_line(&solution, 5),
-            unwrap_h_line(&solution, 4) + 1.as_stave_spaces()
-        ); // h5_s1_l2 == h4_s1_l3 + 1
-        assert_eq!(
-            unwrap_h_line(&solution, 6),
-            unwrap_h_line(&solution, 5) + 1.as_stave_spaces()
-        ); // h6_s1_l1 == h5_s1_l2 + 1
-        assert_eq!(
-            unwrap_h_line(&solution, 12),
-            unwrap_h_line(&solution, 6) + stave_separation
-        ); // h12_lyric_top == h6_s1_l1 + stave_separation
-        assert_eq!(
-            unwrap_h_line(&solution, 13),
-            unwrap_h_line(&solution, 12) + 1.as_stave_spaces()
-        ); // h13_lyric_bottom == h12_lyric_top + 1
-        assert_eq!(
-            unwrap_h_line(&solution, 7),
-            unwrap_h_line(&solution, 13) + stave_separation
-        ); // h7_s2_l5 == h13_lyric_bottom + stave_separation
-        assert_eq!(
-            unwrap_h_line(&solution, 8),
-            unwrap_h_line(&solution, 7) + 1.as_stave_spaces()
-        ); // h8_s2_l4 == h7_s2_l5 + 1
-        assert_eq!(
-            unwrap_h_line(&solution, 9),
-            unwrap_h_line(&solution, 8) + 1.as_stave_spaces()
-        ); // h9_s2_13 == h8_s2_l4 + 1
-        assert_eq!(
-            unwrap_h_line(&solution, 10),
-            unwrap_h_line(&solution, 9) + 1.as_stave_spaces()
-        ); // h10_s2_l2 == h9_s2_l3 + 1
-        assert_eq!(
-            unwrap_h_line(&solution, 11),
-            unwrap_h_line(&solution, 10) + 1.as_stave_spaces()
-        ); // h11_s2_l1 == h10_s2_l2 + 1
+            // Each resolution is added to the solver before the next collision is resolved,
+            // so a block already moved clear of another by an earlier resolution is not
+            // moved again.
 
-        // Ok, the computed grid line positions look good; now let's check that the
-        // blocks for the lines are actually positioned on those grid lines.
+            for (index_a, index_b) in collisions {
+                if let Some((index, constraint)) = LayoutSystem::resolve_colliding_blocks(
+                    system.get_blocks(),
+                    index_a,
+                    index_b,
+                    system.get_minimum_collision_gap(),
+                    &self.solver,
+                    self.block_top_position_variables.as_slice(),
+                    self.block_bottom_position_variables.as_slice(),
+                    self.block_start_position_variables.as_slice(),
+                    self.block_end_position_variables.as_slice(),
+                )? {
+                    self.add_layout_constraint(
+                        ConstraintOrigin::CollisionResolution,
+                        index,
+                        constraint,
+                    )?;
+                }
+            }
 
-        let staveline_blocks = vec![
-            // Tuples are (block index, HorizontalGridLineIndex of stave line)
-            (0, 2),
-            (1, 3),
-            (2, 4),
-            (3, 5),
-            (4, 6),
-            (5, 7),
-            (6, 8),
-            (7, 9),
-            (8, 10),
-            (9, 11),
-        ];
+            *passes += 1;
+        }
+    }
 
-        for (block_index, grid_line_index) in staveline_blocks {
-            assert_eq!(
-                unwrap_block_top(&solution, block_index),
-                unwrap_h_line(&solution, grid_line_index)
-            );
-            assert_eq!(
-                unwrap_block_bottom(&solution, block_index),
-                unwrap_h_line(&solution, grid_line_index)
-            );
+    /// Chooses the shape of every curve in the layout from the solved positions of its
+    /// anchors, bowing each curve just far enough to clear any collidable Blocks lying
+    /// between its anchors. Candidate obstacles are found using the same horizontal and
+    /// vertical index maps used for collision detection, then compared by Skyline. Beams
+    /// obstruct curves through their BeamBlocks. Each CurveBlock is then fitted around
+    /// the extent of its curve's shape.
+    fn settle_curves(&mut self, system: &LayoutSystem) -> Result<(), EngravingError> {
+        let (x_plane_intervals, y_plane_intervals) = LayoutSystem::get_collision_interval_maps(
+            system.get_blocks(),
+            &self.solver,
+            self.block_top_position_variables.as_slice(),
+            self.block_bottom_position_variables.as_slice(),
+            self.block_start_position_variables.as_slice(),
+            self.block_end_position_variables.as_slice(),
+        )?;
 
-            // While we're at it, also check that the start and end positions
-            // of the block match the systemic line and system end grid lines.
+        let gap = system.get_minimum_collision_gap().value as f64;
+
+        let mut shapes = Vec::new();
+
+        for curve in system.get_curves() {
+            let anchors = [curve.get_start_anchor(), curve.get_end_anchor()];
+
+            let mut ranges = Vec::new();
+
+            for anchor in anchors.iter() {
+                ranges.push((
+                    LayoutSystem::get_solved_horizontal_range(
+                        *anchor,
+                        &self.solver,
+                        self.block_start_position_variables.as_slice(),
+                        self.block_end_position_variables.as_slice(),
+                    )?,
+                    LayoutSystem::get_solved_vertical_range(
+                        *anchor,
+                        &self.solver,
+                        self.block_top_position_variables.as_slice(),
+                        self.block_bottom_position_variables.as_slice(),
+                    )?,
+                ));
+            }
 
-            assert_eq!(
-                unwrap_block_start(&solution, block_index),
-                unwrap_v_line(&solution, 1)
-            );
-            assert_eq!(
-                unwrap_block_end(&solution, block_index),
-                unwrap_v_line(&solution, 2)
-            );
-        }
+            let (start, end) =
+                curve.get_endpoints((&ranges[0].0, &ranges[0].1), (&ranges[1].0, &ranges[1].1));
 
-        // Check that the systemic line has expanded to cover the entire vertical
-        // range of the system.
+            // Only Blocks lying horizontally between the ends of the curve, and vertically
+            // within the furthest reach of the curve, can obstruct it.
 
-        assert_eq!(unwrap_block_start(&solution, 10), STAVE_SPACES_ZERO);
-        assert_eq!(unwrap_block_end(&solution, 10), STAVE_SPACES_ZERO);
-        assert_eq!(unwrap_block_top(&solution, 10), unwrap_h_line(&solution, 0));
-        assert_eq!(
-            unwrap_block_bottom(&solution, 10),
-            unwrap_h_line(&solution, 1)
-        );
+            let reach =
+                (curve.get_maximum_height().value + curve.get_thickness().value) as f64 + gap;
 
-        // Now, let's check the vertical grid line positions. We want to be sure
-        // that no columns overlap / collide. So long as every vertical grid line
-        // in sequence has a horizontal position greater than, or equal to, the
-        // preceding vertical grid line, then we can be certain that no columns overlap.
+            let nearest = start.1.min(end.1);
 
-        let ordered_vertical_grid_lines = vec![
-            // The order in which we expect the vertical grid lines to appear,
-            // from system start to system end.
-            0,  // v0_system_start,
-            1,  // v1_systemic_line,
-            3,  // v3_bar1_clef_start,
-            4,  // v4_bar1_clef_end,
-            5,  // v5_bar1_time_sig_start,
-            6,  // v6_bar1_time_sig_end,
-            7,  // v7_bar1_note1_start,
-            19, // v19_bar1_voice1_notehead1_center,
-            8,  // v8_bar1_note1_end,
-            9,  // v9_bar1_note2_start,
-            20, // v20_bar1_voice1_notehead2_center,
-            10, // v10_bar1_note2_end,
-            11, // v11_bar1_barline_start,
-            12, // v12_bar1_barline_end,
-            13, // v13_bar2_note1_start,
-            21, // v21_bar2_voice1_notehead1_center,
-            14, // v14_bar2_note1_end,
-            15, // v15_bar2_note2_start,
-            22, // v22_bar2_voice1_notehead2_center,
-            16, // v16_bar2_note2_end,
-            17, // v17_bar2_barline_start,
-            18, // v18_bar2_barline_end,
-            2,  // v2_system_end,
-        ];
+            let furthest = start.1.max(end.1);
 
-        for (index, grid_line) in ordered_vertical_grid_lines.iter().enumerate() {
-            // Confirm that the position of this grid line ...
+            let vertical_range = match curve.get_direction() {
+                CurveDirection::Above => (nearest - reach)..furthest,
+                CurveDirection::Below => nearest..(furthest + reach),
+            };
 
-            let this_grid_line_position = unwrap_v_line(&solution, *grid_line as usize);
+            let mut obstacles = Vec::new();
 
-            // ... is greater than or equal to the position of the previous grid line
-            // in the sequence.
+            if end.0 > start.0 {
+                let vertical_candidates = y_plane_intervals
+                    .iter(vertical_range)
+                    .map(|(_, index)| *index)
+                    .collect::<Vec<_>>();
 
-            if index > 0 {
-                if let Some(previous_grid_line_position) = ordered_vertical_grid_lines
-                    .get(index - 1)
-                    .map(|grid_line| unwrap_v_line(&solution, *grid_line as usize))
-                {
-                    assert!(this_grid_line_position >= previous_grid_line_position);
+                for (_, index) in x_plane_intervals.iter(start.0..end.0) {
+                    if anchors.contains(index) || !vertical_candidates.contains(index) {
+                        continue;
+                    }
+
+                    let (skyline, skyline_start, skyline_top) = LayoutSystem::get_solved_skyline(
+                        *index,
+                        system.get_blocks(),
+                        &self.solver,
+                        self.block_top_position_variables.as_slice(),
+                        self.block_bottom_position_variables.as_slice(),
+                        self.block_start_position_variables.as_slice(),
+                        self.block_end_position_variables.as_slice(),
+                    )?;
+
+                    for column in skyline.get_columns() {
+                        obstacles.push((
+                            (skyline_start + column.get_start() as f64)
+                                ..(skyline_start + column.get_end() as f64),
+                            (skyline_top + column.get_top() as f64)
+                                ..(skyline_top + column.get_bottom() as f64),
+                        ));
+                    }
                 }
             }
+
+            shapes.push(curve.get_shape(start, end, obstacles.as_slice(), gap));
         }
 
-        // Next, check the positioning of blocks. We already know that no columns
-        // overlap / collide. So, if every block is correctly positioned within its
-        // designated column, then it follows that no blocks are colliding either.
+        for (index, block) in system.get_blocks().iter().enumerate() {
+            if let BlockEnum::Curve(curve_block) = block {
+                let curve_index = curve_block.get_curve_index();
 
-        let blocks_in_columns = vec![
-            // The vertical grid lines between which each block should be placed.
-            // Tuple is (block index, index of grid line before block, index of grid line after block)
-            (11, 3, 4), // b11_bar1_stave1_clef between v3_bar1_clef_start and v4_bar1_clef_end
-            (12, 3, 4), // b12_bar1_stave2_clef between v3_bar1_clef_start and v4_bar1_clef_end
-            (13, 5, 6), // b13_bar1_stave1_time_sig_numerator between v5_bar1_time_sig_start and v6_bar1_time_sig_end
-            (14, 5, 6), // b14_bar1_stave1_time_sig_denominator between v5_bar1_time_sig_start and v6_bar1_time_sig_end
-            (15, 5, 6), // b15_bar1_stave2_time_sig_numerator between v5_bar1_time_sig_start and v6_bar1_time_sig_end
-            (16, 5, 6), // b16_bar1_stave2_time_sig_denominator between v5_bar1_time_sig_start and v6_bar1_time_sig_end
-            (17, 7, 8), // b17_bar1_voice1_notehead1 between v7_bar1_note1_start and v8_bar1_note1_end
-            (18, 9, 10), // b18_bar1_voice1_notehead2 between v9_bar1_note2_start and v10_bar1_note2_end
-            (19, 7, 8), // b19_bar1_voice2_notehead between v7_bar1_note1_start and v8_bar1_note1_end
-            (20, 11, 12), // b20_bar1_barline between v11_bar1_barline_start and v12_bar1_barline_end
-            (21, 13, 14), // b21_bar2_voice1_notehead1 between v13_bar2_note1_start and v14_bar2_note1_end
-            (22, 15, 16), // b22_bar2_voice1_notehead2 between v15_bar2_note2_start and v16_bar2_note2_end
-            (23, 13, 14), // b23_bar1_voice2_notehead2 between v13_bar2_note1_start and v14_bar2_note1_end
-            (24, 17, 18), // b24_bar2_barline between v17_bar2_barline_start and v18_bar2_barline_end
-            (25, 7, 8),   // b25_bar1_lyric1 between v7_bar1_note1_start and v8_bar1_note1_end
-            (26, 9, 10),  // b26_bar1_lyric2 between v9_bar1_note2_start and v10_bar1_note2_end
-            (27, 13, 14), // b27_bar2_lyric1 between v13_bar2_note1_start and v14_bar2_note1_end
-            (28, 15, 16), // b28_bar2_lyric2 between v15_bar2_note2_start and v16_bar2_note2_end
-        ];
+                let extent = shapes
+                    .get(curve_index)
+                    .ok_or(EngravingError::UnknownCurve(curve_index))?
+                    .get_extent();
 
-        for (block_index, grid_line_before, grid_line_after) in blocks_in_columns {
-            assert!(
-                unwrap_block_start(&solution, block_index)
-                    >= unwrap_v_line(&solution, grid_line_before)
-            );
-            assert!(
-                unwrap_block_end(&solution, block_index)
-                    <= unwrap_v_line(&solution, grid_line_after)
-            );
+                self.add_extent_constraints(ConstraintOrigin::CurveBlock, index, extent)?;
+            }
         }
 
-        // Check that column separation gaps and simulated rhythmic padding spaces
-        // after noteheads have been correctly applied.
+        self.curve_shapes = shapes;
 
-        let block_end_next_v_line_separation = vec![
-            // The expected minimum distance between the end of a block and the start of the
-            // given column. Tuple is (block index, grid line index, expected minimum separation)
-            (11, 5, column_separation),
-            (12, 5, column_separation),
-            (13, 7, column_separation),
-            (14, 7, column_separation),
-            (15, 7, column_separation),
-            (16, 7, column_separation),
-            (17, 9, rhythmic_space_separation),
-            (18, 11, rhythmic_space_separation),
-            (19, 9, rhythmic_space_separation),
-            (20, 13, column_separation),
-            (21, 15, rhythmic_space_separation),
-            (22, 17, rhythmic_space_separation),
-            (23, 15, rhythmic_space_separation),
-            (25, 9, column_separation),
-            (26, 11, column_separation),
-            (27, 15, column_separation),
-            (28, 17, column_separation),
-        ];
+        Ok(())
+    }
 
-        for (block_index, grid_line_after, minimum_separation) in block_end_next_v_line_separation {
-            assert!(
-                unwrap_block_end(&solution, block_index) + minimum_separation
-                    <= unwrap_v_line(&solution, grid_line_after)
-            );
+    /// Fits every SpannerBlock in the layout around the solved position of its spanner.
+    /// A SpannerBlock whose spanner cannot be placed on the system is left unplaced.
+    fn settle_spanners(&mut self, system: &LayoutSystem) -> Result<(), EngravingError> {
+        for (index, block) in system.get_blocks().iter().enumerate() {
+            if let BlockEnum::Spanner(spanner_block) = block {
+                if let Some(spanner) =
+                    system.get_solved_spanner(spanner_block.get_spanner_index(), self)
+                {
+                    self.add_extent_constraints(
+                        ConstraintOrigin::SpannerBlock,
+                        index,
+                        spanner.get_extent(),
+                    )?;
+                }
+            }
         }
 
-        // Finally, check that lyric syllables are correctly centered beneath their
-        // respective noteheads. (In a real score, syllables are not necessarily
-        // centered - it usually depends on whether the syllable starts a new word
-        // or not - but in this test we simply centered all syllables.)
+        Ok(())
+    }
 
-        let syllables_underneath_noteheads = vec![
-            // Tuple is (syllable block index, notehead block index, notehead center grid line index)
-            (25, 17, 19),
-            (26, 18, 20),
-            (27, 21, 21),
-            (28, 22, 22),
+    /// Requires the edges of the Block at the given index to lie at the given
+    /// (left, top, right, bottom) extent, using layout constraints with the given origin.
+    fn add_extent_constraints(
+        &mut self,
+        origin: fn(BlockIndex) -> ConstraintOrigin,
+        index: BlockIndex,
+        extent: (f32, f32, f32, f32),
+    ) -> Result<(), EngravingError> {
+        let (left, top, right, bottom) = extent;
+
+        let edges = [
+            (
+                *self
+                    .block_top_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockTopPosition(index))?,
+                top,
+            ),
+            (
+                *self
+                    .block_bottom_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockBottomPosition(index))?,
+                bottom,
+            ),
+            (
+                *self
+                    .block_start_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockStartPosition(index))?,
+                left,
+            ),
+            (
+                *self
+                    .block_end_position_variables
+                    .get(index)
+                    .ok_or(EngravingError::UnknownBlockEndPosition(index))?,
+                right,
+            ),
         ];
 
-        for (syllable_block, notehead_block, notehead_center_grid_line) in
-            syllables_underneath_noteheads
-        {
-            assert_eq!(
-                unwrap_block_start(&solution, syllable_block)
-                    + (unwrap_block_end(&solution, syllable_block)
-                        - unwrap_block_start(&solution, syllable_block))
-                        / 2.0,
-                unwrap_v_line(&solution, notehead_center_grid_line)
-            );
-
-            assert_eq!(
-                unwrap_block_start(&solution, notehead_block)
-                    + (unwrap_block_end(&solution, notehead_block)
-                        - unwrap_block_start(&solution, notehead_block))
-                        / 2.0,
-                unwrap_v_line(&solution, notehead_center_grid_line)
-            );
+        for (variable, value) in edges.iter() {
+            self.add_layout_constraint(origin, index, *variable | EQ(REQUIRED) | *value as f64)?;
         }
+
+        Ok(())
     }
 
-    fn create_staveline_block(
-        staveline: HorizontalGridLineIndex,
-        systemic_line: VerticalGridLineIndex,
-        system_end: VerticalGridLineIndex,
-    ) -> LineBlock {
-        let mut block = LineBlock::new_horizontal(
-            None,
-            Some(TICKS_ZERO),
-            None,
-            0.25.as_stave_spaces(),
-            Color::BLACK,
-            StrokeStyle::Solid,
-            BlockLayer::Foreground,
-        );
+    /// Ties the tips of the intermediate stems of the beam at the given index to the beam,
+    /// then fixes the ends of the beam at quantized positions. Any constraints added by
+    /// a previous call for the same beam are removed first. The ends of the beam are
+    /// quantized from their current solved positions and only ever move away from the
+    /// noteheads, so a beam moved clear of a collision stays clear.
+    fn settle_beam(
+        &mut self,
+        system: &LayoutSystem,
+        index: BeamIndex,
+    ) -> Result<(), EngravingError> {
+        let beam = system
+            .get_beams()
+            .get(index)
+            .ok_or(EngravingError::UnknownBeam(index))?;
 
-        block.lock_start_to_grid_line(systemic_line);
-        block.lock_end_to_grid_line(system_end);
-        block.lock_vertical_center_to_grid_line(staveline);
+        let (previous, remaining) = std::mem::take(&mut self.beam_layout_constraints)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(beam_index, _)| *beam_index == index);
 
-        block
+        self.beam_layout_constraints = remaining;
+
+        for (_, constraint) in previous {
+            self.remove_constraint(&constraint, |err| {
+                EngravingError::RemoveConstraintErrorOnBeam(err, index)
+            })?;
+        }
+
+        let start = *self
+            .beam_start_variables
+            .get(index)
+            .ok_or(EngravingError::UnknownBeam(index))?;
+
+        let end = *self
+            .beam_end_variables
+            .get(index)
+            .ok_or(EngravingError::UnknownBeam(index))?;
+
+        let rise = *self
+            .beam_rise_variables
+            .get(index)
+            .ok_or(EngravingError::UnknownBeam(index))?;
+
+        let stems = beam.get_stems();
+
+        let stem_positions = stems
+            .iter()
+            .map(|stem| {
+                Ok(self.solver.get_value(
+                    *self
+                        .block_start_position_variables
+                        .get(*stem)
+                        .ok_or(EngravingError::UnknownBlockStartPosition(*stem))?,
+                ))
+            })
+            .collect::<Result<Vec<_>, EngravingError>>()?;
+
+        let first_position = stem_positions.first().copied().unwrap_or(0.0);
+
+        let span = stem_positions.last().copied().unwrap_or(first_position) - first_position;
+
+        // The horizontal position of every stem is now known, so the position of the beam
+        // at each intermediate stem is a fixed fraction of the beam's rise.
+
+        let mut constraints = Vec::new();
+
+        for (stem, position) in stems
+            .iter()
+            .zip(stem_positions.iter())
+            .skip(1)
+            .take(stems.len().saturating_sub(2))
+        {
+            let (tip, _) = self.get_stem_variables(beam, *stem)?;
+
+            let fraction = if span > 0.0 {
+                (position - first_position) / span
+            } else {
+                0.0
+            };
+
+            constraints.push(tip | EQ(STRONG) | start + rise * fraction);
+        }
+
+        self.add_beam_layout_constraints(index, constraints)?;
+
+        // Snap the ends of the beam to staveline positions.
+
+        let stave_top = self.solver.get_value(
+            *self
+                .horizontal_grid_line_variables
+                .get(beam.get_stave_top())
+                .ok_or(EngravingError::UnknownHorizontalGridLine(
+                    beam.get_stave_top(),
+                ))?,
+        );
+
+        let stave_bottom = self.solver.get_value(
+            *self
+                .horizontal_grid_line_variables
+                .get(beam.get_stave_bottom())
+                .ok_or(EngravingError::UnknownHorizontalGridLine(
+                    beam.get_stave_bottom(),
+                ))?,
+        );
+
+        let (quantized_start, quantized_end) = beam.get_quantized_positions(
+            self.solver.get_value(start),
+            self.solver.get_value(end),
+            span,
+            stave_top,
+            stave_bottom,
+        );
+
+        self.add_beam_layout_constraints(
+            index,
+            vec![
+                start | EQ(STRONG) | quantized_start,
+                end | EQ(STRONG) | quantized_end,
+            ],
+        )
     }
 
-    fn create_glyph_block_on_staveline(
-        staveline: HorizontalGridLineIndex,
-        column_start: VerticalGridLineIndex,
-        column_end: VerticalGridLineIndex,
-        onset: Ticks,
-        font: &impl SmuflFont,
-        glyph: Glyph,
-    ) -> GlyphBlock {
-        let mut block = GlyphBlock::new(
-            None,
-            Some(onset),
-            None,
-            font,
-            Color::BLACK,
-            glyph,
-            BlockLayer::Foreground,
+    /// Adds the given constraints, settling the beam at the given index, to the solver,
+    /// keeping track of them so they can be removed the next time settle() is called.
+    fn add_beam_layout_constraints(
+        &mut self,
+        index: BeamIndex,
+        constraints: Vec<Constraint>,
+    ) -> Result<(), EngravingError> {
+        for constraint in constraints {
+            self.add_constraint(ConstraintOrigin::Beam(index), &constraint, |err| {
+                EngravingError::AddConstraintErrorOnBeam(err, index)
+            })?;
+
+            self.beam_layout_constraints.push((index, constraint));
+        }
+
+        Ok(())
+    }
+
+    /// Adds the given constraint, originating from the given origin, to the solver.
+    /// If the solver rejects the constraint as unsatisfiable while constraint conflicts
+    /// are being diagnosed, the minimal set of conflicting constraints is returned as an
+    /// EngravingError::ConflictingConstraints error; otherwise, any error is mapped
+    /// using the given function.
+    fn add_constraint(
+        &mut self,
+        origin: ConstraintOrigin,
+        constraint: &Constraint,
+        map_err: impl FnOnce(AddConstraintError) -> EngravingError,
+    ) -> Result<(), EngravingError> {
+        if let Err(err) = self.solver.add_constraint(constraint.clone()) {
+            return Err(match (&self.constraint_log, err) {
+                (Some(log), AddConstraintError::UnsatisfiableConstraint)
+                    if self.do_diagnose_constraint_conflicts =>
+                {
+                    EngravingError::ConflictingConstraints(
+                        log.get_minimal_conflicting_set(origin, constraint),
+                    )
+                }
+                (_, err) => map_err(err),
+            });
+        }
+
+        if let Some(log) = self.constraint_log.as_mut() {
+            log.record(origin, constraint);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the given constraint from the solver, mapping any error using the given function.
+    fn remove_constraint(
+        &mut self,
+        constraint: &Constraint,
+        map_err: impl FnOnce(RemoveConstraintError) -> EngravingError,
+    ) -> Result<(), EngravingError> {
+        self.solver.remove_constraint(constraint).map_err(map_err)?;
+
+        if let Some(log) = self.constraint_log.as_mut() {
+            log.forget(constraint);
+        }
+
+        Ok(())
+    }
+
+    /// Adds the given constraint, created for the Block with the given index during
+    /// collision resolution or system justification, to the solver, and takes ownership
+    /// of it so it can be removed the next time settle() is called.
+    fn add_layout_constraint(
+        &mut self,
+        origin: fn(BlockIndex) -> ConstraintOrigin,
+        index: BlockIndex,
+        constraint: Constraint,
+    ) -> Result<(), EngravingError> {
+        self.add_constraint(origin(index), &constraint, |err| {
+            EngravingError::AddConstraintErrorOnBlock(err, index)
+        })?;
+
+        self.layout_constraints.push((index, constraint));
+
+        Ok(())
+    }
+
+    /// Returns a LayoutTrace of every variable in the solver, with its current solved value,
+    /// and every constraint in the constraint log. If constraints are not being logged,
+    /// the trace contains no constraints.
+    fn create_trace(&self, system: &LayoutSystem) -> LayoutTrace {
+        let mut variables = vec![(self.aligned_start, TraceVariableKind::AlignedStart)];
+
+        for (index, (variable, grid_line)) in self
+            .horizontal_grid_line_variables
+            .iter()
+            .zip(system.get_horizontal_grid_lines())
+            .enumerate()
+        {
+            variables.push((
+                *variable,
+                TraceVariableKind::HorizontalGridLine {
+                    index,
+                    grid_line_type: format!("{:?}", grid_line.get_grid_line_type()),
+                },
+            ));
+        }
+
+        for (index, (variable, grid_line)) in self
+            .vertical_grid_line_variables
+            .iter()
+            .zip(system.get_vertical_grid_lines())
+            .enumerate()
+        {
+            variables.push((
+                *variable,
+                TraceVariableKind::VerticalGridLine {
+                    index,
+                    grid_line_type: format!("{:?}", grid_line.get_grid_line_type()),
+                },
+            ));
+        }
+
+        for (index, (top, bottom, start, end)) in izip!(
+            self.block_top_position_variables.iter(),
+            self.block_bottom_position_variables.iter(),
+            self.block_start_position_variables.iter(),
+            self.block_end_position_variables.iter()
+        )
+        .enumerate()
+        {
+            for (variable, edge) in [
+                (top, BlockEdge::Top),
+                (bottom, BlockEdge::Bottom),
+                (start, BlockEdge::Start),
+                (end, BlockEdge::End),
+            ] {
+                variables.push((*variable, TraceVariableKind::Block { index, edge }));
+            }
+        }
+
+        for (index, (start, end, rise)) in izip!(
+            self.beam_start_variables.iter(),
+            self.beam_end_variables.iter(),
+            self.beam_rise_variables.iter()
+        )
+        .enumerate()
+        {
+            for (variable, beam_variable) in [
+                (start, BeamVariable::Start),
+                (end, BeamVariable::End),
+                (rise, BeamVariable::Rise),
+            ] {
+                variables.push((
+                    *variable,
+                    TraceVariableKind::Beam {
+                        index,
+                        variable: beam_variable,
+                    },
+                ));
+            }
+        }
+
+        let variables = variables
+            .into_iter()
+            .map(|(variable, kind)| (variable, kind, self.solver.get_value(variable)))
+            .collect();
+
+        match self.constraint_log.as_ref() {
+            Some(log) => LayoutTrace::new(variables, log),
+            None => LayoutTrace::new(variables, &ConstraintLog::new()),
+        }
+    }
+
+    /// Returns the current solved values of the given variables.
+    #[inline]
+    fn get_values(&self, variables: &[Variable]) -> Vec<StaveSpaces> {
+        variables
+            .iter()
+            .map(|variable| StaveSpaces::new(self.solver.get_value(*variable) as f32))
+            .collect::<Vec<_>>()
+    }
+}
+
+/// A persistent engraving session for a single LayoutSystem. The session keeps the
+/// linear constraint solver and all its variables alive between engravings, so that
+/// small edits to the layout - adding, removing, or replacing a single Block or
+/// grid line - only need to touch the constraints affected by that edit.
+///
+/// Each call to engrave() returns only the Engravables whose positions or content
+/// have changed since the previous call. Use get_engraved_system() to retrieve a
+/// complete EngravedSystem, e.g. when first displaying the system.
+pub struct EngravingSession {
+    system: LayoutSystem,
+    solver: LayoutSystemSolver,
+    removed_blocks: Vec<bool>,
+    changed_blocks: Vec<bool>,
+    engraved_block_positions: Vec<Option<(StaveSpaces, StaveSpaces, StaveSpaces, StaveSpaces)>>,
+    engraved_beam_lines: Vec<Option<(StaveSpaces, StaveSpaces, StaveSpaces, StaveSpaces)>>,
+    engraved_curve_shapes: Vec<EngravedCurve>,
+    engraved_spanners: Vec<Option<EngravedSpanner>>,
+}
+
+impl EngravingSession {
+    /// Creates a new EngravingSession for the given LayoutSystem.
+    pub fn new(system: LayoutSystem) -> Result<Self, EngravingError> {
+        let mut solver = LayoutSystemSolver::new(&system, false)?;
+
+        solver.settle(&system)?;
+
+        let mut result = EngravingSession {
+            removed_blocks: vec![false; system.get_blocks().len()],
+            changed_blocks: vec![false; system.get_blocks().len()],
+            engraved_block_positions: vec![None; system.get_blocks().len()],
+            engraved_beam_lines: vec![None; system.get_beams().len()],
+            engraved_curve_shapes: solver.curve_shapes.clone(),
+            engraved_spanners: vec![None; system.get_spanners().len()],
+            system,
+            solver,
+        };
+
+        // Every Block has now been engraved once. Record the engraved positions,
+        // so the first call to engrave() only reports subsequent changes.
+
+        for index in 0..result.system.get_blocks().len() {
+            result.engraved_block_positions[index] = result.get_engraved_block_position(index);
+        }
+
+        for index in 0..result.system.get_beams().len() {
+            result.engraved_beam_lines[index] =
+                result.system.get_solved_beam_line(index, &result.solver);
+        }
+
+        for index in 0..result.system.get_spanners().len() {
+            result.engraved_spanners[index] =
+                result.system.get_solved_spanner(index, &result.solver);
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the LayoutSystem being engraved by this session, including any edits
+    /// made during the session.
+    #[inline]
+    pub fn get_layout_system(&self) -> &LayoutSystem {
+        &self.system
+    }
+
+    /// Returns a complete EngravedSystem reflecting the most recent engraving.
+    #[inline]
+    pub fn get_engraved_system(&self) -> EngravedSystem {
+        self.system.create_engraved_system(&self.solver)
+    }
+
+    /// Adds the given Block to the layout, returning its index.
+    ///
+    /// If the new Block would leave the layout invalid, or any of its constraints cannot
+    /// be added to the solver, the Block is removed again before the error is returned,
+    /// leaving the session exactly as it was.
+    pub fn add_block(&mut self, block: BlockEnum) -> Result<BlockIndex, EngravingError> {
+        let index = self.system.blocks.len();
+
+        self.system.blocks.push(block);
+
+        if let Err(err) = self.validate() {
+            self.system.blocks.pop();
+
+            return Err(err);
+        }
+
+        self.solver
+            .block_top_position_variables
+            .push(Variable::new());
+        self.solver
+            .block_bottom_position_variables
+            .push(Variable::new());
+        self.solver
+            .block_start_position_variables
+            .push(Variable::new());
+        self.solver
+            .block_end_position_variables
+            .push(Variable::new());
+        self.solver.block_constraints.push(vec![]);
+
+        if let Err(err) = self
+            .solver
+            .add_block_constraints(self.system.get_blocks(), index)
+        {
+            // Remove any constraints on the new Block that were added before the error,
+            // then remove the Block itself along with its variables.
+
+            self.solver.remove_block_constraints(index)?;
+
+            self.system.blocks.pop();
+            self.solver.block_top_position_variables.pop();
+            self.solver.block_bottom_position_variables.pop();
+            self.solver.block_start_position_variables.pop();
+            self.solver.block_end_position_variables.pop();
+            self.solver.block_constraints.pop();
+
+            return Err(err);
+        }
+
+        self.removed_blocks.push(false);
+        self.changed_blocks.push(true);
+        self.engraved_block_positions.push(None);
+
+        Ok(index)
+    }
+
+    /// Replaces the Block at the given index, along with all its constraints and its Skyline.
+    ///
+    /// If the new Block would leave the layout invalid, or any constraint on the new Block
+    /// cannot be added to the solver, the previous Block and its constraints are restored
+    /// before the error is returned, leaving the session exactly as it was.
+    pub fn replace_block(
+        &mut self,
+        index: BlockIndex,
+        block: BlockEnum,
+    ) -> Result<(), EngravingError> {
+        let previous_block = std::mem::replace(
+            self.system
+                .blocks
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownBlock(index))?,
+            block,
         );
 
-        block.lock_vertical_center_to_grid_line(staveline);
-        block.float_horizontally_between_grid_lines(column_start, column_end);
-        // TODO: AJRC - 22/8/21 - it's tempting to use start_align_between_grid_lines()
-        // on the notehead, but this sets an EQ(STRONG) constraint on the notehead
-        // position that conflicts with the center point of a wide lyric. Only
-        // by floating the notehead between grid lines can we allow the
-        // width of a wide lyric to "win" and push the center of the notehead
-        // sideways. If we use start_align, then the notehead won't budge; the lyric
-        // instead moves, and invariably collides with the lyric in the previous
-        // notehead column. This could indicate that we need to weaken the
-        // EQ() constraint on start_align. Perhaps if it was EQ(MEDIUM) instead of
-        // EQ(STRONG), there'd be less of a problem using start_align. Or we
-        // could allow the block constraint to actually take a strength parameter
-        // when we define it, rather than trying to assign strengths to constraints
-        // as part of LayoutSystem.engrave().
+        if let Err(err) = self.validate() {
+            self.system.blocks[index] = previous_block;
 
-        block
+            return Err(err);
+        }
+
+        self.solver.remove_block_constraints(index)?;
+
+        if let Err(err) = self
+            .solver
+            .add_block_constraints(self.system.get_blocks(), index)
+        {
+            // Remove any constraints on the new Block that were added before the error,
+            // then put back the previous Block. Its constraints were in the solver
+            // until a moment ago, so they can be added again.
+
+            self.solver.remove_block_constraints(index)?;
+
+            self.system.blocks[index] = previous_block;
+
+            self.solver
+                .add_block_constraints(self.system.get_blocks(), index)?;
+
+            return Err(err);
+        }
+
+        self.removed_blocks[index] = false;
+        self.changed_blocks[index] = true;
+
+        Ok(())
     }
 
-    fn create_barline_block(
-        system_top: HorizontalGridLineIndex,
-        system_bottom: HorizontalGridLineIndex,
-        barline_column_start: VerticalGridLineIndex,
-        barline_column_end: VerticalGridLineIndex,
-        onset: Ticks,
-    ) -> LineBlock {
-        let mut block = LineBlock::new_vertical(
+    /// Removes the Block at the given index from the layout.
+    ///
+    /// So that the indices of all other Blocks remain stable, the removed Block is
+    /// replaced by an invisible, unconstrained line of zero thickness. Unlike a spacing block,
+    /// the placeholder takes no part in rhythmic spacing, justification, or compression.
+    /// A Block that is still referred to by a constraint on another Block, or by a beam
+    /// or curve, cannot be removed; remove or replace the elements referring to it first.
+    pub fn remove_block(&mut self, index: BlockIndex) -> Result<(), EngravingError> {
+        self.system
+            .blocks
+            .get(index)
+            .ok_or(EngravingError::UnknownBlock(index))?;
+
+        if self.system.is_referenced(LayoutElement::Block(index)) {
+            return Err(EngravingError::RemovingReferencedElement(
+                LayoutElement::Block(index),
+            ));
+        }
+
+        let mut placeholder = LineBlock::new_horizontal(
             None,
-            Some(onset),
             None,
-            0.5.as_stave_spaces(),
+            None,
+            STAVE_SPACES_ZERO,
             Color::BLACK,
             StrokeStyle::Solid,
-            BlockLayer::Foreground,
+            BlockLayer::Background,
         );
 
-        block.lock_top_to_grid_line(system_top);
-        block.lock_bottom_to_grid_line(system_bottom);
-        block.lock_start_between_grid_lines(
-            barline_column_start,
-            barline_column_end,
-            STAVE_SPACES_ZERO,
+        placeholder.set_visible(false);
+
+        let previous_block = std::mem::replace(
+            self.system
+                .blocks
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownBlock(index))?,
+            placeholder.into(),
         );
 
-        block
+        if let Err(err) = self.validate() {
+            self.system.blocks[index] = previous_block;
+
+            return Err(err);
+        }
+
+        self.solver.remove_block_constraints(index)?;
+
+        self.removed_blocks[index] = true;
+        self.changed_blocks[index] = true;
+
+        Ok(())
     }
 
-    fn create_systemic_line_block(
-        system_top: HorizontalGridLineIndex,
-        system_bottom: HorizontalGridLineIndex,
-        systemic_line: VerticalGridLineIndex,
-    ) -> LineBlock {
-        let mut block = LineBlock::new_vertical(
-            None,
-            Some(TICKS_ZERO),
-            None,
-            0.25.as_stave_spaces(),
-            Color::BLACK,
-            StrokeStyle::Solid,
-            BlockLayer::Foreground,
+    /// Adds the given HorizontalGridLine to the layout, returning its index.
+    ///
+    /// If the new grid line would leave the layout invalid, or any of its constraints cannot
+    /// be added to the solver, the grid line is removed again before the error is returned.
+    pub fn add_horizontal_grid_line(
+        &mut self,
+        grid_line: HorizontalGridLine,
+    ) -> Result<HorizontalGridLineIndex, EngravingError> {
+        let index = self.system.horizontal_grid_lines.len();
+
+        self.system.horizontal_grid_lines.push(grid_line);
+
+        if let Err(err) = self.validate() {
+            self.system.horizontal_grid_lines.pop();
+
+            return Err(err);
+        }
+
+        self.solver
+            .horizontal_grid_line_variables
+            .push(Variable::new());
+        self.solver.horizontal_grid_line_constraints.push(vec![]);
+
+        if let Err(err) = self
+            .solver
+            .add_horizontal_grid_line_constraints(self.system.get_horizontal_grid_lines(), index)
+        {
+            self.solver.remove_horizontal_grid_line_constraints(index)?;
+
+            self.system.horizontal_grid_lines.pop();
+            self.solver.horizontal_grid_line_variables.pop();
+            self.solver.horizontal_grid_line_constraints.pop();
+
+            return Err(err);
+        }
+
+        Ok(index)
+    }
+
+    /// Replaces the HorizontalGridLine at the given index, along with all its constraints.
+    ///
+    /// If the new grid line would leave the layout invalid, or any constraint on the new
+    /// grid line cannot be added to the solver, the previous grid line and its constraints
+    /// are restored before the error is returned.
+    pub fn replace_horizontal_grid_line(
+        &mut self,
+        index: HorizontalGridLineIndex,
+        grid_line: HorizontalGridLine,
+    ) -> Result<(), EngravingError> {
+        let previous_grid_line = std::mem::replace(
+            self.system
+                .horizontal_grid_lines
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownHorizontalGridLine(index))?,
+            grid_line,
         );
 
-        block.lock_top_to_grid_line(system_top);
-        block.lock_bottom_to_grid_line(system_bottom);
-        block.lock_horizontal_center_to_grid_line(systemic_line);
+        if let Err(err) = self.validate() {
+            self.system.horizontal_grid_lines[index] = previous_grid_line;
+
+            return Err(err);
+        }
+
+        self.solver.remove_horizontal_grid_line_constraints(index)?;
+
+        if let Err(err) = self
+            .solver
+            .add_horizontal_grid_line_constraints(self.system.get_horizontal_grid_lines(), index)
+        {
+            self.solver.remove_horizontal_grid_line_constraints(index)?;
+
+            self.system.horizontal_grid_lines[index] = previous_grid_line;
+
+            self.solver.add_horizontal_grid_line_constraints(
+                self.system.get_horizontal_grid_lines(),
+                index,
+            )?;
+
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the HorizontalGridLine at the given index from the layout.
+    ///
+    /// So that the indices of all other grid lines remain stable, the removed grid line
+    /// is replaced by an unconstrained grid line of the same type. The system's top edge,
+    /// and any grid line still referred to by a constraint, beam, or spanner, cannot
+    /// be removed.
+    pub fn remove_horizontal_grid_line(
+        &mut self,
+        index: HorizontalGridLineIndex,
+    ) -> Result<(), EngravingError> {
+        let grid_line_type = self
+            .system
+            .horizontal_grid_lines
+            .get(index)
+            .ok_or(EngravingError::UnknownHorizontalGridLine(index))?
+            .get_grid_line_type();
+
+        if self
+            .system
+            .is_referenced(LayoutElement::HorizontalGridLine(index))
+        {
+            return Err(EngravingError::RemovingReferencedElement(
+                LayoutElement::HorizontalGridLine(index),
+            ));
+        }
+
+        let previous_grid_line = std::mem::replace(
+            &mut self.system.horizontal_grid_lines[index],
+            HorizontalGridLine::new(grid_line_type),
+        );
+
+        if let Err(err) = self.validate() {
+            self.system.horizontal_grid_lines[index] = previous_grid_line;
+
+            return Err(err);
+        }
+
+        self.solver.remove_horizontal_grid_line_constraints(index)?;
+
+        Ok(())
+    }
+
+    /// Adds the given VerticalGridLine to the layout, returning its index.
+    ///
+    /// If the new grid line would leave the layout invalid, or any of its constraints cannot
+    /// be added to the solver, the grid line is removed again before the error is returned.
+    pub fn add_vertical_grid_line(
+        &mut self,
+        grid_line: VerticalGridLine,
+    ) -> Result<VerticalGridLineIndex, EngravingError> {
+        let index = self.system.vertical_grid_lines.len();
+
+        self.system.vertical_grid_lines.push(grid_line);
+
+        if let Err(err) = self.validate() {
+            self.system.vertical_grid_lines.pop();
+
+            return Err(err);
+        }
+
+        self.solver
+            .vertical_grid_line_variables
+            .push(Variable::new());
+        self.solver.vertical_grid_line_constraints.push(vec![]);
+
+        if let Err(err) = self
+            .solver
+            .add_vertical_grid_line_constraints(self.system.get_vertical_grid_lines(), index)
+        {
+            self.solver.remove_vertical_grid_line_constraints(index)?;
+
+            self.system.vertical_grid_lines.pop();
+            self.solver.vertical_grid_line_variables.pop();
+            self.solver.vertical_grid_line_constraints.pop();
+
+            return Err(err);
+        }
+
+        Ok(index)
+    }
+
+    /// Replaces the VerticalGridLine at the given index, along with all its constraints.
+    ///
+    /// If the new grid line would leave the layout invalid, or any constraint on the new
+    /// grid line cannot be added to the solver, the previous grid line and its constraints
+    /// are restored before the error is returned.
+    pub fn replace_vertical_grid_line(
+        &mut self,
+        index: VerticalGridLineIndex,
+        grid_line: VerticalGridLine,
+    ) -> Result<(), EngravingError> {
+        let previous_grid_line = std::mem::replace(
+            self.system
+                .vertical_grid_lines
+                .get_mut(index)
+                .ok_or(EngravingError::UnknownVerticalGridLine(index))?,
+            grid_line,
+        );
+
+        if let Err(err) = self.validate() {
+            self.system.vertical_grid_lines[index] = previous_grid_line;
+
+            return Err(err);
+        }
+
+        self.solver.remove_vertical_grid_line_constraints(index)?;
+
+        if let Err(err) = self
+            .solver
+            .add_vertical_grid_line_constraints(self.system.get_vertical_grid_lines(), index)
+        {
+            self.solver.remove_vertical_grid_line_constraints(index)?;
+
+            self.system.vertical_grid_lines[index] = previous_grid_line;
+
+            self.solver
+                .add_vertical_grid_line_constraints(self.system.get_vertical_grid_lines(), index)?;
+
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the VerticalGridLine at the given index from the layout.
+    ///
+    /// So that the indices of all other grid lines remain stable, the removed grid line
+    /// is replaced by an unconstrained grid line of the same column and type. The system's
+    /// leading edge, and any grid line still referred to by a constraint or spanner,
+    /// cannot be removed.
+    pub fn remove_vertical_grid_line(
+        &mut self,
+        index: VerticalGridLineIndex,
+    ) -> Result<(), EngravingError> {
+        let (column, grid_line_type) = self
+            .system
+            .vertical_grid_lines
+            .get(index)
+            .map(|grid_line| (grid_line.get_column(), grid_line.get_grid_line_type()))
+            .ok_or(EngravingError::UnknownVerticalGridLine(index))?;
+
+        if self
+            .system
+            .is_referenced(LayoutElement::VerticalGridLine(index))
+        {
+            return Err(EngravingError::RemovingReferencedElement(
+                LayoutElement::VerticalGridLine(index),
+            ));
+        }
+
+        let previous_grid_line = std::mem::replace(
+            &mut self.system.vertical_grid_lines[index],
+            VerticalGridLine::new(column, grid_line_type),
+        );
+
+        if let Err(err) = self.validate() {
+            self.system.vertical_grid_lines[index] = previous_grid_line;
+
+            return Err(err);
+        }
+
+        self.solver.remove_vertical_grid_line_constraints(index)?;
+
+        Ok(())
+    }
+
+    /// Re-engraves the layout after any edits made since the previous engraving,
+    /// returning only those Engravables whose positions or content have changed.
+    ///
+    /// Collision resolution and system justification depend on the layout as a whole,
+    /// so both are recalculated in full. Debugging guides for grid lines and block
+    /// outlines are not included; use get_engraved_system() to retrieve them.
+    pub fn engrave(&mut self) -> Result<Vec<EngravingSessionChange>, EngravingError> {
+        self.solver.settle(&self.system)?;
+
+        let mut changes = Vec::new();
+
+        for (index, block) in self.system.get_blocks().iter().enumerate() {
+            let position = self.get_engraved_block_position(index);
+
+            if position != self.engraved_block_positions[index] || self.changed_blocks[index] {
+                match position {
+                    Some((top, bottom, start, end)) => {
+                        changes.push(EngravingSessionChange::Engraved(
+                            index,
+                            block.get_layer(),
+                            Engravable::new_from_block(block, top, bottom, start, end),
+                        ));
+                    }
+                    None => {
+                        if self.engraved_block_positions[index].is_some() {
+                            changes.push(EngravingSessionChange::Removed(index));
+                        }
+                    }
+                }
+            }
+
+            self.engraved_block_positions[index] = position;
+            self.changed_blocks[index] = false;
+        }
+
+        // Stems lengthen and shorten with their beams, so beams are re-engraved whenever
+        // their position changes.
+
+        for index in 0..self.system.get_beams().len() {
+            let line = self.system.get_solved_beam_line(index, &self.solver);
+
+            if line != self.engraved_beam_lines[index] {
+                if let Some(engravable) =
+                    line.and_then(|line| self.system.create_engravable_for_beam(index, line))
+                {
+                    changes.push(EngravingSessionChange::BeamEngraved(index, engravable));
+                }
+            }
+
+            self.engraved_beam_lines[index] = line;
+        }
+
+        // Spanners move with the grid lines they are locked to.
+
+        for index in 0..self.system.get_spanners().len() {
+            let spanner = self.system.get_solved_spanner(index, &self.solver);
+
+            if spanner != self.engraved_spanners[index] {
+                if let Some(spanner) = spanner.as_ref() {
+                    changes.push(EngravingSessionChange::SpannerEngraved(
+                        index,
+                        self.system.create_engravables_for_spanner(index, spanner),
+                    ));
+                }
+            }
+
+            self.engraved_spanners[index] = spanner;
+        }
+
+        // Curves are reshaped around the Blocks between their anchors on every engraving,
+        // so they are re-engraved whenever their shape changes.
+
+        for (index, shape) in self.solver.curve_shapes.iter().enumerate() {
+            if self.engraved_curve_shapes.get(index) != Some(shape) {
+                changes.push(EngravingSessionChange::CurveEngraved(
+                    index,
+                    self.system.create_engravables_for_curve(index, shape),
+                ));
+            }
+        }
+
+        self.engraved_curve_shapes = self.solver.curve_shapes.clone();
+
+        Ok(changes)
+    }
+
+    /// Checks the structure of the LayoutSystem after an edit, so that an edit leaving
+    /// the layout invalid - for example, by closing a cycle of Lock* constraints - is rejected
+    /// before it reaches the solver.
+    #[inline]
+    fn validate(&self) -> Result<(), EngravingError> {
+        self.system
+            .validate()
+            .map_err(EngravingError::InvalidLayout)
+    }
+
+    /// Returns the solved (top, bottom, start, end) position of the Block at the given index,
+    /// or None if the Block is not included in the engraving.
+    fn get_engraved_block_position(
+        &self,
+        index: BlockIndex,
+    ) -> Option<(StaveSpaces, StaveSpaces, StaveSpaces, StaveSpaces)> {
+        let block = self.system.get_blocks().get(index)?;
+
+        if self.removed_blocks[index]
+            || !block.is_visible()
+            || (block.is_spacing_block() && !self.system.debug_do_show_rhythmic_spacing)
+        {
+            return None;
+        }
+
+        let value = |variables: &[Variable]| {
+            variables
+                .get(index)
+                .map(|variable| StaveSpaces::new(self.solver.solver.get_value(*variable) as f32))
+        };
+
+        Some((
+            value(&self.solver.block_top_position_variables)?,
+            value(&self.solver.block_bottom_position_variables)?,
+            value(&self.solver.block_start_position_variables)?,
+            value(&self.solver.block_end_position_variables)?,
+        ))
+    }
+}
+
+impl Debug for EngravingSession {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // The linear constraint solver cannot be printed, so we show the layout
+        // and the most recently engraved state only.
+
+        f.debug_struct("EngravingSession")
+            .field("system", &self.system)
+            .field("removed_blocks", &self.removed_blocks)
+            .field("changed_blocks", &self.changed_blocks)
+            .field("engraved_block_positions", &self.engraved_block_positions)
+            .field("engraved_beam_lines", &self.engraved_beam_lines)
+            .field("engraved_curve_shapes", &self.engraved_curve_shapes)
+            .field("engraved_spanners", &self.engraved_spanners)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A single change to the engraving of a LayoutSystem, as returned by EngravingSession::engrave().
+#[derive(Debug)]
+pub enum EngravingSessionChange {
+    /// The Block at the given index has been engraved for the first time, or its position
+    /// or content has changed since it was last engraved. The new Engravable belongs
+    /// in the given layer.
+    Engraved(BlockIndex, BlockLayer, Engravable),
+
+    /// The Block at the given index is no longer engraved, either because it was removed
+    /// from the layout or because it is no longer visible.
+    Removed(BlockIndex),
+
+    /// The beam at the given index has moved since it was last engraved. The new
+    /// Engravable belongs in the foreground layer.
+    BeamEngraved(BeamIndex, Engravable),
+
+    /// The curve at the given index has changed shape since it was last engraved.
+    /// The new Engravables, one for each line of the flattened curve, belong in the
+    /// foreground layer.
+    CurveEngraved(CurveIndex, Vec<Engravable>),
+
+    /// The spanner at the given index has moved since it was last engraved. The new
+    /// Engravables, one for each line of the spanner, belong in the foreground layer.
+    SpannerEngraved(SpannerIndex, Vec<Engravable>),
+}
+
+/// The errors that can occur while engraving a LayoutSystem.
+///
+/// EngravingError is Clone but no longer Copy. UnresolvedCollisions, ConflictingConstraints,
+/// and InvalidLayout each carry a diagnosis of unbounded size - every pair of Blocks still
+/// colliding, the smallest set of conflicting constraints, and every structural problem
+/// found - which no fixed-size payload could hold without losing part of it. Callers that
+/// copied an EngravingError out of a reference must clone it instead; callers that only
+/// match on or display the error are unaffected.
+#[derive(Debug, Clone)]
+pub enum EngravingError {
+    UnknownHorizontalGridLine(HorizontalGridLineIndex),
+    UnknownVerticalGridLine(VerticalGridLineIndex),
+    UnknownBlockTopPosition(BlockIndex),
+    UnknownBlockBottomPosition(BlockIndex),
+    UnknownBlockStartPosition(BlockIndex),
+    UnknownBlockEndPosition(BlockIndex),
+    UnknownBlock(BlockIndex),
+    UnknownTopEdge(HorizontalGridLineIndex),
+    UnknownLeadingEdge(VerticalGridLineIndex),
+    UnknownBeam(BeamIndex),
+    UnknownSpanner(SpannerIndex),
+    UnknownCurve(CurveIndex),
+    AddConstraintErrorOnHorizontalGridLine(AddConstraintError, HorizontalGridLineIndex),
+    AddConstraintErrorOnVerticalGridLine(AddConstraintError, VerticalGridLineIndex),
+    AddConstraintErrorOnBlock(AddConstraintError, BlockIndex),
+    AddConstraintErrorOnBeam(AddConstraintError, BeamIndex),
+    AddConstraintErrorOnSpanner(AddConstraintError, SpannerIndex),
+    RemoveConstraintErrorOnHorizontalGridLine(RemoveConstraintError, HorizontalGridLineIndex),
+    RemoveConstraintErrorOnVerticalGridLine(RemoveConstraintError, VerticalGridLineIndex),
+    RemoveConstraintErrorOnBlock(RemoveConstraintError, BlockIndex),
+    RemoveConstraintErrorOnBeam(RemoveConstraintError, BeamIndex),
+    DefineJustificationError(AddEditVariableError),
+    ApplyJustificationError(SuggestValueError),
+    UnresolvedCollisions(Vec<(BlockIndex, BlockIndex)>),
+    SystemOverfull(StaveSpaces),
+    ConflictingConstraints(Vec<ConstraintRecord>),
+    InvalidLayout(Vec<LayoutValidationError>),
+    RemovingReferencedElement(LayoutElement),
+}
+
+impl Display for EngravingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                EngravingError::UnknownHorizontalGridLine(index) =>
+                    format!("Unknown horizontal grid line variable index: {}", index),
+                EngravingError::UnknownVerticalGridLine(index) =>
+                    format!("Unknown vertical grid line variable index: {}", index),
+                EngravingError::UnknownBlockTopPosition(index) =>
+                    format!("Unknown block top position variable index: {}", index),
+                EngravingError::UnknownBlockBottomPosition(index) =>
+                    format!("Unknown block bottom position variable index: {}", index),
+                EngravingError::UnknownBlockStartPosition(index) =>
+                    format!("Unknown block start position variable index: {}", index),
+                EngravingError::UnknownBlockEndPosition(index) =>
+                    format!("Unknown block end position variable index: {}", index),
+                EngravingError::UnknownBlock(index) =>
+                    format!("Unknown block index: {}", index),
+                EngravingError::UnknownTopEdge(index) =>
+                    format!("System top edge refers to unknown horizontal grid line: {}", index),
+                EngravingError::UnknownLeadingEdge(index) =>
+                    format!("System leading edge refers to unknown vertical grid line: {}", index),
+                EngravingError::UnknownBeam(index) =>
+                    format!("Unknown beam index: {}", index),
+                EngravingError::UnknownSpanner(index) =>
+                    format!("Unknown spanner index: {}", index),
+                EngravingError::UnknownCurve(index) =>
+                    format!("Unknown curve index: {}", index),
+                EngravingError::AddConstraintErrorOnHorizontalGridLine(err, index) => match err {
+                    AddConstraintError::DuplicateConstraint => format!(
+                        "Error processing constraint on horizontal grid line {}: Duplicate constraint",
+                        index
+                    ),
+                    AddConstraintError::UnsatisfiableConstraint =>
+                        format!("Error processing constraint on horizontal grid line {}: Unsatisfiable constraint", index),
+                    AddConstraintError::InternalSolverError(err) =>
+                        format!("Error processing constraint on horizontal grid line {}: Internal solver error: {}",index, err),
+                },
+                EngravingError::AddConstraintErrorOnVerticalGridLine(err, index) => match err {
+                    AddConstraintError::DuplicateConstraint =>
+                        format!("Error processing constraint on vertical grid line {}: Duplicate constraint", index),
+                    AddConstraintError::UnsatisfiableConstraint =>
+                        format!("Error processing constraint on vertical grid line {}: Unsatisfiable constraint", index),
+                    AddConstraintError::InternalSolverError(err) =>
+                        format!("Error processing constraint on vertical grid line {}: Internal solver error: {}", index, err),
+                },
+                EngravingError::AddConstraintErrorOnBlock(err, index) => match err {
+                    AddConstraintError::DuplicateConstraint =>
+                        format!("Error processing constraint on block {}: Duplicate constraint", index),
+                    AddConstraintError::UnsatisfiableConstraint =>
+                        format!("Error processing constraint on block {}: Unsatisfiable constraint", index),
+                    AddConstraintError::InternalSolverError(err) =>
+                        format!("Error processing constraint on block {}: Internal solver error: {}", index, err),
+                },
+                EngravingError::AddConstraintErrorOnBeam(err, index) => match err {
+                    AddConstraintError::DuplicateConstraint =>
+                        format!("Error processing constraint on beam {}: Duplicate constraint", index),
+                    AddConstraintError::UnsatisfiableConstraint =>
+                        format!("Error processing constraint on beam {}: Unsatisfiable constraint", index),
+                    AddConstraintError::InternalSolverError(err) =>
+                        format!("Error processing constraint on beam {}: Internal solver error: {}", index, err),
+                },
+                EngravingError::AddConstraintErrorOnSpanner(err, index) => match err {
+                    AddConstraintError::DuplicateConstraint =>
+                        format!("Error processing constraint on spanner {}: Duplicate constraint", index),
+                    AddConstraintError::UnsatisfiableConstraint =>
+                        format!("Error processing constraint on spanner {}: Unsatisfiable constraint", index),
+                    AddConstraintError::InternalSolverError(err) =>
+                        format!("Error processing constraint on spanner {}: Internal solver error: {}", index, err),
+                },
+                EngravingError::RemoveConstraintErrorOnHorizontalGridLine(err, index) => match err {
+                    RemoveConstraintError::UnknownConstraint =>
+                        format!("Error removing constraint on horizontal grid line {}: Unknown constraint", index),
+                    RemoveConstraintError::InternalSolverError(err) =>
+                        format!("Error removing constraint on horizontal grid line {}: Internal solver error: {}", index, err),
+                },
+                EngravingError::RemoveConstraintErrorOnVerticalGridLine(err, index) => match err {
+                    RemoveConstraintError::UnknownConstraint =>
+                        format!("Error removing constraint on vertical grid line {}: Unknown constraint", index),
+                    RemoveConstraintError::InternalSolverError(err) =>
+                        format!("Error removing constraint on vertical grid line {}: Internal solver error: {}", index, err),
+                },
+                EngravingError::RemoveConstraintErrorOnBlock(err, index) => match err {
+                    RemoveConstraintError::UnknownConstraint =>
+                        format!("Error removing constraint on block {}: Unknown constraint", index),
+                    RemoveConstraintError::InternalSolverError(err) =>
+                        format!("Error removing constraint on block {}: Internal solver error: {}", index, err),
+                },
+                EngravingError::RemoveConstraintErrorOnBeam(err, index) => match err {
+                    RemoveConstraintError::UnknownConstraint =>
+                        format!("Error removing constraint on beam {}: Unknown constraint", index),
+                    RemoveConstraintError::InternalSolverError(err) =>
+                        format!("Error removing constraint on beam {}: Internal solver error: {}", index, err),
+                },
+                EngravingError::DefineJustificationError(err) => match err {
+                    AddEditVariableError::DuplicateEditVariable =>
+                        "Error defining system justification: Duplicate edit variable".to_string(),
+                    AddEditVariableError::BadRequiredStrength =>
+                        "Error defining system justification: Invalid required strength".to_string(),
+                },
+                EngravingError::ApplyJustificationError(err) => match err {
+                    SuggestValueError::UnknownEditVariable =>
+                        "Error applying system justification: Unknown edit variable".to_string(),
+                    SuggestValueError::InternalSolverError(err) =>
+                        format!("Error applying system justification: Internal solver error: {}", err),
+                },
+                EngravingError::UnresolvedCollisions(collisions) => format!(
+                    "Unable to resolve collisions between block indices: {}",
+                    collisions
+                        .iter()
+                        .map(|(index_a, index_b)| format!("({}, {})", index_a, index_b))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                EngravingError::SystemOverfull(overflow) => format!(
+                    "System exceeds target system width by {} stave spaces at maximum compression",
+                    overflow.value
+                ),
+                EngravingError::ConflictingConstraints(records) => format!(
+                    "Unsatisfiable constraint; the smallest set of conflicting constraints is: {}",
+                    records
+                        .iter()
+                        .map(|record| record.to_string())
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                ),
+                EngravingError::InvalidLayout(errors) => format!(
+                    "Invalid layout: {}",
+                    errors
+                        .iter()
+                        .map(|error| error.to_string())
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                ),
+                EngravingError::RemovingReferencedElement(element) => format!(
+                    "Cannot remove {}, which is still referred to by the layout",
+                    element
+                ),
+            }
+        )
+    }
+}
+
+impl Error for EngravingError {}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::concepts::border::Border;
+    use crate::models::display::concepts::color::Color;
+    use crate::models::display::concepts::markup::MarkedUpLine;
+    use crate::models::display::concepts::stave_spaces::{
+        AsStaveSpacesExt, StavePoint, StaveSpaces, STAVE_SPACES_ZERO,
+    };
+    use crate::models::display::concepts::stroke::StrokeStyle;
+    use crate::models::display::engraving::engravable::{Engravable, EngravableItem};
+    use crate::models::display::engraving::region::system::EngravedSystem;
+    use crate::models::display::glyphs::bravura::Bravura;
+    use crate::models::display::glyphs::smufl_font::SmuflFont;
+    use crate::models::display::glyphs::Glyph;
+    use crate::models::display::grid::horizontal::{
+        HorizontalGridLine, HorizontalGridLineIndex, HorizontalGridLineType,
+    };
+    use crate::models::display::grid::vertical::{
+        VerticalGridLine, VerticalGridLineIndex, VerticalGridLineType,
+    };
+    use crate::models::display::layout::beam::{BeamDirection, LayoutBeam};
+    use crate::models::display::layout::block::glyph::GlyphBlock;
+    use crate::models::display::layout::block::line::LineBlock;
+    use crate::models::display::layout::block::markup::MarkupBlock;
+    use crate::models::display::layout::block::spacing::SpacingBlock;
+    use crate::models::display::layout::block::{Block, BlockConstraint, BlockEnum, BlockLayer};
+    use crate::models::display::layout::curve::{
+        CurveDirection, CurveKind, LayoutCurve, CURVE_FLATTENING_SEGMENTS,
+    };
+    use crate::models::display::layout::diagnostics::{
+        BeamVariable, BlockEdge, ConstraintOrigin, LayoutTrace, TraceTerm, TraceVariableKind,
+    };
+    use crate::models::display::layout::rhythmic_spacing::RhythmicSpacingModel;
+    use crate::models::display::layout::skyline::Skyline;
+    use crate::models::display::layout::snapshot::assert_matches_golden_file;
+    use crate::models::display::layout::spanner::{LayoutSpanner, SpannerAnchor, SpannerKind};
+    use crate::models::display::layout::strength::ConstraintStrength;
+    use crate::models::display::layout::system::{
+        BlockIndex, EngravingError, EngravingSessionChange, LayoutSystem, LayoutSystemSolver,
+    };
+    use crate::models::display::layout::validation::{
+        LayoutElement, LayoutValidationError, LayoutVariable,
+    };
+    use crate::models::display::stylesheet::stylesheet_option::SystemJustification;
+    use crate::models::music::concepts::ticks::{AsTicksExt, Ticks, TICKS_ZERO};
+    use crate::protos::display::concepts::LineLayout;
+    use crate::protos::music::concepts::NotatedDuration;
+    use cassowary::strength::REQUIRED;
+    use cassowary::WeightedRelation::EQ;
+    use cassowary::{Solver, Variable};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_engrave() {
+        // Simulate, by constructing blocks and grid lines by hand, a system containing
+        // two bars of 2/4 in two voices across two staves. Check computed engraved positions.
+
+        let font = Bravura::new();
+
+        let column_separation = 0.25.as_stave_spaces();
+
+        let stave_separation = 3.as_stave_spaces();
+
+        let rhythmic_space_separation = 1.5.as_stave_spaces();
+
+        let h0_system_top = HorizontalGridLine::new(HorizontalGridLineType::SystemTop);
+
+        let mut h1_system_bottom = HorizontalGridLine::new(HorizontalGridLineType::SystemBottom);
+
+        let v0_system_start = VerticalGridLine::new(0, VerticalGridLineType::SystemStart);
+
+        let mut v1_systemic_line = VerticalGridLine::new(0, VerticalGridLineType::SystemicLine);
+
+        v1_systemic_line.lock_to_grid_line(0);
+
+        let mut v2_system_end = VerticalGridLine::new(1, VerticalGridLineType::SystemEnd);
+
+        // Create grid lines and blocks for stavelines on stave 1.
+
+        let mut h2_s1_l5 = HorizontalGridLine::new(HorizontalGridLineType::Staveline5);
+        let mut h3_s1_l4 = HorizontalGridLine::new(HorizontalGridLineType::Staveline4);
+        let mut h4_s1_l3 = HorizontalGridLine::new(HorizontalGridLineType::Staveline3);
+        let mut h5_s1_l2 = HorizontalGridLine::new(HorizontalGridLineType::Staveline2);
+        let mut h6_s1_l1 = HorizontalGridLine::new(HorizontalGridLineType::Staveline1);
+
+        h2_s1_l5.lock_to_grid_line(0);
+        h3_s1_l4.lock_below_grid_line(2, 1.as_stave_spaces());
+        h4_s1_l3.lock_below_grid_line(3, 1.as_stave_spaces());
+        h5_s1_l2.lock_below_grid_line(4, 1.as_stave_spaces());
+        h6_s1_l1.lock_below_grid_line(5, 1.as_stave_spaces());
+
+        let b0_s1_l5 = create_staveline_block(2, 1, 2);
+        let b1_s1_l4 = create_staveline_block(3, 1, 2);
+        let b2_s1_l3 = create_staveline_block(4, 1, 2);
+        let b3_s1_l2 = create_staveline_block(5, 1, 2);
+        let b4_s1_l1 = create_staveline_block(6, 1, 2);
+
+        // Create lyric underlay grid lines between staves 1 and 2.
+
+        let mut h12_lyric_top =
+            HorizontalGridLine::new(HorizontalGridLineType::LyricBelowStaveLine1Top);
+
+        h12_lyric_top.lock_below_grid_line(6, stave_separation);
+
+        let mut h13_lyric_bottom =
+            HorizontalGridLine::new(HorizontalGridLineType::LyricBelowStaveLine1Bottom);
+
+        h13_lyric_bottom.float_below_grid_line(12, 1.as_stave_spaces());
+
+        // Create grid lines and blocks for stavelines on stave 2.
+
+        let mut h7_s2_l5 = HorizontalGridLine::new(HorizontalGridLineType::Staveline5);
+        let mut h8_s2_l4 = HorizontalGridLine::new(HorizontalGridLineType::Staveline4);
+        let mut h9_s2_l3 = HorizontalGridLine::new(HorizontalGridLineType::Staveline3);
+        let mut h10_s2_l2 = HorizontalGridLine::new(HorizontalGridLineType::Staveline2);
+        let mut h11_s2_l1 = HorizontalGridLine::new(HorizontalGridLineType::Staveline1);
+
+        h7_s2_l5.lock_below_grid_line(13, stave_separation);
+        h8_s2_l4.lock_below_grid_line(7, 1.as_stave_spaces());
+        h9_s2_l3.lock_below_grid_line(8, 1.as_stave_spaces());
+        h10_s2_l2.lock_below_grid_line(9, 1.as_stave_spaces());
+        h11_s2_l1.lock_below_grid_line(10, 1.as_stave_spaces());
+
+        let b5_s2_l5 = create_staveline_block(7, 1, 2);
+        let b6_s2_l4 = create_staveline_block(8, 1, 2);
+        let b7_s2_l3 = create_staveline_block(9, 1, 2);
+        let b8_s2_l2 = create_staveline_block(10, 1, 2);
+        let b9_s2_l1 = create_staveline_block(11, 1, 2);
+
+        h1_system_bottom.lock_to_grid_line(11);
+
+        let b10_systemic_line = create_systemic_line_block(0, 1, 1);
+
+        // Place blocks on staves in relation to stavelines and columns.
+
+        // First bar of 2/4. Let's put a clef and time signature on each stave.
+
+        let mut v3_bar1_clef_start =
+            VerticalGridLine::new(2, VerticalGridLineType::ClefColumnStart);
+
+        v3_bar1_clef_start.float_after_grid_line(1, column_separation);
+
+        let mut v4_bar1_clef_end = VerticalGridLine::new(2, VerticalGridLineType::ClefColumnEnd);
+
+        v4_bar1_clef_end.float_after_grid_line(3, STAVE_SPACES_ZERO);
+
+        let b11_bar1_stave1_clef =
+            create_glyph_block_on_staveline(5, 3, 4, TICKS_ZERO, &font, Glyph::GClef);
+
+        let b12_bar1_stave2_clef =
+            create_glyph_block_on_staveline(8, 3, 4, TICKS_ZERO, &font, Glyph::FClef);
+
+        let mut v5_bar1_time_sig_start =
+            VerticalGridLine::new(3, VerticalGridLineType::TimeSignatureColumnStart);
+
+        v5_bar1_time_sig_start.float_after_grid_line(4, column_separation);
+
+        let mut v6_bar1_time_sig_end =
+            VerticalGridLine::new(3, VerticalGridLineType::TimeSignatureColumnEnd);
+
+        v6_bar1_time_sig_end.float_after_grid_line(5, STAVE_SPACES_ZERO);
+
+        let b13_bar1_stave1_time_sig_numerator =
+            create_glyph_block_on_staveline(3, 5, 6, TICKS_ZERO, &font, Glyph::TimeSig2Numerator);
+
+        let b14_bar1_stave1_time_sig_denominator =
+            create_glyph_block_on_staveline(5, 5, 6, TICKS_ZERO, &font, Glyph::TimeSig4Denominator);
+
+        let b15_bar1_stave2_time_sig_numerator =
+            create_glyph_block_on_staveline(8, 5, 6, TICKS_ZERO, &font, Glyph::TimeSig2Numerator);
+
+        let b16_bar1_stave2_time_sig_denominator = create_glyph_block_on_staveline(
+            10,
+            5,
+            6,
+            TICKS_ZERO,
+            &font,
+            Glyph::TimeSig4Denominator,
+        );
+
+        // In this test, we can only create noteheads on stavelines (not above or below
+        // stavelines), and we do not include stems, so our test musical data is
+        // rather artificial. The musical content will be:
+
+        // voice 1 = { G2 T:2/4 g4 bes | g ees | }
+        // voice 2 = { G2 T:2/4 f2 | d }
+
+        // Add noteheads in bar 1, voice 1.
+
+        let mut v7_bar1_note1_start =
+            VerticalGridLine::new(4, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        v7_bar1_note1_start.float_after_grid_line(6, column_separation);
+
+        let mut v8_bar1_note1_end =
+            VerticalGridLine::new(4, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        v8_bar1_note1_end.float_after_grid_line(7, STAVE_SPACES_ZERO);
+
+        let mut b17_bar1_voice1_notehead1 =
+            create_glyph_block_on_staveline(5, 7, 8, TICKS_ZERO, &font, Glyph::NoteheadBlack);
+
+        b17_bar1_voice1_notehead1.set_end_padding(rhythmic_space_separation); // Simulate rhythmic padding.
+
+        let mut v9_bar1_note2_start =
+            VerticalGridLine::new(5, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        v9_bar1_note2_start.float_after_grid_line(8, column_separation);
+
+        let mut v10_bar1_note2_end =
+            VerticalGridLine::new(5, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        v10_bar1_note2_end.float_after_grid_line(9, STAVE_SPACES_ZERO);
+
+        let mut b18_bar1_voice1_notehead2 = create_glyph_block_on_staveline(
+            4,
+            9,
+            10,
+            NotatedDuration::Crotchet.as_ticks(),
+            &font,
+            Glyph::NoteheadBlack,
+        );
+
+        b18_bar1_voice1_notehead2.set_end_padding(rhythmic_space_separation); // Simulate rhythmic padding.
+
+        // Add notehead in bar 1, voice 2.
+
+        let b19_bar1_voice2_notehead =
+            create_glyph_block_on_staveline(8, 7, 8, TICKS_ZERO, &font, Glyph::NoteheadHalf);
+
+        // Add barline at end of bar 1.
+
+        let mut v11_bar1_barline_start =
+            VerticalGridLine::new(6, VerticalGridLineType::BarlineStart);
+
+        v11_bar1_barline_start.float_after_grid_line(10, column_separation);
+
+        let mut v12_bar1_barline_end = VerticalGridLine::new(6, VerticalGridLineType::BarlineEnd);
+
+        v12_bar1_barline_end.float_after_grid_line(11, STAVE_SPACES_ZERO);
+
+        let b20_bar1_barline =
+            create_barline_block(0, 1, 11, 12, NotatedDuration::Minim.as_ticks());
+
+        // Add noteheads in bar 2, voice 1.
+
+        let mut v13_bar2_note1_start =
+            VerticalGridLine::new(7, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        v13_bar2_note1_start.float_after_grid_line(12, column_separation);
+
+        let mut v14_bar2_note1_end =
+            VerticalGridLine::new(7, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        v14_bar2_note1_end.float_after_grid_line(13, STAVE_SPACES_ZERO);
+
+        let mut b21_bar2_voice1_notehead1 = create_glyph_block_on_staveline(
+            5,
+            13,
+            14,
+            NotatedDuration::Minim.as_ticks(),
+            &font,
+            Glyph::NoteheadBlack,
+        );
+
+        b21_bar2_voice1_notehead1.set_end_padding(rhythmic_space_separation); // Simulate rhythmic padding.
+
+        let mut v15_bar2_note2_start =
+            VerticalGridLine::new(8, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        v15_bar2_note2_start.float_after_grid_line(14, column_separation);
+
+        let mut v16_bar2_note2_end =
+            VerticalGridLine::new(8, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        v16_bar2_note2_end.float_after_grid_line(15, STAVE_SPACES_ZERO);
+
+        let mut b22_bar2_voice1_notehead2 = create_glyph_block_on_staveline(
+            6,
+            15,
+            16,
+            NotatedDuration::Minim * 1.5,
+            &font,
+            Glyph::NoteheadBlack,
+        );
+
+        b22_bar2_voice1_notehead2.set_end_padding(rhythmic_space_separation); // Simulate rhythmic padding.
+
+        // Add notehead in bar 2, voice 2.
+
+        let b23_bar1_voice2_notehead2 = create_glyph_block_on_staveline(
+            9,
+            13,
+            14,
+            NotatedDuration::Minim.as_ticks(),
+            &font,
+            Glyph::NoteheadHalf,
+        );
+
+        // Add barline at end of bar 2.
+
+        let mut v17_bar2_barline_start =
+            VerticalGridLine::new(9, VerticalGridLineType::BarlineStart);
+
+        v17_bar2_barline_start.float_after_grid_line(16, column_separation);
+
+        let mut v18_bar2_barline_end = VerticalGridLine::new(9, VerticalGridLineType::BarlineEnd);
+
+        v18_bar2_barline_end.float_after_grid_line(17, STAVE_SPACES_ZERO);
+
+        let b24_bar2_barline =
+            create_barline_block(0, 1, 17, 18, NotatedDuration::Minim.as_ticks());
+
+        // Create lyrics underneath voice 1 noteheads in bar 1. To do this,
+        // we create a vertical grid line locked at the center of the target notehead,
+        // then center a markup block containing the lyric on that grid line.
+        // We float the lyric inside the grid lines that denote the start and end
+        // of each notehead's containing column.
+
+        let v19_bar1_voice1_notehead1_center =
+            VerticalGridLine::new(4, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        b17_bar1_voice1_notehead1.lock_horizontal_center_to_grid_line(19);
+
+        let b25_bar1_lyric1 = create_lyric_underlay_block(12, 13, 7, 19, 8, "A");
+
+        let v20_bar1_voice1_notehead2_center =
+            VerticalGridLine::new(5, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        b18_bar1_voice1_notehead2.lock_horizontal_center_to_grid_line(20);
+
+        let b26_bar1_lyric2 = create_lyric_underlay_block(12, 13, 9, 20, 10, "ve");
+
+        // Similarly, create lyrics underneath voice 1 noteheads in bar2.
+        // Let's make the lyric underneath the first notehead a silly length, to test
+        // that the grid lines either side of the notehead push apart to accommodate it.
+
+        let v21_bar2_voice1_notehead1_center =
+            VerticalGridLine::new(7, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        b21_bar2_voice1_notehead1.lock_horizontal_center_to_grid_line(21);
+
+        let b27_bar2_lyric1 =
+            create_lyric_underlay_block(12, 13, 13, 21, 14, "A lyric of very silly length");
+
+        let v22_bar2_voice1_notehead2_center =
+            VerticalGridLine::new(8, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        b22_bar2_voice1_notehead2.lock_horizontal_center_to_grid_line(22);
+
+        let b28_bar2_lyric2 = create_lyric_underlay_block(12, 13, 15, 22, 16, "Short");
+
+        // Connect the end of the system to the trailing edge of the second barline.
+        // Since all stavelines are connected to the end of the system, this will
+        // set the width of all stavelines.
+
+        v2_system_end.float_after_grid_line(18, STAVE_SPACES_ZERO);
+
+        // Add all grid lines and blocks to layout.
+
+        let layout = LayoutSystem::new(
+            0,
+            0.as_ticks(),
+            0.as_ticks(),
+            SystemJustification::AlignStart,
+            100.as_stave_spaces(),
+            vec![
+                h0_system_top,
+                h1_system_bottom,
+                h2_s1_l5,
+                h3_s1_l4,
+                h4_s1_l3,
+                h5_s1_l2,
+                h6_s1_l1,
+                h7_s2_l5,
+                h8_s2_l4,
+                h9_s2_l3,
+                h10_s2_l2,
+                h11_s2_l1,
+                h12_lyric_top,
+                h13_lyric_bottom,
+            ],
+            vec![
+                v0_system_start,
+                v1_systemic_line,
+                v2_system_end,
+                v3_bar1_clef_start,
+                v4_bar1_clef_end,
+                v5_bar1_time_sig_start,
+                v6_bar1_time_sig_end,
+                v7_bar1_note1_start,
+                v8_bar1_note1_end,
+                v9_bar1_note2_start,
+                v10_bar1_note2_end,
+                v11_bar1_barline_start,
+                v12_bar1_barline_end,
+                v13_bar2_note1_start,
+                v14_bar2_note1_end,
+                v15_bar2_note2_start,
+                v16_bar2_note2_end,
+                v17_bar2_barline_start,
+                v18_bar2_barline_end,
+                v19_bar1_voice1_notehead1_center,
+                v20_bar1_voice1_notehead2_center,
+                v21_bar2_voice1_notehead1_center,
+                v22_bar2_voice1_notehead2_center,
+            ],
+            0,
+            0,
+            vec![
+                b0_s1_l5.into(),
+                b1_s1_l4.into(),
+                b2_s1_l3.into(),
+                b3_s1_l2.into(),
+                b4_s1_l1.into(),
+                b5_s2_l5.into(),
+                b6_s2_l4.into(),
+                b7_s2_l3.into(),
+                b8_s2_l2.into(),
+                b9_s2_l1.into(),
+                b10_systemic_line.into(),
+                b11_bar1_stave1_clef.into(),
+                b12_bar1_stave2_clef.into(),
+                b13_bar1_stave1_time_sig_numerator.into(),
+                b14_bar1_stave1_time_sig_denominator.into(),
+                b15_bar1_stave2_time_sig_numerator.into(),
+                b16_bar1_stave2_time_sig_denominator.into(),
+                b17_bar1_voice1_notehead1.into(),
+                b18_bar1_voice1_notehead2.into(),
+                b19_bar1_voice2_notehead.into(),
+                b20_bar1_barline.into(),
+                b21_bar2_voice1_notehead1.into(),
+                b22_bar2_voice1_notehead2.into(),
+                b23_bar1_voice2_notehead2.into(),
+                b24_bar2_barline.into(),
+                b25_bar1_lyric1.into(),
+                b26_bar1_lyric2.into(),
+                b27_bar2_lyric1.into(),
+                b28_bar2_lyric2.into(),
+            ],
+            false,
+            false,
+            false,
+            false,
+        );
+
+        // Check computed engraved positions.
+
+        let solution = layout.engrave();
+
+        assert!(solution.is_ok());
+
+        // Let's check the staveline positions first.
+
+        assert_eq!(unwrap_h_line(&solution, 0), STAVE_SPACES_ZERO); // h0_system_top
+        assert_eq!(unwrap_h_line(&solution, 1), unwrap_h_line(&solution, 11)); // h1_system_bottom should be aligned to last staveline, h11_s2_l1
+        assert_eq!(unwrap_h_line(&solution, 2), unwrap_h_line(&solution, 0)); // h2_s1_l5 should be at the system top
+        assert_eq!(
+            unwrap_h_line(&solution, 3),
+            unwrap_h_line(&solution, 2) + 1.as_stave_spaces()
+        ); // h3_s1_l4 == h2_s1_l5 + 1
+        assert_eq!(
+            unwrap_h_line(&solution, 4),
+            unwrap_h_line(&solution, 3) + 1.as_stave_spaces()
+        ); // h4_s1_l3 == h3_s1_l4 + 1
+        assert_eq!(
+            unwrap_h_line(&solution, 5),
+            unwrap_h_line(&solution, 4) + 1.as_stave_spaces()
+        ); // h5_s1_l2 == h4_s1_l3 + 1
+        assert_eq!(
+            unwrap_h_line(&solution, 6),
+            unwrap_h_line(&solution, 5) + 1.as_stave_spaces()
+        ); // h6_s1_l1 == h5_s1_l2 + 1
+        assert_eq!(
+            unwrap_h_line(&solution, 12),
+            unwrap_h_line(&solution, 6) + stave_separation
+        ); // h12_lyric_top == h6_s1_l1 + stave_separation
+        assert_eq!(
+            unwrap_h_line(&solution, 13),
+            unwrap_h_line(&solution, 12) + 1.as_stave_spaces()
+        ); // h13_lyric_bottom == h12_lyric_top + 1
+        assert_eq!(
+            unwrap_h_line(&solution, 7),
+            unwrap_h_line(&solution, 13) + stave_separation
+        ); // h7_s2_l5 == h13_lyric_bottom + stave_separation
+        assert_eq!(
+            unwrap_h_line(&solution, 8),
+            unwrap_h_line(&solution, 7) + 1.as_stave_spaces()
+        ); // h8_s2_l4 == h7_s2_l5 + 1
+        assert_eq!(
+            unwrap_h_line(&solution, 9),
+            unwrap_h_line(&solution, 8) + 1.as_stave_spaces()
+        ); // h9_s2_13 == h8_s2_l4 + 1
+        assert_eq!(
+            unwrap_h_line(&solution, 10),
+            unwrap_h_line(&solution, 9) + 1.as_stave_spaces()
+        ); // h10_s2_l2 == h9_s2_l3 + 1
+        assert_eq!(
+            unwrap_h_line(&solution, 11),
+            unwrap_h_line(&solution, 10) + 1.as_stave_spaces()
+        ); // h11_s2_l1 == h10_s2_l2 + 1
+
+        // Ok, the computed grid line positions look good; now let's check that the
+        // blocks for the lines are actually positioned on those grid lines.
+
+        let staveline_blocks = vec![
+            // Tuples are (block index, HorizontalGridLineIndex of stave line)
+            (0, 2),
+            (1, 3),
+            (2, 4),
+            (3, 5),
+            (4, 6),
+            (5, 7),
+            (6, 8),
+            (7, 9),
+            (8, 10),
+            (9, 11),
+        ];
+
+        for (block_index, grid_line_index) in staveline_blocks {
+            assert_eq!(
+                unwrap_block_top(&solution, block_index),
+                unwrap_h_line(&solution, grid_line_index)
+            );
+            assert_eq!(
+                unwrap_block_bottom(&solution, block_index),
+                unwrap_h_line(&solution, grid_line_index)
+            );
+
+            // While we're at it, also check that the start and end positions
+            // of the block match the systemic line and system end grid lines.
+
+            assert_eq!(
+                unwrap_block_start(&solution, block_index),
+                unwrap_v_line(&solution, 1)
+            );
+            assert_eq!(
+                unwrap_block_end(&solution, block_index),
+                unwrap_v_line(&solution, 2)
+            );
+        }
+
+        // Check that the systemic line has expanded to cover the entire vertical
+        // range of the system.
+
+        assert_eq!(unwrap_block_start(&solution, 10), STAVE_SPACES_ZERO);
+        assert_eq!(unwrap_block_end(&solution, 10), STAVE_SPACES_ZERO);
+        assert_eq!(unwrap_block_top(&solution, 10), unwrap_h_line(&solution, 0));
+        assert_eq!(
+            unwrap_block_bottom(&solution, 10),
+            unwrap_h_line(&solution, 1)
+        );
+
+        // Now, let's check the vertical grid line positions. We want to be sure
+        // that no columns overlap / collide. So long as every vertical grid line
+        // in sequence has a horizontal position greater than, or equal to, the
+        // preceding vertical grid line, then we can be certain that no columns overlap.
+
+        let ordered_vertical_grid_lines = vec![
+            // The order in which we expect the vertical grid lines to appear,
+            // from system start to system end.
+            0,  // v0_system_start,
+            1,  // v1_systemic_line,
+            3,  // v3_bar1_clef_start,
+            4,  // v4_bar1_clef_end,
+            5,  // v5_bar1_time_sig_start,
+            6,  // v6_bar1_time_sig_end,
+            7,  // v7_bar1_note1_start,
+            19, // v19_bar1_voice1_notehead1_center,
+            8,  // v8_bar1_note1_end,
+            9,  // v9_bar1_note2_start,
+            20, // v20_bar1_voice1_notehead2_center,
+            10, // v10_bar1_note2_end,
+            11, // v11_bar1_barline_start,
+            12, // v12_bar1_barline_end,
+            13, // v13_bar2_note1_start,
+            21, // v21_bar2_voice1_notehead1_center,
+            14, // v14_bar2_note1_end,
+            15, // v15_bar2_note2_start,
+            22, // v22_bar2_voice1_notehead2_center,
+            16, // v16_bar2_note2_end,
+            17, // v17_bar2_barline_start,
+            18, // v18_bar2_barline_end,
+            2,  // v2_system_end,
+        ];
+
+        for (index, grid_line) in ordered_vertical_grid_lines.iter().enumerate() {
+            // Confirm that the position of this grid line ...
+
+            let this_grid_line_position = unwrap_v_line(&solution, *grid_line as usize);
+
+            // ... is greater than or equal to the position of the previous grid line
+            // in the sequence.
+
+            if index > 0 {
+                if let Some(previous_grid_line_position) = ordered_vertical_grid_lines
+                    .get(index - 1)
+                    .map(|grid_line| unwrap_v_line(&solution, *grid_line as usize))
+                {
+                    assert!(this_grid_line_position >= previous_grid_line_position);
+                }
+            }
+        }
+
+        // Next, check the positioning of blocks. We already know that no columns
+        // overlap / collide. So, if every block is correctly positioned within its
+        // designated column, then it follows that no blocks are colliding either.
+
+        let blocks_in_columns = vec![
+            // The vertical grid lines between which each block should be placed.
+            // Tuple is (block index, index of grid line before block, index of grid line after block)
+            (11, 3, 4), // b11_bar1_stave1_clef between v3_bar1_clef_start and v4_bar1_clef_end
+            (12, 3, 4), // b12_bar1_stave2_clef between v3_bar1_clef_start and v4_bar1_clef_end
+            (13, 5, 6), // b13_bar1_stave1_time_sig_numerator between v5_bar1_time_sig_start and v6_bar1_time_sig_end
+            (14, 5, 6), // b14_bar1_stave1_time_sig_denominator between v5_bar1_time_sig_start and v6_bar1_time_sig_end
+            (15, 5, 6), // b15_bar1_stave2_time_sig_numerator between v5_bar1_time_sig_start and v6_bar1_time_sig_end
+            (16, 5, 6), // b16_bar1_stave2_time_sig_denominator between v5_bar1_time_sig_start and v6_bar1_time_sig_end
+            (17, 7, 8), // b17_bar1_voice1_notehead1 between v7_bar1_note1_start and v8_bar1_note1_end
+            (18, 9, 10), // b18_bar1_voice1_notehead2 between v9_bar1_note2_start and v10_bar1_note2_end
+            (19, 7, 8), // b19_bar1_voice2_notehead between v7_bar1_note1_start and v8_bar1_note1_end
+            (20, 11, 12), // b20_bar1_barline between v11_bar1_barline_start and v12_bar1_barline_end
+            (21, 13, 14), // b21_bar2_voice1_notehead1 between v13_bar2_note1_start and v14_bar2_note1_end
+            (22, 15, 16), // b22_bar2_voice1_notehead2 between v15_bar2_note2_start and v16_bar2_note2_end
+            (23, 13, 14), // b23_bar1_voice2_notehead2 between v13_bar2_note1_start and v14_bar2_note1_end
+            (24, 17, 18), // b24_bar2_barline between v17_bar2_barline_start and v18_bar2_barline_end
+            (25, 7, 8),   // b25_bar1_lyric1 between v7_bar1_note1_start and v8_bar1_note1_end
+            (26, 9, 10),  // b26_bar1_lyric2 between v9_bar1_note2_start and v10_bar1_note2_end
+            (27, 13, 14), // b27_bar2_lyric1 between v13_bar2_note1_start and v14_bar2_note1_end
+            (28, 15, 16), // b28_bar2_lyric2 between v15_bar2_note2_start and v16_bar2_note2_end
+        ];
+
+        for (block_index, grid_line_before, grid_line_after) in blocks_in_columns {
+            assert!(
+                unwrap_block_start(&solution, block_index)
+                    >= unwrap_v_line(&solution, grid_line_before)
+            );
+            assert!(
+                unwrap_block_end(&solution, block_index)
+                    <= unwrap_v_line(&solution, grid_line_after)
+            );
+        }
+
+        // Check that column separation gaps and simulated rhythmic padding spaces
+        // after noteheads have been correctly applied.
+
+        let block_end_next_v_line_separation = vec![
+            // The expected minimum distance between the end of a block and the start of the
+            // given column. Tuple is (block index, grid line index, expected minimum separation)
+            (11, 5, column_separation),
+            (12, 5, column_separation),
+            (13, 7, column_separation),
+            (14, 7, column_separation),
+            (15, 7, column_separation),
+            (16, 7, column_separation),
+            (17, 9, rhythmic_space_separation),
+            (18, 11, rhythmic_space_separation),
+            (19, 9, rhythmic_space_separation),
+            (20, 13, column_separation),
+            (21, 15, rhythmic_space_separation),
+            (22, 17, rhythmic_space_separation),
+            (23, 15, rhythmic_space_separation),
+            (25, 9, column_separation),
+            (26, 11, column_separation),
+            (27, 15, column_separation),
+            (28, 17, column_separation),
+        ];
+
+        for (block_index, grid_line_after, minimum_separation) in block_end_next_v_line_separation {
+            assert!(
+                unwrap_block_end(&solution, block_index) + minimum_separation
+                    <= unwrap_v_line(&solution, grid_line_after)
+            );
+        }
+
+        // Finally, check that lyric syllables are correctly centered beneath their
+        // respective noteheads. (In a real score, syllables are not necessarily
+        // centered - it usually depends on whether the syllable starts a new word
+        // or not - but in this test we simply centered all syllables.)
+
+        let syllables_underneath_noteheads = vec![
+            // Tuple is (syllable block index, notehead block index, notehead center grid line index)
+            (25, 17, 19),
+            (26, 18, 20),
+            (27, 21, 21),
+            (28, 22, 22),
+        ];
+
+        for (syllable_block, notehead_block, notehead_center_grid_line) in
+            syllables_underneath_noteheads
+        {
+            assert_eq!(
+                unwrap_block_start(&solution, syllable_block)
+                    + (unwrap_block_end(&solution, syllable_block)
+                        - unwrap_block_start(&solution, syllable_block))
+                        / 2.0,
+                unwrap_v_line(&solution, notehead_center_grid_line)
+            );
+
+            assert_eq!(
+                unwrap_block_start(&solution, notehead_block)
+                    + (unwrap_block_end(&solution, notehead_block)
+                        - unwrap_block_start(&solution, notehead_block))
+                        / 2.0,
+                unwrap_v_line(&solution, notehead_center_grid_line)
+            );
+        }
+    }
+
+    fn create_staveline_block(
+        staveline: HorizontalGridLineIndex,
+        systemic_line: VerticalGridLineIndex,
+        system_end: VerticalGridLineIndex,
+    ) -> LineBlock {
+        let mut block = LineBlock::new_horizontal(
+            None,
+            Some(TICKS_ZERO),
+            None,
+            0.25.as_stave_spaces(),
+            Color::BLACK,
+            StrokeStyle::Solid,
+            BlockLayer::Foreground,
+        );
+
+        block.lock_start_to_grid_line(systemic_line);
+        block.lock_end_to_grid_line(system_end);
+        block.lock_vertical_center_to_grid_line(staveline);
+
+        block
+    }
+
+    fn create_glyph_block_on_staveline(
+        staveline: HorizontalGridLineIndex,
+        column_start: VerticalGridLineIndex,
+        column_end: VerticalGridLineIndex,
+        onset: Ticks,
+        font: &impl SmuflFont,
+        glyph: Glyph,
+    ) -> GlyphBlock {
+        let mut block = GlyphBlock::new(
+            None,
+            Some(onset),
+            None,
+            font,
+            Color::BLACK,
+            glyph,
+            BlockLayer::Foreground,
+        );
+
+        block.lock_vertical_center_to_grid_line(staveline);
+        block.float_horizontally_between_grid_lines(column_start, column_end);
+        // TODO: AJRC - 22/8/21 - it's tempting to use start_align_between_grid_lines()
+        // on the notehead, but this sets an EQ(STRONG) constraint on the notehead
+        // position that conflicts with the center point of a wide lyric. Only
+        // by floating the notehead between grid lines can we allow the
+        // width of a wide lyric to "win" and push the center of the notehead
+        // sideways. If we use start_align, then the notehead won't budge; the lyric
+        // instead moves, and invariably collides with the lyric in the previous
+        // notehead column. This could indicate that we need to weaken the
+        // EQ() constraint on start_align. Perhaps if it was EQ(MEDIUM) instead of
+        // EQ(STRONG), there'd be less of a problem using start_align. Or we
+        // could allow the block constraint to actually take a strength parameter
+        // when we define it, rather than trying to assign strengths to constraints
+        // as part of LayoutSystem.engrave().
+
+        block
+    }
+
+    fn create_barline_block(
+        system_top: HorizontalGridLineIndex,
+        system_bottom: HorizontalGridLineIndex,
+        barline_column_start: VerticalGridLineIndex,
+        barline_column_end: VerticalGridLineIndex,
+        onset: Ticks,
+    ) -> LineBlock {
+        let mut block = LineBlock::new_vertical(
+            None,
+            Some(onset),
+            None,
+            0.5.as_stave_spaces(),
+            Color::BLACK,
+            StrokeStyle::Solid,
+            BlockLayer::Foreground,
+        );
+
+        block.lock_top_to_grid_line(system_top);
+        block.lock_bottom_to_grid_line(system_bottom);
+        block.lock_start_between_grid_lines(
+            barline_column_start,
+            barline_column_end,
+            STAVE_SPACES_ZERO,
+        );
+
+        block
+    }
+
+    fn create_systemic_line_block(
+        system_top: HorizontalGridLineIndex,
+        system_bottom: HorizontalGridLineIndex,
+        systemic_line: VerticalGridLineIndex,
+    ) -> LineBlock {
+        let mut block = LineBlock::new_vertical(
+            None,
+            Some(TICKS_ZERO),
+            None,
+            0.25.as_stave_spaces(),
+            Color::BLACK,
+            StrokeStyle::Solid,
+            BlockLayer::Foreground,
+        );
+
+        block.lock_top_to_grid_line(system_top);
+        block.lock_bottom_to_grid_line(system_bottom);
+        block.lock_horizontal_center_to_grid_line(systemic_line);
+
+        block
+    }
+
+    fn create_lyric_underlay_block(
+        lyric_underlay_top: HorizontalGridLineIndex,
+        lyric_underlay_bottom: HorizontalGridLineIndex,
+        notehead_start: VerticalGridLineIndex,
+        notehead_center: VerticalGridLineIndex,
+        notehead_end: VerticalGridLineIndex,
+        lyric: &str,
+    ) -> MarkupBlock {
+        // We simulate the width for this test by assuming 0.5 stave spaces per character.
+
+        let lyric_width = StaveSpaces::new(lyric.len() as f32 * 0.5);
+
+        let lyric_height = 1.as_stave_spaces();
+
+        let mut block = MarkupBlock::new(
+            None,
+            None,
+            None,
+            vec![MarkedUpLine::new(
+                STAVE_SPACES_ZERO,
+                STAVE_SPACES_ZERO,
+                STAVE_SPACES_ZERO,
+                STAVE_SPACES_ZERO,
+                lyric_width,
+                lyric_height,
+                vec![],
+                LineLayout::LineStartAligned,
+                Border::none(),
+            )],
+            BlockLayer::Foreground,
+            Some(lyric_width),
+            Some(lyric_height),
+        );
+
+        block.lock_top_to_grid_line(lyric_underlay_top);
+        block.lock_bottom_to_grid_line(lyric_underlay_bottom);
+        block.float_horizontally_between_grid_lines(notehead_start, notehead_end);
+        block.lock_horizontal_center_to_grid_line(notehead_center);
+
+        block
+    }
+
+    fn create_collidable_markup_block(
+        onset: Option<Ticks>,
+        width: StaveSpaces,
+        height: StaveSpaces,
+    ) -> MarkupBlock {
+        let mut block = MarkupBlock::new(
+            None,
+            onset,
+            None,
+            vec![MarkedUpLine::new(
+                STAVE_SPACES_ZERO,
+                STAVE_SPACES_ZERO,
+                STAVE_SPACES_ZERO,
+                STAVE_SPACES_ZERO,
+                width,
+                height,
+                vec![],
+                LineLayout::LineStartAligned,
+                Border::none(),
+            )],
+            BlockLayer::Foreground,
+            Some(width),
+            Some(height),
+        );
+
+        block.set_collidable(true);
+
+        block
+    }
+
+    fn unwrap_h_line(
+        solution: &Result<EngravedSystem, EngravingError>,
+        index: HorizontalGridLineIndex,
+    ) -> StaveSpaces {
+        assert!(solution.is_ok());
+
+        let result = solution
+            .as_ref()
+            .unwrap()
+            .get_horizontal_grid_line_positions()
+            .get(index);
+
+        assert!(result.is_some());
+
+        *result.unwrap()
+    }
+
+    fn unwrap_v_line(
+        solution: &Result<EngravedSystem, EngravingError>,
+        index: VerticalGridLineIndex,
+    ) -> StaveSpaces {
+        assert!(solution.is_ok());
+
+        let result = solution
+            .as_ref()
+            .unwrap()
+            .get_vertical_grid_line_positions()
+            .get(index);
+
+        assert!(result.is_some());
+
+        *result.unwrap()
+    }
+
+    fn unwrap_block_top(
+        solution: &Result<EngravedSystem, EngravingError>,
+        index: BlockIndex,
+    ) -> StaveSpaces {
+        assert!(solution.is_ok());
+
+        let result = solution.as_ref().unwrap().get_foreground().get(index);
+
+        assert!(result.is_some());
+
+        result.unwrap().get_y()
+    }
+
+    fn unwrap_block_start(
+        solution: &Result<EngravedSystem, EngravingError>,
+        index: BlockIndex,
+    ) -> StaveSpaces {
+        assert!(solution.is_ok());
+
+        let result = solution.as_ref().unwrap().get_foreground().get(index);
+
+        assert!(result.is_some());
+
+        result.unwrap().get_x()
+    }
+
+    fn unwrap_block_end(
+        solution: &Result<EngravedSystem, EngravingError>,
+        index: BlockIndex,
+    ) -> StaveSpaces {
+        assert!(solution.is_ok());
+
+        let result = solution.as_ref().unwrap().get_foreground().get(index);
+
+        assert!(result.is_some());
+
+        result.unwrap().get_x() + result.unwrap().get_width()
+    }
+
+    fn unwrap_block_bottom(
+        solution: &Result<EngravedSystem, EngravingError>,
+        index: BlockIndex,
+    ) -> StaveSpaces {
+        assert!(solution.is_ok());
+
+        let result = solution.as_ref().unwrap().get_foreground().get(index);
+
+        assert!(result.is_some());
+
+        result.unwrap().get_y() + result.unwrap().get_height()
+    }
+
+    #[test]
+    fn test_system_start_align() {
+        let solution = create_justification_test(SystemJustification::AlignStart).engrave();
+
+        assert!(solution.is_ok());
+
+        let solution = solution.unwrap();
+
+        // Start alignment should have a leading edge at 0.0 and, for this
+        // justification test, a trailing edge at 15.0. The fact that the test
+        // asks for a target system width of 30.0 is irrelevant when the
+        // system justification is set to start alignment.
+
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(0).unwrap(),
+            0.as_stave_spaces()
+        );
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(1).unwrap(),
+            15.as_stave_spaces()
+        );
+    }
+
+    #[test]
+    fn test_system_end_align() {
+        let solution = create_justification_test(SystemJustification::AlignEnd).engrave();
+
+        assert!(solution.is_ok());
+
+        let solution = solution.unwrap();
+
+        // The justification test scenario has a total width of 15 stave spaces
+        // and sets a target system width of 30 stave spaces, so end alignment
+        // should have a leading edge at 15 and a trailing edge at 30.
+
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(0).unwrap(),
+            15.as_stave_spaces()
+        );
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(1).unwrap(),
+            30.as_stave_spaces()
+        );
+    }
+
+    #[test]
+    fn test_system_center_align() {
+        let solution = create_justification_test(SystemJustification::Centered).engrave();
+
+        assert!(solution.is_ok());
+
+        let solution = solution.unwrap();
+
+        // The justification test scenario has a total width of 15 stave spaces
+        // and sets a target system width of 30 stave spaces, so center alignment
+        // should have a leading edge at (30 - 15) / 2 = 7.5 and a trailing edge
+        // at 7.5 + 15 = 22.5.
+
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(0).unwrap(),
+            7.5.as_stave_spaces()
+        );
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(1).unwrap(),
+            22.5.as_stave_spaces()
+        );
+    }
+
+    #[test]
+    fn test_system_justify() {
+        let solution = create_justification_test(SystemJustification::Justified).engrave();
+
+        assert!(solution.is_ok());
+
+        let solution = solution.unwrap();
+
+        // The justification test scenario has a total width of 15 stave spaces.
+        // There are three notehead glyphs, each followed by a spacing block.
+        // Justifying the test out from 15 stave spaces to 30 stave spaces
+        // means we expect each spacing block to take on (30 - 15) / 3 additional
+        // stave spaces of padding. The spacing blocks themselves are filtered out
+        // when blocks are converted to engravable, so we only have the positions
+        // of the glyphs available to examine. Before justification, the spacing
+        // blocks ensured that the glyphs appeared at (0,0), (5,0) and (10,0); adding
+        // (30 - 15) / 3 = 5 additional stave spaces of padding to each spacing
+        // block should result in the two glyphs now appearing at (0+5*0,0),
+        // (5+5*1,0) and (10+5*2,0) = (0,0), (10,0) and (20,0) in the engraving.
+
+        // Because the simulated staveline is in the background layer, and the
+        // spacing blocks are filtered out of the final engraving, we expect to find
+        // the glyph engravable at index positions 0, 1, and 2 in the foreground layer.
+
+        assert_eq!(
+            solution.get_foreground().get(0).unwrap().get_x(),
+            STAVE_SPACES_ZERO
+        );
+        assert_eq!(
+            solution.get_foreground().get(1).unwrap().get_x(),
+            10.as_stave_spaces()
+        );
+        assert_eq!(
+            solution.get_foreground().get(2).unwrap().get_x(),
+            20.as_stave_spaces()
+        );
+
+        // In addition to the glyph blocks moving, we also expect to see the system end
+        // vertical grid line at index 1 expand its position to 30 stave spaces.
+
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(1).unwrap(),
+            30.as_stave_spaces()
+        );
+    }
+
+    #[test]
+    fn test_system_justify_if_full() {
+        // The justification test scenario has a natural width of 15 stave spaces, exactly
+        // half the target system width of 30 stave spaces. With a justification threshold
+        // of 0.75, we expect the system to be start aligned rather than justified.
+
+        let mut layout = create_justification_test(SystemJustification::Justified);
+
+        layout.set_justification_threshold(Some(0.75));
+
+        let solution = layout.engrave().unwrap();
+
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(0).unwrap(),
+            STAVE_SPACES_ZERO
+        );
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(1).unwrap(),
+            15.as_stave_spaces()
+        );
+
+        // Lowering the threshold below 0.5 should see the system justified out to 30 stave spaces.
+
+        let mut layout = create_justification_test(SystemJustification::Justified);
+
+        layout.set_justification_threshold(Some(0.4));
+
+        let solution = layout.engrave().unwrap();
+
+        assert_eq!(
+            solution.get_foreground().get(1).unwrap().get_x(),
+            10.as_stave_spaces()
+        );
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(1).unwrap(),
+            30.as_stave_spaces()
+        );
+    }
+
+    #[test]
+    fn test_system_justify_with_maximum_stretch() {
+        // With a maximum justification ratio of 1, spacing blocks may not stretch at all,
+        // so the system should keep its natural width of 15 stave spaces.
+
+        let mut layout = create_justification_test(SystemJustification::Justified);
+
+        layout.set_maximum_justification_ratio(Some(1.0));
+
+        let solution = layout.engrave().unwrap();
+
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(1).unwrap(),
+            15.as_stave_spaces()
+        );
+
+        // With a generous maximum justification ratio, the system should be fully justified.
+
+        let mut layout = create_justification_test(SystemJustification::Justified);
+
+        layout.set_maximum_justification_ratio(Some(10.0));
+
+        let solution = layout.engrave().unwrap();
+
+        assert_eq!(
+            solution.get_vertical_grid_line_positions().get(1).unwrap(),
+            30.as_stave_spaces()
+        );
+
+        // In between, the glyphs should remain evenly spaced, with the system stretched
+        // beyond its natural width but stopping short of the target width.
+
+        let mut layout = create_justification_test(SystemJustification::Justified);
+
+        layout.set_maximum_justification_ratio(Some(1.5));
+
+        let solution = layout.engrave().unwrap();
+
+        let x0 = solution.get_foreground().get(0).unwrap().get_x();
+
+        let x1 = solution.get_foreground().get(1).unwrap().get_x();
+
+        let x2 = solution.get_foreground().get(2).unwrap().get_x();
+
+        assert!(((x1 - x0).value - (x2 - x1).value).abs() < 0.001);
+
+        let width = *solution.get_vertical_grid_line_positions().get(1).unwrap();
+
+        assert!(width > 15.as_stave_spaces());
+        assert!(width < 30.as_stave_spaces());
+    }
+
+    #[test]
+    fn test_system_justify_with_unknown_durations() {
+        // The spacing blocks in the justification test scenario carry no onsets, so
+        // the durations they represent are unknown. A non-linear rhythmic spacing model
+        // should fall back to stretching each spacing block in proportion to its
+        // natural width, giving the same result as linear spacing.
+
+        let mut layout = create_justification_test(SystemJustification::Justified);
+
+        layout.set_rhythmic_spacing_model(RhythmicSpacingModel::Logarithmic { increment: 0.5 });
+
+        let solution = layout.engrave().unwrap();
+
+        assert_eq!(
+            solution.get_foreground().get(1).unwrap().get_x(),
+            10.as_stave_spaces()
+        );
+        assert_eq!(
+            solution.get_foreground().get(2).unwrap().get_x(),
+            20.as_stave_spaces()
+        );
+    }
+
+    #[test]
+    fn test_system_rhythmic_spacing() {
+        // Give the spacing blocks in the justification test scenario the onsets of the
+        // noteheads they follow, and end the system a semibreve after it starts, so the
+        // spacing blocks represent a crotchet, a crotchet, and a minim. Under square root
+        // spacing, the minim's spacing block should take root 2 times the space of
+        // a crotchet's spacing block, rather than the same space as its natural width
+        // suggests, or twice the space as linear spacing would suggest.
+
+        let create_layout = |justification: SystemJustification| {
+            let mut layout = create_justification_test(justification);
+
+            layout.set_rhythmic_spacing_model(RhythmicSpacingModel::Gourlay { exponent: 0.5 });
+
+            layout.blocks[2].set_source_onset(Some(TICKS_ZERO));
+            layout.blocks[4].set_source_onset(Some(NotatedDuration::Crotchet.as_ticks()));
+            layout.blocks[6].set_source_onset(Some(NotatedDuration::Minim.as_ticks()));
+
+            layout.end_ticks = NotatedDuration::Semibreve.as_ticks();
+
+            layout
+        };
+
+        let font = Bravura::new();
+
+        let notehead_width = GlyphBlock::new(
+            None,
+            None,
+            None,
+            &font,
+            Color::BLACK,
+            Glyph::NoteheadBlack,
+            BlockLayer::Foreground,
+        )
+        .get_fixed_width()
+        .value;
+
+        let get_spacing_widths = |solution: &EngravedSystem| {
+            let x0 = solution.get_foreground().get(0).unwrap().get_x().value;
+
+            let x1 = solution.get_foreground().get(1).unwrap().get_x().value;
+
+            let x2 = solution.get_foreground().get(2).unwrap().get_x().value;
+
+            let end = solution
+                .get_vertical_grid_line_positions()
+                .get(1)
+                .unwrap()
+                .value;
+
+            (
+                x1 - x0 - notehead_width,
+                x2 - x1 - notehead_width,
+                end - x2 - notehead_width,
+            )
+        };
+
+        // Without justification, the ideal widths given by the spacing model set
+        // the natural spacing of the system.
+
+        let natural_width = 5.0 - notehead_width;
+
+        let (first, second, third) = get_spacing_widths(
+            &create_layout(SystemJustification::AlignStart)
+                .engrave()
+                .unwrap(),
+        );
+
+        assert!((first - natural_width).abs() < 0.001);
+        assert!((second - natural_width).abs() < 0.001);
+        assert!((third - natural_width * 2.0_f32.sqrt()).abs() < 0.001);
+
+        // Once justified out to 30 stave spaces, the additional width is shared out
+        // in the same proportions, so the system still follows the spacing model.
+
+        let solution = create_layout(SystemJustification::Justified)
+            .engrave()
+            .unwrap();
+
+        assert!(
+            (solution
+                .get_vertical_grid_line_positions()
+                .get(1)
+                .unwrap()
+                .value
+                - 30.0)
+                .abs()
+                < 0.001
+        );
+
+        let (first, second, third) = get_spacing_widths(&solution);
+
+        assert!(first > natural_width);
+        assert!((first - second).abs() < 0.001);
+        assert!((third / first - 2.0_f32.sqrt()).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_system_compress() {
+        // The justification test scenario has a natural width of 15 stave spaces.
+        // Asking for a target system width of 13 stave spaces should see the three
+        // spacing blocks each give up (15 - 13) / 3 stave spaces, well within
+        // a minimum compression ratio of 0.5.
+
+        let mut layout = create_justification_test_with_target_system_width(
+            SystemJustification::AlignStart,
+            13.as_stave_spaces(),
+        );
+
+        layout.set_minimum_compression_ratio(0.5);
+
+        let solution = layout.engrave().unwrap();
+
+        let x1 = solution.get_foreground().get(1).unwrap().get_x();
+
+        let x2 = solution.get_foreground().get(2).unwrap().get_x();
+
+        let width = *solution.get_vertical_grid_line_positions().get(1).unwrap();
+
+        assert!((x1.value - (5.0 - 2.0 / 3.0)).abs() < 0.001);
+        assert!((x2.value - (10.0 - 4.0 / 3.0)).abs() < 0.001);
+        assert!((width.value - 13.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_system_compress_with_minimum_widths() {
+        // As above, but the first spacing block may not be compressed at all, so the
+        // other two spacing blocks must each give up (15 - 13) / 2 stave spaces.
+
+        let mut layout = create_justification_test_with_target_system_width(
+            SystemJustification::AlignStart,
+            13.as_stave_spaces(),
+        );
+
+        layout.set_minimum_compression_ratio(0.5);
+
+        let natural_width = layout.blocks[2].get_fixed_width();
+
+        layout.blocks[2].set_minimum_width(Some(natural_width));
+
+        let solution = layout.engrave().unwrap();
+
+        let x1 = solution.get_foreground().get(1).unwrap().get_x();
+
+        let x2 = solution.get_foreground().get(2).unwrap().get_x();
+
+        let width = *solution.get_vertical_grid_line_positions().get(1).unwrap();
+
+        assert!((x1.value - 5.0).abs() < 0.001);
+        assert!((x2.value - 9.0).abs() < 0.001);
+        assert!((width.value - 13.0).abs() < 0.001);
+
+        // If no spacing block may be compressed, the system cannot fit, and the whole
+        // overflow is reported, irrespective of the minimum compression ratio.
+
+        let mut layout = create_justification_test_with_target_system_width(
+            SystemJustification::AlignStart,
+            13.as_stave_spaces(),
+        );
+
+        layout.set_minimum_compression_ratio(0.5);
+
+        for index in [2, 4, 6] {
+            let natural_width = layout.blocks[index].get_fixed_width();
+
+            layout.blocks[index].set_minimum_width(Some(natural_width));
+        }
+
+        match layout.engrave() {
+            Err(EngravingError::SystemOverfull(overflow)) => {
+                assert!((overflow.value - 2.0).abs() < 0.001)
+            }
+            Err(err) => panic!("expected an overfull system, got {}", err),
+            Ok(_) => panic!("expected an overfull system, but the system engraved"),
+        }
+    }
+
+    #[test]
+    fn test_system_justify_conflicting_constraints() {
+        // Replace the second spacing block in the justification test scenario with a
+        // narrower spacing block whose start and end are both required to match those
+        // of the first spacing block. Before justification, the solver can trade off the
+        // strong fixed widths of the two blocks, but justification requires each spacing
+        // block to take its natural width plus its share of the padding. The two blocks
+        // have different natural widths, so justification cannot be satisfied.
+
+        let mut layout = create_justification_test(SystemJustification::Justified);
+
+        layout.set_debug_do_diagnose_constraint_conflicts(true);
+
+        let mut b4 = SpacingBlock::new(2.as_stave_spaces());
+
+        b4.float_horizontally_between_grid_lines(8, 9);
+        b4.add_constraint(BlockConstraint::LockStartToBlockStart(
+            2,
+            Some(ConstraintStrength::Required),
+        ));
+        b4.add_constraint(BlockConstraint::LockEndToBlockEnd(
+            2,
+            Some(ConstraintStrength::Required),
+        ));
+
+        layout.blocks[4] = b4.into();
+
+        // The rejected justification constraint should be reported alongside the
+        // constraints it conflicts with, rather than as a bare solver error.
+
+        match layout.engrave() {
+            Err(EngravingError::ConflictingConstraints(records)) => {
+                assert!(matches!(
+                    records.last().unwrap().get_origin(),
+                    ConstraintOrigin::Justification(4)
+                ));
+                assert!(records.iter().any(|record| matches!(
+                    record.get_origin(),
+                    ConstraintOrigin::Justification(2)
+                )));
+                assert!(records.iter().any(|record| matches!(
+                    record.get_origin(),
+                    ConstraintOrigin::Block(4, BlockConstraint::LockStartToBlockStart(2, _))
+                )));
+            }
+            Err(err) => panic!("expected conflicting constraints, got {}", err),
+            Ok(_) => panic!("expected conflicting constraints, but the system engraved"),
+        }
+    }
+
+    #[test]
+    fn test_system_overfull() {
+        // The spacing blocks in the justification test scenario take up less than
+        // 15 stave spaces of the natural width, so at the default minimum compression
+        // ratio they can give up less than 3 stave spaces between them. That is not
+        // enough for the system to fit in 10 stave spaces.
+
+        let layout = create_justification_test_with_target_system_width(
+            SystemJustification::Justified,
+            10.as_stave_spaces(),
+        );
+
+        assert!(matches!(
+            layout.engrave(),
+            Err(EngravingError::SystemOverfull(overflow)) if overflow.value > 0.0
+        ));
+    }
+
+    #[test]
+    fn test_system_constraint_strength() {
+        // A notehead is locked to two vertical grid lines four stave spaces apart.
+        // Left at their default strengths, the two Lock* constraints pull equally hard;
+        // here the second constraint is given an explicit strength, which decides
+        // which grid line the notehead follows.
+
+        let create_layout = |strength: ConstraintStrength| {
+            let font = Bravura::new();
+
+            let h0_system_top = HorizontalGridLine::new(HorizontalGridLineType::SystemTop);
+
+            let v0_system_start = VerticalGridLine::new(0, VerticalGridLineType::SystemStart);
+
+            let mut v1_notehead_start =
+                VerticalGridLine::new(1, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+            v1_notehead_start.lock_after_grid_line_with_strength(
+                0,
+                4.as_stave_spaces(),
+                ConstraintStrength::Required,
+            );
+
+            let mut v2_notehead_start =
+                VerticalGridLine::new(2, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+            v2_notehead_start.lock_after_grid_line_with_strength(
+                0,
+                8.as_stave_spaces(),
+                ConstraintStrength::Required,
+            );
+
+            let mut b0_notehead = GlyphBlock::new(
+                None,
+                Some(TICKS_ZERO),
+                None,
+                &font,
+                Color::BLACK,
+                Glyph::NoteheadBlack,
+                BlockLayer::Foreground,
+            );
+
+            b0_notehead.lock_top_to_grid_line(0);
+            b0_notehead.lock_start_to_grid_line(1);
+            b0_notehead.lock_start_to_grid_line_with_strength(2, strength);
+
+            LayoutSystem::new(
+                0,
+                TICKS_ZERO,
+                TICKS_ZERO,
+                SystemJustification::AlignStart,
+                30.as_stave_spaces(),
+                vec![h0_system_top],
+                vec![v0_system_start, v1_notehead_start, v2_notehead_start],
+                0,
+                0,
+                vec![b0_notehead.into()],
+                false,
+                false,
+                false,
+                false,
+            )
+        };
+
+        // A Medium strength constraint gives way to the default Strong constraint...
+
+        let solution = create_layout(ConstraintStrength::Medium).engrave();
+
+        assert_eq!(unwrap_block_start(&solution, 0), 4.as_stave_spaces());
+
+        // ... but a Required constraint overrides it.
+
+        let solution = create_layout(ConstraintStrength::Required).engrave();
+
+        assert_eq!(unwrap_block_start(&solution, 0), 8.as_stave_spaces());
+    }
+
+    #[test]
+    fn test_system_bars() {
+        // A system covering ticks 1000 to 2250, with barlines at ticks 1500 and 2000,
+        // so the final bar continues onto the next system. The barline columns are
+        // listed out of order, to check that bars are ordered by position.
+
+        let h0 = HorizontalGridLine::new(HorizontalGridLineType::SystemTop);
+
+        let mut h1 = HorizontalGridLine::new(HorizontalGridLineType::SystemBottom);
+
+        h1.lock_below_grid_line(0, 4.as_stave_spaces());
+
+        let v0 = VerticalGridLine::new(0, VerticalGridLineType::SystemStart);
+
+        let mut v1 = VerticalGridLine::new(0, VerticalGridLineType::SystemEnd);
+
+        let mut v2 = VerticalGridLine::new(2, VerticalGridLineType::BarlineStart);
+
+        let mut v3 = VerticalGridLine::new(2, VerticalGridLineType::BarlineEnd);
+
+        let mut v4 = VerticalGridLine::new(1, VerticalGridLineType::BarlineStart);
+
+        let mut v5 = VerticalGridLine::new(1, VerticalGridLineType::BarlineEnd);
+
+        v4.lock_after_grid_line(0, 10.as_stave_spaces());
+        v5.lock_after_grid_line(4, 0.5.as_stave_spaces());
+        v2.lock_after_grid_line(5, 10.as_stave_spaces());
+        v3.lock_after_grid_line(2, 0.5.as_stave_spaces());
+        v1.lock_after_grid_line(3, 5.as_stave_spaces());
+
+        // A Block from some other onset that happens to lie inside the first barline
+        // column, but is not locked to it, should not be mistaken for the barline.
+
+        let mut decoy = LineBlock::new_vertical(
+            None,
+            Some(9999.as_ticks()),
+            None,
+            0.25.as_stave_spaces(),
+            Color::BLACK,
+            StrokeStyle::Solid,
+            BlockLayer::Foreground,
+        );
+
+        decoy.lock_top_to_grid_line(0);
+        decoy.lock_bottom_to_grid_line(1);
+        decoy.float_horizontally_between_grid_lines(4, 5);
+
+        // A Block locked to the first barline column with an earlier onset, listed ahead
+        // of the barline itself, should not cut the first bar short.
+
+        let mut early = create_barline_block(0, 1, 4, 5, 1250.as_ticks());
+
+        early.set_collidable(false);
+
+        let layout = LayoutSystem::new(
+            0,
+            1000.as_ticks(),
+            2250.as_ticks(),
+            SystemJustification::AlignStart,
+            100.as_stave_spaces(),
+            vec![h0, h1],
+            vec![v0, v1, v2, v3, v4, v5],
+            0,
+            0,
+            vec![
+                decoy.into(),
+                early.into(),
+                create_barline_block(0, 1, 2, 3, 2000.as_ticks()).into(),
+                create_barline_block(0, 1, 4, 5, 1500.as_ticks()).into(),
+            ],
+            false,
+            false,
+            false,
+            false,
+        );
+
+        let solution = layout.engrave().unwrap();
+
+        let positions = solution.get_vertical_grid_line_positions();
+
+        let width = *positions.get(1).unwrap();
+
+        assert_eq!(width, 26.as_stave_spaces());
+
+        assert_eq!(
+            layout.get_bar_extents(positions, width),
+            vec![
+                (
+                    1000.as_ticks(),
+                    1500.as_ticks(),
+                    STAVE_SPACES_ZERO,
+                    10.5.as_stave_spaces()
+                ),
+                (
+                    1500.as_ticks(),
+                    2000.as_ticks(),
+                    10.5.as_stave_spaces(),
+                    21.as_stave_spaces()
+                ),
+                (
+                    2000.as_ticks(),
+                    2250.as_ticks(),
+                    21.as_stave_spaces(),
+                    26.as_stave_spaces()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_system_validation() {
+        assert_eq!(
+            create_justification_test(SystemJustification::AlignStart).validate(),
+            Ok(())
+        );
+
+        // A system with several structural problems should report all of them at once,
+        // before any solving takes place.
+
+        let mut layout = create_justification_test(SystemJustification::AlignStart);
+
+        layout.top_edge = 99;
+        layout.leading_edge = 99;
+
+        // Vertical grid line 4 is already locked to grid line 3; lock it to a grid line
+        // that does not exist as well.
+
+        layout.vertical_grid_lines[4].lock_to_grid_line(42);
+
+        // Vertical grid line 2 is already locked to grid line 0; lock grid line 0 back
+        // to grid line 2 to create a cycle.
+
+        layout.vertical_grid_lines[0].lock_to_grid_line(2);
+
+        let errors = vec![
+            LayoutValidationError::UnknownTopEdge(99),
+            LayoutValidationError::UnknownLeadingEdge(99),
+            LayoutValidationError::UnknownReference {
+                element: LayoutElement::VerticalGridLine(4),
+                constraint: 1,
+                reference: LayoutElement::VerticalGridLine(42),
+            },
+            LayoutValidationError::CyclicLockChain(vec![
+                LayoutVariable::VerticalGridLine(0),
+                LayoutVariable::VerticalGridLine(2),
+            ]),
+        ];
+
+        assert_eq!(layout.validate(), Err(errors.clone()));
+
+        // The origin of the system is checked before anything else is validated, so engraving
+        // reports the unknown top edge alone. Once the origin is restored, engraving reports
+        // every remaining structural problem.
+
+        assert!(matches!(
+            layout.engrave(),
+            Err(EngravingError::UnknownTopEdge(99))
+        ));
+
+        layout.top_edge = 0;
+        layout.leading_edge = 0;
+
+        assert!(matches!(
+            layout.engrave(),
+            Err(EngravingError::InvalidLayout(found)) if found == errors[2..]
+        ));
+
+        // Lock* constraints are followed per solver variable: the top of block 1 locked to
+        // the top of block 3, and the start of block 3 locked to the start of block 1,
+        // do not form a cycle, but locking the top of block 3 back to the top of block 1 does.
+
+        let mut layout = create_justification_test(SystemJustification::AlignStart);
+
+        layout.blocks[1].add_constraint(BlockConstraint::LockTopToBlockTop(3, None));
+        layout.blocks[3].add_constraint(BlockConstraint::LockStartToBlockStart(1, None));
+
+        assert_eq!(layout.validate(), Ok(()));
+
+        layout.blocks[3].add_constraint(BlockConstraint::LockTopToBlockTop(1, None));
+
+        assert_eq!(
+            layout.validate(),
+            Err(vec![LayoutValidationError::CyclicLockChain(vec![
+                LayoutVariable::Block(1, BlockEdge::Top),
+                LayoutVariable::Block(3, BlockEdge::Top),
+            ])])
+        );
+    }
+
+    #[test]
+    fn test_system_unknown_origin() {
+        // A system whose origin refers to grid lines that do not exist should fail
+        // with a typed error before any solving takes place.
+
+        let mut layout = create_justification_test(SystemJustification::AlignStart);
+
+        layout.top_edge = 99;
+
+        assert!(matches!(
+            layout.engrave(),
+            Err(EngravingError::UnknownTopEdge(99))
+        ));
+
+        let mut layout = create_justification_test(SystemJustification::AlignStart);
+
+        layout.leading_edge = 99;
+
+        assert!(matches!(
+            layout.engrave(),
+            Err(EngravingError::UnknownLeadingEdge(99))
+        ));
+    }
+
+    #[test]
+    fn test_collision_pipeline_unknown_block() {
+        // The collision pipeline should report a bad block index, or a block without
+        // position variables, as an error rather than panicking.
+
+        let blocks: Vec<BlockEnum> =
+            vec![
+                create_collidable_markup_block(None, 1.as_stave_spaces(), 1.as_stave_spaces())
+                    .into(),
+            ];
+
+        // Solve the block one stave space wide, so it takes part in collision detection.
+
+        let (top, bottom, start, end) = (
+            Variable::new(),
+            Variable::new(),
+            Variable::new(),
+            Variable::new(),
+        );
+
+        let mut solver = Solver::new();
+
+        solver.add_constraint(start | EQ(REQUIRED) | 0.0).unwrap();
+        solver.add_constraint(end | EQ(REQUIRED) | 1.0).unwrap();
+
+        assert!(matches!(
+            LayoutSystem::detect_colliding_blocks(
+                blocks.as_slice(),
+                0,
+                0,
+                &solver,
+                &[top],
+                &[bottom],
+                &[start],
+                &[],
+            ),
+            Err(EngravingError::UnknownBlockEndPosition(0))
+        ));
+
+        assert!(matches!(
+            LayoutSystem::detect_colliding_blocks(
+                blocks.as_slice(),
+                0,
+                0,
+                &solver,
+                &[],
+                &[bottom],
+                &[start],
+                &[end],
+            ),
+            Err(EngravingError::UnknownBlockTopPosition(0))
+        ));
+
+        // A collision with a block that does not exist.
+
+        assert!(matches!(
+            LayoutSystem::resolve_colliding_blocks(
+                blocks.as_slice(),
+                0,
+                99,
+                0.25.as_stave_spaces(),
+                &solver,
+                &[top],
+                &[bottom],
+                &[start],
+                &[end],
+            ),
+            Err(EngravingError::UnknownBlock(99))
+        ));
+    }
+
+    #[test]
+    fn test_horizontal_collision_order() {
+        let crotchet = NotatedDuration::Crotchet.as_ticks();
+
+        // The block with the later onset moves, even if it currently starts earlier
+        // or has the lower index.
+
+        assert_eq!(
+            LayoutSystem::get_horizontal_collision_order(
+                0,
+                Some(crotchet),
+                Some(0),
+                1.0,
+                1,
+                Some(TICKS_ZERO),
+                Some(0),
+                2.0
+            ),
+            (0, 1)
+        );
+
+        // With equal or unknown onsets, the block with the later solved start position moves.
+
+        assert_eq!(
+            LayoutSystem::get_horizontal_collision_order(
+                0,
+                Some(crotchet),
+                Some(0),
+                3.0,
+                1,
+                Some(crotchet),
+                Some(1),
+                2.0
+            ),
+            (0, 1)
+        );
+
+        assert_eq!(
+            LayoutSystem::get_horizontal_collision_order(
+                0,
+                None,
+                Some(0),
+                3.0,
+                1,
+                None,
+                Some(0),
+                2.0
+            ),
+            (0, 1)
+        );
+
+        // With equal start positions, the block from the higher-indexed voice moves.
+
+        assert_eq!(
+            LayoutSystem::get_horizontal_collision_order(
+                0,
+                Some(crotchet),
+                Some(1),
+                2.0,
+                1,
+                Some(crotchet),
+                Some(0),
+                2.0 + 0.0001
+            ),
+            (0, 1)
+        );
+
+        // If nothing distinguishes the blocks, the block with the higher index moves.
+
+        assert_eq!(
+            LayoutSystem::get_horizontal_collision_order(
+                1,
+                None,
+                Some(0),
+                2.0,
+                0,
+                None,
+                Some(0),
+                2.0
+            ),
+            (1, 0)
+        );
+    }
+
+    #[test]
+    fn test_system_vertical_collision() {
+        // A dynamic is locked to the middle line of a stave, beneath a notehead on
+        // the same line, so the two overlap. The dynamic is allowed to move down to avoid
+        // collisions; it must move fully clear of the notehead, despite being locked to
+        // the staveline, while the notehead stays where it is.
+
+        let font = Bravura::new();
+
+        let h0_system_top = HorizontalGridLine::new(HorizontalGridLineType::SystemTop);
+
+        let mut h1_staveline = HorizontalGridLine::new(HorizontalGridLineType::Staveline3);
+
+        h1_staveline.lock_below_grid_line(0, 4.as_stave_spaces());
+
+        let v0_system_start = VerticalGridLine::new(0, VerticalGridLineType::SystemStart);
+
+        let mut v1_notehead_start =
+            VerticalGridLine::new(1, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+        v1_notehead_start.lock_after_grid_line(0, 2.as_stave_spaces());
+
+        let mut b0_notehead = GlyphBlock::new(
+            None,
+            Some(TICKS_ZERO),
+            Some(0),
+            &font,
+            Color::BLACK,
+            Glyph::NoteheadBlack,
+            BlockLayer::Foreground,
+        );
+
+        b0_notehead.set_collidable(true);
+        b0_notehead.lock_vertical_center_to_grid_line(1);
+        b0_notehead.lock_start_to_grid_line(1);
+
+        let mut b1_dynamic =
+            create_collidable_markup_block(None, 2.as_stave_spaces(), 1.5.as_stave_spaces());
+
+        b1_dynamic.set_can_move_down_to_avoid_vertical_collision(true);
+        b1_dynamic.lock_top_to_grid_line(1);
+        b1_dynamic.lock_start_to_grid_line(1);
+
+        let layout = LayoutSystem::new(
+            0,
+            TICKS_ZERO,
+            TICKS_ZERO,
+            SystemJustification::AlignStart,
+            30.as_stave_spaces(),
+            vec![h0_system_top, h1_staveline],
+            vec![v0_system_start, v1_notehead_start],
+            0,
+            0,
+            vec![b0_notehead.into(), b1_dynamic.into()],
+            false,
+            false,
+            false,
+            false,
+        );
+
+        let solution = layout.engrave();
+
+        let notehead_bottom = unwrap_block_bottom(&solution, 0);
+
+        // The notehead stays centered on the staveline.
+
+        assert!(
+            ((unwrap_block_top(&solution, 0) + notehead_bottom).value / 2.0 - 4.0).abs() < 0.001
+        );
+
+        // The dynamic moves down just far enough to leave the minimum collision gap
+        // beneath the notehead. Had collision resolution been no stronger than the lock
+        // to the staveline, the solver would have left the dynamic part way between the
+        // two positions, still overlapping the notehead.
+
+        let dynamic_top = unwrap_block_top(&solution, 1);
+
+        assert!(
+            (dynamic_top - notehead_bottom - layout.minimum_collision_gap)
+                .value
+                .abs()
+                < 0.001
+        );
+        assert_eq!(unwrap_block_start(&solution, 1), 2.as_stave_spaces());
+    }
+
+    fn create_nested_skyline_test(is_flag_skyline_set: bool) -> LayoutSystem {
+        // A 2 x 4 block, shaped like a flag above a notehead, with a 1 x 1 block placed
+        // in the cut-out at its top right corner. Their bounding boxes overlap, but if
+        // the flag's skyline is set, the blocks themselves do not. The smaller block
+        // is allowed to move down to avoid collisions.
+
+        let h0_system_top = HorizontalGridLine::new(HorizontalGridLineType::SystemTop);
+
+        let mut h1_staveline = HorizontalGridLine::new(HorizontalGridLineType::Staveline1);
+
+        h1_staveline.lock_below_grid_line(0, 1.as_stave_spaces());
+
+        let v0_system_start = VerticalGridLine::new(0, VerticalGridLineType::SystemStart);
+
+        let mut v1_stem_start = VerticalGridLine::new(1, VerticalGridLineType::StemColumnStart);
+
+        v1_stem_start.lock_after_grid_line(0, 2.as_stave_spaces());
+
+        let mut v2_stem_end = VerticalGridLine::new(1, VerticalGridLineType::StemColumnEnd);
+
+        v2_stem_end.lock_after_grid_line(1, 1.as_stave_spaces());
+
+        let mut b0_flag =
+            create_collidable_markup_block(None, 2.as_stave_spaces(), 4.as_stave_spaces());
+
+        b0_flag.lock_top_to_grid_line(0);
+        b0_flag.lock_start_to_grid_line(1);
+
+        if is_flag_skyline_set {
+            // As a SMuFL font's metadata would describe it: a bounding box from (0, -1)
+            // to (2, 3) around the glyph origin, with the top right corner cut away
+            // down to (1, 0).
+
+            b0_flag.set_skyline(Some(Skyline::from_smufl_anchors(
+                StavePoint::new(STAVE_SPACES_ZERO, StaveSpaces::new(-1.0)),
+                StavePoint::new(2.as_stave_spaces(), 3.as_stave_spaces()),
+                None,
+                Some(StavePoint::new(1.as_stave_spaces(), STAVE_SPACES_ZERO)),
+                None,
+                None,
+            )));
+        }
+
+        let mut b1_dot =
+            create_collidable_markup_block(None, 1.as_stave_spaces(), 1.as_stave_spaces());
+
+        b1_dot.set_can_move_down_to_avoid_vertical_collision(true);
+        b1_dot.lock_top_to_grid_line(1);
+        b1_dot.lock_start_to_grid_line(2);
+
+        LayoutSystem::new(
+            0,
+            TICKS_ZERO,
+            TICKS_ZERO,
+            SystemJustification::AlignStart,
+            30.as_stave_spaces(),
+            vec![h0_system_top, h1_staveline],
+            vec![v0_system_start, v1_stem_start, v2_stem_end],
+            0,
+            0,
+            vec![b0_flag.into(), b1_dot.into()],
+            false,
+            false,
+            false,
+            false,
+        )
+    }
+
+    #[test]
+    fn test_system_nested_skylines() {
+        // With the flag's skyline set, the smaller block nests in the flag's cut-out
+        // and neither block moves.
+
+        let layout = create_nested_skyline_test(true);
+
+        let solution = layout.engrave();
+
+        assert_eq!(unwrap_block_top(&solution, 0), STAVE_SPACES_ZERO);
+        assert_eq!(unwrap_block_start(&solution, 0), 2.as_stave_spaces());
+        assert_eq!(unwrap_block_top(&solution, 1), 1.as_stave_spaces());
+        assert_eq!(unwrap_block_start(&solution, 1), 3.as_stave_spaces());
+
+        // Without it, the flag fills its bounding box, and the smaller block must move
+        // down beneath it.
+
+        let layout = create_nested_skyline_test(false);
+
+        let solution = layout.engrave();
+
+        assert!(
+            (unwrap_block_top(&solution, 1) - 4.as_stave_spaces() - layout.minimum_collision_gap)
+                .value
+                .abs()
+                < 0.001
+        );
+
+        // The skyline belongs to the block, so replacing the flag in an engraving session
+        // with a block without a skyline moves the smaller block down, and replacing it
+        // again with the original flag moves the smaller block back.
+
+        let mut session = create_nested_skyline_test(true)
+            .into_engraving_session()
+            .unwrap();
+
+        let flag = session.get_layout_system().get_blocks()[0].clone();
+
+        let mut unshaped_flag = flag.clone();
+
+        unshaped_flag.set_skyline(None);
+
+        let get_dot_top = |changes: Vec<EngravingSessionChange>| {
+            changes.iter().find_map(|change| match change {
+                EngravingSessionChange::Engraved(1, _, engravable) => Some(engravable.get_y()),
+                _ => None,
+            })
+        };
+
+        session.replace_block(0, unshaped_flag).unwrap();
+
+        let dot_top = get_dot_top(session.engrave().unwrap()).unwrap();
+
+        assert!(
+            (dot_top - 4.as_stave_spaces() - layout.minimum_collision_gap)
+                .value
+                .abs()
+                < 0.001
+        );
+
+        session.replace_block(0, flag).unwrap();
+
+        assert_eq!(
+            get_dot_top(session.engrave().unwrap()),
+            Some(1.as_stave_spaces())
+        );
+    }
+
+    #[test]
+    fn test_system_collision_passes() {
+        // Three blocks, locked to the same row, that cannot move vertically. The second
+        // block overlaps the first; the third block starts just clear of the second.
+        // Pushing the second block sideways to clear the first makes it collide with
+        // the third, so a second pass of collision detection and resolution is needed
+        // before the layout settles.
+
+        let create_layout = || {
+            let h0_system_top = HorizontalGridLine::new(HorizontalGridLineType::SystemTop);
+
+            let v0_system_start = VerticalGridLine::new(0, VerticalGridLineType::SystemStart);
+
+            let mut v1_block_start =
+                VerticalGridLine::new(1, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+            v1_block_start.lock_after_grid_line(0, 1.as_stave_spaces());
+
+            let mut v2_block_start =
+                VerticalGridLine::new(2, VerticalGridLineType::NoteheadLine0NoteheadStackStart);
+
+            v2_block_start.lock_after_grid_line(0, 3.5.as_stave_spaces());
+
+            let mut blocks: Vec<BlockEnum> = Vec::new();
+
+            for (column, onset) in [
+                (0, TICKS_ZERO),
+                (1, NotatedDuration::Crotchet.as_ticks()),
+                (2, NotatedDuration::Minim.as_ticks()),
+            ] {
+                let mut block = create_collidable_markup_block(
+                    Some(onset),
+                    2.as_stave_spaces(),
+                    1.as_stave_spaces(),
+                );
+
+                block.lock_top_to_grid_line(0);
+                block.lock_start_to_grid_line(column);
+
+                blocks.push(block.into());
+            }
+
+            LayoutSystem::new(
+                0,
+                TICKS_ZERO,
+                TICKS_ZERO,
+                SystemJustification::AlignStart,
+                30.as_stave_spaces(),
+                vec![h0_system_top],
+                vec![v0_system_start, v1_block_start, v2_block_start],
+                0,
+                0,
+                blocks,
+                false,
+                false,
+                false,
+                false,
+            )
+        };
+
+        // With the default number of passes, the later block of each colliding pair
+        // is pushed the minimum collision gap clear of the earlier block.
+
+        let solution = create_layout().engrave();
+
+        assert_eq!(unwrap_block_start(&solution, 0), STAVE_SPACES_ZERO);
+        assert!((unwrap_block_start(&solution, 1).value - 2.25).abs() < 0.001);
+        assert!((unwrap_block_start(&solution, 2).value - 4.5).abs() < 0.001);
+
+        // With only a single pass allowed, the collision created by the first pass
+        // is reported as unresolved.
+
+        let mut layout = create_layout();
+
+        layout.set_maximum_collision_resolution_passes(1);
+
+        assert!(matches!(
+            layout.engrave(),
+            Err(EngravingError::UnresolvedCollisions(collisions)) if collisions == vec![(1, 2)]
+        ));
+
+        // With no passes allowed, the original collision is reported.
+
+        layout.set_maximum_collision_resolution_passes(0);
+
+        assert!(matches!(
+            layout.engrave(),
+            Err(EngravingError::UnresolvedCollisions(collisions)) if collisions == vec![(0, 1)]
+        ));
+    }
+
+    fn create_beam_test(
+        stems: &[(VerticalGridLineIndex, HorizontalGridLineIndex)],
+    ) -> (LayoutSystem, Vec<BlockIndex>) {
+        let mut layout = create_justification_test(SystemJustification::AlignStart);
+
+        // Add four stavelines a stave space apart beneath the existing horizontal grid line,
+        // and an upwards stem at the end of each of the given notehead grid lines, with its
+        // root locked to the given staveline. The noteheads are 5 stave spaces apart.
+
+        for (index, grid_line_type) in [
+            HorizontalGridLineType::Staveline2,
+            HorizontalGridLineType::Staveline3,
+            HorizontalGridLineType::Staveline4,
+            HorizontalGridLineType::Staveline5,
+        ]
+        .iter()
+        .enumerate()
+        {
+            let mut grid_line = HorizontalGridLine::new(*grid_line_type);
+
+            grid_line.lock_below_grid_line(index, 1.as_stave_spaces());
+
+            layout.horizontal_grid_lines.push(grid_line);
+        }
+
+        let mut result = Vec::new();
+
+        for (notehead_end, staveline) in stems {
+            let mut stem = LineBlock::new_vertical(
+                None,
+                None,
+                None,
+                0.1.as_stave_spaces(),
+                Color::BLACK,
+                StrokeStyle::Solid,
+                BlockLayer::Foreground,
+            );
+
+            stem.lock_start_to_grid_line(*notehead_end);
+            stem.lock_bottom_to_grid_line(*staveline);
+
+            result.push(layout.blocks.len());
+            layout.blocks.push(stem.into());
+        }
+
+        (layout, result)
+    }
+
+    #[test]
+    fn test_system_beam() {
+        // Three upwards stems beamed together; we pretend the noteheads sit on the bottom line,
+        // the second line from the top, and the middle line of the stave.
+
+        let (mut layout, stems) = create_beam_test(&[(3, 4), (7, 2), (11, 3)]);
+
+        let foreground_without_beam = layout.engrave().unwrap().get_foreground().len();
+
+        // A beam linking a single stem, or a stem that does not exist, is invalid.
+
+        layout.add_beam(LayoutBeam::new(vec![99], BeamDirection::Above, 0, 4));
+
+        assert_eq!(
+            layout.validate(),
+            Err(vec![
+                LayoutValidationError::TooFewBeamStems(0),
+                LayoutValidationError::UnknownBeamReference {
+                    beam: 0,
+                    reference: LayoutElement::Block(99),
+                },
+            ])
+        );
+
+        layout.blocks.pop();
+        layout.beams.clear();
+
+        let beam = layout.add_beam(LayoutBeam::new(stems.clone(), BeamDirection::Above, 0, 4));
+
+        let (system, trace) = layout.engrave_with_trace().unwrap();
+
+        let value = |kind: TraceVariableKind| {
+            trace
+                .get_variables()
+                .iter()
+                .find(|variable| *variable.get_kind() == kind)
+                .unwrap()
+                .get_value()
+        };
+
+        let beam_value = |variable: BeamVariable| {
+            value(TraceVariableKind::Beam {
+                index: beam,
+                variable,
+            })
+        };
+
+        let stem_tip = |index: BlockIndex| {
+            value(TraceVariableKind::Block {
+                index,
+                edge: BlockEdge::Top,
+            })
+        };
+
+        // With minimum stem lengths of 2.5 stave spaces, the outer stems would prefer
+        // to end at 1.5 and 0.5, but the middle stem must also reach the beam, pulling
+        // the whole beam up while keeping the rise of the noteheads. Both ends of the beam
+        // already sit on or straddle a staveline, so quantization leaves them in place.
+
+        assert!((beam_value(BeamVariable::Start) - 0.0).abs() < 0.001);
+        assert!((beam_value(BeamVariable::End) - -1.0).abs() < 0.001);
+        assert!((beam_value(BeamVariable::Rise) - -1.0).abs() < 0.001);
+
+        // Every stem is lengthened to meet the beam.
+
+        assert!((stem_tip(stems[0]) - 0.0).abs() < 0.001);
+        assert!((stem_tip(stems[1]) - -0.5).abs() < 0.001);
+        assert!((stem_tip(stems[2]) - -1.0).abs() < 0.001);
+
+        // The beam is engraved in the foreground, after all Blocks; the BeamBlock standing in
+        // for the beam during collision detection is not engraved.
+
+        assert_eq!(system.get_foreground().len(), foreground_without_beam + 1);
+    }
+
+    #[test]
+    fn test_system_beam_collisions() {
+        // A dynamic with no source voice sits beneath the start of a beam above the noteheads.
+        // Either may move to avoid the other, but they must not be left overlapping.
+
+        let (mut layout, stems) = create_beam_test(&[(3, 4), (7, 2), (11, 3)]);
+
+        layout.add_beam(LayoutBeam::new(stems, BeamDirection::Above, 0, 4));
+
+        let beam_block = layout.blocks.len() - 1;
+
+        let mut dynamic = create_collidable_markup_block(
+            Some(TICKS_ZERO),
+            1.as_stave_spaces(),
+            1.as_stave_spaces(),
+        );
+
+        dynamic.set_can_move_down_to_avoid_vertical_collision(true);
+        dynamic.lock_top_to_grid_line(0);
+        dynamic.lock_start_to_grid_line(3);
+
+        let dynamic_block = layout.blocks.len();
+
+        layout.blocks.push(dynamic.into());
+
+        let mut solver = LayoutSystemSolver::new(&layout, false).unwrap();
+
+        solver.settle(&layout).unwrap();
+
+        let value =
+            |variables: &[Variable], index: BlockIndex| solver.solver.get_value(variables[index]);
+
+        assert!(
+            value(&solver.block_top_position_variables, dynamic_block)
+                - value(&solver.block_bottom_position_variables, beam_block)
+                >= layout.get_minimum_collision_gap().value as f64 - 0.001
+        );
+
+        // The middle stem of a beamed group is lengthened by 2 stave spaces to meet a flat beam,
+        // running into a dynamic that sits clear of the stem's minimum length. The stem cannot
+        // move, so the dynamic must move down beneath the stem's root. The stem touching
+        // its own beam is not a collision.
+
+        let (mut layout, stems) = create_beam_test(&[(3, 2), (7, 4), (11, 2)]);
+
+        for stem in stems.iter() {
+            layout.blocks[*stem].set_collidable(true);
+        }
+
+        layout.add_beam(LayoutBeam::new(stems.clone(), BeamDirection::Above, 0, 4));
+
+        let mut dynamic = create_collidable_markup_block(
+            Some(TICKS_ZERO),
+            1.as_stave_spaces(),
+            0.5.as_stave_spaces(),
+        );
+
+        dynamic.set_can_move_down_to_avoid_vertical_collision(true);
+        dynamic.lock_vertical_center_to_grid_line(1);
+        dynamic.lock_start_to_grid_line(7);
+
+        let dynamic_block = layout.blocks.len();
+
+        layout.blocks.push(dynamic.into());
+
+        let mut solver = LayoutSystemSolver::new(&layout, false).unwrap();
+
+        solver.settle(&layout).unwrap();
+
+        let value =
+            |variables: &[Variable], index: BlockIndex| solver.solver.get_value(variables[index]);
+
+        assert!((value(&solver.block_top_position_variables, stems[1]) - -0.5).abs() < 0.001);
+        assert!(
+            (value(&solver.block_top_position_variables, dynamic_block)
+                - value(&solver.block_bottom_position_variables, stems[1])
+                - layout.get_minimum_collision_gap().value as f64)
+                .abs()
+                < 0.001
+        );
+    }
+
+    #[test]
+    fn test_system_curve() {
+        let mut layout = create_justification_test(SystemJustification::AlignStart);
+
+        let foreground_without_curve = layout.engrave().unwrap().get_foreground().len();
+
+        // A curve anchored to a Block that does not exist is invalid.
+
+        layout.add_curve(LayoutCurve::new(
+            CurveKind::Slur,
+            CurveDirection::Above,
+            1,
+            99,
+        ));
+
+        assert_eq!(
+            layout.validate(),
+            Err(vec![LayoutValidationError::UnknownCurveAnchor {
+                curve: 0,
+                anchor: 99
+            }])
+        );
+
+        layout.blocks.pop();
+        layout.curves.clear();
+
+        // A slur over the first and last noteheads must arch over the middle notehead,
+        // which sits at the same height and lies directly beneath the slur's midpoint.
+
+        layout.add_curve(LayoutCurve::new(
+            CurveKind::Slur,
+            CurveDirection::Above,
+            1,
+            5,
+        ));
+
+        let mut solver = LayoutSystemSolver::new(&layout, false).unwrap();
+
+        solver.settle(&layout).unwrap();
+
+        let shape = solver.curve_shapes.first().unwrap();
+
+        let middle_notehead_top = solver
+            .solver
+            .get_value(solver.block_top_position_variables[3]);
+
+        assert!(shape.get_start().y.value as f64 <= middle_notehead_top);
+        assert!(
+            (shape.get_point(0.5).y.value as f64)
+                < middle_notehead_top - layout.get_minimum_collision_gap().value as f64
+        );
+
+        // The curve's CurveBlock is fitted around the curve's extent.
+
+        let curve_block = layout.get_blocks().len() - 1;
+
+        let (left, top, right, bottom) = shape.get_extent();
+
+        for (variables, value) in [
+            (&solver.block_top_position_variables, top),
+            (&solver.block_bottom_position_variables, bottom),
+            (&solver.block_start_position_variables, left),
+            (&solver.block_end_position_variables, right),
+        ]
+        .iter()
+        {
+            assert!(
+                (solver.solver.get_value(variables[curve_block]) - *value as f64).abs() < 0.001
+            );
+        }
+
+        // The curve is engraved as a run of lines in the foreground, after all Blocks,
+        // ending at the end of the curve. The CurveBlock itself is not engraved.
+
+        let system = layout.engrave().unwrap();
+
+        assert_eq!(
+            system.get_foreground().len(),
+            foreground_without_curve + CURVE_FLATTENING_SEGMENTS
+        );
+
+        match system.get_foreground().last() {
+            Some(Engravable::Line(line)) => {
+                assert!((line.get_to().x.value - shape.get_end().x.value).abs() < 0.001);
+                assert!((line.get_to().y.value - shape.get_end().y.value).abs() < 0.001);
+            }
+            _ => panic!("expected the curve's last line"),
+        }
+    }
+
+    #[test]
+    fn test_system_spanner() {
+        let mut layout = create_justification_test(SystemJustification::AlignStart);
+
+        // We pretend the system ends at the onset of the last notehead, so that notehead
+        // falls on the next system.
+
+        layout.end_ticks = NotatedDuration::Minim.as_ticks();
+
+        let foreground_without_spanners = layout.engrave().unwrap().get_foreground().len();
+
+        // An onset on the system must be given a grid line.
+
+        layout.add_spanner(LayoutSpanner::new(
+            SpannerKind::Crescendo,
+            SpannerAnchor::new(TICKS_ZERO, None),
+            SpannerAnchor::new(NotatedDuration::Crotchet.as_ticks(), Some(6)),
+            0,
+        ));
+
+        assert_eq!(
+            layout.validate(),
+            Err(vec![LayoutValidationError::UnplacedSpannerEnd(0)])
+        );
+
+        layout.blocks.pop();
+        layout.spanners.clear();
+
+        // A crescendo from the first notehead to the second, and a pedal line from the
+        // second notehead continuing onto the next system.
+
+        let crescendo = layout.add_spanner(LayoutSpanner::new(
+            SpannerKind::Crescendo,
+            SpannerAnchor::new(TICKS_ZERO, Some(2)),
+            SpannerAnchor::new(NotatedDuration::Crotchet.as_ticks(), Some(6)),
+            0,
+        ));
+
+        let pedal = layout.add_spanner(LayoutSpanner::new(
+            SpannerKind::Pedal,
+            SpannerAnchor::new(NotatedDuration::Crotchet.as_ticks(), Some(6)),
+            SpannerAnchor::new(NotatedDuration::Minim.as_ticks(), None),
+            0,
+        ));
+
+        let mut solver = LayoutSystemSolver::new(&layout, false).unwrap();
+
+        solver.settle(&layout).unwrap();
+
+        let grid_line = |index: usize| {
+            solver
+                .solver
+                .get_value(solver.vertical_grid_line_variables[index]) as f32
+        };
+
+        // Each end of the crescendo is locked to the grid line of its onset.
+
+        let spanner = layout.get_solved_spanner(crescendo, &solver).unwrap();
+
+        assert_eq!(spanner.get_start().x.value, grid_line(2));
+        assert_eq!(spanner.get_end().x.value, grid_line(6));
+        assert!(!spanner.is_continued_from_previous_system());
+        assert!(!spanner.is_continued_onto_next_system());
+
+        // The pedal line runs to the end of the system, and is flagged as continuing.
+
+        let spanner = layout.get_solved_spanner(pedal, &solver).unwrap();
+
+        assert_eq!(spanner.get_start().x.value, grid_line(6));
+        assert_eq!(spanner.get_end().x.value, grid_line(1));
+        assert!(!spanner.is_continued_from_previous_system());
+        assert!(spanner.is_continued_onto_next_system());
+
+        // The pedal line's SpannerBlock is fitted around the pedal line, from its hook
+        // at the start to the end of the system.
+
+        let pedal_block = layout.get_blocks().len() - 1;
+
+        let (left, top, right, bottom) = spanner.get_extent();
+
+        for (variables, value) in [
+            (&solver.block_top_position_variables, top),
+            (&solver.block_bottom_position_variables, bottom),
+            (&solver.block_start_position_variables, left),
+            (&solver.block_end_position_variables, right),
+        ]
+        .iter()
+        {
+            assert!(
+                (solver.solver.get_value(variables[pedal_block]) - *value as f64).abs() < 0.001
+            );
+        }
+
+        // Each spanner is engraved as one line per segment in the foreground, after all
+        // Blocks. The SpannerBlocks themselves are not engraved.
+
+        let segments = [crescendo, pedal]
+            .iter()
+            .map(|index| {
+                layout
+                    .get_solved_spanner(*index, &solver)
+                    .unwrap()
+                    .get_segments()
+                    .len()
+            })
+            .sum::<usize>();
+
+        let system = layout.engrave().unwrap();
+
+        assert_eq!(
+            system.get_foreground().len(),
+            foreground_without_spanners + segments
+        );
+
+        assert!(system
+            .get_foreground()
+            .iter()
+            .rev()
+            .take(segments)
+            .all(|engravable| matches!(engravable, Engravable::Line(_))));
+    }
+
+    #[test]
+    fn test_system_trace() {
+        let layout = create_justification_test(SystemJustification::AlignStart);
+
+        let (_, trace) = layout.engrave_with_trace().unwrap();
+
+        // The trace should include the aligned start of the system, every grid line,
+        // and four edges for every block.
+
+        assert_eq!(
+            trace.get_variables().len(),
+            1 + layout.get_horizontal_grid_lines().len()
+                + layout.get_vertical_grid_lines().len()
+                + 4 * layout.get_blocks().len()
+        );
+
+        // The second glyph starts 5 stave spaces into the system.
+
+        let start = trace
+            .get_variables()
+            .iter()
+            .find(|variable| {
+                *variable.get_kind()
+                    == TraceVariableKind::Block {
+                        index: 3,
+                        edge: BlockEdge::Start,
+                    }
+            })
+            .unwrap();
+
+        assert!((start.get_value() - 5.0).abs() < 0.001);
+
+        // The first constraint added to the solver always fixes the top edge of the system.
+
+        let constraint = trace.get_constraints().first().unwrap();
+
+        assert_eq!(
+            constraint.get_origin(),
+            "system top edge at horizontal grid line 0"
+        );
+        assert_eq!(constraint.get_strength(), REQUIRED);
+
+        assert!(trace.to_json().unwrap().contains("\"AlignedStart\""));
+    }
+
+    #[test]
+    fn test_system_save_and_load() {
+        let mut layout = create_justification_test(SystemJustification::Justified);
+
+        layout.set_rhythmic_spacing_model(RhythmicSpacingModel::Gourlay { exponent: 0.5 });
+
+        let path = std::env::temp_dir().join("test_system_save_and_load.json");
+
+        layout.save_to_file(&path).unwrap();
+
+        let loaded = LayoutSystem::load_from_file(&path).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.get_blocks().len(), layout.get_blocks().len());
+        assert_eq!(loaded.get_justification(), layout.get_justification());
+        assert_eq!(
+            loaded.get_rhythmic_spacing_model(),
+            layout.get_rhythmic_spacing_model()
+        );
+
+        // The reloaded system should engrave identically to the original.
+
+        let original = layout.engrave().unwrap();
+
+        let replayed = loaded.engrave().unwrap();
+
+        assert_eq!(
+            replayed.get_vertical_grid_line_positions(),
+            original.get_vertical_grid_line_positions()
+        );
+        assert_eq!(
+            replayed
+                .get_foreground()
+                .iter()
+                .map(|engravable| engravable.get_x())
+                .collect::<Vec<_>>(),
+            original
+                .get_foreground()
+                .iter()
+                .map(|engravable| engravable.get_x())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[ignore = "golden files must first be blessed by running with BLESS_ENGRAVING_SNAPSHOTS set"]
+    fn test_golden_snapshots() {
+        // Compare the complete engraving of each justification test scenario against
+        // its golden file. Run with BLESS_ENGRAVING_SNAPSHOTS set to create the golden
+        // files, or to update them after an intended layout change. Golden files are only
+        // ever generated by the harness, never written by hand, so this test is ignored
+        // until they have been blessed and checked in.
+
+        for (name, justification) in [
+            ("justification_align_start", SystemJustification::AlignStart),
+            ("justification_align_end", SystemJustification::AlignEnd),
+            ("justification_centered", SystemJustification::Centered),
+            ("justification_justified", SystemJustification::Justified),
+        ] {
+            let layout = create_snapshot_test(justification, 30.as_stave_spaces());
+
+            assert_matches_golden_file(name, &layout.engrave().unwrap());
+        }
+
+        let mut layout = create_snapshot_test(SystemJustification::Justified, 30.as_stave_spaces());
+
+        layout.set_justification_threshold(Some(0.75));
+
+        assert_matches_golden_file(
+            "justification_justified_if_full",
+            &layout.engrave().unwrap(),
+        );
+
+        let mut layout = create_snapshot_test(SystemJustification::Justified, 30.as_stave_spaces());
+
+        layout.set_maximum_justification_ratio(Some(2.0));
+
+        assert_matches_golden_file(
+            "justification_justified_with_maximum_stretch",
+            &layout.engrave().unwrap(),
+        );
+
+        let mut layout = create_snapshot_test(SystemJustification::Justified, 13.as_stave_spaces());
+
+        layout.set_minimum_compression_ratio(0.5);
+
+        assert_matches_golden_file("justification_compressed", &layout.engrave().unwrap());
+    }
+
+    #[test]
+    fn test_random_layout_systems() {
+        // Engrave a large number of randomly generated, but valid, layout systems, most of
+        // which contain overlapping blocks. Engraving must never panic, and must either
+        // succeed or give up cleanly once the maximum number of collision resolution
+        // passes is reached.
+
+        // Collision resolution only ever pushes blocks sideways, so in every engraved system
+        // each fixed block size, each grid line Lock* constraint, and each vertical Lock*
+        // constraint must hold. Each horizontal Lock* constraint must also hold, unless
+        // it belongs to a block yielding to collision resolution.
+
+        let mut generator = RandomLayoutSystemGenerator::new(0x5eed_1a70_u64);
+
+        let mut systems_with_collisions = 0;
+
+        for iteration in 0..200 {
+            let layout = generator.create_layout_system();
+
+            let trace = match layout.engrave_with_trace() {
+                Ok((_, trace)) => trace,
+                Err(EngravingError::UnresolvedCollisions(_)) => continue,
+                Err(err) => panic!(
+                    "random layout system {} failed to engrave: {}",
+                    iteration, err
+                ),
+            };
+
+            let yielding = get_blocks_yielding_to_collision_resolution(&layout, &trace);
+
+            if !yielding.is_empty() {
+                systems_with_collisions += 1;
+            }
+
+            for constraint in trace.get_constraints() {
+                let origin = constraint.get_origin();
+
+                if !origin.contains(": Lock") && !origin.contains(": fixed") {
+                    continue;
+                }
+
+                let is_yielding = origin
+                    .strip_prefix("block ")
+                    .and_then(|rest| rest.split(':').next())
+                    .and_then(|index| index.parse::<BlockIndex>().ok())
+                    .map(|index| yielding.contains(&index))
+                    .unwrap_or(false);
+
+                let is_horizontal = constraint.get_terms().iter().any(|term| {
+                    matches!(
+                        get_term_block_edge(&trace, term),
+                        Some((_, BlockEdge::Start)) | Some((_, BlockEdge::End))
+                    )
+                });
+
+                if origin.contains(": Lock") && is_yielding && is_horizontal {
+                    continue;
+                }
+
+                assert!(
+                    trace.get_violation(constraint) < 0.001,
+                    "random layout system {} violates constraint {} by {}",
+                    iteration,
+                    origin,
+                    trace.get_violation(constraint)
+                );
+            }
+        }
+
+        // The generator overlaps blocks deliberately, so collision resolution must have been
+        // exercised by at least some of the engraved systems.
+
+        assert!(systems_with_collisions > 0);
+    }
+
+    /// Returns the indices of the blocks whose horizontal Lock* constraints may yield to
+    /// collision resolution in the given trace of the given LayoutSystem: every block taking
+    /// part in a collision resolution constraint, whether moved or stationary, and every
+    /// block those blocks are chained to, directly or indirectly, by a block-to-block
+    /// constraint. The solver can only satisfy a collision resolution constraint by
+    /// overriding a Lock* constraint somewhere along these chains.
+    fn get_blocks_yielding_to_collision_resolution(
+        layout: &LayoutSystem,
+        trace: &LayoutTrace,
+    ) -> HashSet<BlockIndex> {
+        let mut yielding = HashSet::new();
+
+        for constraint in trace.get_constraints() {
+            if constraint.get_origin().ends_with(": collision resolution") {
+                for term in constraint.get_terms() {
+                    if let Some((index, _)) = get_term_block_edge(trace, term) {
+                        yielding.insert(index);
+                    }
+                }
+            }
+        }
+
+        // Blocks are only ever chained to earlier blocks, so walking the blocks in reverse
+        // reaches every block in a chain after the block chained to it.
+
+        for (index, block) in layout.get_blocks().iter().enumerate().rev() {
+            if !yielding.contains(&index) {
+                continue;
+            }
+
+            for constraint in block.get_constraints() {
+                match constraint {
+                    BlockConstraint::LockStartToBlockStart(other_block, _)
+                    | BlockConstraint::LockEndToBlockEnd(other_block, _)
+                    | BlockConstraint::LockHorizontalCenterToBlockCenter(other_block, _)
+                    | BlockConstraint::LockAfterBlockByDistance(other_block, _, _) => {
+                        yielding.insert(*other_block);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        yielding
+    }
+
+    /// Returns the index and edge of the block whose position is the variable
+    /// in the given term, or None if the term's variable is not a block edge.
+    fn get_term_block_edge(
+        trace: &LayoutTrace,
+        term: &TraceTerm,
+    ) -> Option<(BlockIndex, BlockEdge)> {
+        match term
+            .get_variable()
+            .and_then(|id| trace.get_variables().get(id))
+            .map(|variable| variable.get_kind())
+        {
+            Some(TraceVariableKind::Block { index, edge }) => Some((*index, *edge)),
+            _ => None,
+        }
+    }
+
+    /// Generates random LayoutSystems from a fixed seed, so any failure is reproducible.
+    /// Each system contains a random chain of vertical grid lines, each locked to or floating
+    /// after an earlier vertical grid line, and a chain of horizontal grid lines marking rows.
+    ///
+    /// Every row starts with an anchor glyph, locked at Required strength to a random vertical
+    /// grid line and locked to its row. The anchor is followed by a random number of glyphs,
+    /// each locked at Medium strength to the start, end or center of an earlier glyph in
+    /// the same row, or to just after it, and locked vertically to the anchor. These glyphs
+    /// overlap one another, so collision resolution must push them apart, overriding their
+    /// horizontal Lock* constraints. Being weaker than the Strong constraint fixing the width
+    /// of each glyph, these Lock* constraints always yield before any width does.
+    ///
+    /// Every glyph lies within 8 stave spaces of its row's grid line, and rows are kept
+    /// at least 20 stave spaces apart, so glyphs in different rows never collide; in particular,
+    /// two anchors never need to be pushed apart.
+    struct RandomLayoutSystemGenerator {
+        state: u64,
+    }
+
+    impl RandomLayoutSystemGenerator {
+        fn new(seed: u64) -> Self {
+            RandomLayoutSystemGenerator { state: seed.max(1) }
+        }
+
+        /// Returns the next value from a xorshift64* pseudo-random sequence.
+        fn next(&mut self) -> u64 {
+            self.state ^= self.state >> 12;
+            self.state ^= self.state << 25;
+            self.state ^= self.state >> 27;
+            self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        /// Returns a random index less than the given bound.
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+
+        /// Returns a random value between the given bounds.
+        fn between(&mut self, low: f32, high: f32) -> f32 {
+            low + (high - low) * (self.next() % 10_000) as f32 / 10_000.0
+        }
+
+        /// Returns a collidable GlyphBlock showing a random glyph, none of which are taller
+        /// than 8 stave spaces.
+        fn create_glyph_block(&mut self, font: &Bravura) -> GlyphBlock {
+            let glyphs = [
+                Glyph::NoteheadBlack,
+                Glyph::NoteheadHalf,
+                Glyph::GClef,
+                Glyph::FClef,
+                Glyph::TimeSig2Numerator,
+                Glyph::TimeSig4Denominator,
+            ];
+
+            let mut block = GlyphBlock::new(
+                None,
+                None,
+                None,
+                font,
+                Color::BLACK,
+                glyphs[self.below(glyphs.len())],
+                BlockLayer::Foreground,
+            );
+
+            block.set_collidable(true);
+
+            block
+        }
+
+        fn create_layout_system(&mut self) -> LayoutSystem {
+            let font = Bravura::new();
+
+            let mut vertical_grid_lines =
+                vec![VerticalGridLine::new(0, VerticalGridLineType::SystemStart)];
+
+            for index in 1..1 + self.below(12) {
+                let mut grid_line = VerticalGridLine::new(
+                    index,
+                    VerticalGridLineType::NoteheadLine0NoteheadStackStart,
+                );
+
+                let before = self.below(index);
+
+                if self.below(2) == 0 {
+                    grid_line.lock_to_grid_line(before);
+                } else {
+                    grid_line
+                        .float_after_grid_line(before, self.between(0.0, 5.0).as_stave_spaces());
+                }
+
+                vertical_grid_lines.push(grid_line);
+            }
+
+            let mut horizontal_grid_lines =
+                vec![HorizontalGridLine::new(HorizontalGridLineType::SystemTop)];
+
+            let mut blocks: Vec<BlockEnum> = Vec::new();
+
+            for row in 1..1 + self.below(10) {
+                let mut grid_line = HorizontalGridLine::new(HorizontalGridLineType::Staveline3);
+
+                grid_line.lock_below_grid_line(row - 1, self.between(20.0, 30.0).as_stave_spaces());
+
+                horizontal_grid_lines.push(grid_line);
+
+                let anchor = blocks.len();
+
+                let mut block = self.create_glyph_block(&font);
+
+                let column = self.below(vertical_grid_lines.len());
+
+                match self.below(3) {
+                    0 => block.lock_start_to_grid_line_with_strength(
+                        column,
+                        ConstraintStrength::Required,
+                    ),
+                    1 => block
+                        .lock_end_to_grid_line_with_strength(column, ConstraintStrength::Required),
+                    _ => block.lock_horizontal_center_to_grid_line_with_strength(
+                        column,
+                        ConstraintStrength::Required,
+                    ),
+                }
+
+                match self.below(3) {
+                    0 => block.lock_top_to_grid_line(row),
+                    1 => block.lock_bottom_to_grid_line(row),
+                    _ => block.lock_vertical_center_to_grid_line(row),
+                }
+
+                blocks.push(block.into());
+
+                for _ in 0..self.below(6) {
+                    let earlier = anchor + self.below(blocks.len() - anchor);
+
+                    let strength = Some(ConstraintStrength::Medium);
+
+                    let mut block = self.create_glyph_block(&font);
+
+                    block.add_constraint(match self.below(4) {
+                        0 => BlockConstraint::LockStartToBlockStart(earlier, strength),
+                        1 => BlockConstraint::LockEndToBlockEnd(earlier, strength),
+                        2 => BlockConstraint::LockHorizontalCenterToBlockCenter(earlier, strength),
+                        _ => BlockConstraint::LockAfterBlockByDistance(
+                            earlier,
+                            self.between(-1.0, 0.5) as f64,
+                            strength,
+                        ),
+                    });
+
+                    // Locking vertically to the anchor, rather than to the earlier glyph,
+                    // keeps every glyph in the row within 8 stave spaces of the row's grid line.
+
+                    block.add_constraint(match self.below(3) {
+                        0 => BlockConstraint::LockTopToBlockTop(anchor, None),
+                        1 => BlockConstraint::LockBottomToBlockBottom(anchor, None),
+                        _ => BlockConstraint::LockVerticalCenterToBlockCenter(anchor, None),
+                    });
+
+                    blocks.push(block.into());
+                }
+            }
+
+            let mut layout = LayoutSystem::new(
+                0,
+                TICKS_ZERO,
+                NotatedDuration::Semibreve.as_ticks(),
+                SystemJustification::AlignStart,
+                1000.as_stave_spaces(),
+                horizontal_grid_lines,
+                vertical_grid_lines,
+                0,
+                0,
+                blocks,
+                false,
+                false,
+                false,
+                false,
+            );
+
+            // Pushing one glyph clear of another can push it into a third glyph in the same row,
+            // so allow enough passes for every glyph in the longest possible row to be moved.
+
+            layout.set_maximum_collision_resolution_passes(16);
 
-        block
+            layout
+        }
     }
 
-    fn create_lyric_underlay_block(
-        lyric_underlay_top: HorizontalGridLineIndex,
-        lyric_underlay_bottom: HorizontalGridLineIndex,
-        notehead_start: VerticalGridLineIndex,
-        notehead_center: VerticalGridLineIndex,
-        notehead_end: VerticalGridLineIndex,
-        lyric: &str,
-    ) -> MarkupBlock {
-        // We simulate the width for this test by assuming 0.5 stave spaces per character.
+    #[test]
+    fn test_engraving_session_replace_block() {
+        let mut session = create_justification_test(SystemJustification::AlignStart)
+            .into_engraving_session()
+            .unwrap();
 
-        let lyric_width = StaveSpaces::new(lyric.len() as f32 * 0.5);
+        // Nothing has changed since the session was created, so we expect
+        // no changes from re-engraving.
 
-        let lyric_height = 1.as_stave_spaces();
+        assert!(session.engrave().unwrap().is_empty());
 
-        let mut block = MarkupBlock::new(
+        // Replace the first spacing block with one 5 stave spaces wider. This should
+        // push the second and third glyphs 5 stave spaces to the right, and extend
+        // the simulated staveline by the same amount, but leave the first glyph untouched.
+
+        let font = Bravura::new();
+
+        let notehead_width = GlyphBlock::new(
             None,
             None,
             None,
-            vec![MarkedUpLine::new(
-                STAVE_SPACES_ZERO,
-                STAVE_SPACES_ZERO,
-                STAVE_SPACES_ZERO,
-                STAVE_SPACES_ZERO,
-                lyric_width,
-                lyric_height,
-                vec![],
-                LineLayout::LineStartAligned,
-                Border::none(),
-            )],
+            &font,
+            Color::BLACK,
+            Glyph::NoteheadBlack,
             BlockLayer::Foreground,
-            Some(lyric_width),
-            Some(lyric_height),
-        );
+        )
+        .get_fixed_width();
 
-        block.lock_top_to_grid_line(lyric_underlay_top);
-        block.lock_bottom_to_grid_line(lyric_underlay_bottom);
-        block.float_horizontally_between_grid_lines(notehead_start, notehead_end);
-        block.lock_horizontal_center_to_grid_line(notehead_center);
+        let mut spacing = SpacingBlock::new(10.as_stave_spaces() - notehead_width);
 
-        block
-    }
+        spacing.float_horizontally_between_grid_lines(4, 5);
 
-    fn unwrap_h_line(
-        solution: &Result<EngravedSystem, EngravingError>,
-        index: HorizontalGridLineIndex,
-    ) -> StaveSpaces {
-        assert!(solution.is_ok());
+        session.replace_block(2, spacing.into()).unwrap();
 
-        let result = solution
-            .as_ref()
-            .unwrap()
-            .get_horizontal_grid_line_positions()
-            .get(index);
+        let changes = session.engrave().unwrap();
 
-        assert!(result.is_some());
+        let changed_blocks = changes
+            .iter()
+            .filter_map(|change| match change {
+                EngravingSessionChange::Engraved(index, _, engravable) => {
+                    Some((*index, engravable.get_x()))
+                }
+                EngravingSessionChange::Removed(_) => None,
+                EngravingSessionChange::BeamEngraved(_, _) => None,
+                EngravingSessionChange::CurveEngraved(_, _) => None,
+                EngravingSessionChange::SpannerEngraved(_, _) => None,
+            })
+            .collect::<Vec<_>>();
 
-        *result.unwrap()
+        assert_eq!(
+            changed_blocks,
+            vec![
+                (0, STAVE_SPACES_ZERO),
+                (3, 10.as_stave_spaces()),
+                (5, 15.as_stave_spaces())
+            ]
+        );
+
+        assert_eq!(
+            session
+                .get_engraved_system()
+                .get_vertical_grid_line_positions()
+                .get(1)
+                .unwrap(),
+            20.as_stave_spaces()
+        );
     }
 
-    fn unwrap_v_line(
-        solution: &Result<EngravedSystem, EngravingError>,
-        index: VerticalGridLineIndex,
-    ) -> StaveSpaces {
-        assert!(solution.is_ok());
+    #[test]
+    fn test_engraving_session_replace_block_rollback() {
+        let mut session = create_justification_test(SystemJustification::AlignStart)
+            .into_engraving_session()
+            .unwrap();
 
-        let result = solution
-            .as_ref()
+        let spacing_width = session
+            .get_layout_system()
+            .get_blocks()
+            .get(2)
             .unwrap()
-            .get_vertical_grid_line_positions()
-            .get(index);
+            .get_fixed_width();
 
-        assert!(result.is_some());
+        // Add a grid line required to sit 2 stave spaces after the leading edge, then replace
+        // the first spacing block with one required to start both at the leading edge and at
+        // the new grid line. The layout is structurally valid, so the replacement passes
+        // validation, but fails part way through adding its constraints to the solver.
 
-        *result.unwrap()
-    }
+        let mut grid_line = VerticalGridLine::new(0, VerticalGridLineType::SystemStart);
 
-    fn unwrap_block_top(
-        solution: &Result<EngravedSystem, EngravingError>,
-        index: BlockIndex,
-    ) -> StaveSpaces {
-        assert!(solution.is_ok());
+        grid_line.lock_after_grid_line_with_strength(
+            0,
+            2.as_stave_spaces(),
+            ConstraintStrength::Required,
+        );
 
-        let result = solution.as_ref().unwrap().get_foreground().get(index);
+        let grid_line = session.add_vertical_grid_line(grid_line).unwrap();
 
-        assert!(result.is_some());
+        let mut spacing = SpacingBlock::new(10.as_stave_spaces());
 
-        result.unwrap().get_y()
-    }
+        spacing.float_horizontally_between_grid_lines(4, 5);
+        spacing.lock_start_to_grid_line_with_strength(0, ConstraintStrength::Required);
+        spacing.lock_start_to_grid_line_with_strength(grid_line, ConstraintStrength::Required);
 
-    fn unwrap_block_start(
-        solution: &Result<EngravedSystem, EngravingError>,
-        index: BlockIndex,
-    ) -> StaveSpaces {
-        assert!(solution.is_ok());
+        assert!(matches!(
+            session.replace_block(2, spacing.clone().into()),
+            Err(EngravingError::AddConstraintErrorOnBlock(_, 2))
+        ));
 
-        let result = solution.as_ref().unwrap().get_foreground().get(index);
+        // The previous spacing block and its constraints should have been restored,
+        // so re-engraving reports no changes.
 
-        assert!(result.is_some());
+        assert_eq!(
+            session
+                .get_layout_system()
+                .get_blocks()
+                .get(2)
+                .unwrap()
+                .get_fixed_width(),
+            spacing_width
+        );
 
-        result.unwrap().get_x()
-    }
+        assert!(session.engrave().unwrap().is_empty());
 
-    fn unwrap_block_end(
-        solution: &Result<EngravedSystem, EngravingError>,
-        index: BlockIndex,
-    ) -> StaveSpaces {
-        assert!(solution.is_ok());
+        // Adding the same spacing block as a new Block should fail in the same way,
+        // and leave no trace of the new Block behind.
 
-        let result = solution.as_ref().unwrap().get_foreground().get(index);
+        let block_count = session.get_layout_system().get_blocks().len();
 
-        assert!(result.is_some());
+        assert!(matches!(
+            session.add_block(spacing.into()),
+            Err(EngravingError::AddConstraintErrorOnBlock(_, index)) if index == block_count
+        ));
 
-        result.unwrap().get_x() + result.unwrap().get_width()
+        assert_eq!(session.get_layout_system().get_blocks().len(), block_count);
+
+        assert!(session.engrave().unwrap().is_empty());
     }
 
-    fn unwrap_block_bottom(
-        solution: &Result<EngravedSystem, EngravingError>,
-        index: BlockIndex,
-    ) -> StaveSpaces {
-        assert!(solution.is_ok());
+    #[test]
+    fn test_engraving_session_validation() {
+        let mut session = create_justification_test(SystemJustification::AlignStart)
+            .into_engraving_session()
+            .unwrap();
 
-        let result = solution.as_ref().unwrap().get_foreground().get(index);
+        let block_count = session.get_layout_system().get_blocks().len();
 
-        assert!(result.is_some());
+        // A Block referring to a grid line that does not exist is rejected before
+        // it reaches the solver.
 
-        result.unwrap().get_y() + result.unwrap().get_height()
-    }
+        let mut spacing = SpacingBlock::new(1.as_stave_spaces());
 
-    #[test]
-    fn test_system_start_align() {
-        let solution = create_justification_test(SystemJustification::AlignStart).engrave();
+        spacing.lock_start_to_grid_line(99);
 
-        assert!(solution.is_ok());
+        assert!(matches!(
+            session.add_block(spacing.into()),
+            Err(EngravingError::InvalidLayout(_))
+        ));
 
-        let solution = solution.unwrap();
+        assert_eq!(session.get_layout_system().get_blocks().len(), block_count);
 
-        // Start alignment should have a leading edge at 0.0 and, for this
-        // justification test, a trailing edge at 15.0. The fact that the test
-        // asks for a target system width of 30.0 is irrelevant when the
-        // system justification is set to start alignment.
+        // Locking the first notehead to a Block that is itself locked to the notehead
+        // closes a cycle, so the replacement is rejected and the notehead is left as it was.
 
-        assert_eq!(
-            solution.get_vertical_grid_line_positions().get(0).unwrap(),
-            0.as_stave_spaces()
-        );
-        assert_eq!(
-            solution.get_vertical_grid_line_positions().get(1).unwrap(),
-            15.as_stave_spaces()
-        );
-    }
+        let mut marker = SpacingBlock::new(1.as_stave_spaces());
 
-    #[test]
-    fn test_system_end_align() {
-        let solution = create_justification_test(SystemJustification::AlignEnd).engrave();
+        marker.add_constraint(BlockConstraint::LockStartToBlockStart(1, None));
 
-        assert!(solution.is_ok());
+        let marker = session.add_block(marker.into()).unwrap();
 
-        let solution = solution.unwrap();
+        let original = session
+            .get_layout_system()
+            .get_blocks()
+            .get(1)
+            .unwrap()
+            .clone();
 
-        // The justification test scenario has a total width of 15 stave spaces
-        // and sets a target system width of 30 stave spaces, so end alignment
-        // should have a leading edge at 15 and a trailing edge at 30.
+        let mut notehead = original.clone();
+
+        notehead.add_constraint(BlockConstraint::LockStartToBlockStart(marker, None));
+
+        assert!(matches!(
+            session.replace_block(1, notehead),
+            Err(EngravingError::InvalidLayout(errors))
+                if matches!(errors.as_slice(), [LayoutValidationError::CyclicLockChain(_)])
+        ));
 
         assert_eq!(
-            solution.get_vertical_grid_line_positions().get(0).unwrap(),
-            15.as_stave_spaces()
-        );
-        assert_eq!(
-            solution.get_vertical_grid_line_positions().get(1).unwrap(),
-            30.as_stave_spaces()
+            session
+                .get_layout_system()
+                .get_blocks()
+                .get(1)
+                .unwrap()
+                .get_constraints(),
+            original.get_constraints()
         );
+
+        assert!(session.engrave().is_ok());
     }
 
     #[test]
-    fn test_system_center_align() {
-        let solution = create_justification_test(SystemJustification::Centered).engrave();
+    fn test_engraving_session_remove() {
+        let mut session = create_justification_test(SystemJustification::AlignStart)
+            .into_engraving_session()
+            .unwrap();
 
-        assert!(solution.is_ok());
+        // A Block locked to the first notehead keeps the notehead from being removed.
 
-        let solution = solution.unwrap();
+        let mut marker = SpacingBlock::new(1.as_stave_spaces());
 
-        // The justification test scenario has a total width of 15 stave spaces
-        // and sets a target system width of 30 stave spaces, so center alignment
-        // should have a leading edge at (30 - 15) / 2 = 7.5 and a trailing edge
-        // at 7.5 + 15 = 22.5.
+        marker.add_constraint(BlockConstraint::LockStartToBlockStart(1, None));
 
-        assert_eq!(
-            solution.get_vertical_grid_line_positions().get(0).unwrap(),
-            7.5.as_stave_spaces()
-        );
-        assert_eq!(
-            solution.get_vertical_grid_line_positions().get(1).unwrap(),
-            22.5.as_stave_spaces()
-        );
-    }
+        let marker = session.add_block(marker.into()).unwrap();
 
-    #[test]
-    fn test_system_justify() {
-        let solution = create_justification_test(SystemJustification::Justified).engrave();
+        assert!(matches!(
+            session.remove_block(1),
+            Err(EngravingError::RemovingReferencedElement(
+                LayoutElement::Block(1)
+            ))
+        ));
 
-        assert!(solution.is_ok());
+        // Once the referring Block is removed, the notehead can be removed too,
+        // and is reported as removed on the next engraving.
 
-        let solution = solution.unwrap();
+        session.remove_block(marker).unwrap();
+        session.remove_block(1).unwrap();
 
-        // The justification test scenario has a total width of 15 stave spaces.
-        // There are three notehead glyphs, each followed by a spacing block.
-        // Justifying the test out from 15 stave spaces to 30 stave spaces
-        // means we expect each spacing block to take on (30 - 15) / 3 additional
-        // stave spaces of padding. The spacing blocks themselves are filtered out
-        // when blocks are converted to engravable, so we only have the positions
-        // of the glyphs available to examine. Before justification, the spacing
-        // blocks ensured that the glyphs appeared at (0,0), (5,0) and (10,0); adding
-        // (30 - 15) / 3 = 5 additional stave spaces of padding to each spacing
-        // block should result in the two glyphs now appearing at (0+5*0,0),
-        // (5+5*1,0) and (10+5*2,0) = (0,0), (10,0) and (20,0) in the engraving.
+        // The placeholder left behind is not a spacing block, so it cannot disturb
+        // rhythmic spacing or justification.
 
-        // Because the simulated staveline is in the background layer, and the
-        // spacing blocks are filtered out of the final engraving, we expect to find
-        // the glyph engravable at index positions 0, 1, and 2 in the foreground layer.
+        assert!(!session
+            .get_layout_system()
+            .get_blocks()
+            .get(1)
+            .unwrap()
+            .is_spacing_block());
+
+        assert!(session
+            .engrave()
+            .unwrap()
+            .iter()
+            .any(|change| matches!(change, EngravingSessionChange::Removed(1))));
+
+        // The system's top and leading edges, and grid lines referred to by other
+        // grid lines or Blocks, cannot be removed.
+
+        assert!(matches!(
+            session.remove_horizontal_grid_line(0),
+            Err(EngravingError::RemovingReferencedElement(
+                LayoutElement::HorizontalGridLine(0)
+            ))
+        ));
+
+        assert!(matches!(
+            session.remove_vertical_grid_line(0),
+            Err(EngravingError::RemovingReferencedElement(
+                LayoutElement::VerticalGridLine(0)
+            ))
+        ));
+
+        assert!(matches!(
+            session.remove_vertical_grid_line(5),
+            Err(EngravingError::RemovingReferencedElement(
+                LayoutElement::VerticalGridLine(5)
+            ))
+        ));
+
+        // An unreferenced grid line can be removed. Its index remains in use by
+        // an unconstrained grid line of the same type.
+
+        let mut grid_line = VerticalGridLine::new(3, VerticalGridLineType::RhythmicSpacingEnd);
+
+        grid_line.lock_after_grid_line(13, 2.as_stave_spaces());
+
+        let index = session.add_vertical_grid_line(grid_line).unwrap();
+
+        session.remove_vertical_grid_line(index).unwrap();
+
+        let grid_line = session
+            .get_layout_system()
+            .get_vertical_grid_lines()
+            .get(index)
+            .unwrap();
 
         assert_eq!(
-            solution.get_foreground().get(0).unwrap().get_x(),
-            STAVE_SPACES_ZERO
-        );
-        assert_eq!(
-            solution.get_foreground().get(1).unwrap().get_x(),
-            10.as_stave_spaces()
-        );
-        assert_eq!(
-            solution.get_foreground().get(2).unwrap().get_x(),
-            20.as_stave_spaces()
+            grid_line.get_grid_line_type(),
+            VerticalGridLineType::RhythmicSpacingEnd
         );
+        assert!(grid_line.get_constraints().is_empty());
 
-        // In addition to the glyph blocks moving, we also expect to see the system end
-        // vertical grid line at index 1 expand its position to 30 stave spaces.
+        assert!(session.engrave().is_ok());
+    }
 
-        assert_eq!(
-            solution.get_vertical_grid_line_positions().get(1).unwrap(),
-            30.as_stave_spaces()
+    fn create_snapshot_test(
+        justification: SystemJustification,
+        target_system_width: StaveSpaces,
+    ) -> LayoutSystem {
+        // The justification test scenario, with the simulated staveline fixed to the
+        // horizontal grid line, each notehead replaced by a 1 x 1 markup block centered on
+        // the staveline, and each spacing block narrowed to 4 stave spaces. The total width
+        // is still 15 stave spaces, but no position in the engraving depends on font metrics,
+        // so the golden files do not change with the music font.
+
+        let mut layout =
+            create_justification_test_with_target_system_width(justification, target_system_width);
+
+        let mut staveline = LineBlock::new_horizontal(
+            None,
+            None,
+            None,
+            0.25.as_stave_spaces(),
+            Color::BLACK,
+            StrokeStyle::Solid,
+            BlockLayer::Background,
         );
+
+        staveline.lock_start_to_grid_line(0);
+        staveline.lock_end_to_grid_line(1);
+        staveline.lock_vertical_center_to_grid_line(0);
+
+        layout.blocks[0] = staveline.into();
+
+        for (index, column_start, column_end) in [(1, 2, 3), (3, 6, 7), (5, 10, 11)] {
+            let mut block =
+                create_collidable_markup_block(None, 1.as_stave_spaces(), 1.as_stave_spaces());
+
+            block.lock_vertical_center_to_grid_line(0);
+            block.float_horizontally_between_grid_lines(column_start, column_end);
+
+            layout.blocks[index] = block.into();
+        }
+
+        for (index, column_start, column_end) in [(2, 4, 5), (4, 8, 9), (6, 12, 13)] {
+            let mut block = SpacingBlock::new(4.as_stave_spaces());
+
+            block.float_horizontally_between_grid_lines(column_start, column_end);
+
+            layout.blocks[index] = block.into();
+        }
+
+        layout
     }
 
     fn create_justification_test(justification: SystemJustification) -> LayoutSystem {
+        create_justification_test_with_target_system_width(justification, 30.as_stave_spaces())
+    }
+
+    fn create_justification_test_with_target_system_width(
+        justification: SystemJustification,
+        target_system_width: StaveSpaces,
+    ) -> LayoutSystem {
         // A simple set of blocks and constraints that let us play with
         // justification settings.
 
         // We align six blocks on a single horizontal grid line: a glyph, a spacer,
         // a glyph, a spacer, a glyph, and a spacer. The total width will be
-        // 15 stave spaces. We ask for a target system width double that,
+        // 15 stave spaces. By default we ask for a target system width double that,
         // so the effects of system alignment are clear.
 
         let font = Bravura::new();
@@ -3125,7 +8823,7 @@ pub mod tests {
             0.as_ticks(),
             0.as_ticks(),
             justification,
-            30.as_stave_spaces(),
+            target_system_width,
             vec![h0],
             vec![v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11, v12, v13],
             0,
@@ -0,0 +1,879 @@
+use crate::models::display::grid::horizontal::{
+    HorizontalGridLine, HorizontalGridLineConstraint, HorizontalGridLineIndex,
+};
+use crate::models::display::grid::vertical::{
+    VerticalGridLine, VerticalGridLineConstraint, VerticalGridLineIndex,
+};
+use crate::models::display::layout::beam::{BeamIndex, LayoutBeam};
+use crate::models::display::layout::block::{Block, BlockConstraint, BlockEnum, BlockIndex};
+use crate::models::display::layout::curve::{CurveIndex, LayoutCurve};
+use crate::models::display::layout::diagnostics::BlockEdge;
+use crate::models::display::layout::spanner::{LayoutSpanner, SpannerIndex};
+use crate::models::music::concepts::ticks::Ticks;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// A single grid line or Block in a LayoutSystem.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LayoutElement {
+    HorizontalGridLine(HorizontalGridLineIndex),
+    VerticalGridLine(VerticalGridLineIndex),
+    Block(BlockIndex),
+}
+
+impl Display for LayoutElement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutElement::HorizontalGridLine(index) => {
+                write!(f, "horizontal grid line {}", index)
+            }
+            LayoutElement::VerticalGridLine(index) => write!(f, "vertical grid line {}", index),
+            LayoutElement::Block(index) => write!(f, "block {}", index),
+        }
+    }
+}
+
+/// A single position in a LayoutSystem represented by its own solver variable: the position
+/// of a grid line, or one edge of a Block.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LayoutVariable {
+    HorizontalGridLine(HorizontalGridLineIndex),
+    VerticalGridLine(VerticalGridLineIndex),
+    Block(BlockIndex, BlockEdge),
+}
+
+impl LayoutVariable {
+    /// Returns the grid line or Block positioned by this variable.
+    pub fn get_element(&self) -> LayoutElement {
+        match self {
+            LayoutVariable::HorizontalGridLine(index) => LayoutElement::HorizontalGridLine(*index),
+            LayoutVariable::VerticalGridLine(index) => LayoutElement::VerticalGridLine(*index),
+            LayoutVariable::Block(index, _) => LayoutElement::Block(*index),
+        }
+    }
+
+    /// Returns true if this variable is a y position, or false if it is an x position.
+    fn is_vertical(&self) -> bool {
+        matches!(
+            self,
+            LayoutVariable::HorizontalGridLine(_)
+                | LayoutVariable::Block(_, BlockEdge::Top)
+                | LayoutVariable::Block(_, BlockEdge::Bottom)
+        )
+    }
+}
+
+impl Display for LayoutVariable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutVariable::Block(index, edge) => write!(
+                f,
+                "{} of block {}",
+                match edge {
+                    BlockEdge::Top => "top",
+                    BlockEdge::Bottom => "bottom",
+                    BlockEdge::Start => "start",
+                    BlockEdge::End => "end",
+                },
+                index
+            ),
+            _ => write!(f, "{}", self.get_element()),
+        }
+    }
+}
+
+/// A single structural problem in a LayoutSystem, as found by LayoutSystem::validate().
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutValidationError {
+    /// The top edge of the system refers to a HorizontalGridLine that does not exist.
+    UnknownTopEdge(HorizontalGridLineIndex),
+
+    /// The leading edge of the system refers to a VerticalGridLine that does not exist.
+    UnknownLeadingEdge(VerticalGridLineIndex),
+
+    /// The constraint at the given position in the given element's list of constraints
+    /// refers to a grid line or Block that does not exist.
+    UnknownReference {
+        element: LayoutElement,
+        constraint: usize,
+        reference: LayoutElement,
+    },
+
+    /// Each variable in the chain is locked to the next by a Lock* constraint, and
+    /// the last variable is locked back to the first.
+    CyclicLockChain(Vec<LayoutVariable>),
+
+    /// The beam at the given index refers to a stem Block or staveline grid line
+    /// that does not exist.
+    UnknownBeamReference {
+        beam: BeamIndex,
+        reference: LayoutElement,
+    },
+
+    /// The beam at the given index links fewer than two stems.
+    TooFewBeamStems(BeamIndex),
+
+    /// The beam at the given index links a fixed height stem Block, which cannot
+    /// be lengthened to meet the beam.
+    FixedHeightBeamStem { beam: BeamIndex, stem: BlockIndex },
+
+    /// The curve at the given index is anchored to a Block that does not exist.
+    UnknownCurveAnchor {
+        curve: CurveIndex,
+        anchor: BlockIndex,
+    },
+
+    /// The spanner at the given index refers to a grid line that does not exist.
+    UnknownSpannerReference {
+        spanner: SpannerIndex,
+        reference: LayoutElement,
+    },
+
+    /// The spanner at the given index ends at an earlier onset than it starts.
+    SpannerEndsBeforeStart(SpannerIndex),
+
+    /// The spanner at the given index does not reach the onsets covered by this system.
+    SpannerOutsideSystem(SpannerIndex),
+
+    /// An end of the spanner at the given index cannot be placed on this system: either
+    /// its onset falls on this system but has no grid line, or the spanner continues onto
+    /// the next system but this system has no end grid line to lock it to.
+    UnplacedSpannerEnd(SpannerIndex),
+}
+
+impl Display for LayoutValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutValidationError::UnknownTopEdge(index) => write!(
+                f,
+                "System top edge refers to unknown horizontal grid line {}",
+                index
+            ),
+            LayoutValidationError::UnknownLeadingEdge(index) => write!(
+                f,
+                "System leading edge refers to unknown vertical grid line {}",
+                index
+            ),
+            LayoutValidationError::UnknownReference {
+                element,
+                constraint,
+                reference,
+            } => write!(
+                f,
+                "Constraint {} on {} refers to unknown {}",
+                constraint, element, reference
+            ),
+            LayoutValidationError::CyclicLockChain(chain) => write!(
+                f,
+                "Cyclic lock chain: {} -> {}",
+                chain
+                    .iter()
+                    .map(|variable| variable.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> "),
+                chain
+                    .first()
+                    .map(|variable| variable.to_string())
+                    .unwrap_or_default()
+            ),
+            LayoutValidationError::UnknownBeamReference { beam, reference } => {
+                write!(f, "Beam {} refers to unknown {}", beam, reference)
+            }
+            LayoutValidationError::TooFewBeamStems(beam) => {
+                write!(f, "Beam {} links fewer than two stems", beam)
+            }
+            LayoutValidationError::FixedHeightBeamStem { beam, stem } => write!(
+                f,
+                "Beam {} links fixed height block {}, which cannot be lengthened as a stem",
+                beam, stem
+            ),
+            LayoutValidationError::UnknownCurveAnchor { curve, anchor } => {
+                write!(f, "Curve {} is anchored to unknown block {}", curve, anchor)
+            }
+            LayoutValidationError::UnknownSpannerReference { spanner, reference } => {
+                write!(f, "Spanner {} refers to unknown {}", spanner, reference)
+            }
+            LayoutValidationError::SpannerEndsBeforeStart(spanner) => {
+                write!(f, "Spanner {} ends before it starts", spanner)
+            }
+            LayoutValidationError::SpannerOutsideSystem(spanner) => {
+                write!(f, "Spanner {} lies entirely outside the system", spanner)
+            }
+            LayoutValidationError::UnplacedSpannerEnd(spanner) => write!(
+                f,
+                "Spanner {} has an end with no grid line to lock it to",
+                spanner
+            ),
+        }
+    }
+}
+
+/// Returns every structural problem in a layout made up of the given grid lines, Blocks,
+/// beams, curves, and spanners, with its origin at the given top and leading edges, covering
+/// the onsets from the given start ticks up to the given end ticks. An empty list means the layout
+/// is structurally sound, though its constraints may still be unsatisfiable.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn validate_layout(
+    horizontal_grid_lines: &[HorizontalGridLine],
+    vertical_grid_lines: &[VerticalGridLine],
+    top_edge: HorizontalGridLineIndex,
+    leading_edge: VerticalGridLineIndex,
+    blocks: &[BlockEnum],
+    beams: &[LayoutBeam],
+    curves: &[LayoutCurve],
+    spanners: &[LayoutSpanner],
+    start_ticks: Ticks,
+    end_ticks: Ticks,
+) -> Vec<LayoutValidationError> {
+    let mut errors = Vec::new();
+
+    if top_edge >= horizontal_grid_lines.len() {
+        errors.push(LayoutValidationError::UnknownTopEdge(top_edge));
+    }
+
+    if leading_edge >= vertical_grid_lines.len() {
+        errors.push(LayoutValidationError::UnknownLeadingEdge(leading_edge));
+    }
+
+    // Collect every reference made by every constraint, checking that each referenced
+    // element exists.
+
+    let exists = |element: &LayoutElement| match element {
+        LayoutElement::HorizontalGridLine(index) => *index < horizontal_grid_lines.len(),
+        LayoutElement::VerticalGridLine(index) => *index < vertical_grid_lines.len(),
+        LayoutElement::Block(index) => *index < blocks.len(),
+    };
+
+    for (element, position, (referenced_elements, _)) in
+        get_constraint_references(horizontal_grid_lines, vertical_grid_lines, blocks)
+    {
+        for reference in referenced_elements {
+            if !exists(&reference) {
+                errors.push(LayoutValidationError::UnknownReference {
+                    element,
+                    constraint: position,
+                    reference,
+                });
+            }
+        }
+    }
+
+    // The solver variables locked to one another by Lock* constraints on existing elements
+    // form a directed graph we search for cycles. Lock* constraints only ever relate
+    // variables on the same axis, so we search the x and y positions separately.
+
+    let locks = get_constraint_locks(horizontal_grid_lines, vertical_grid_lines, blocks)
+        .into_iter()
+        .filter(|(_, to)| exists(&to.get_element()))
+        .collect::<Vec<_>>();
+
+    for is_vertical in [false, true] {
+        let axis_locks = locks
+            .iter()
+            .filter(|(from, _)| from.is_vertical() == is_vertical)
+            .copied()
+            .collect::<Vec<_>>();
+
+        errors.append(&mut find_cyclic_lock_chains(axis_locks.as_slice()));
+    }
+
+    // Every beam must link at least two existing stems, each of which must be able
+    // to lengthen to meet the beam, and must sit on an existing stave.
+
+    for (index, beam) in beams.iter().enumerate() {
+        if beam.get_stems().len() < 2 {
+            errors.push(LayoutValidationError::TooFewBeamStems(index));
+        }
+
+        let stave_lines = [
+            LayoutElement::HorizontalGridLine(beam.get_stave_top()),
+            LayoutElement::HorizontalGridLine(beam.get_stave_bottom()),
+        ];
+
+        for reference in stave_lines.iter().copied().chain(
+            beam.get_stems()
+                .iter()
+                .map(|stem| LayoutElement::Block(*stem)),
+        ) {
+            if !exists(&reference) {
+                errors.push(LayoutValidationError::UnknownBeamReference {
+                    beam: index,
+                    reference,
+                });
+            }
+        }
+
+        for stem in beam.get_stems() {
+            if let Some(block) = blocks.get(*stem) {
+                if block.is_fixed_height() {
+                    errors.push(LayoutValidationError::FixedHeightBeamStem {
+                        beam: index,
+                        stem: *stem,
+                    });
+                }
+            }
+        }
+    }
+
+    // Every curve must be anchored to existing Blocks at both ends.
+
+    for (index, curve) in curves.iter().enumerate() {
+        for anchor in [curve.get_start_anchor(), curve.get_end_anchor()].iter() {
+            if *anchor >= blocks.len() {
+                errors.push(LayoutValidationError::UnknownCurveAnchor {
+                    curve: index,
+                    anchor: *anchor,
+                });
+            }
+        }
+    }
+
+    // Every spanner must run forwards across some part of the system, and both its ends
+    // must be lockable to existing grid lines.
+
+    for (index, spanner) in spanners.iter().enumerate() {
+        let references = [
+            Some(LayoutElement::HorizontalGridLine(spanner.get_line())),
+            spanner
+                .get_start()
+                .get_grid_line()
+                .map(LayoutElement::VerticalGridLine),
+            spanner
+                .get_end()
+                .get_grid_line()
+                .map(LayoutElement::VerticalGridLine),
+        ];
+
+        for reference in references.iter().flatten() {
+            if !exists(reference) {
+                errors.push(LayoutValidationError::UnknownSpannerReference {
+                    spanner: index,
+                    reference: *reference,
+                });
+            }
+        }
+
+        if spanner.get_end().get_onset() < spanner.get_start().get_onset() {
+            errors.push(LayoutValidationError::SpannerEndsBeforeStart(index));
+        } else if spanner.get_end().get_onset() < start_ticks
+            || spanner.get_start().get_onset() >= end_ticks
+        {
+            errors.push(LayoutValidationError::SpannerOutsideSystem(index));
+        } else {
+            let (start, end) =
+                spanner.get_grid_lines(vertical_grid_lines, leading_edge, start_ticks, end_ticks);
+
+            if start.is_none() || end.is_none() {
+                errors.push(LayoutValidationError::UnplacedSpannerEnd(index));
+            }
+        }
+    }
+
+    errors
+}
+
+/// Returns true if the given element is the top or leading edge of a layout made up of the
+/// given grid lines, Blocks, beams, curves, and spanners, or if any other element, beam, curve,
+/// or spanner in the layout refers to it. A referenced element cannot be removed from
+/// the layout without leaving the elements that refer to it dangling.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn is_referenced(
+    element: LayoutElement,
+    horizontal_grid_lines: &[HorizontalGridLine],
+    vertical_grid_lines: &[VerticalGridLine],
+    top_edge: HorizontalGridLineIndex,
+    leading_edge: VerticalGridLineIndex,
+    blocks: &[BlockEnum],
+    beams: &[LayoutBeam],
+    curves: &[LayoutCurve],
+    spanners: &[LayoutSpanner],
+    start_ticks: Ticks,
+    end_ticks: Ticks,
+) -> bool {
+    if element == LayoutElement::HorizontalGridLine(top_edge)
+        || element == LayoutElement::VerticalGridLine(leading_edge)
+    {
+        return true;
+    }
+
+    // An element's references to itself are removed along with it.
+
+    if get_constraint_references(horizontal_grid_lines, vertical_grid_lines, blocks)
+        .iter()
+        .any(|(referrer, _, (references, _))| *referrer != element && references.contains(&element))
+    {
+        return true;
+    }
+
+    match element {
+        LayoutElement::HorizontalGridLine(index) => {
+            beams
+                .iter()
+                .any(|beam| beam.get_stave_top() == index || beam.get_stave_bottom() == index)
+                || spanners.iter().any(|spanner| spanner.get_line() == index)
+        }
+        LayoutElement::VerticalGridLine(index) => spanners.iter().any(|spanner| {
+            let (start, end) =
+                spanner.get_grid_lines(vertical_grid_lines, leading_edge, start_ticks, end_ticks);
+
+            start == Some(index) || end == Some(index)
+        }),
+        LayoutElement::Block(index) => {
+            beams.iter().any(|beam| beam.get_stems().contains(&index))
+                || curves.iter().any(|curve| {
+                    curve.get_start_anchor() == index || curve.get_end_anchor() == index
+                })
+        }
+    }
+}
+
+/// Returns the elements referenced by every constraint on the given grid lines and Blocks,
+/// as a list of (constrained element, position of the constraint in the element's list of
+/// constraints, (referenced elements, whether the constraint is a Lock* constraint)).
+#[allow(clippy::type_complexity)]
+fn get_constraint_references(
+    horizontal_grid_lines: &[HorizontalGridLine],
+    vertical_grid_lines: &[VerticalGridLine],
+    blocks: &[BlockEnum],
+) -> Vec<(LayoutElement, usize, (Vec<LayoutElement>, bool))> {
+    let mut references = Vec::new();
+
+    for (index, grid_line) in horizontal_grid_lines.iter().enumerate() {
+        for (position, constraint) in grid_line.get_constraints().iter().enumerate() {
+            references.push((
+                LayoutElement::HorizontalGridLine(index),
+                position,
+                get_horizontal_grid_line_constraint_references(constraint),
+            ));
+        }
+    }
+
+    for (index, grid_line) in vertical_grid_lines.iter().enumerate() {
+        for (position, constraint) in grid_line.get_constraints().iter().enumerate() {
+            references.push((
+                LayoutElement::VerticalGridLine(index),
+                position,
+                get_vertical_grid_line_constraint_references(constraint),
+            ));
+        }
+    }
+
+    for (index, block) in blocks.iter().enumerate() {
+        for (position, constraint) in block.get_constraints().iter().enumerate() {
+            references.push((
+                LayoutElement::Block(index),
+                position,
+                get_block_constraint_references(constraint),
+            ));
+        }
+    }
+
+    references
+}
+
+/// Returns the solver variables locked to one another by every Lock* constraint on the given
+/// grid lines and Blocks, as a list of (locked variable, variable locked to). A Lock* constraint
+/// involving a Block's center locks both of the Block's edges on that axis.
+fn get_constraint_locks(
+    horizontal_grid_lines: &[HorizontalGridLine],
+    vertical_grid_lines: &[VerticalGridLine],
+    blocks: &[BlockEnum],
+) -> Vec<(LayoutVariable, LayoutVariable)> {
+    let mut locks = Vec::new();
+
+    for (index, grid_line) in horizontal_grid_lines.iter().enumerate() {
+        for constraint in grid_line.get_constraints() {
+            let (references, is_lock) = get_horizontal_grid_line_constraint_references(constraint);
+
+            if is_lock {
+                for reference in references {
+                    if let LayoutElement::HorizontalGridLine(other) = reference {
+                        locks.push((
+                            LayoutVariable::HorizontalGridLine(index),
+                            LayoutVariable::HorizontalGridLine(other),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    for (index, grid_line) in vertical_grid_lines.iter().enumerate() {
+        for constraint in grid_line.get_constraints() {
+            let (references, is_lock) = get_vertical_grid_line_constraint_references(constraint);
+
+            if is_lock {
+                for reference in references {
+                    if let LayoutElement::VerticalGridLine(other) = reference {
+                        locks.push((
+                            LayoutVariable::VerticalGridLine(index),
+                            LayoutVariable::VerticalGridLine(other),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    for (index, block) in blocks.iter().enumerate() {
+        for constraint in block.get_constraints() {
+            let (locked, locked_to) = get_block_constraint_locks(index, block, constraint);
+
+            for from in locked.iter() {
+                for to in locked_to.iter() {
+                    locks.push((*from, *to));
+                }
+            }
+        }
+    }
+
+    locks
+}
+
+/// Returns the cycles in the directed graph formed by the given Lock* constraints,
+/// each given as a pair of (locked variable, variable locked to). Every group of variables
+/// locked to one another in a cycle is reported at least once, although overlapping
+/// cycles within the same group may not all be listed separately. The edges of a Block
+/// are treated as independent of one another, even if the Block has a fixed size.
+fn find_cyclic_lock_chains(
+    locks: &[(LayoutVariable, LayoutVariable)],
+) -> Vec<LayoutValidationError> {
+    #[derive(Copy, Clone, PartialEq)]
+    enum State {
+        Unvisited,
+        OnPath,
+        Finished,
+    }
+
+    let mut variables = locks
+        .iter()
+        .flat_map(|(from, to)| [*from, *to])
+        .collect::<Vec<_>>();
+
+    variables.sort();
+    variables.dedup();
+
+    let mut states = variables
+        .iter()
+        .map(|variable| (*variable, State::Unvisited))
+        .collect::<HashMap<_, _>>();
+
+    let mut cycles = Vec::new();
+
+    for start in variables {
+        if states.get(&start) != Some(&State::Unvisited) {
+            continue;
+        }
+
+        // Walk the graph depth first, keeping the current path from the start variable
+        // together with the position of the next outgoing lock to follow from each
+        // variable on the path. Reaching a variable already on the path closes a cycle.
+
+        let mut path: Vec<(LayoutVariable, usize)> = vec![(start, 0)];
+
+        states.insert(start, State::OnPath);
+
+        while let Some((variable, next)) = path.last().copied() {
+            let outgoing = locks
+                .iter()
+                .filter(|(from, _)| *from == variable)
+                .map(|(_, to)| *to)
+                .nth(next);
+
+            match outgoing {
+                Some(target) => {
+                    if let Some(last) = path.last_mut() {
+                        last.1 += 1;
+                    }
+
+                    match states.get(&target).copied().unwrap_or(State::Unvisited) {
+                        State::Unvisited => {
+                            states.insert(target, State::OnPath);
+
+                            path.push((target, 0));
+                        }
+                        State::OnPath => {
+                            let chain = path
+                                .iter()
+                                .map(|(variable, _)| *variable)
+                                .skip_while(|variable| *variable != target)
+                                .collect();
+
+                            cycles.push(LayoutValidationError::CyclicLockChain(chain));
+                        }
+                        State::Finished => {}
+                    }
+                }
+                None => {
+                    states.insert(variable, State::Finished);
+
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    cycles
+}
+
+/// Returns the elements referenced by the given HorizontalGridLineConstraint,
+/// and whether the constraint is a Lock* constraint.
+fn get_horizontal_grid_line_constraint_references(
+    constraint: &HorizontalGridLineConstraint,
+) -> (Vec<LayoutElement>, bool) {
+    use LayoutElement::HorizontalGridLine as H;
+
+    match constraint {
+        HorizontalGridLineConstraint::LockAboveHorizontalGridLineByDistance(below, _, _) => {
+            (vec![H(*below)], true)
+        }
+        HorizontalGridLineConstraint::FloatAboveHorizontalGridLineByDistance(below, _, _) => {
+            (vec![H(*below)], false)
+        }
+        HorizontalGridLineConstraint::LockBelowHorizontalGridLineByDistance(above, _, _) => {
+            (vec![H(*above)], true)
+        }
+        HorizontalGridLineConstraint::FloatBelowHorizontalGridLineByDistance(above, _, _) => {
+            (vec![H(*above)], false)
+        }
+        HorizontalGridLineConstraint::VerticallyCenterBetweenHorizontalGridLines(
+            above,
+            below,
+            _,
+        ) => (vec![H(*above), H(*below)], false),
+    }
+}
+
+/// Returns the elements referenced by the given VerticalGridLineConstraint,
+/// and whether the constraint is a Lock* constraint.
+fn get_vertical_grid_line_constraint_references(
+    constraint: &VerticalGridLineConstraint,
+) -> (Vec<LayoutElement>, bool) {
+    use LayoutElement::VerticalGridLine as V;
+
+    match constraint {
+        VerticalGridLineConstraint::LockBeforeVerticalGridLineByDistance(after, _, _) => {
+            (vec![V(*after)], true)
+        }
+        VerticalGridLineConstraint::FloatBeforeVerticalGridLineByDistance(after, _, _) => {
+            (vec![V(*after)], false)
+        }
+        VerticalGridLineConstraint::LockAfterVerticalGridLineByDistance(before, _, _) => {
+            (vec![V(*before)], true)
+        }
+        VerticalGridLineConstraint::FloatAfterVerticalGridLineByDistance(before, _, _) => {
+            (vec![V(*before)], false)
+        }
+    }
+}
+
+/// Returns the elements referenced by the given BlockConstraint,
+/// and whether the constraint is a Lock* constraint.
+pub(crate) fn get_block_constraint_references(
+    constraint: &BlockConstraint,
+) -> (Vec<LayoutElement>, bool) {
+    use LayoutElement::Block as B;
+    use LayoutElement::HorizontalGridLine as H;
+    use LayoutElement::VerticalGridLine as V;
+
+    match constraint {
+        BlockConstraint::LockTopToHorizontalGridLine(above, _) => (vec![H(*above)], true),
+        BlockConstraint::FloatTopAfterHorizontalGridLine(above, _) => (vec![H(*above)], false),
+        BlockConstraint::FloatBottomBeforeHorizontalGridLine(below, _) => (vec![H(*below)], false),
+        BlockConstraint::LockBottomToHorizontalGridLine(below, _) => (vec![H(*below)], true),
+        BlockConstraint::LockStartToVerticalGridLine(before, _) => (vec![V(*before)], true),
+        BlockConstraint::FloatStartAfterVerticalGridLine(before, _) => (vec![V(*before)], false),
+        BlockConstraint::FloatEndBeforeVerticalGridLine(after, _) => (vec![V(*after)], false),
+        BlockConstraint::LockEndToVerticalGridLine(after, _) => (vec![V(*after)], true),
+        BlockConstraint::LockVerticalCenterHalfwayBetweenHorizontalGridLines(above, below, _) => {
+            (vec![H(*above), H(*below)], true)
+        }
+        BlockConstraint::LockVerticalCenterToHorizontalGridLine(center, _) => {
+            (vec![H(*center)], true)
+        }
+        BlockConstraint::LockHorizontalCenterHalfwayBetweenVerticalGridLines(before, after, _) => {
+            (vec![V(*before), V(*after)], true)
+        }
+        BlockConstraint::LockHorizontalCenterToVerticalGridLine(center, _) => {
+            (vec![V(*center)], true)
+        }
+        BlockConstraint::PushHorizontalGridLineDownToAccommodateBlockHeight(below, _) => {
+            (vec![H(*below)], false)
+        }
+        BlockConstraint::PushVerticalGridLineSidewaysToAccommodateBlockWidth(after, _) => {
+            (vec![V(*after)], false)
+        }
+        BlockConstraint::FloatAfterBlockByDistance(other, _, _)
+        | BlockConstraint::FloatBeforeBlockByDistance(other, _, _)
+        | BlockConstraint::FloatAboveBlockByDistance(other, _, _)
+        | BlockConstraint::FloatBeneathBlockByDistance(other, _, _) => (vec![B(*other)], false),
+        BlockConstraint::FloatHorizontalCenterToBlockCenter(other, _) => (vec![B(*other)], false),
+        BlockConstraint::LockStartToBlockStart(other, _)
+        | BlockConstraint::LockEndToBlockEnd(other, _)
+        | BlockConstraint::LockTopToBlockTop(other, _)
+        | BlockConstraint::LockBottomToBlockBottom(other, _)
+        | BlockConstraint::LockHorizontalCenterToBlockCenter(other, _)
+        | BlockConstraint::LockVerticalCenterToBlockCenter(other, _)
+        | BlockConstraint::LockTopToBlockCenter(other, _)
+        | BlockConstraint::LockBottomToBlockCenter(other, _) => (vec![B(*other)], true),
+        BlockConstraint::LockAfterBlockByDistance(other, _, _)
+        | BlockConstraint::LockBeforeBlockByDistance(other, _, _)
+        | BlockConstraint::LockAboveBlockByDistance(other, _, _)
+        | BlockConstraint::LockBeneathBlockByDistance(other, _, _) => (vec![B(*other)], true),
+        BlockConstraint::LockHorizontalCenterBetweenBlocks(first, second, _)
+        | BlockConstraint::LockVerticalCenterBetweenBlocks(first, second, _) => {
+            (vec![B(*first), B(*second)], true)
+        }
+    }
+}
+
+/// Returns the solver variables of the given Block locked by the given BlockConstraint,
+/// and the solver variables they are locked to. Both lists are empty if the constraint
+/// is not a Lock* constraint. Where the solver positions a fixed size Block by its top
+/// or start edge alone, that is the edge reported as locked.
+fn get_block_constraint_locks(
+    index: BlockIndex,
+    block: &BlockEnum,
+    constraint: &BlockConstraint,
+) -> (Vec<LayoutVariable>, Vec<LayoutVariable>) {
+    use LayoutVariable::HorizontalGridLine as H;
+    use LayoutVariable::VerticalGridLine as V;
+
+    let top = |block: BlockIndex| LayoutVariable::Block(block, BlockEdge::Top);
+    let bottom = |block: BlockIndex| LayoutVariable::Block(block, BlockEdge::Bottom);
+    let start = |block: BlockIndex| LayoutVariable::Block(block, BlockEdge::Start);
+    let end = |block: BlockIndex| LayoutVariable::Block(block, BlockEdge::End);
+
+    let height_edge = if block.is_fixed_height() {
+        top(index)
+    } else {
+        bottom(index)
+    };
+
+    let width_edge = if block.is_fixed_width() {
+        start(index)
+    } else {
+        end(index)
+    };
+
+    match constraint {
+        BlockConstraint::LockTopToHorizontalGridLine(above, _) => {
+            (vec![top(index)], vec![H(*above)])
+        }
+        BlockConstraint::LockBottomToHorizontalGridLine(below, _) => {
+            (vec![height_edge], vec![H(*below)])
+        }
+        BlockConstraint::LockStartToVerticalGridLine(before, _) => {
+            (vec![start(index)], vec![V(*before)])
+        }
+        BlockConstraint::LockEndToVerticalGridLine(after, _) => (vec![width_edge], vec![V(*after)]),
+        BlockConstraint::LockVerticalCenterHalfwayBetweenHorizontalGridLines(above, below, _) => {
+            (vec![top(index)], vec![H(*above), H(*below)])
+        }
+        BlockConstraint::LockVerticalCenterToHorizontalGridLine(center, _) => {
+            (vec![top(index)], vec![H(*center)])
+        }
+        BlockConstraint::LockHorizontalCenterHalfwayBetweenVerticalGridLines(before, after, _) => {
+            (vec![start(index)], vec![V(*before), V(*after)])
+        }
+        BlockConstraint::LockHorizontalCenterToVerticalGridLine(center, _) => {
+            (vec![start(index)], vec![V(*center)])
+        }
+        BlockConstraint::LockStartToBlockStart(other, _) => {
+            (vec![start(index)], vec![start(*other)])
+        }
+        BlockConstraint::LockEndToBlockEnd(other, _) => (vec![end(index)], vec![end(*other)]),
+        BlockConstraint::LockTopToBlockTop(other, _) => (vec![top(index)], vec![top(*other)]),
+        BlockConstraint::LockBottomToBlockBottom(other, _) => {
+            (vec![bottom(index)], vec![bottom(*other)])
+        }
+        BlockConstraint::LockHorizontalCenterBetweenBlocks(before, after, _) => {
+            (vec![start(index)], vec![end(*before), start(*after)])
+        }
+        BlockConstraint::LockVerticalCenterBetweenBlocks(above, beneath, _) => {
+            (vec![top(index)], vec![bottom(*above), top(*beneath)])
+        }
+        BlockConstraint::LockHorizontalCenterToBlockCenter(other, _) => (
+            vec![start(index), end(index)],
+            vec![start(*other), end(*other)],
+        ),
+        BlockConstraint::LockVerticalCenterToBlockCenter(other, _) => (
+            vec![top(index), bottom(index)],
+            vec![top(*other), bottom(*other)],
+        ),
+        BlockConstraint::LockAfterBlockByDistance(before, _, _) => {
+            (vec![start(index)], vec![end(*before)])
+        }
+        BlockConstraint::LockBeforeBlockByDistance(after, _, _) => {
+            (vec![end(index)], vec![start(*after)])
+        }
+        BlockConstraint::LockAboveBlockByDistance(beneath, _, _) => {
+            (vec![bottom(index)], vec![top(*beneath)])
+        }
+        BlockConstraint::LockBeneathBlockByDistance(above, _, _) => {
+            (vec![top(index)], vec![bottom(*above)])
+        }
+        BlockConstraint::LockTopToBlockCenter(other, _) => {
+            (vec![top(index)], vec![top(*other), bottom(*other)])
+        }
+        BlockConstraint::LockBottomToBlockCenter(other, _) => {
+            (vec![bottom(index)], vec![top(*other), bottom(*other)])
+        }
+        BlockConstraint::FloatTopAfterHorizontalGridLine(_, _)
+        | BlockConstraint::FloatBottomBeforeHorizontalGridLine(_, _)
+        | BlockConstraint::FloatStartAfterVerticalGridLine(_, _)
+        | BlockConstraint::FloatEndBeforeVerticalGridLine(_, _)
+        | BlockConstraint::PushHorizontalGridLineDownToAccommodateBlockHeight(_, _)
+        | BlockConstraint::PushVerticalGridLineSidewaysToAccommodateBlockWidth(_, _)
+        | BlockConstraint::FloatAfterBlockByDistance(_, _, _)
+        | BlockConstraint::FloatBeforeBlockByDistance(_, _, _)
+        | BlockConstraint::FloatAboveBlockByDistance(_, _, _)
+        | BlockConstraint::FloatBeneathBlockByDistance(_, _, _)
+        | BlockConstraint::FloatHorizontalCenterToBlockCenter(_, _) => (vec![], vec![]),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::display::layout::diagnostics::BlockEdge;
+    use crate::models::display::layout::validation::{
+        find_cyclic_lock_chains, LayoutValidationError, LayoutVariable,
+    };
+
+    #[test]
+    fn test_cyclic_lock_chains() {
+        use LayoutVariable::VerticalGridLine as V;
+
+        let start = |block| LayoutVariable::Block(block, BlockEdge::Start);
+        let end = |block| LayoutVariable::Block(block, BlockEdge::End);
+
+        // The starts of blocks 0 -> 1 -> 2 -> 0 form a cycle; the start of block 3 is locked
+        // into the cycle but is not part of it. Vertical grid lines 0 -> 1 form a simple chain.
+        // The end of block 1 is locked to the end of block 0, but since the end of block 0
+        // is not locked to anything, that is not a cycle.
+
+        let locks = vec![
+            (start(0), start(1)),
+            (start(1), start(2)),
+            (start(2), start(0)),
+            (start(3), start(1)),
+            (V(0), V(1)),
+            (end(1), end(0)),
+        ];
+
+        assert_eq!(
+            find_cyclic_lock_chains(locks.as_slice()),
+            vec![LayoutValidationError::CyclicLockChain(vec![
+                start(0),
+                start(1),
+                start(2)
+            ])]
+        );
+
+        assert_eq!(
+            LayoutValidationError::CyclicLockChain(vec![start(0), start(1), V(2)]).to_string(),
+            "Cyclic lock chain: start of block 0 -> start of block 1 -> vertical grid line 2 \
+                -> start of block 0"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use crate::models::display::concepts::stave_spaces::{StaveSpaces, STAVE_SPACES_ZERO};
+use crate::models::display::layout::strength::ConstraintStrength;
+use serde::{Deserialize, Serialize};
+
+pub type VerticalGridLineIndex = usize;
+
+/// The role a VerticalGridLine plays in a LayoutSystem. The type of a grid line
+/// has no effect on layout; it identifies the grid line when debugging and tracing,
+/// and lets spanners continued across a system break find the edges of the system interior.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerticalGridLineType {
+    SystemStart,
+    PartGroupNameStart,
+    PartGroupNameEnd,
+    PartNameStart,
+    PartNameEnd,
+    PartStaveBraceStart,
+    PartStaveBraceEnd,
+    PartGroupLine,
+    PartGroupBracketStart,
+    PartGroupBracketEnd,
+    SystemicLine,
+    InstrumentLayoutStart,
+    AnteriorStart,
+    AnteriorEnd,
+    InteriorStart,
+    ClefColumnStart,
+    ClefColumnEnd,
+    KeySignatureColumnStart,
+    KeySignatureColumnEnd,
+    TimeSignatureColumnStart,
+    TimeSignatureColumnEnd,
+    StemColumnStart,
+    NoteheadLine0AccidentalStackStart,
+    NoteheadLine0AccidentalStackEnd,
+    NoteheadLine0NoteheadStackStart,
+    RhythmicSpacingStart,
+    RhythmicSpacingEnd,
+    LyricSyllableEnd,
+    StemColumnEnd,
+    BarlineStart,
+    BarlineEnd,
+    InteriorEnd,
+    PosteriorStart,
+    PosteriorEnd,
+    InstrumentLayoutEnd,
+    SystemEnd,
+}
+
+/// A single constraint on the position of a VerticalGridLine, relative to another
+/// VerticalGridLine. As with BlockConstraint, every constraint carries an optional
+/// ConstraintStrength; if no strength is given, Lock* constraints are STRONG and Float*
+/// constraints are WEAK.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum VerticalGridLineConstraint {
+    LockBeforeVerticalGridLineByDistance(VerticalGridLineIndex, f64, Option<ConstraintStrength>),
+    FloatBeforeVerticalGridLineByDistance(VerticalGridLineIndex, f64, Option<ConstraintStrength>),
+    LockAfterVerticalGridLineByDistance(VerticalGridLineIndex, f64, Option<ConstraintStrength>),
+    FloatAfterVerticalGridLineByDistance(VerticalGridLineIndex, f64, Option<ConstraintStrength>),
+}
+
+/// A flat vertical line expressing a single, initially undefined, x coordinate
+/// in a LayoutSystem. Blocks are aligned horizontally by constraining their edges
+/// to vertical grid lines. Each vertical grid line belongs to a column, numbered
+/// from the start of the system.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerticalGridLine {
+    column: usize,
+    grid_line_type: VerticalGridLineType,
+    constraints: Vec<VerticalGridLineConstraint>,
+}
+
+impl VerticalGridLine {
+    pub fn new(column: usize, grid_line_type: VerticalGridLineType) -> Self {
+        VerticalGridLine {
+            column,
+            grid_line_type,
+            constraints: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn get_column(&self) -> usize {
+        self.column
+    }
+
+    #[inline]
+    pub fn get_grid_line_type(&self) -> VerticalGridLineType {
+        self.grid_line_type
+    }
+
+    #[inline]
+    pub fn get_constraints(&self) -> &[VerticalGridLineConstraint] {
+        self.constraints.as_slice()
+    }
+
+    #[inline]
+    pub fn add_constraint(&mut self, constraint: VerticalGridLineConstraint) {
+        self.constraints.push(constraint);
+    }
+
+    /// Locks this grid line to the given VerticalGridLine.
+    #[inline]
+    pub fn lock_to_grid_line(&mut self, grid_line: VerticalGridLineIndex) {
+        self.lock_after_grid_line(grid_line, STAVE_SPACES_ZERO);
+    }
+
+    #[inline]
+    pub fn lock_to_grid_line_with_strength(
+        &mut self,
+        grid_line: VerticalGridLineIndex,
+        strength: ConstraintStrength,
+    ) {
+        self.lock_after_grid_line_with_strength(grid_line, STAVE_SPACES_ZERO, strength);
+    }
+
+    /// Locks this grid line the given distance before the given VerticalGridLine.
+    #[inline]
+    pub fn lock_before_grid_line(
+        &mut self,
+        grid_line_after: VerticalGridLineIndex,
+        distance: StaveSpaces,
+    ) {
+        self.add_constraint(
+            VerticalGridLineConstraint::LockBeforeVerticalGridLineByDistance(
+                grid_line_after,
+                distance.value as f64,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    pub fn lock_before_grid_line_with_strength(
+        &mut self,
+        grid_line_after: VerticalGridLineIndex,
+        distance: StaveSpaces,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            VerticalGridLineConstraint::LockBeforeVerticalGridLineByDistance(
+                grid_line_after,
+                distance.value as f64,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Floats this grid line at least the given distance before the given VerticalGridLine.
+    #[inline]
+    pub fn float_before_grid_line(
+        &mut self,
+        grid_line_after: VerticalGridLineIndex,
+        distance: StaveSpaces,
+    ) {
+        self.add_constraint(
+            VerticalGridLineConstraint::FloatBeforeVerticalGridLineByDistance(
+                grid_line_after,
+                distance.value as f64,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    pub fn float_before_grid_line_with_strength(
+        &mut self,
+        grid_line_after: VerticalGridLineIndex,
+        distance: StaveSpaces,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            VerticalGridLineConstraint::FloatBeforeVerticalGridLineByDistance(
+                grid_line_after,
+                distance.value as f64,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Locks this grid line the given distance after the given VerticalGridLine.
+    #[inline]
+    pub fn lock_after_grid_line(
+        &mut self,
+        grid_line_before: VerticalGridLineIndex,
+        distance: StaveSpaces,
+    ) {
+        self.add_constraint(
+            VerticalGridLineConstraint::LockAfterVerticalGridLineByDistance(
+                grid_line_before,
+                distance.value as f64,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    pub fn lock_after_grid_line_with_strength(
+        &mut self,
+        grid_line_before: VerticalGridLineIndex,
+        distance: StaveSpaces,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            VerticalGridLineConstraint::LockAfterVerticalGridLineByDistance(
+                grid_line_before,
+                distance.value as f64,
+                Some(strength),
+            ),
+        );
+    }
+
+    /// Floats this grid line at least the given distance after the given VerticalGridLine.
+    #[inline]
+    pub fn float_after_grid_line(
+        &mut self,
+        grid_line_before: VerticalGridLineIndex,
+        distance: StaveSpaces,
+    ) {
+        self.add_constraint(
+            VerticalGridLineConstraint::FloatAfterVerticalGridLineByDistance(
+                grid_line_before,
+                distance.value as f64,
+                None,
+            ),
+        );
+    }
+
+    #[inline]
+    pub fn float_after_grid_line_with_strength(
+        &mut self,
+        grid_line_before: VerticalGridLineIndex,
+        distance: StaveSpaces,
+        strength: ConstraintStrength,
+    ) {
+        self.add_constraint(
+            VerticalGridLineConstraint::FloatAfterVerticalGridLineByDistance(
+                grid_line_before,
+                distance.value as f64,
+                Some(strength),
+            ),
+        );
+    }
+}